@@ -1,5 +1,11 @@
+// Compare sliding attack backends by running the benchmarks with
+// `--features fancy-magic` or `--features compact`, or with
+// `RUSTFLAGS="-C target-feature=+bmi2"`.
+
 use iai::black_box;
-use shakmaty::{fen::Fen, perft, san::San, CastlingMode, Chess, Move, Position, Role, Square};
+use shakmaty::{
+    attacks, fen::Fen, perft, san::San, Bitboard, CastlingMode, Chess, Move, Position, Role, Square,
+};
 
 fn bench_shallow_perft() {
     let pos = Chess::default();
@@ -91,6 +97,18 @@ fn bench_play_sans() -> Chess {
     pos
 }
 
+fn bench_rook_attacks() -> Bitboard {
+    let occupied = black_box(Bitboard(0x3f7f_2880_2826_f5b9));
+    Square::ALL.into_iter().fold(Bitboard::EMPTY, |acc, sq| {
+        acc ^ attacks::rook_attacks(sq, occupied)
+    })
+}
+
+fn bench_attacks_set() -> Bitboard {
+    let occupied = black_box(Bitboard(0x3f7f_2880_2826_f5b9));
+    attacks::rook_attacks_set(occupied, occupied) | attacks::bishop_attacks_set(occupied, occupied)
+}
+
 iai::main!(
     bench_shallow_perft,
     bench_deep_perft,
@@ -99,4 +117,6 @@ iai::main!(
     bench_play_unchecked,
     bench_san_candidates,
     bench_play_sans,
+    bench_rook_attacks,
+    bench_attacks_set,
 );
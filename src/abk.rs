@@ -0,0 +1,270 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Read Arena opening books (`.abk`).
+//!
+//! An Arena book is a tree of moves, starting from the standard initial
+//! position. The tree is indexed by position when the book is loaded, so
+//! that probing finds moves regardless of move order.
+//!
+//! ChessBase books (`.ctg`) are not supported. Their format is
+//! undocumented and would need a separate reader.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use shakmaty::{abk::AbkBook, Chess};
+//!
+//! let book = AbkBook::from_bytes(&std::fs::read("book.abk")?)?;
+//! for entry in book.probe(&Chess::default()) {
+//!     println!("{:?} played in {} games", entry.m, entry.games);
+//! }
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{collections::HashMap, convert::TryInto, error::Error, fmt};
+
+use crate::{uci::Uci, zobrist::ZobristHash, Chess, ErrorKind, Move, Position, Role, Square};
+
+const ENTRY_SIZE: usize = 28;
+const ROOT: usize = 900;
+
+/// Error when loading an invalid Arena book.
+#[derive(Clone, Debug)]
+pub struct InvalidAbkError;
+
+impl fmt::Display for InvalidAbkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid abk book")
+    }
+}
+
+impl Error for InvalidAbkError {}
+
+impl InvalidAbkError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidBook
+    }
+}
+
+/// A move with its statistics, as stored in an Arena book.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AbkEntry {
+    /// The legal move.
+    pub m: Move,
+    /// Priority of the move, as configured by the book author.
+    pub priority: u8,
+    /// Number of games with this move.
+    pub games: u32,
+    /// Number of games won after this move.
+    pub wins: u32,
+    /// Number of games lost after this move.
+    pub losses: u32,
+}
+
+/// An Arena opening book, indexed by position.
+#[derive(Debug, Clone, Default)]
+pub struct AbkBook {
+    entries: HashMap<u64, Vec<AbkEntry>>,
+}
+
+struct RawEntry {
+    from: u8,
+    to: u8,
+    promotion: i8,
+    priority: u8,
+    games: u32,
+    wins: u32,
+    losses: u32,
+    first_child: i32,
+    next_sibling: i32,
+}
+
+impl RawEntry {
+    fn parse(data: &[u8]) -> RawEntry {
+        let int = |offset: usize| {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+        RawEntry {
+            from: data[0],
+            to: data[1],
+            promotion: data[2] as i8,
+            priority: data[3],
+            games: int(4),
+            wins: int(8),
+            losses: int(12),
+            // Bytes 16..20 are unused.
+            first_child: int(20) as i32,
+            next_sibling: int(24) as i32,
+        }
+    }
+
+    fn uci(&self) -> Option<Uci> {
+        if self.from >= 64 || self.to >= 64 {
+            return None;
+        }
+        Some(Uci::Normal {
+            from: Square::new(u32::from(self.from)),
+            to: Square::new(u32::from(self.to)),
+            promotion: match self.promotion.unsigned_abs() {
+                0 => None,
+                1 => Some(Role::Rook),
+                2 => Some(Role::Knight),
+                3 => Some(Role::Bishop),
+                4 => Some(Role::Queen),
+                _ => return None,
+            },
+        })
+    }
+}
+
+fn index(link: i32, len: usize) -> Option<usize> {
+    usize::try_from(link).ok().filter(|idx| *idx < len)
+}
+
+impl AbkBook {
+    /// Loads and indexes a book from the contents of an `.abk` file.
+    ///
+    /// Entries with moves that are illegal in their position (and their
+    /// subtrees) are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidAbkError`] if the data is not structured like an
+    /// Arena book.
+    pub fn from_bytes(data: &[u8]) -> Result<AbkBook, InvalidAbkError> {
+        if data.len() % ENTRY_SIZE != 0 || data.len() < (ROOT + 1) * ENTRY_SIZE {
+            return Err(InvalidAbkError);
+        }
+
+        let raw: Vec<RawEntry> = data.chunks_exact(ENTRY_SIZE).map(RawEntry::parse).collect();
+        let mut visited = vec![false; raw.len()];
+        let mut book = AbkBook::default();
+
+        // Each stack item is the start of a list of siblings.
+        let mut stack = vec![(ROOT, Chess::default())];
+        while let Some((first, pos)) = stack.pop() {
+            let key = pos.zobrist_hash();
+            let mut next = Some(first);
+            while let Some(idx) = next {
+                if visited[idx] {
+                    break;
+                }
+                visited[idx] = true;
+
+                let entry = &raw[idx];
+                next = index(entry.next_sibling, raw.len());
+
+                let m = match entry.uci().and_then(|uci| uci.to_move(&pos).ok()) {
+                    Some(m) => m,
+                    None => continue,
+                };
+
+                if let Some(child) = index(entry.first_child, raw.len()) {
+                    let mut after = pos.clone();
+                    after.play_unchecked(&m);
+                    stack.push((child, after));
+                }
+
+                book.insert(
+                    key,
+                    AbkEntry {
+                        m,
+                        priority: entry.priority,
+                        games: entry.games,
+                        wins: entry.wins,
+                        losses: entry.losses,
+                    },
+                );
+            }
+        }
+
+        Ok(book)
+    }
+
+    fn insert(&mut self, key: u64, entry: AbkEntry) {
+        let entries = self.entries.entry(key).or_default();
+        if let Some(existing) = entries.iter_mut().find(|e| e.m == entry.m) {
+            // Transposition into a position that is already in the tree.
+            existing.priority = existing.priority.max(entry.priority);
+            existing.games += entry.games;
+            existing.wins += entry.wins;
+            existing.losses += entry.losses;
+        } else {
+            entries.push(entry);
+        }
+    }
+
+    /// Gets the book moves in `pos`.
+    pub fn probe(&self, pos: &Chess) -> &[AbkEntry] {
+        self.entries
+            .get(&pos.zobrist_hash::<u64>())
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of distinct positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Tests if the book has no moves.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(from: Square, to: Square, games: u32, first_child: i32, next_sibling: i32) -> [u8; 28] {
+        let mut entry = [0; 28];
+        entry[0] = u8::from(from);
+        entry[1] = u8::from(to);
+        entry[4..8].copy_from_slice(&games.to_le_bytes());
+        entry[20..24].copy_from_slice(&first_child.to_le_bytes());
+        entry[24..28].copy_from_slice(&next_sibling.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    fn test_abk() {
+        let mut data = vec![0; ROOT * ENTRY_SIZE];
+        data.extend(raw(Square::E2, Square::E4, 10, 902, 901)); // 900
+        data.extend(raw(Square::D2, Square::D4, 5, 903, -1)); // 901
+        data.extend(raw(Square::D7, Square::D5, 7, 904, -1)); // 902
+        data.extend(raw(Square::E7, Square::E5, 2, -1, 905)); // 903
+        data.extend(raw(Square::D2, Square::D4, 3, -1, -1)); // 904
+        data.extend(raw(Square::D7, Square::D5, 1, 900, -1)); // 905, cyclic link
+
+        let book = AbkBook::from_bytes(&data).expect("valid book");
+        assert_eq!(book.len(), 4);
+
+        let pos = Chess::default();
+        let entries = book.probe(&pos);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].games, 10);
+        assert_eq!(entries[1].games, 5);
+
+        // Link back to the root is not followed.
+        let pos = pos.play(&entries[1].m).expect("legal");
+        let d5 = book.probe(&pos).iter().find(|e| e.games == 1).expect("d5");
+        assert_eq!(d5.m.to(), Square::D5);
+
+        assert!(AbkBook::from_bytes(&data[1..]).is_err());
+    }
+}
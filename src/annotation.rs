@@ -0,0 +1,693 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Annotations embedded in PGN comments.
+//!
+//! Square highlights and arrows are stored in `[%csl]` and `[%cal]`
+//! commands, for example `[%csl Gd4,Rf7][%cal Ge2e4]`. The same shapes are
+//! used by the Lichess study API.
+//!
+//! Engine evaluations are stored in `[%eval]` commands, for example
+//! `[%eval 0.17,20]` or `[%eval #-3]`. See [`Eval`]. Moves can be
+//! judged by the evaluations before and after them, see [`judge()`].
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{annotation::{parse_shapes, format_shapes, Brush, Shape}, Square};
+//!
+//! let shapes = parse_shapes(b"Threatens mate. [%cal Rd1d8] [%csl Gd8]");
+//! assert_eq!(
+//!     shapes,
+//!     [
+//!         Shape::Highlight { brush: Brush::Green, square: Square::D8 },
+//!         Shape::Arrow { brush: Brush::Red, from: Square::D1, to: Square::D8 },
+//!     ]
+//! );
+//! assert_eq!(format_shapes(&shapes), "[%csl Gd8][%cal Rd1d8]");
+//! ```
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{
+    color::{ByColor, Color},
+    error::ErrorKind,
+    pgn::Nag,
+    square::Square,
+};
+
+/// Error when parsing an invalid shape.
+#[derive(Clone, Debug)]
+pub struct ParseShapeError;
+
+impl fmt::Display for ParseShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid shape")
+    }
+}
+
+impl Error for ParseShapeError {}
+
+impl ParseShapeError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidShape
+    }
+}
+
+/// Error when parsing an invalid engine evaluation.
+#[derive(Clone, Debug)]
+pub struct ParseEvalError;
+
+impl fmt::Display for ParseEvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid eval")
+    }
+}
+
+impl Error for ParseEvalError {}
+
+impl ParseEvalError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidEval
+    }
+}
+
+/// Color of a shape.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Brush {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl Brush {
+    pub const ALL: [Brush; 4] = [Brush::Green, Brush::Red, Brush::Yellow, Brush::Blue];
+
+    /// Gets the brush from its uppercase letter, like `G`.
+    pub fn from_char(ch: char) -> Option<Brush> {
+        Some(match ch {
+            'G' => Brush::Green,
+            'R' => Brush::Red,
+            'Y' => Brush::Yellow,
+            'B' => Brush::Blue,
+            _ => return None,
+        })
+    }
+
+    /// Gets the uppercase letter of the brush, like `G`.
+    pub fn char(self) -> char {
+        match self {
+            Brush::Green => 'G',
+            Brush::Red => 'R',
+            Brush::Yellow => 'Y',
+            Brush::Blue => 'B',
+        }
+    }
+
+    /// Gets the brush from its name in the Lichess API, like `green`.
+    pub fn from_name(name: &str) -> Option<Brush> {
+        Brush::ALL.into_iter().find(|brush| brush.name() == name)
+    }
+
+    /// Gets the name of the brush in the Lichess API, like `green`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Brush::Green => "green",
+            Brush::Red => "red",
+            Brush::Yellow => "yellow",
+            Brush::Blue => "blue",
+        }
+    }
+}
+
+/// A square highlight or an arrow.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Shape {
+    Highlight {
+        brush: Brush,
+        square: Square,
+    },
+    Arrow {
+        brush: Brush,
+        from: Square,
+        to: Square,
+    },
+}
+
+impl Shape {
+    /// Parses a shape like `Gd4` (highlight) or `Re2e4` (arrow).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseShapeError`] if the input is not a valid shape.
+    pub fn from_ascii(s: &[u8]) -> Result<Shape, ParseShapeError> {
+        let (&brush, squares) = s.split_first().ok_or(ParseShapeError)?;
+        let brush = Brush::from_char(char::from(brush)).ok_or(ParseShapeError)?;
+        let square = |s| Square::from_ascii(s).map_err(|_| ParseShapeError);
+        Ok(match squares.len() {
+            2 => Shape::Highlight {
+                brush,
+                square: square(squares)?,
+            },
+            4 => Shape::Arrow {
+                brush,
+                from: square(&squares[..2])?,
+                to: square(&squares[2..])?,
+            },
+            _ => return Err(ParseShapeError),
+        })
+    }
+
+    /// Gets the brush of the shape.
+    pub fn brush(self) -> Brush {
+        match self {
+            Shape::Highlight { brush, .. } | Shape::Arrow { brush, .. } => brush,
+        }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = ParseShapeError;
+
+    fn from_str(s: &str) -> Result<Shape, ParseShapeError> {
+        Shape::from_ascii(s.as_bytes())
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Shape::Highlight { brush, square } => write!(f, "{}{}", brush.char(), square),
+            Shape::Arrow { brush, from, to } => write!(f, "{}{}{}", brush.char(), from, to),
+        }
+    }
+}
+
+/// An engine score, from the point of view of White.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Score {
+    /// Advantage in centipawns, like `0.17` for `17`.
+    Centipawns(i32),
+    /// Mate in the given number of moves, like `#-3` for `-3` if Black
+    /// mates in three.
+    Mate(i32),
+}
+
+impl Score {
+    /// Gets the winning chances of White in the range from `-1.0` to
+    /// `1.0`, using the same model as Lichess.
+    ///
+    /// Centipawns are capped at ±1000. Mate in `n` counts as
+    /// `(21 - min(n, 10)) * 100` centipawns. `Mate(0)` means that the side
+    /// to move is already checkmated, and counts as a loss (`-1.0`).
+    pub fn winning_chances(self) -> f64 {
+        let cp = match self {
+            Score::Centipawns(cp) => cp.clamp(-1000, 1000),
+            Score::Mate(0) => return -1.0,
+            Score::Mate(moves) => (21 - moves.abs().min(10)) * 100 * moves.signum(),
+        };
+        2.0 / (1.0 + (-0.003_682_08 * f64::from(cp)).exp()) - 1.0
+    }
+
+    /// Gets the winning chances of `color`. See
+    /// [`Score::winning_chances()`].
+    pub fn winning_chances_for(self, color: Color) -> f64 {
+        color.fold_wb(1.0, -1.0) * self.winning_chances()
+    }
+}
+
+/// Classification of a bad move. See [`judge()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Judgement {
+    /// Lost at least `0.1` winning chances.
+    Inaccuracy,
+    /// Lost at least `0.2` winning chances.
+    Mistake,
+    /// Lost at least `0.3` winning chances.
+    Blunder,
+}
+
+impl Judgement {
+    /// Gets the NAG that marks the move: `?!`, `?` or `??`.
+    pub fn nag(self) -> Nag {
+        match self {
+            Judgement::Inaccuracy => Nag::DUBIOUS_MOVE,
+            Judgement::Mistake => Nag::MISTAKE,
+            Judgement::Blunder => Nag::BLUNDER,
+        }
+    }
+
+    /// Gets the name of the judgement, like `Inaccuracy`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+impl fmt::Display for Judgement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Judges a move by `mover`, given the evaluations before and after it,
+/// with the thresholds used by Lichess. Returns `None` if the move lost
+/// less than `0.1` winning chances (see [`Score::winning_chances()`]).
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::annotation::{judge, Judgement, Score};
+/// use shakmaty::Color;
+///
+/// let before = Score::Centipawns(30);
+/// assert_eq!(judge(Color::White, before, Score::Centipawns(0)), None);
+/// assert_eq!(judge(Color::White, before, Score::Centipawns(-120)), Some(Judgement::Mistake));
+/// assert_eq!(judge(Color::White, before, Score::Mate(-2)), Some(Judgement::Blunder));
+/// assert_eq!(judge(Color::Black, before, Score::Centipawns(120)), Some(Judgement::Inaccuracy));
+/// ```
+pub fn judge(mover: Color, before: Score, after: Score) -> Option<Judgement> {
+    let loss = before.winning_chances_for(mover) - after.winning_chances_for(mover);
+    if loss >= 0.3 {
+        Some(Judgement::Blunder)
+    } else if loss >= 0.2 {
+        Some(Judgement::Mistake)
+    } else if loss >= 0.1 {
+        Some(Judgement::Inaccuracy)
+    } else {
+        None
+    }
+}
+
+/// Judges all moves of a game, given the evaluation of the starting
+/// position (with `turn` to move), followed by the evaluation after each
+/// move. Returns one judgement per move.
+pub fn judge_moves(turn: Color, scores: &[Score]) -> Vec<Option<Judgement>> {
+    scores
+        .windows(2)
+        .zip([turn, !turn].into_iter().cycle())
+        .map(|(window, mover)| judge(mover, window[0], window[1]))
+        .collect()
+}
+
+/// Gets the accuracy of a move by `mover` in percent, given the
+/// evaluations before and after it, with the formula used by Lichess.
+///
+/// Moves that do not lose winning chances have an accuracy of `100.0`.
+pub fn move_accuracy(mover: Color, before: Score, after: Score) -> f64 {
+    let win_percent = |score: Score| 50.0 + 50.0 * score.winning_chances_for(mover);
+    let (before, after) = (win_percent(before), win_percent(after));
+    if after >= before {
+        return 100.0;
+    }
+    let raw = 103.166_810_071_164_9 * (-0.043_544_153_867_539_51 * (before - after)).exp()
+        - 3.166_924_740_191_411;
+    (raw + 1.0).clamp(0.0, 100.0)
+}
+
+/// Gets the accuracy of both players over a game in percent, given the
+/// evaluation of the starting position (with `turn` to move), followed by
+/// the evaluation after each move. `None` for players without moves.
+///
+/// Like Lichess, this is the average of the harmonic mean of the move
+/// accuracies, and their mean weighted by the volatility of the
+/// evaluation around each move. Volatile phases count more, so that a
+/// long tail of trivial moves does not hide the mistakes.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::annotation::{accuracy, Score};
+/// use shakmaty::Color;
+///
+/// let scores = [15, 20, 10, -300, -280].map(Score::Centipawns);
+/// let accuracy = accuracy(Color::White, &scores);
+/// assert!(accuracy.black.expect("black moved") > 95.0);
+/// assert!(accuracy.white.expect("white moved") < 60.0);
+/// ```
+pub fn accuracy(turn: Color, scores: &[Score]) -> ByColor<Option<f64>> {
+    let win_percents: Vec<f64> = scores
+        .iter()
+        .map(|score| 50.0 + 50.0 * score.winning_chances())
+        .collect();
+    let window_size = (scores.len() / 10).clamp(2, 8);
+
+    let mut accuracies = ByColor::<Vec<(f64, f64)>>::default();
+    for (i, (window, mover)) in scores
+        .windows(2)
+        .zip([turn, !turn].into_iter().cycle())
+        .enumerate()
+    {
+        let start = i.saturating_sub(window_size - 2);
+        let volatility = &win_percents[start..(start + window_size).min(win_percents.len())];
+        let mean = volatility.iter().sum::<f64>() / volatility.len() as f64;
+        let variance =
+            volatility.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / volatility.len() as f64;
+        let weight = variance.sqrt().clamp(0.5, 12.0);
+        accuracies
+            .get_mut(mover)
+            .push((move_accuracy(mover, window[0], window[1]), weight));
+    }
+
+    accuracies.map(|accuracies| {
+        if accuracies.is_empty() {
+            return None;
+        }
+        let weighted = accuracies.iter().map(|(a, w)| a * w).sum::<f64>()
+            / accuracies.iter().map(|(_, w)| w).sum::<f64>();
+        let harmonic = if accuracies.iter().any(|(a, _)| *a <= 0.0) {
+            0.0
+        } else {
+            accuracies.len() as f64 / accuracies.iter().map(|(a, _)| 1.0 / a).sum::<f64>()
+        };
+        Some((weighted + harmonic) / 2.0)
+    })
+}
+
+/// An engine evaluation, as stored in a `[%eval]` command.
+///
+/// The value of the command is the score in pawns (`0.17`) or moves to
+/// mate (`#-3`), optionally followed by the search depth (`0.17,20`).
+/// The name of the engine is not part of the standard command. It is
+/// stored in a separate `[%engine]` command, which other programs ignore.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::annotation::{format_eval, parse_eval, Eval, Score};
+///
+/// let eval = parse_eval(b"[%eval -1.5,24] [%engine Stockfish 16] Dubious.").expect("eval");
+/// assert_eq!(eval.score, Score::Centipawns(-150));
+/// assert_eq!(eval.depth, Some(24));
+/// assert_eq!(eval.engine.as_deref(), Some("Stockfish 16"));
+/// assert_eq!(format_eval(&eval), "[%eval -1.50,24][%engine Stockfish 16]");
+///
+/// assert_eq!("#3".parse::<Eval>()?, Eval::new(Score::Mate(3)));
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Eval {
+    /// The score.
+    pub score: Score,
+    /// Search depth, if known.
+    pub depth: Option<u32>,
+    /// Name of the engine, if known.
+    pub engine: Option<String>,
+}
+
+impl Eval {
+    /// Creates an evaluation with unknown depth and engine.
+    pub fn new(score: Score) -> Eval {
+        Eval {
+            score,
+            depth: None,
+            engine: None,
+        }
+    }
+
+    /// Parses the value of an `[%eval]` command, like `0.17,20` or `#-3`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseEvalError`] if the input is not a valid evaluation.
+    pub fn from_ascii(s: &[u8]) -> Result<Eval, ParseEvalError> {
+        let (score, depth) = match s.iter().position(|ch| *ch == b',') {
+            Some(comma) => (
+                &s[..comma],
+                Some(btoi::btou(&s[comma + 1..]).map_err(|_| ParseEvalError)?),
+            ),
+            None => (s, None),
+        };
+
+        let score = if let Some(mate) = score.strip_prefix(b"#") {
+            Score::Mate(btoi::btoi(mate).map_err(|_| ParseEvalError)?)
+        } else {
+            let (negative, unsigned) = match score.split_first() {
+                Some((b'-', rest)) => (true, rest),
+                Some((b'+', rest)) => (false, rest),
+                _ => (false, score),
+            };
+            let (pawns, fraction) = match unsigned.iter().position(|ch| *ch == b'.') {
+                Some(dot) => (&unsigned[..dot], &unsigned[dot + 1..]),
+                None => (unsigned, &b""[..]),
+            };
+            if (pawns.is_empty() && fraction.is_empty()) || fraction.len() > 2 {
+                return Err(ParseEvalError);
+            }
+            let mut centipawns: i32 = if pawns.is_empty() {
+                0
+            } else {
+                btoi::btou::<i32>(pawns)
+                    .ok()
+                    .and_then(|pawns| pawns.checked_mul(100))
+                    .ok_or(ParseEvalError)?
+            };
+            for (digit, scale) in fraction.iter().zip([10, 1]) {
+                if !digit.is_ascii_digit() {
+                    return Err(ParseEvalError);
+                }
+                centipawns = centipawns
+                    .checked_add(i32::from(digit - b'0') * scale)
+                    .ok_or(ParseEvalError)?;
+            }
+            Score::Centipawns(if negative { -centipawns } else { centipawns })
+        };
+
+        Ok(Eval {
+            score,
+            depth,
+            engine: None,
+        })
+    }
+}
+
+impl FromStr for Eval {
+    type Err = ParseEvalError;
+
+    fn from_str(s: &str) -> Result<Eval, ParseEvalError> {
+        Eval::from_ascii(s.as_bytes())
+    }
+}
+
+impl fmt::Display for Eval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.score {
+            Score::Centipawns(cp) => write!(
+                f,
+                "{}{}.{:02}",
+                if cp < 0 { "-" } else { "" },
+                cp.unsigned_abs() / 100,
+                cp.unsigned_abs() % 100
+            )?,
+            Score::Mate(moves) => write!(f, "#{}", moves)?,
+        }
+        if let Some(depth) = self.depth {
+            write!(f, ",{}", depth)?;
+        }
+        Ok(())
+    }
+}
+
+fn find_command<'a>(comment: &'a [u8], command: &[u8]) -> Option<&'a [u8]> {
+    let start = comment
+        .windows(command.len())
+        .position(|window| window == command)?
+        + command.len();
+    let rest = &comment[start..];
+    let end = rest.iter().position(|ch| *ch == b']')?;
+    let value = &rest[..end];
+    let first = value.iter().position(|ch| !ch.is_ascii_whitespace())?;
+    let last = value.iter().rposition(|ch| !ch.is_ascii_whitespace())?;
+    Some(&value[first..=last])
+}
+
+/// Parses the evaluation from the `[%eval]` command in a PGN comment,
+/// including the engine name from an `[%engine]` command, if any.
+pub fn parse_eval(comment: &[u8]) -> Option<Eval> {
+    let mut eval = Eval::from_ascii(find_command(comment, b"[%eval ")?).ok()?;
+    eval.engine = find_command(comment, b"[%engine ")
+        .map(|engine| String::from_utf8_lossy(engine).into_owned());
+    Some(eval)
+}
+
+/// Formats an evaluation as an `[%eval]` command for a PGN comment,
+/// followed by an `[%engine]` command if the engine is known.
+pub fn format_eval(eval: &Eval) -> String {
+    let mut formatted = format!("[%eval {}]", eval);
+    if let Some(ref engine) = eval.engine {
+        formatted.push_str(&format!("[%engine {}]", engine));
+    }
+    formatted
+}
+
+/// Removes all `[%eval]` and `[%engine]` commands from a PGN comment.
+pub fn remove_eval(comment: &str) -> String {
+    let mut result = String::with_capacity(comment.len());
+    let mut rest = comment;
+    while let Some(start) = ["[%eval ", "[%engine "]
+        .iter()
+        .filter_map(|command| rest.find(command))
+        .min()
+    {
+        result.push_str(&rest[..start]);
+        rest = match rest[start..].find(']') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Parses all shapes from the `[%csl]` and `[%cal]` commands in a PGN
+/// comment. Highlights come first, and invalid shapes are skipped.
+pub fn parse_shapes(comment: &[u8]) -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    for (command, highlight) in [(&b"[%csl"[..], true), (&b"[%cal"[..], false)] {
+        let mut rest = comment;
+        while let Some(start) = rest
+            .windows(command.len())
+            .position(|window| window == command)
+        {
+            rest = &rest[start + command.len()..];
+            let end = rest.iter().position(|ch| *ch == b']').unwrap_or(rest.len());
+            shapes.extend(
+                rest[..end]
+                    .split(|ch| *ch == b',' || ch.is_ascii_whitespace())
+                    .filter_map(|s| Shape::from_ascii(s).ok())
+                    .filter(|shape| matches!(shape, Shape::Highlight { .. }) == highlight),
+            );
+            rest = &rest[end..];
+        }
+    }
+    shapes
+}
+
+/// Formats shapes as `[%csl]` and `[%cal]` commands for a PGN comment.
+/// Commands without shapes are omitted.
+pub fn format_shapes(shapes: &[Shape]) -> String {
+    let mut formatted = String::new();
+    for (command, highlight) in [("[%csl ", true), ("[%cal ", false)] {
+        let mut shapes = shapes
+            .iter()
+            .filter(|shape| matches!(shape, Shape::Highlight { .. }) == highlight)
+            .peekable();
+        if shapes.peek().is_some() {
+            formatted.push_str(command);
+            for (i, shape) in shapes.enumerate() {
+                if i > 0 {
+                    formatted.push(',');
+                }
+                formatted.push_str(&shape.to_string());
+            }
+            formatted.push(']');
+        }
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shapes() {
+        for shape in ["Gd4", "Ba1h8", "Yh1h1"] {
+            assert_eq!(shape.parse::<Shape>().expect("valid").to_string(), shape);
+        }
+        for invalid in ["", "G", "Xd4", "Gd9", "Ge2e", "gd4"] {
+            assert!(invalid.parse::<Shape>().is_err(), "{}", invalid);
+        }
+
+        let shapes = parse_shapes(b"[%cal Ge2e4, Rd7d5,Xa1a2] text [%csl Yf7] [%csl Bc3");
+        assert_eq!(format_shapes(&shapes), "[%csl Yf7,Bc3][%cal Ge2e4,Rd7d5]");
+        assert_eq!(parse_shapes(format_shapes(&shapes).as_bytes()), shapes);
+        assert_eq!(format_shapes(&[]), "");
+
+        for brush in Brush::ALL {
+            assert_eq!(Brush::from_name(brush.name()), Some(brush));
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        for (value, score) in [
+            ("0.00", Score::Centipawns(0)),
+            ("-0.05", Score::Centipawns(-5)),
+            ("12.30", Score::Centipawns(1230)),
+            ("#0", Score::Mate(0)),
+            ("#-12", Score::Mate(-12)),
+        ] {
+            let eval = value.parse::<Eval>().expect("valid");
+            assert_eq!(eval.score, score);
+            assert_eq!(eval.to_string(), value);
+        }
+        assert_eq!(
+            "+.5".parse::<Eval>().expect("valid").score,
+            Score::Centipawns(50)
+        );
+        assert_eq!(
+            "3".parse::<Eval>().expect("valid").score,
+            Score::Centipawns(300)
+        );
+        for invalid in ["", ".", "0.123", "#", "1.5,", "a", "#1.5", "0.1x"] {
+            assert!(invalid.parse::<Eval>().is_err(), "{}", invalid);
+        }
+
+        let comment = " [%eval 0.17,20] [%clk 0:01:00] [%engine Stockfish] ";
+        let eval = parse_eval(comment.as_bytes()).expect("eval");
+        assert_eq!(eval.depth, Some(20));
+        assert_eq!(parse_eval(format_eval(&eval).as_bytes()), Some(eval));
+        assert_eq!(remove_eval(comment), "  [%clk 0:01:00]  ");
+        assert_eq!(parse_eval(b"[%eval x]"), None);
+    }
+
+    #[test]
+    fn test_judge_moves() {
+        let scores = [20, 40, 120, -50, -40].map(Score::Centipawns);
+        assert_eq!(
+            judge_moves(Color::White, &scores),
+            [
+                None,
+                Some(Judgement::Inaccuracy),
+                Some(Judgement::Blunder),
+                None
+            ]
+        );
+        assert_eq!(judge_moves(Color::Black, &scores[..1]), []);
+        assert!(Score::Mate(1).winning_chances() > Score::Centipawns(1000).winning_chances());
+        assert_eq!(move_accuracy(Color::White, scores[0], scores[1]), 100.0);
+        assert!(move_accuracy(Color::White, scores[2], scores[3]) < 60.0);
+        let accuracy = accuracy(Color::White, &scores);
+        assert!(accuracy.white.expect("white moved") < accuracy.black.expect("black moved"));
+        assert_eq!(super::accuracy(Color::White, &scores[..2]).black, None);
+
+        let black_mates = Score::Mate(-1).winning_chances_for(Color::Black);
+        assert!((black_mates - Score::Mate(1).winning_chances()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_winning_chances_mated() {
+        assert_eq!(Score::Mate(0).winning_chances(), -1.0);
+        assert!(Score::Mate(0).winning_chances() < Score::Mate(-1).winning_chances());
+    }
+}
@@ -16,6 +16,14 @@
 
 //! Attack and ray tables.
 //!
+//! All lookups are stateless and work directly on squares and bitboards, so
+//! they can be used without constructing a (legal) position, e.g. in board
+//! editors or trainers.
+//!
+//! On x86-64, sliding attacks are looked up using `PEXT` if BMI2 is enabled
+//! at compile time (e.g. with `-C target-cpu=native`). The `PEXT` tables are
+//! only included in such builds.
+//!
 //! # Example
 //!
 //! ```
@@ -36,7 +44,14 @@
 //! assert!(!attacks.contains(Square::H7));
 //! ```
 
-use crate::{bitboard::Bitboard, color::Color, magics, role::Role, square::Square, types::Piece};
+use crate::{
+    bitboard::{Bitboard, Direction},
+    color::Color,
+    magics,
+    role::Role,
+    square::Square,
+    types::Piece,
+};
 
 include!(concat!(env!("OUT_DIR"), "/attacks.rs")); // generated by build.rs
 
@@ -62,16 +77,44 @@ pub fn king_attacks(sq: Square) -> Bitboard {
 }
 
 /// Looks up attacks for a rook on `sq` with `occupied` squares.
+#[cfg(not(feature = "compact"))]
 #[inline]
 pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
-    let m = &magics::ROOK_MAGICS[usize::from(sq)];
+    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    {
+        pext::rook_attacks(sq, occupied)
+    }
+    #[cfg(all(
+        feature = "fancy-magic",
+        not(all(target_arch = "x86_64", target_feature = "bmi2"))
+    ))]
+    {
+        fancy::rook_attacks(sq, occupied)
+    }
+    #[cfg(not(any(
+        feature = "fancy-magic",
+        all(target_arch = "x86_64", target_feature = "bmi2")
+    )))]
+    {
+        let m = &magics::ROOK_MAGICS[usize::from(sq)];
+
+        // Safety: The attack table was generated with sufficient size
+        // for all relevant occupancies (all subsets of m.mask). Omitting bounds
+        // checks is worth about 2% in move generation and perft.
+        let idx = (m.factor.wrapping_mul(occupied.0 & m.mask) >> (64 - 12)) as usize + m.offset;
+        debug_assert!(idx < ATTACKS.len());
+        Bitboard(unsafe { *ATTACKS.get_unchecked(idx) })
+    }
+}
 
-    // Safety: The attack table was generated with sufficient size
-    // for all relevant occupancies (all subsets of m.mask). Omitting bounds
-    // checks is worth about 2% in move generation and perft.
-    let idx = (m.factor.wrapping_mul(occupied.0 & m.mask) >> (64 - 12)) as usize + m.offset;
-    debug_assert!(idx < ATTACKS.len());
-    Bitboard(unsafe { *ATTACKS.get_unchecked(idx) })
+/// Computes attacks for a rook on `sq` with `occupied` squares.
+#[cfg(feature = "compact")]
+#[inline]
+pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    Bitboard(
+        line_attacks(sq, occupied, Bitboard::from_file(sq.file()).0)
+            | line_attacks(sq, occupied, Bitboard::from_rank(sq.rank()).0),
+    )
 }
 
 /// Gets the set of potential blocking squares for a rook on `sq`.
@@ -98,16 +141,61 @@ pub fn rook_mask(sq: Square) -> Bitboard {
 }
 
 /// Looks up attacks for a bishop on `sq` with `occupied` squares.
+#[cfg(not(feature = "compact"))]
+#[inline]
+pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+    {
+        pext::bishop_attacks(sq, occupied)
+    }
+    #[cfg(all(
+        feature = "fancy-magic",
+        not(all(target_arch = "x86_64", target_feature = "bmi2"))
+    ))]
+    {
+        fancy::bishop_attacks(sq, occupied)
+    }
+    #[cfg(not(any(
+        feature = "fancy-magic",
+        all(target_arch = "x86_64", target_feature = "bmi2")
+    )))]
+    {
+        let m = &magics::BISHOP_MAGICS[usize::from(sq)];
+
+        // Safety: The attack table was generated with sufficient size
+        // for all relevant occupancies (all subsets of m.mask). Omitting bounds
+        // checks is worth about 2% in move generation and perft.
+        let idx = (m.factor.wrapping_mul(occupied.0 & m.mask) >> (64 - 9)) as usize + m.offset;
+        debug_assert!(idx < ATTACKS.len());
+        Bitboard(unsafe { *ATTACKS.get_unchecked(idx) })
+    }
+}
+
+/// Computes attacks for a bishop on `sq` with `occupied` squares.
+#[cfg(feature = "compact")]
 #[inline]
 pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
-    let m = &magics::BISHOP_MAGICS[usize::from(sq)];
+    let file = i32::from(sq.file());
+    let rank = i32::from(sq.rank());
+    let diagonal = Bitboard(0x8040_2010_0804_0201).shift(8 * (rank - file));
+    let anti_diagonal = Bitboard(0x0102_0408_1020_4080).shift(8 * (rank + file - 7));
+    Bitboard(line_attacks(sq, occupied, diagonal.0) | line_attacks(sq, occupied, anti_diagonal.0))
+}
 
-    // Safety: The attack table was generated with sufficient size
-    // for all relevant occupancies (all subsets of m.mask). Omitting bounds
-    // checks is worth about 2% in move generation and perft.
-    let idx = (m.factor.wrapping_mul(occupied.0 & m.mask) >> (64 - 9)) as usize + m.offset;
-    debug_assert!(idx < ATTACKS.len());
-    Bitboard(unsafe { *ATTACKS.get_unchecked(idx) })
+/// Attacks of a slider on `sq` along a single `line` through `sq`, using
+/// hyperbola quintessence instead of lookup tables.
+#[cfg(any(feature = "compact", test))]
+#[inline]
+fn line_attacks(sq: Square, occupied: Bitboard, line: u64) -> u64 {
+    let slider = Bitboard::from_square(sq).0;
+    let line = line & !slider;
+    let occupied = occupied.0 & line;
+    let forward = occupied.wrapping_sub(slider.wrapping_mul(2));
+    let reverse = occupied
+        .reverse_bits()
+        .wrapping_sub(slider.reverse_bits().wrapping_mul(2))
+        .reverse_bits();
+    (forward ^ reverse) & line
 }
 
 /// Gets the set of potential blocking squares for a bishop on `sq`.
@@ -152,6 +240,177 @@ pub fn attacks(sq: Square, piece: Piece, occupied: Bitboard) -> Bitboard {
     }
 }
 
+/// Fancy magic bitboards: One index bit per relevant occupancy, so that
+/// the table is as dense as with `PEXT`, at the cost of a variable shift.
+#[cfg(all(
+    feature = "fancy-magic",
+    not(feature = "compact"),
+    not(all(target_arch = "x86_64", target_feature = "bmi2"))
+))]
+mod fancy {
+    use super::{
+        FANCY_ATTACKS, FANCY_BISHOP_OFFSETS, FANCY_BISHOP_SHIFTS, FANCY_ROOK_OFFSETS,
+        FANCY_ROOK_SHIFTS,
+    };
+    use crate::{bitboard::Bitboard, magics, square::Square};
+
+    #[inline]
+    fn lookup(mask: u64, factor: u64, shift: u32, offset: usize, occupied: Bitboard) -> Bitboard {
+        // Safety: The attack table was generated for all relevant
+        // occupancies (all subsets of mask).
+        let idx = (factor.wrapping_mul(occupied.0 & mask) >> shift) as usize + offset;
+        debug_assert!(idx < FANCY_ATTACKS.len());
+        Bitboard(unsafe { *FANCY_ATTACKS.get_unchecked(idx) })
+    }
+
+    #[inline]
+    pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+        let i = usize::from(sq);
+        lookup(
+            magics::ROOK_MAGICS[i].mask,
+            magics::FANCY_ROOK_FACTORS[i],
+            FANCY_ROOK_SHIFTS[i],
+            FANCY_ROOK_OFFSETS[i],
+            occupied,
+        )
+    }
+
+    #[inline]
+    pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+        let i = usize::from(sq);
+        lookup(
+            magics::BISHOP_MAGICS[i].mask,
+            magics::FANCY_BISHOP_FACTORS[i],
+            FANCY_BISHOP_SHIFTS[i],
+            FANCY_BISHOP_OFFSETS[i],
+            occupied,
+        )
+    }
+}
+
+/// Lookups using the `PEXT` instruction from BMI2, with separate, densely
+/// packed attack tables. Note that `PEXT` is microcoded and slow on AMD
+/// CPUs before Zen 3.
+#[cfg(all(
+    target_arch = "x86_64",
+    target_feature = "bmi2",
+    not(feature = "compact")
+))]
+mod pext {
+    use std::arch::x86_64::_pext_u64;
+
+    use super::{BISHOP_PEXT_OFFSETS, PEXT_ATTACKS, ROOK_PEXT_OFFSETS};
+    use crate::{bitboard::Bitboard, magics, square::Square};
+
+    #[inline]
+    pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+        let mask = magics::ROOK_MAGICS[usize::from(sq)].mask;
+        let offset = ROOK_PEXT_OFFSETS[usize::from(sq)];
+
+        // Safety: BMI2 is enabled at compile time. The attack table has an
+        // entry for each subset of mask.
+        Bitboard(unsafe {
+            *PEXT_ATTACKS.get_unchecked(offset + _pext_u64(occupied.0, mask) as usize)
+        })
+    }
+
+    #[inline]
+    pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+        let mask = magics::BISHOP_MAGICS[usize::from(sq)].mask;
+        let offset = BISHOP_PEXT_OFFSETS[usize::from(sq)];
+
+        // Safety: BMI2 is enabled at compile time. The attack table has an
+        // entry for each subset of mask.
+        Bitboard(unsafe {
+            *PEXT_ATTACKS.get_unchecked(offset + _pext_u64(occupied.0, mask) as usize)
+        })
+    }
+}
+
+/// Looks up attacks for all pawns of `color` in `pawns`.
+///
+/// # Example
+///
+/// ```
+/// use shakmaty::{attacks, Bitboard, Color, Rank};
+///
+/// let attacks = attacks::pawn_attacks_set(Color::White, Bitboard::from(Rank::Second));
+/// assert_eq!(attacks, Bitboard::from(Rank::Third));
+/// ```
+#[inline]
+pub fn pawn_attacks_set(color: Color, pawns: Bitboard) -> Bitboard {
+    match color {
+        Color::White => {
+            Direction::NorthWest.translate(pawns) | Direction::NorthEast.translate(pawns)
+        }
+        Color::Black => {
+            Direction::SouthWest.translate(pawns) | Direction::SouthEast.translate(pawns)
+        }
+    }
+}
+
+/// Looks up attacks for all knights in `knights`.
+pub fn knight_attacks_set(knights: Bitboard) -> Bitboard {
+    knights
+        .into_iter()
+        .fold(Bitboard::EMPTY, |acc, sq| acc | knight_attacks(sq))
+}
+
+/// Looks up attacks for all kings in `kings`.
+pub fn king_attacks_set(kings: Bitboard) -> Bitboard {
+    kings
+        .into_iter()
+        .fold(Bitboard::EMPTY, |acc, sq| acc | king_attacks(sq))
+}
+
+/// Looks up attacks for all rooks in `rooks` with `occupied` squares.
+pub fn rook_attacks_set(rooks: Bitboard, occupied: Bitboard) -> Bitboard {
+    rooks
+        .into_iter()
+        .fold(Bitboard::EMPTY, |acc, sq| acc | rook_attacks(sq, occupied))
+}
+
+/// Looks up attacks for all bishops in `bishops` with `occupied` squares.
+pub fn bishop_attacks_set(bishops: Bitboard, occupied: Bitboard) -> Bitboard {
+    bishops.into_iter().fold(Bitboard::EMPTY, |acc, sq| {
+        acc | bishop_attacks(sq, occupied)
+    })
+}
+
+/// Looks up attacks for all queens in `queens` with `occupied` squares.
+pub fn queen_attacks_set(queens: Bitboard, occupied: Bitboard) -> Bitboard {
+    rook_attacks_set(queens, occupied) | bishop_attacks_set(queens, occupied)
+}
+
+/// Looks up attacks for `piece` on all squares in `squares` with `occupied`
+/// squares.
+///
+/// This is the union of [`attacks()`] for each square, and does not require
+/// the pieces to actually be placed on a board.
+///
+/// # Example
+///
+/// ```
+/// use shakmaty::{attacks, Bitboard, Color, Square};
+///
+/// let rooks = Bitboard::from(Square::A1) | Square::H1;
+/// let occupied = rooks | Square::E1;
+/// let attacks = attacks::attacks_set(rooks, Color::White.rook(), occupied);
+/// assert!(attacks.contains(Square::E1));
+/// assert!(attacks.contains(Square::H8));
+/// assert!(!attacks.contains(Square::A1));
+/// ```
+pub fn attacks_set(squares: Bitboard, piece: Piece, occupied: Bitboard) -> Bitboard {
+    match piece.role {
+        Role::Pawn => pawn_attacks_set(piece.color, squares),
+        Role::Knight => knight_attacks_set(squares),
+        Role::Bishop => bishop_attacks_set(squares, occupied),
+        Role::Rook => rook_attacks_set(squares, occupied),
+        Role::Queen => queen_attacks_set(squares, occupied),
+        Role::King => king_attacks_set(squares),
+    }
+}
+
 /// The rank, file or diagonal with the two squares (or an empty [`Bitboard`]
 /// if they are not aligned).
 ///
@@ -223,4 +482,79 @@ mod tests {
             Bitboard(0x8370808000000)
         );
     }
+
+    #[test]
+    fn test_line_attacks() {
+        fn slow(sq: Square, occupied: Bitboard, deltas: [(i32, i32); 4]) -> Bitboard {
+            let mut attacks = Bitboard(0);
+            for (df, dr) in deltas {
+                let mut file = sq.file().offset(df);
+                let mut rank = sq.rank().offset(dr);
+                while let (Some(f), Some(r)) = (file, rank) {
+                    let to = Square::from_coords(f, r);
+                    attacks.add(to);
+                    if occupied.contains(to) {
+                        break;
+                    }
+                    file = f.offset(df);
+                    rank = r.offset(dr);
+                }
+            }
+            attacks
+        }
+
+        let mut occupied = Bitboard(0x3f7f_2880_2826_f5b9);
+        for _ in 0..32 {
+            for sq in Square::ALL {
+                let file = Bitboard::from_file(sq.file()).0;
+                let rank = Bitboard::from_rank(sq.rank()).0;
+                assert_eq!(
+                    Bitboard(line_attacks(sq, occupied, file) | line_attacks(sq, occupied, rank)),
+                    slow(sq, occupied, [(0, 1), (0, -1), (1, 0), (-1, 0)])
+                );
+                assert_eq!(
+                    rook_attacks(sq, occupied),
+                    slow(sq, occupied, [(0, 1), (0, -1), (1, 0), (-1, 0)])
+                );
+                assert_eq!(
+                    bishop_attacks(sq, occupied),
+                    slow(sq, occupied, [(1, 1), (1, -1), (-1, 1), (-1, -1)])
+                );
+            }
+            occupied = Bitboard(occupied.0.rotate_left(13) ^ occupied.0.wrapping_mul(0x9e37_79b9));
+        }
+    }
+
+    #[test]
+    fn test_attacks_set() {
+        let occupied = Bitboard(0x3f7f28802826f5b9);
+        for color in Color::ALL {
+            for role in Role::ALL {
+                let piece = role.of(color);
+                let expected = occupied.into_iter().fold(Bitboard::EMPTY, |acc, sq| {
+                    acc | attacks(sq, piece, occupied)
+                });
+                assert_eq!(attacks_set(occupied, piece, occupied), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sliding_attacks_set() {
+        let mut occupied = Bitboard(0x3f7f_2880_2826_f5b9);
+        for _ in 0..32 {
+            let (rooks, bishops) = occupied.into_iter().fold(
+                (Bitboard::EMPTY, Bitboard::EMPTY),
+                |(rooks, bishops), sq| {
+                    (
+                        rooks | rook_attacks(sq, occupied),
+                        bishops | bishop_attacks(sq, occupied),
+                    )
+                },
+            );
+            assert_eq!(rook_attacks_set(occupied, occupied), rooks);
+            assert_eq!(bishop_attacks_set(occupied, occupied), bishops);
+            occupied = Bitboard(occupied.0.rotate_left(13) ^ occupied.0.wrapping_mul(0x9e37_79b9));
+        }
+    }
 }
@@ -132,6 +132,25 @@ impl Bitboard {
         })
     }
 
+    /// Moves all squares a single step in the given direction. Unlike
+    /// [`Bitboard::shift()`], squares do not wrap around to the other side
+    /// of the board.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{bitboard::Direction, Bitboard, Square};
+    ///
+    /// let bitboard = Bitboard::from(Square::H4) | Square::A5;
+    /// assert_eq!(bitboard.translate(Direction::East), Bitboard::from(Square::B5));
+    /// assert_eq!(bitboard.translate(Direction::NorthWest), Bitboard::from(Square::G5));
+    /// ```
+    #[must_use]
+    #[inline]
+    pub fn translate(self, direction: Direction) -> Bitboard {
+        direction.translate(self)
+    }
+
     /// Tests if `self` is non-empty.
     ///
     /// # Examples
@@ -163,8 +182,8 @@ impl Bitboard {
 
     /// Tests if `self` contains the given square.
     #[inline]
-    pub fn contains(self, sq: Square) -> bool {
-        (self & Bitboard::from_square(sq)).any()
+    pub const fn contains(self, sq: Square) -> bool {
+        self.0 & Bitboard::from_square(sq).0 != 0
     }
 
     /// Adds `squares`.
@@ -296,7 +315,7 @@ impl Bitboard {
 
     /// Returns the first square, if any.
     #[inline]
-    pub fn first(self) -> Option<Square> {
+    pub const fn first(self) -> Option<Square> {
         if self.is_empty() {
             None
         } else {
@@ -312,10 +331,25 @@ impl Bitboard {
 
     /// Returns `self` without the first square.
     #[inline]
-    pub fn without_first(self) -> Bitboard {
-        let mut bb = self;
-        bb.discard_first();
-        bb
+    pub const fn without_first(self) -> Bitboard {
+        Bitboard(self.0 & self.0.wrapping_sub(1))
+    }
+
+    /// Returns a bitboard containing only the first square, or an empty
+    /// bitboard if `self` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Bitboard, Square};
+    ///
+    /// assert_eq!(Bitboard::CENTER.isolate_first(), Bitboard::from(Square::D4));
+    /// assert_eq!(Bitboard::EMPTY.isolate_first(), Bitboard::EMPTY);
+    /// ```
+    #[must_use]
+    #[inline]
+    pub const fn isolate_first(self) -> Bitboard {
+        Bitboard(self.0 & self.0.wrapping_neg())
     }
 
     /// Removes and returns the last square, if any.
@@ -353,10 +387,26 @@ impl Bitboard {
 
     /// Tests if there is more than one square in `self`.
     #[inline]
-    pub fn more_than_one(self) -> bool {
+    pub const fn more_than_one(self) -> bool {
         self.without_first().any()
     }
 
+    /// Tests if there is exactly one square in `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Bitboard, Square};
+    ///
+    /// assert!(Bitboard::from(Square::D4).exactly_one());
+    /// assert!(!Bitboard::EMPTY.exactly_one());
+    /// assert!(!Bitboard::CENTER.exactly_one());
+    /// ```
+    #[inline]
+    pub const fn exactly_one(self) -> bool {
+        self.any() && !self.more_than_one()
+    }
+
     /// Gets the only square in the set, if there is exactly one.
     ///
     /// # Examples
@@ -368,7 +418,7 @@ impl Bitboard {
     /// assert_eq!(Bitboard::from(Rank::First).single_square(), None);
     /// ```
     #[inline]
-    pub fn single_square(self) -> Option<Square> {
+    pub const fn single_square(self) -> Option<Square> {
         if self.more_than_one() {
             None
         } else {
@@ -708,6 +758,23 @@ impl Bitboard {
     /// ```
     pub const CENTER: Bitboard = Bitboard(0x0000_0018_1800_0000);
 
+    /// The sixteen squares of the extended center.
+    ///
+    /// ```
+    /// # use shakmaty::Bitboard;
+    /// #
+    /// # let bitboard = Bitboard::EXTENDED_CENTER;
+    /// // . . . . . . . .
+    /// // . . . . . . . .
+    /// // . . 1 1 1 1 . .
+    /// // . . 1 1 1 1 . .
+    /// // . . 1 1 1 1 . .
+    /// // . . 1 1 1 1 . .
+    /// // . . . . . . . .
+    /// // . . . . . . . .
+    /// ```
+    pub const EXTENDED_CENTER: Bitboard = Bitboard(0x0000_3c3c_3c3c_0000);
+
     /// The northern half of the board.
     ///
     /// ```
@@ -775,6 +842,54 @@ impl Bitboard {
     /// // . . . . 1 1 1 1
     /// ```
     pub const EAST: Bitboard = Bitboard(0xf0f0_f0f0_f0f0_f0f0);
+
+    /// All squares on the a-file.
+    pub const FILE_A: Bitboard = Bitboard::from_file(File::A);
+
+    /// All squares on the b-file.
+    pub const FILE_B: Bitboard = Bitboard::from_file(File::B);
+
+    /// All squares on the c-file.
+    pub const FILE_C: Bitboard = Bitboard::from_file(File::C);
+
+    /// All squares on the d-file.
+    pub const FILE_D: Bitboard = Bitboard::from_file(File::D);
+
+    /// All squares on the e-file.
+    pub const FILE_E: Bitboard = Bitboard::from_file(File::E);
+
+    /// All squares on the f-file.
+    pub const FILE_F: Bitboard = Bitboard::from_file(File::F);
+
+    /// All squares on the g-file.
+    pub const FILE_G: Bitboard = Bitboard::from_file(File::G);
+
+    /// All squares on the h-file.
+    pub const FILE_H: Bitboard = Bitboard::from_file(File::H);
+
+    /// All squares on the 1st rank.
+    pub const RANK_1: Bitboard = Bitboard::from_rank(Rank::First);
+
+    /// All squares on the 2nd rank.
+    pub const RANK_2: Bitboard = Bitboard::from_rank(Rank::Second);
+
+    /// All squares on the 3rd rank.
+    pub const RANK_3: Bitboard = Bitboard::from_rank(Rank::Third);
+
+    /// All squares on the 4th rank.
+    pub const RANK_4: Bitboard = Bitboard::from_rank(Rank::Fourth);
+
+    /// All squares on the 5th rank.
+    pub const RANK_5: Bitboard = Bitboard::from_rank(Rank::Fifth);
+
+    /// All squares on the 6th rank.
+    pub const RANK_6: Bitboard = Bitboard::from_rank(Rank::Sixth);
+
+    /// All squares on the 7th rank.
+    pub const RANK_7: Bitboard = Bitboard::from_rank(Rank::Seventh);
+
+    /// All squares on the 8th rank.
+    pub const RANK_8: Bitboard = Bitboard::from_rank(Rank::Eighth);
 }
 
 /// Square masks.
@@ -810,32 +925,138 @@ const FILES: [u64; 8] = {
     masks
 };
 
-#[derive(Copy, Clone)]
-pub(crate) enum Direction {
-    NorthWest,
+/// Layout of squares in the bits of an integer mask, file by file and rank
+/// by rank, starting at the bottom left.
+///
+/// [`Bitboard`] uses the [`Standard`] 8x8 geometry. Scaffolding that is
+/// written against this trait can be reused for larger boards (for example
+/// 10x8 with `u128` masks).
+pub(crate) trait Geometry {
+    /// Integer with (at least) one bit per square.
+    type Mask: Copy
+        + ops::BitAnd<Output = Self::Mask>
+        + ops::Not<Output = Self::Mask>
+        + ops::Shl<u32, Output = Self::Mask>
+        + ops::Shr<u32, Output = Self::Mask>;
+
+    /// Number of files.
+    const FILES: u32;
+
+    /// Mask of all squares on the board.
+    fn board() -> Self::Mask;
+
+    /// Mask of all squares on the given file.
+    fn file(file: u32) -> Self::Mask;
+
+    /// Moves all squares by `files` to the right and `ranks` to the top.
+    /// Squares that would leave the board are discarded, rather than
+    /// wrapping around to the other side.
+    #[inline(always)]
+    fn translate(mut mask: Self::Mask, files: i32, ranks: i32) -> Self::Mask {
+        if files >= 0 {
+            for file in 0..files.unsigned_abs() {
+                mask = mask & !Self::file(Self::FILES - 1 - file);
+            }
+        } else {
+            for file in 0..files.unsigned_abs() {
+                mask = mask & !Self::file(file);
+            }
+        }
+        let offset = ranks * Self::FILES as i32 + files;
+        Self::board()
+            & if offset >= 0 {
+                mask << offset.unsigned_abs()
+            } else {
+                mask >> offset.unsigned_abs()
+            }
+    }
+}
+
+/// The standard 8x8 board with `u64` masks.
+pub(crate) struct Standard;
+
+impl Geometry for Standard {
+    type Mask = u64;
+
+    const FILES: u32 = 8;
+
+    #[inline(always)]
+    fn board() -> u64 {
+        !0
+    }
+
+    #[inline(always)]
+    fn file(file: u32) -> u64 {
+        FILES[file as usize]
+    }
+}
+
+/// One of the eight directions a [`Bitboard`] can be translated in.
+///
+/// North is towards the eighth rank, east is towards the h-file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Direction {
+    North,
     NorthEast,
-    SouthWest,
+    East,
     SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
 }
 
 impl Direction {
+    /// All directions, clockwise starting with [`Direction::North`].
+    pub const ALL: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    /// Square index offset of a single step in this direction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{bitboard::Direction, Square};
+    ///
+    /// assert_eq!(Square::E4.offset(Direction::NorthWest.offset()), Some(Square::D5));
+    /// ```
     #[inline(always)]
-    pub fn offset(self) -> i32 {
+    pub const fn offset(self) -> i32 {
         match self {
-            Direction::NorthWest => 7,
-            Direction::SouthWest => -9,
+            Direction::North => 8,
             Direction::NorthEast => 9,
+            Direction::East => 1,
             Direction::SouthEast => -7,
+            Direction::South => -8,
+            Direction::SouthWest => -9,
+            Direction::West => -1,
+            Direction::NorthWest => 7,
         }
     }
 
+    /// Moves all squares of `bitboard` a single step in this direction.
+    /// Squares that would leave the board are discarded, rather than
+    /// wrapping around to the other side.
+    #[must_use]
     #[inline(always)]
     pub fn translate(self, bitboard: Bitboard) -> Bitboard {
         Bitboard(match self {
-            Direction::NorthWest => (bitboard.0 & !FILES[0]) << 7,
-            Direction::SouthWest => (bitboard.0 & !FILES[0]) >> 9,
-            Direction::NorthEast => (bitboard.0 & !FILES[7]) << 9,
-            Direction::SouthEast => (bitboard.0 & !FILES[7]) >> 7,
+            Direction::North => Standard::translate(bitboard.0, 0, 1),
+            Direction::NorthEast => Standard::translate(bitboard.0, 1, 1),
+            Direction::East => Standard::translate(bitboard.0, 1, 0),
+            Direction::SouthEast => Standard::translate(bitboard.0, 1, -1),
+            Direction::South => Standard::translate(bitboard.0, 0, -1),
+            Direction::SouthWest => Standard::translate(bitboard.0, -1, -1),
+            Direction::West => Standard::translate(bitboard.0, -1, 0),
+            Direction::NorthWest => Standard::translate(bitboard.0, -1, 1),
         })
     }
 }
@@ -1114,13 +1335,86 @@ impl FusedIterator for CarryRippler {}
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_geometry() {
+        struct Capablanca;
+
+        impl Geometry for Capablanca {
+            type Mask = u128;
+
+            const FILES: u32 = 10;
+
+            fn board() -> u128 {
+                (1 << (Self::FILES * 8)) - 1
+            }
+
+            fn file(file: u32) -> u128 {
+                (0..8).fold(0, |mask, rank| mask | 1 << (rank * Self::FILES + file))
+            }
+        }
+
+        let corners = Capablanca::file(0) ^ Capablanca::file(9);
+        assert_eq!(
+            Capablanca::translate(corners, 1, 1),
+            Capablanca::file(1) & !(1 << 1)
+        );
+        assert_eq!(Capablanca::translate(corners, -1, 0), Capablanca::file(8));
+
+        for sq in Square::ALL {
+            let bb = Bitboard::from_square(sq);
+            for (files, ranks) in [(1, 0), (-1, 0), (0, 1), (0, -1), (2, 1), (-1, -2)] {
+                let expected = sq
+                    .file()
+                    .offset(files)
+                    .zip(sq.rank().offset(ranks))
+                    .map_or(Bitboard(0), |(file, rank)| {
+                        Bitboard::from_square(Square::from_coords(file, rank))
+                    });
+                assert_eq!(Bitboard(Standard::translate(bb.0, files, ranks)), expected);
+            }
+        }
+    }
+
     #[test]
     fn test_more_than_one() {
-        assert_eq!(Bitboard(0).more_than_one(), false);
-        assert_eq!(Bitboard(1).more_than_one(), false);
-        assert_eq!(Bitboard(2).more_than_one(), false);
-        assert_eq!(Bitboard(3).more_than_one(), true);
-        assert_eq!(Bitboard::FULL.more_than_one(), true);
+        assert!(!Bitboard(0).more_than_one());
+        assert!(!Bitboard(1).more_than_one());
+        assert!(!Bitboard(2).more_than_one());
+        assert!(Bitboard(3).more_than_one());
+        assert!(Bitboard::FULL.more_than_one());
+    }
+
+    #[test]
+    fn test_isolate_first() {
+        for bb in [
+            Bitboard(0),
+            Bitboard(1),
+            Bitboard::DARK_SQUARES,
+            Bitboard(1 << 63),
+        ] {
+            assert_eq!(
+                bb.isolate_first(),
+                bb.first().map_or(Bitboard(0), Bitboard::from)
+            );
+        }
+    }
+
+    #[test]
+    fn test_translate() {
+        for dir in Direction::ALL {
+            for sq in Square::ALL {
+                let expected = sq
+                    .offset(dir.offset())
+                    .filter(|to| to.distance(sq) == 1)
+                    .map_or(Bitboard(0), Bitboard::from);
+                assert_eq!(Bitboard::from(sq).translate(dir), expected);
+            }
+            let diagonal = dir.offset().abs() == 7 || dir.offset().abs() == 9;
+            assert_eq!(
+                Bitboard::FULL.translate(dir).count(),
+                if diagonal { 49 } else { 56 }
+            );
+        }
     }
 
     #[test]
@@ -1179,4 +1473,46 @@ mod tests {
     fn test_binary() {
         assert_eq!(format!("{:#0b}", Bitboard(42)), format!("{:#0b}", 42));
     }
+
+    #[test]
+    fn test_const_tables() {
+        const KING_ATTACKS: [Bitboard; 64] = {
+            let mut table = [Bitboard::EMPTY; 64];
+            let mut i = 0;
+            while i < 64 {
+                let from = Square::new(i);
+                let mut j = 0;
+                while j < 64 {
+                    let to = Square::new(j);
+                    if from.distance(to) == 1 {
+                        table[i as usize] =
+                            Bitboard(table[i as usize].0 | Bitboard::from_square(to).0);
+                    }
+                    j += 1;
+                }
+                i += 1;
+            }
+            table
+        };
+
+        for sq in Square::ALL {
+            assert_eq!(
+                KING_ATTACKS[usize::from(sq)],
+                crate::attacks::king_attacks(sq)
+            );
+        }
+
+        assert_eq!(
+            Bitboard::FILE_C | Bitboard::RANK_7,
+            Bitboard::from(File::C) | Rank::Seventh
+        );
+        assert_eq!(
+            Bitboard::EXTENDED_CENTER,
+            Square::ALL
+                .into_iter()
+                .filter(|sq| (2..6).contains(&u32::from(sq.file()))
+                    && (2..6).contains(&u32::from(sq.rank())))
+                .collect()
+        );
+    }
 }
@@ -22,8 +22,34 @@ use std::{
     iter::{FromIterator, FusedIterator},
 };
 
+use bitflags::bitflags;
+
 use crate::{attacks, Bitboard, ByColor, ByRole, Color, File, Piece, Rank, Role, Square};
 
+bitflags! {
+    /// Plausibility checks for the material on a [`Board`]. See
+    /// [`Board::check_material()`].
+    pub struct MaterialChecks: u32 {
+        /// A side has more than one king.
+        const TOO_MANY_KINGS = 1 << 0;
+
+        /// A side has more than 8 pawns.
+        const TOO_MANY_PAWNS = 1 << 1;
+
+        /// A side has more than 16 pieces.
+        const TOO_MANY_PIECES = 1 << 2;
+
+        /// A side has more promoted pieces than missing pawns, counting
+        /// pieces beyond one queen and two rooks, bishops or knights as
+        /// promoted.
+        const UNACCOUNTED_PROMOTIONS = 1 << 3;
+
+        /// Like [`MaterialChecks::UNACCOUNTED_PROMOTIONS`], but counting
+        /// bishops beyond one on each square color as promoted.
+        const BISHOP_COLORS = 1 << 4;
+    }
+}
+
 /// [`Piece`] positions on a board.
 ///
 /// # Examples
@@ -117,6 +143,60 @@ impl Board {
         }
     }
 
+    /// Tests if the board is one of the 960 starting positions of
+    /// Chess960: Pawns on the second and seventh rank, a full set of pieces
+    /// on each back rank, mirrored for both sides, with bishops on squares
+    /// of opposite colors and the king between the rooks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::Board;
+    ///
+    /// assert!(Board::default().is_valid_chess960_start());
+    ///
+    /// let board = Board::from_ascii_board_fen(b"bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR")?;
+    /// assert!(board.is_valid_chess960_start());
+    ///
+    /// // Bishops on squares of the same color.
+    /// let board = Board::from_ascii_board_fen(b"bnbqkrnr/pppppppp/8/8/8/8/PPPPPPPP/BNBQKRNR")?;
+    /// assert!(!board.is_valid_chess960_start());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_valid_chess960_start(&self) -> bool {
+        let backrank = Bitboard::from(Rank::First);
+        if self.white() != backrank | Rank::Second
+            || self.black() != Bitboard::from(Rank::Seventh) | Rank::Eighth
+            || self.pawns() != Bitboard::from(Rank::Second) | Rank::Seventh
+        {
+            return false;
+        }
+
+        for role in Role::ALL {
+            let pieces = self.by_role(role) & backrank;
+            if pieces.flip_vertical() != self.by_role(role) & Rank::Eighth {
+                return false;
+            }
+        }
+
+        let material = self.material_side(Color::White);
+        if material.king != 1
+            || material.queen != 1
+            || material.rook != 2
+            || material.bishop != 2
+            || material.knight != 2
+        {
+            return false;
+        }
+
+        let bishops = self.bishops() & backrank;
+        let rooks = self.rooks() & backrank;
+        let king = self.kings() & backrank;
+        (bishops & Bitboard::DARK_SQUARES).count() == 1
+            && rooks.first() < king.first()
+            && king.first() < rooks.last()
+    }
+
     #[inline]
     pub fn occupied(&self) -> Bitboard {
         self.occupied
@@ -262,6 +342,124 @@ impl Board {
                 | (attacks::pawn_attacks(!attacker, sq) & self.by_role.pawn))
     }
 
+    /// Tests if `sq` is attacked by any piece of `attacker`, given the
+    /// `occupied` squares.
+    ///
+    /// Equivalent to `board.attacks_to(sq, attacker, occupied).any()`, but
+    /// returns as soon as the first attacker is found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Board, Color, Square};
+    ///
+    /// let board = Board::new();
+    /// assert!(board.is_attacked(Square::F3, Color::White, board.occupied()));
+    /// assert!(!board.is_attacked(Square::E4, Color::White, board.occupied()));
+    /// ```
+    #[inline]
+    pub fn is_attacked(&self, sq: Square, attacker: Color, occupied: Bitboard) -> bool {
+        let them = self.by_color(attacker);
+        (attacks::pawn_attacks(!attacker, sq) & self.by_role.pawn & them).any()
+            || (attacks::knight_attacks(sq) & self.by_role.knight & them).any()
+            || (attacks::king_attacks(sq) & self.by_role.king & them).any()
+            || (attacks::rook_attacks(sq, occupied) & self.rooks_and_queens() & them).any()
+            || (attacks::bishop_attacks(sq, occupied) & self.bishops_and_queens() & them).any()
+    }
+
+    /// Squares attacked by any piece of `attacker`, given the `occupied`
+    /// squares. Attacks are computed for all pieces of a kind at once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Bitboard, Board, Color, Rank, Square};
+    ///
+    /// let board = Board::new();
+    /// let attacked = board.attacked_squares(Color::White, board.occupied());
+    /// assert!(attacked.contains(Square::F3));
+    /// assert!(attacked.contains(Square::D1));
+    /// assert!((attacked & Bitboard::from(Rank::Fourth)).is_empty());
+    /// ```
+    pub fn attacked_squares(&self, attacker: Color, occupied: Bitboard) -> Bitboard {
+        let them = self.by_color(attacker);
+        attacks::pawn_attacks_set(attacker, self.by_role.pawn & them)
+            | attacks::knight_attacks_set(self.by_role.knight & them)
+            | attacks::king_attacks_set(self.by_role.king & them)
+            | attacks::rook_attacks_set(self.rooks_and_queens() & them, occupied)
+            | attacks::bishop_attacks_set(self.bishops_and_queens() & them, occupied)
+    }
+
+    /// Control of each square: the number of white pieces attacking it,
+    /// minus the number of black pieces attacking it. Indexed by rank and
+    /// file, so that `control[0][0]` is a1 and `control[7][0]` is a8.
+    /// Useful to render heatmaps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Board, Square};
+    ///
+    /// let control = Board::new().control();
+    /// assert_eq!(control[2][5], 3); // f3: e2, g2 and g1
+    /// assert_eq!(control[5][5], -3); // f6: e7, g7 and g8
+    /// assert_eq!(control[3][4], 0); // e4
+    /// ```
+    pub fn control(&self) -> [[i8; 8]; 8] {
+        let mut control = [[0; 8]; 8];
+        for from in self.occupied {
+            let delta = if self.by_color.white.contains(from) {
+                1
+            } else {
+                -1
+            };
+            for to in self.attacks_from(from) {
+                control[usize::from(to.rank())][usize::from(to.file())] += delta;
+            }
+        }
+        control
+    }
+
+    /// Finds the least valuable piece of `attacker` that attacks `sq`,
+    /// given the `occupied` squares. Pieces that are not in `occupied` are
+    /// considered removed from the board.
+    ///
+    /// Roles are ordered by value: pawn, knight, bishop, rook, queen, king.
+    /// Pins are not taken into account. Useful to implement static exchange
+    /// evaluation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Bitboard, Board, Color, Role, Square};
+    ///
+    /// let board = Board::new();
+    /// assert_eq!(
+    ///     board.least_valuable_attacker(Square::F3, Color::White, board.occupied()),
+    ///     Some((Square::E2, Role::Pawn))
+    /// );
+    ///
+    /// // Without pawns, the knight is the least valuable attacker.
+    /// let occupied = board.occupied() & !board.pawns();
+    /// assert_eq!(
+    ///     board.least_valuable_attacker(Square::F3, Color::White, occupied),
+    ///     Some((Square::G1, Role::Knight))
+    /// );
+    /// ```
+    pub fn least_valuable_attacker(
+        &self,
+        sq: Square,
+        attacker: Color,
+        occupied: Bitboard,
+    ) -> Option<(Square, Role)> {
+        let attackers = self.attacks_to(sq, attacker, occupied) & occupied;
+        Role::ALL.into_iter().find_map(|role| {
+            (attackers & self.by_role(role))
+                .first()
+                .map(|from| (from, role))
+        })
+    }
+
     pub fn material_side(&self, color: Color) -> ByRole<u8> {
         let side = self.by_color(color);
         self.by_role
@@ -273,6 +471,66 @@ impl Board {
         ByColor::new_with(|color| self.material_side(color))
     }
 
+    /// Runs the given plausibility `checks` on the material of both sides,
+    /// and returns the checks that failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, MaterialChecks};
+    ///
+    /// // Two light-squared bishops, but no missing pawns.
+    /// let board = "4k3/8/8/8/8/8/PPPPPPPP/3BKB2 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_setup()
+    ///     .board;
+    ///
+    /// assert!(board.check_material(MaterialChecks::UNACCOUNTED_PROMOTIONS).is_empty());
+    /// assert_eq!(
+    ///     board.check_material(MaterialChecks::all()),
+    ///     MaterialChecks::BISHOP_COLORS
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn check_material(&self, checks: MaterialChecks) -> MaterialChecks {
+        let mut failed = MaterialChecks::empty();
+
+        for color in Color::ALL {
+            let side = self.by_color(color);
+            let count = |role: Role| (self.by_role(role) & side).count();
+            let pawns = count(Role::Pawn);
+            let promoted = count(Role::Queen).saturating_sub(1)
+                + count(Role::Rook).saturating_sub(2)
+                + count(Role::Knight).saturating_sub(2);
+            let bishops = self.bishops() & side;
+
+            if count(Role::King) > 1 {
+                failed |= MaterialChecks::TOO_MANY_KINGS;
+            }
+            if pawns > 8 {
+                failed |= MaterialChecks::TOO_MANY_PAWNS;
+            }
+            if side.count() > 16 {
+                failed |= MaterialChecks::TOO_MANY_PIECES;
+            }
+            if pawns + promoted + bishops.count().saturating_sub(2) > 8 {
+                failed |= MaterialChecks::UNACCOUNTED_PROMOTIONS;
+            }
+            if pawns
+                + promoted
+                + (bishops & Bitboard::LIGHT_SQUARES)
+                    .count()
+                    .saturating_sub(1)
+                + (bishops & Bitboard::DARK_SQUARES).count().saturating_sub(1)
+                > 8
+            {
+                failed |= MaterialChecks::BISHOP_COLORS;
+            }
+        }
+
+        failed & checks
+    }
+
     fn transform<F>(&mut self, f: F)
     where
         F: Fn(Bitboard) -> Bitboard,
@@ -284,6 +542,34 @@ impl Board {
         self.occupied = self.by_color.white | self.by_color.black;
     }
 
+    /// Swap the colors of all pieces.
+    pub fn swap_colors(&mut self) {
+        self.by_color.flip();
+    }
+
+    /// Tests if `other` is this board [mirrored vertically](Board::flip_vertical)
+    /// with [colors swapped](Board::swap_colors).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::Board;
+    ///
+    /// let board: Board = "4k3/8/8/8/8/8/4P3/4K3".parse()?;
+    /// let mirrored: Board = "4k3/4p3/8/8/8/8/8/4K3".parse()?;
+    /// assert!(board.is_mirror_of(&mirrored));
+    /// assert!(!board.is_mirror_of(&board));
+    /// assert!(Board::default().is_mirror_of(&Board::default()));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_mirror_of(&self, other: &Board) -> bool {
+        Role::ALL
+            .into_iter()
+            .all(|role| self.by_role(role).flip_vertical() == other.by_role(role))
+            && self.white().flip_vertical() == other.black()
+            && self.black().flip_vertical() == other.white()
+    }
+
     /// Mirror the board vertically. See [`Bitboard::flip_vertical`].
     pub fn flip_vertical(&mut self) {
         self.transform(Bitboard::flip_vertical);
@@ -450,6 +736,105 @@ mod tests {
         assert_eq!(board.piece_at(Square::C1), Some(Black.queen()));
     }
 
+    #[test]
+    fn test_check_material() {
+        assert!(Board::new()
+            .check_material(MaterialChecks::all())
+            .is_empty());
+
+        let board: Board = "kk6/pppppppp/p7/8/8/8/8/QQ2K3".parse().expect("valid fen");
+        assert_eq!(
+            board.check_material(MaterialChecks::all()),
+            MaterialChecks::TOO_MANY_KINGS
+                | MaterialChecks::TOO_MANY_PAWNS
+                | MaterialChecks::UNACCOUNTED_PROMOTIONS
+                | MaterialChecks::BISHOP_COLORS
+        );
+        assert_eq!(
+            board.check_material(MaterialChecks::TOO_MANY_KINGS | MaterialChecks::TOO_MANY_PIECES),
+            MaterialChecks::TOO_MANY_KINGS
+        );
+
+        let board: Board = "4k3/8/8/8/8/N7/NNNNNNNN/NNNNKNNN"
+            .parse()
+            .expect("valid fen");
+        assert_eq!(
+            board.check_material(MaterialChecks::TOO_MANY_PIECES),
+            MaterialChecks::TOO_MANY_PIECES
+        );
+    }
+
+    #[test]
+    fn test_is_attacked() {
+        let board: Board = "1qrb4/1k2n3/1P2p3/1N1K4/1BQ5/1R1R4/1Q2B3/1K3N2"
+            .parse()
+            .expect("valid fen");
+        for sq in Square::ALL {
+            for color in Color::ALL {
+                assert_eq!(
+                    board.is_attacked(sq, color, board.occupied()),
+                    board.attacks_to(sq, color, board.occupied()).any()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_control() {
+        let board: Board = "1qrb4/1k2n3/1P2p3/1N1K4/1BQ5/1R1R4/1Q2B3/1K3N2"
+            .parse()
+            .expect("valid fen");
+        let control = board.control();
+        for sq in Square::ALL {
+            let white = board.attacks_to(sq, White, board.occupied()).count() as i8;
+            let black = board.attacks_to(sq, Black, board.occupied()).count() as i8;
+            assert_eq!(
+                control[usize::from(sq.rank())][usize::from(sq.file())],
+                white - black,
+                "{}",
+                sq
+            );
+        }
+    }
+
+    #[test]
+    fn test_chess960_starts() {
+        fn permute(rest: &mut Vec<u8>, backrank: &mut Vec<u8>, valid: &mut usize) {
+            if rest.is_empty() {
+                let fen = format!(
+                    "{}/pppppppp/8/8/8/8/PPPPPPPP/{}",
+                    String::from_utf8(backrank.clone()).unwrap(),
+                    String::from_utf8(backrank.to_ascii_uppercase()).unwrap(),
+                );
+                let board = Board::from_ascii_board_fen(fen.as_bytes()).expect("valid fen");
+                if board.is_valid_chess960_start() {
+                    *valid += 1;
+                }
+                return;
+            }
+            let mut tried = Vec::new();
+            for i in 0..rest.len() {
+                if tried.contains(&rest[i]) {
+                    continue;
+                }
+                tried.push(rest[i]);
+                let piece = rest.remove(i);
+                backrank.push(piece);
+                permute(rest, backrank, valid);
+                backrank.pop();
+                rest.insert(i, piece);
+            }
+        }
+
+        let mut valid = 0;
+        permute(&mut b"rnbqkbnr".to_vec(), &mut Vec::new(), &mut valid);
+        assert_eq!(valid, 960);
+
+        let mut board = Board::default();
+        board.discard_piece_at(Square::E2);
+        assert!(!board.is_valid_chess960_start());
+    }
+
     #[test]
     fn test_board_transformation() {
         let board: Board = "1qrb4/1k2n3/1P2p3/1N1K4/1BQ5/1R1R4/1Q2B3/1K3N2"
@@ -0,0 +1,412 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Build opening books from game records.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{
+//!     book::{GameBookBuilder, GameInfo},
+//!     san::San,
+//!     Chess, Color, Outcome,
+//! };
+//!
+//! let mut builder = GameBookBuilder::new();
+//!
+//! let sans = ["e4", "e5", "Nf3"].map(|san| san.parse::<San>().unwrap());
+//! builder.add_san_game(
+//!     Chess::default(),
+//!     &sans,
+//!     &GameInfo {
+//!         outcome: Some(Outcome::Decisive {
+//!             winner: Color::White,
+//!         }),
+//!         ..GameInfo::default()
+//!     },
+//! )?;
+//!
+//! let moves = builder.get(&Chess::default());
+//! assert_eq!(moves.len(), 1);
+//! assert_eq!(moves[0].1.white, 1);
+//!
+//! let mut polyglot = Vec::new();
+//! builder.write_polyglot(&mut polyglot)?;
+//! assert_eq!(polyglot.len(), 3 * 16);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{collections::HashMap, io};
+
+use crate::{
+    color::ByColor,
+    explorer::{CompactMove, Counts, Explorer},
+    polyglot,
+    san::{San, SanError},
+    zobrist::ZobristHash,
+    Color, Move, Outcome, PlayError, Position,
+};
+
+/// Information about a game, other than its moves.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct GameInfo {
+    /// Result of the game, or `None` if unknown.
+    pub outcome: Option<Outcome>,
+    /// Ratings of the players, if known.
+    pub ratings: ByColor<Option<u16>>,
+}
+
+/// Aggregated statistics for a move in a position.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct MoveTally {
+    /// Number of games with this move, including games with unknown result.
+    pub games: u64,
+    /// Number of games won by white.
+    pub white: u64,
+    /// Number of drawn games.
+    pub draws: u64,
+    /// Number of games won by black.
+    pub black: u64,
+    rating_sum: u64,
+    rated_games: u64,
+}
+
+impl MoveTally {
+    /// Records a game with the given `outcome`, where the move was played
+    /// by a player with the given `rating`.
+    pub fn record(&mut self, outcome: Option<Outcome>, rating: Option<u16>) {
+        self.games += 1;
+        match outcome {
+            Some(Outcome::Decisive {
+                winner: Color::White,
+            }) => self.white += 1,
+            Some(Outcome::Decisive {
+                winner: Color::Black,
+            }) => self.black += 1,
+            Some(Outcome::Draw) => self.draws += 1,
+            None => (),
+        }
+        if let Some(rating) = rating {
+            self.rating_sum += u64::from(rating);
+            self.rated_games += 1;
+        }
+    }
+
+    /// Merges statistics from `other` into `self`.
+    pub fn merge(&mut self, other: &MoveTally) {
+        self.games += other.games;
+        self.white += other.white;
+        self.draws += other.draws;
+        self.black += other.black;
+        self.rating_sum += other.rating_sum;
+        self.rated_games += other.rated_games;
+    }
+
+    /// Average rating of the players who played the move, or `None` if
+    /// no ratings are known.
+    pub fn average_rating(&self) -> Option<u16> {
+        (self.rated_games > 0).then(|| (self.rating_sum / self.rated_games) as u16)
+    }
+
+    /// Number of games won by `color`.
+    pub fn wins(&self, color: Color) -> u64 {
+        color.fold_wb(self.white, self.black)
+    }
+
+    /// Polyglot style weight of the move for `turn`, i.e.
+    /// 2 points for each win and 1 point for each draw.
+    pub fn weight(&self, turn: Color) -> u64 {
+        2 * self.wins(turn) + self.draws
+    }
+}
+
+/// Statistics for all moves recorded in a position.
+#[derive(Debug, Clone)]
+struct Entry {
+    turn: Color,
+    moves: Vec<(Move, MoveTally)>,
+}
+
+/// Aggregates per-position move statistics from game records.
+///
+/// Positions are identified by their 64-bit Zobrist hash, which is
+/// compatible with Polyglot opening books for standard chess.
+#[derive(Debug, Clone)]
+pub struct GameBookBuilder {
+    max_plies: usize,
+    entries: HashMap<u64, Entry>,
+}
+
+impl Default for GameBookBuilder {
+    fn default() -> GameBookBuilder {
+        GameBookBuilder::new()
+    }
+}
+
+impl GameBookBuilder {
+    /// Creates an empty builder, recording up to 40 plies of each game.
+    pub fn new() -> GameBookBuilder {
+        GameBookBuilder::with_max_plies(40)
+    }
+
+    /// Creates an empty builder, recording up to `max_plies` plies of each
+    /// game.
+    pub fn with_max_plies(max_plies: usize) -> GameBookBuilder {
+        GameBookBuilder {
+            max_plies,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Records a game that starts in `pos`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PlayError`] if a move is illegal. The moves before the
+    /// illegal move are still recorded.
+    pub fn add_game<P: Position + ZobristHash>(
+        &mut self,
+        mut pos: P,
+        moves: &[Move],
+        info: &GameInfo,
+    ) -> Result<(), PlayError<P>> {
+        for m in moves.iter().take(self.max_plies) {
+            if !pos.is_legal(m) {
+                return Err(PlayError {
+                    m: m.clone(),
+                    inner: pos,
+                });
+            }
+            self.record(&pos, m, info);
+            pos.play_unchecked(m);
+        }
+        Ok(())
+    }
+
+    /// Records a game that starts in `pos`, given as a sequence of SANs.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SanError`] if a SAN is illegal or ambiguous. The moves
+    /// before that are still recorded.
+    pub fn add_san_game<P: Position + ZobristHash>(
+        &mut self,
+        mut pos: P,
+        sans: &[San],
+        info: &GameInfo,
+    ) -> Result<(), SanError> {
+        for san in sans.iter().take(self.max_plies) {
+            let m = san.to_move(&pos)?;
+            self.record(&pos, &m, info);
+            pos.play_unchecked(&m);
+        }
+        Ok(())
+    }
+
+    fn record<P: Position + ZobristHash>(&mut self, pos: &P, m: &Move, info: &GameInfo) {
+        let turn = pos.turn();
+        let entry = self
+            .entries
+            .entry(pos.zobrist_hash())
+            .or_insert_with(|| Entry {
+                turn,
+                moves: Vec::new(),
+            });
+        let stats = match entry.moves.iter_mut().position(|(other, _)| other == m) {
+            Some(idx) => &mut entry.moves[idx].1,
+            None => {
+                entry.moves.push((m.clone(), MoveTally::default()));
+                &mut entry.moves.last_mut().expect("just pushed").1
+            }
+        };
+        stats.record(info.outcome, *info.ratings.get(turn));
+    }
+
+    /// Gets the recorded moves and their statistics in `pos`.
+    pub fn get<P: ZobristHash>(&self, pos: &P) -> &[(Move, MoveTally)] {
+        self.entries
+            .get(&pos.zobrist_hash())
+            .map_or(&[], |entry| &entry.moves)
+    }
+
+    /// Number of distinct positions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Tests if no positions have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Builds a compact [`Explorer`] from the recorded moves.
+    ///
+    /// Only games with known results are included, and ratings are
+    /// discarded. Counts that exceed the 32-bit range of [`Counts`] are
+    /// capped at `u32::MAX`.
+    pub fn to_explorer(&self) -> Explorer {
+        let mut explorer = Explorer::with_capacity(self.entries.len());
+        for (key, entry) in &self.entries {
+            for (m, stats) in &entry.moves {
+                explorer.insert(
+                    *key,
+                    CompactMove::from_move(m),
+                    Counts {
+                        white: saturating_u32(stats.white),
+                        draws: saturating_u32(stats.draws),
+                        black: saturating_u32(stats.black),
+                    },
+                );
+            }
+        }
+        explorer
+    }
+
+    /// Writes all recorded moves as a Polyglot opening book.
+    ///
+    /// Entries are sorted by key, and by descending weight within each key.
+    /// Weights are computed with [`MoveTally::weight()`], and scaled down
+    /// per position if they exceed the 16-bit range. Drops are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn write_polyglot<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        let mut polyglot = polyglot::BookBuilder::new();
+        for (key, entry) in &self.entries {
+            let max_weight = entry
+                .moves
+                .iter()
+                .map(|(_, stats)| stats.weight(entry.turn))
+                .max()
+                .unwrap_or(0);
+            for (m, stats) in &entry.moves {
+                let weight = stats.weight(entry.turn);
+                let weight = if max_weight > u64::from(u16::MAX) {
+                    weight * u64::from(u16::MAX) / max_weight
+                } else {
+                    weight
+                };
+                if let Some(raw_move) = polyglot::encode_move(m) {
+                    polyglot.insert_entry(polyglot::Entry {
+                        key: *key,
+                        raw_move,
+                        weight: weight as u16,
+                        learn: 0,
+                    });
+                }
+            }
+        }
+        polyglot.write_bin(writer)
+    }
+}
+
+fn saturating_u32(n: u64) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chess, Role, Square};
+
+    #[test]
+    fn test_book_builder() {
+        let mut builder = GameBookBuilder::with_max_plies(1);
+        let e4 = Move::Normal {
+            role: Role::Pawn,
+            from: Square::E2,
+            capture: None,
+            to: Square::E4,
+            promotion: None,
+        };
+        let d4 = Move::Normal {
+            role: Role::Pawn,
+            from: Square::D2,
+            capture: None,
+            to: Square::D4,
+            promotion: None,
+        };
+
+        let white_wins = GameInfo {
+            outcome: Some(Outcome::Decisive {
+                winner: Color::White,
+            }),
+            ratings: ByColor {
+                white: Some(2000),
+                black: Some(1000),
+            },
+        };
+        let draw = GameInfo {
+            outcome: Some(Outcome::Draw),
+            ratings: ByColor {
+                white: Some(1500),
+                black: None,
+            },
+        };
+
+        let e4_d4 = [e4.clone(), d4.clone()];
+        let d4_d4 = [d4.clone(), d4]; // Illegal, but truncated at max plies
+        builder
+            .add_game(Chess::default(), &e4_d4, &white_wins)
+            .expect("legal");
+        builder
+            .add_game(Chess::default(), &e4_d4, &draw)
+            .expect("legal");
+        builder
+            .add_game(Chess::default(), &d4_d4, &draw)
+            .expect("legal");
+        builder
+            .add_game(Chess::default(), &d4_d4, &draw)
+            .expect("legal");
+        assert_eq!(builder.len(), 1);
+
+        let moves = builder.get(&Chess::default());
+        assert_eq!(moves[0].0, e4);
+        assert_eq!(moves[0].1.games, 2);
+        assert_eq!(moves[0].1.white, 1);
+        assert_eq!(moves[0].1.draws, 1);
+        assert_eq!(moves[0].1.average_rating(), Some(1750));
+        assert_eq!(moves[1].1.games, 2);
+        assert_eq!(moves[1].1.weight(Color::White), 2);
+
+        let explorer = builder.to_explorer();
+        assert_eq!(explorer.len(), 1);
+        assert_eq!(explorer.get(&Chess::default())[0].1.games(), 2);
+
+        let mut polyglot = Vec::new();
+        builder.write_polyglot(&mut polyglot).expect("write");
+        assert_eq!(
+            polyglot,
+            [
+                0x46, 0x3b, 0x96, 0x18, 0x16, 0x91, 0xfc, 0x9c, // key
+                0x03, 0x1c, // e2e4
+                0x00, 0x03, // weight
+                0x00, 0x00, 0x00, 0x00, // learn
+                0x46, 0x3b, 0x96, 0x18, 0x16, 0x91, 0xfc, 0x9c, // key
+                0x02, 0xdb, // d2d4
+                0x00, 0x02, // weight
+                0x00, 0x00, 0x00, 0x00, // learn
+            ]
+        );
+
+        for entry in builder.entries.values_mut() {
+            entry.moves[0].1.white = u64::from(u32::MAX) + 1;
+        }
+        let explorer = builder.to_explorer();
+        assert_eq!(explorer.get(&Chess::default())[0].1.white, u32::MAX);
+    }
+}
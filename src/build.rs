@@ -17,11 +17,15 @@
 #![cfg_attr(docs_rs, feature(doc_cfg))]
 #![forbid(unsafe_op_in_unsafe_fn)]
 #![allow(dead_code)]
+// The build script cannot declare its own cfgs, and docs_rs is only ever set
+// for rustdoc.
+#![allow(unknown_lints, unexpected_cfgs)]
 
 use std::{env, fmt::LowerHex, fs::File, io, io::Write, path::Path};
 
 mod bitboard;
 mod color;
+mod error;
 mod magics;
 mod role;
 mod square;
@@ -115,12 +119,30 @@ fn dump_table<W: Write, T: LowerHex>(
 }
 
 fn main() -> io::Result<()> {
+    // docs_rs is set by rustdoc-args in Cargo.toml
+    println!("cargo:rustc-check-cfg=cfg(docs_rs)");
+
     // generate attacks.rs
     let out_dir = env::var("OUT_DIR").expect("got OUT_DIR");
     let attacks_path = Path::new(&out_dir).join("attacks.rs");
     let mut f = File::create(&attacks_path).expect("created attacks.rs");
     generate_basics(&mut f)?;
-    generate_sliding_attacks(&mut f)
+    if env::var_os("CARGO_FEATURE_COMPACT").is_none() {
+        let x86_64 = env::var("CARGO_CFG_TARGET_ARCH").map_or(false, |arch| arch == "x86_64");
+        let bmi2 = env::var("CARGO_CFG_TARGET_FEATURE").map_or(false, |features| {
+            features.split(',').any(|feature| feature == "bmi2")
+        });
+        let fancy_magic = env::var_os("CARGO_FEATURE_FANCY_MAGIC").is_some();
+        if !(fancy_magic || x86_64 && bmi2) {
+            generate_sliding_attacks(&mut f)?;
+        }
+        if x86_64 && bmi2 {
+            generate_pext_attacks(&mut f)?;
+        } else if fancy_magic {
+            generate_fancy_attacks(&mut f)?;
+        }
+    }
+    Ok(())
 }
 
 fn generate_basics<W: Write>(f: &mut W) -> io::Result<()> {
@@ -187,3 +209,92 @@ fn generate_sliding_attacks<W: Write>(f: &mut W) -> io::Result<()> {
 
     dump_slice(f, "ATTACKS", "u64", &attacks)
 }
+
+fn init_pext(sq: Square, mask: u64, attacks: &mut Vec<Bitboard>, deltas: &[i32]) -> usize {
+    // Subsets are enumerated in the same order as the bits would be
+    // extracted by PEXT.
+    let offset = attacks.len();
+    for subset in Bitboard(mask).carry_rippler() {
+        attacks.push(sliding_attacks(sq, subset, deltas));
+    }
+    offset
+}
+
+fn generate_pext_attacks<W: Write>(f: &mut W) -> io::Result<()> {
+    let mut attacks = Vec::new();
+    let mut rook_offsets = [0; 64];
+    let mut bishop_offsets = [0; 64];
+
+    for sq in Square::ALL {
+        rook_offsets[usize::from(sq)] = init_pext(
+            sq,
+            magics::ROOK_MAGICS[usize::from(sq)].mask,
+            &mut attacks,
+            &ROOK_DELTAS,
+        );
+        bishop_offsets[usize::from(sq)] = init_pext(
+            sq,
+            magics::BISHOP_MAGICS[usize::from(sq)].mask,
+            &mut attacks,
+            &BISHOP_DELTAS,
+        );
+    }
+
+    dump_slice(f, "ROOK_PEXT_OFFSETS", "usize", &rook_offsets)?;
+    dump_slice(f, "BISHOP_PEXT_OFFSETS", "usize", &bishop_offsets)?;
+    dump_slice(f, "PEXT_ATTACKS", "u64", &attacks)
+}
+
+fn init_fancy(
+    sq: Square,
+    mask: u64,
+    factor: u64,
+    attacks: &mut Vec<Bitboard>,
+    deltas: &[i32],
+) -> (usize, u32) {
+    let offset = attacks.len();
+    let shift = 64 - mask.count_ones();
+    attacks.resize(offset + (1 << mask.count_ones()), Bitboard(0));
+    for subset in Bitboard(mask).carry_rippler() {
+        let attack = sliding_attacks(sq, subset, deltas);
+        let idx = (factor.wrapping_mul(subset.0) >> shift) as usize + offset;
+        assert!(
+            attacks[idx].is_empty() || attacks[idx] == attack,
+            "bad magic factor"
+        );
+        attacks[idx] = attack;
+    }
+    (offset, shift)
+}
+
+fn generate_fancy_attacks<W: Write>(f: &mut W) -> io::Result<()> {
+    let mut attacks = Vec::new();
+    let mut rook_offsets = [0; 64];
+    let mut rook_shifts = [0; 64];
+    let mut bishop_offsets = [0; 64];
+    let mut bishop_shifts = [0; 64];
+
+    for sq in Square::ALL {
+        let i = usize::from(sq);
+        (rook_offsets[i], rook_shifts[i]) = init_fancy(
+            sq,
+            magics::ROOK_MAGICS[i].mask,
+            magics::FANCY_ROOK_FACTORS[i],
+            &mut attacks,
+            &ROOK_DELTAS,
+        );
+        (bishop_offsets[i], bishop_shifts[i]) = init_fancy(
+            sq,
+            magics::BISHOP_MAGICS[i].mask,
+            magics::FANCY_BISHOP_FACTORS[i],
+            &mut attacks,
+            &BISHOP_DELTAS,
+        );
+    }
+
+    dump_slice(f, "FANCY_ROOK_OFFSETS", "usize", &rook_offsets)?;
+    dump_slice(f, "FANCY_ROOK_SHIFTS", "u32", &rook_shifts)?;
+    dump_slice(f, "FANCY_BISHOP_OFFSETS", "usize", &bishop_offsets)?;
+    dump_slice(f, "FANCY_BISHOP_SHIFTS", "u32", &bishop_shifts)?;
+    dump_slice(f, "FANCY_ATTACKS", "u64", &attacks)
+}
@@ -0,0 +1,614 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Time controls and clocks of game records.
+//!
+//! # Examples
+//!
+//! Parse a PGN `TimeControl` tag and follow the clocks through `[%clk]`
+//! comments:
+//!
+//! ```
+//! use std::time::Duration;
+//! use shakmaty::{clock::{Clock, TimeControl}, Color};
+//!
+//! let tc: TimeControl = "600+5".parse()?;
+//! let mut clock = Clock::new(&tc);
+//! assert_eq!(clock.remaining(Color::White), Some(Duration::from_secs(600)));
+//!
+//! clock.update_from_comment(Color::White, b"[%clk 0:09:58.3] good move");
+//! assert_eq!(clock.remaining(Color::White), Some(Duration::from_millis(598_300)));
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{error::Error, fmt, str::FromStr, time::Duration};
+
+use crate::{
+    color::{ByColor, Color},
+    error::ErrorKind,
+};
+
+/// Error when parsing an invalid time control.
+#[derive(Clone, Debug)]
+pub struct ParseTimeControlError;
+
+impl fmt::Display for ParseTimeControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid time control")
+    }
+}
+
+impl Error for ParseTimeControlError {}
+
+impl ParseTimeControlError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidTimeControl
+    }
+}
+
+/// A period of a [`TimeControl`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Period {
+    /// Number of moves that have to be played in this period, or `None` if
+    /// the period lasts until the end of the game.
+    pub moves: Option<u32>,
+    /// Time available for the period.
+    pub base: Duration,
+    /// Time added after each move.
+    pub increment: Duration,
+}
+
+/// A time control, as in the `TimeControl` tag of PGNs.
+///
+/// The PGN format denotes periods like `40/5400` (40 moves in 90 minutes),
+/// `300` (sudden death) or `600+5` (with increment), separated by `:`.
+/// Lichess style `180+2` is a single period with increment.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum TimeControl {
+    /// `?`
+    Unknown,
+    /// `-`
+    Unlimited,
+    /// Sandclock, for example `*180`: Time used by one side is added to the
+    /// other.
+    Sandclock(Duration),
+    /// Successive periods. The last period repeats if it has a move limit.
+    Periods(Vec<Period>),
+}
+
+impl TimeControl {
+    /// Parses a time control.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseTimeControlError`] if the input is not a valid time
+    /// control.
+    pub fn from_ascii(s: &[u8]) -> Result<TimeControl, ParseTimeControlError> {
+        Ok(match s {
+            b"?" => TimeControl::Unknown,
+            b"-" => TimeControl::Unlimited,
+            _ => match s.strip_prefix(b"*") {
+                Some(seconds) => TimeControl::Sandclock(parse_seconds(seconds)?),
+                None => TimeControl::Periods(
+                    s.split(|ch| *ch == b':')
+                        .map(parse_period)
+                        .collect::<Result<_, _>>()?,
+                ),
+            },
+        })
+    }
+
+    /// Gets the time available at the start of the game.
+    pub fn initial_time(&self) -> Option<Duration> {
+        match *self {
+            TimeControl::Unknown | TimeControl::Unlimited => None,
+            TimeControl::Sandclock(time) => Some(time),
+            TimeControl::Periods(ref periods) => periods.first().map(|period| period.base),
+        }
+    }
+}
+
+fn parse_seconds(s: &[u8]) -> Result<Duration, ParseTimeControlError> {
+    btoi::btou(s)
+        .map(Duration::from_secs)
+        .map_err(|_| ParseTimeControlError)
+}
+
+fn parse_period(s: &[u8]) -> Result<Period, ParseTimeControlError> {
+    let (moves, s) = match s.iter().position(|ch| *ch == b'/') {
+        Some(slash) => (
+            Some(btoi::btou(&s[..slash]).map_err(|_| ParseTimeControlError)?),
+            &s[slash + 1..],
+        ),
+        None => (None, s),
+    };
+    let (base, increment) = match s.iter().position(|ch| *ch == b'+') {
+        Some(plus) => (&s[..plus], parse_seconds(&s[plus + 1..])?),
+        None => (s, Duration::ZERO),
+    };
+    Ok(Period {
+        moves,
+        base: parse_seconds(base)?,
+        increment,
+    })
+}
+
+impl FromStr for TimeControl {
+    type Err = ParseTimeControlError;
+
+    fn from_str(s: &str) -> Result<TimeControl, ParseTimeControlError> {
+        TimeControl::from_ascii(s.as_bytes())
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TimeControl::Unknown => f.write_str("?"),
+            TimeControl::Unlimited => f.write_str("-"),
+            TimeControl::Sandclock(time) => write!(f, "*{}", time.as_secs()),
+            TimeControl::Periods(ref periods) => {
+                for (i, period) in periods.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(":")?;
+                    }
+                    if let Some(moves) = period.moves {
+                        write!(f, "{}/", moves)?;
+                    }
+                    write!(f, "{}", period.base.as_secs())?;
+                    if !period.increment.is_zero() {
+                        write!(f, "+{}", period.increment.as_secs())?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses the remaining time from a `[%clk h:mm:ss]` command in a PGN
+/// comment. Fractions of seconds are optional.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use shakmaty::clock::parse_clk;
+///
+/// assert_eq!(parse_clk(b"[%clk 1:02:03]"), Some(Duration::from_secs(3723)));
+/// assert_eq!(parse_clk(b"[%eval 0.17] [%clk 0:00:07.5]"), Some(Duration::from_millis(7500)));
+/// assert_eq!(parse_clk(b"no clock"), None);
+/// ```
+pub fn parse_clk(comment: &[u8]) -> Option<Duration> {
+    const COMMAND: &[u8] = b"[%clk";
+    let start = comment
+        .windows(COMMAND.len())
+        .position(|window| window == COMMAND)?
+        + COMMAND.len();
+    let rest = &comment[start..];
+    let end = rest.iter().position(|ch| *ch == b']')?;
+    let mut value = &rest[..end];
+    while let Some((first, tail)) = value.split_first() {
+        if !first.is_ascii_whitespace() {
+            break;
+        }
+        value = tail;
+    }
+    while let Some((last, init)) = value.split_last() {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+        value = init;
+    }
+
+    let (value, fraction) = match value.iter().position(|ch| *ch == b'.') {
+        Some(dot) => (&value[..dot], &value[dot + 1..]),
+        None => (value, &b""[..]),
+    };
+
+    let mut seconds = 0u64;
+    for part in value.split(|ch| *ch == b':') {
+        seconds = seconds
+            .checked_mul(60)?
+            .checked_add(btoi::btou(part).ok()?)?;
+    }
+
+    let mut nanos = 0u32;
+    let mut scale = 100_000_000;
+    for digit in fraction {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        nanos += u32::from(digit - b'0') * scale;
+        scale /= 10;
+    }
+
+    Some(Duration::new(seconds, nanos))
+}
+
+/// Formats a remaining time as a `[%clk h:mm:ss]` command for a PGN
+/// comment, with tenths of seconds if there are any. The inverse of
+/// [`parse_clk()`], up to tenths of seconds.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use shakmaty::clock::format_clk;
+///
+/// assert_eq!(format_clk(Duration::from_secs(3723)), "[%clk 1:02:03]");
+/// assert_eq!(format_clk(Duration::from_millis(7580)), "[%clk 0:00:07.5]");
+/// ```
+pub fn format_clk(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    let tenths = remaining.subsec_millis() / 100;
+    let mut clk = format!(
+        "[%clk {}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if tenths > 0 {
+        clk.push_str(&format!(".{}", tenths));
+    }
+    clk.push(']');
+    clk
+}
+
+/// Removes all `[%clk]` commands from a PGN comment.
+pub fn remove_clk(comment: &str) -> String {
+    let mut result = String::with_capacity(comment.len());
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%clk ") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start..].find(']') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Remaining time of both players during a game.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Clock {
+    remaining: ByColor<Option<Duration>>,
+}
+
+impl Clock {
+    /// Starts clocks for a game with the given time control.
+    pub fn new(time_control: &TimeControl) -> Clock {
+        let initial = time_control.initial_time();
+        Clock {
+            remaining: ByColor {
+                white: initial,
+                black: initial,
+            },
+        }
+    }
+
+    /// Gets the remaining time of `color`, if known.
+    pub fn remaining(&self, color: Color) -> Option<Duration> {
+        *self.remaining.get(color)
+    }
+
+    /// Sets the remaining time of `color`.
+    pub fn set_remaining(&mut self, color: Color, remaining: Duration) {
+        *self.remaining.get_mut(color) = Some(remaining);
+    }
+
+    /// Updates the remaining time of `color` from a `[%clk]` command in the
+    /// comment after a move of `color`. Returns `false` if there is no
+    /// valid clock command.
+    pub fn update_from_comment(&mut self, color: Color, comment: &[u8]) -> bool {
+        match parse_clk(comment) {
+            Some(remaining) => {
+                self.set_remaining(color, remaining);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Progress of a player through the periods of a time control.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub(crate) struct PeriodProgress {
+    index: usize,
+    moves: u32,
+}
+
+impl Clock {
+    /// Charges `spent` to `color`, then adds the increment and the base time
+    /// of the next period when reaching the move limit of the current
+    /// period. In sandclock games, the time is instead added to the
+    /// opponent.
+    ///
+    /// Returns `false` if the flag fell. The clock of `color` is then set to
+    /// zero, and no time is added.
+    pub(crate) fn charge(
+        &mut self,
+        time_control: &TimeControl,
+        progress: &mut PeriodProgress,
+        color: Color,
+        spent: Duration,
+    ) -> bool {
+        let remaining = match self.remaining(color) {
+            Some(remaining) => remaining,
+            None => return true,
+        };
+        let mut remaining = match remaining.checked_sub(spent) {
+            Some(remaining) => remaining,
+            None => {
+                self.set_remaining(color, Duration::ZERO);
+                return false;
+            }
+        };
+
+        match *time_control {
+            TimeControl::Sandclock(_) => {
+                if let Some(other) = self.remaining(!color) {
+                    self.set_remaining(!color, other + spent);
+                }
+            }
+            TimeControl::Periods(ref periods) => {
+                if let Some(period) = periods.get(progress.index) {
+                    remaining += period.increment;
+                    progress.moves += 1;
+                    if period.moves == Some(progress.moves) {
+                        progress.index = (progress.index + 1).min(periods.len() - 1);
+                        progress.moves = 0;
+                        remaining += periods[progress.index].base;
+                    }
+                }
+            }
+            TimeControl::Unknown | TimeControl::Unlimited => (),
+        }
+        self.set_remaining(color, remaining);
+        true
+    }
+}
+
+/// A move simulated by [`ClockSimulation`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ClockPly {
+    /// The player who made the move.
+    pub color: Color,
+    /// Time spent on the move.
+    pub spent: Duration,
+    /// Remaining time of the player after the move, including increments
+    /// and time added for reaching the next period, or `None` if time is
+    /// not limited.
+    pub remaining: Option<Duration>,
+    /// Whether the player ran out of time on this move.
+    pub flagged: bool,
+}
+
+/// Simulates the clocks of a game from the time spent on each move.
+///
+/// Increments are added after each move, and the base time of the next
+/// period is added when reaching the move limit of the current period.
+/// Once a player runs out of time, their clock stays at zero and no
+/// more time is added.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use shakmaty::{clock::{ClockSimulation, TimeControl}, Color};
+///
+/// let tc: TimeControl = "60+1".parse()?;
+/// let mut sim = ClockSimulation::new(&tc, Color::White);
+///
+/// // Moves made 5, 20 and 120 seconds after the start of the game.
+/// assert!(!sim.play_at(Duration::from_secs(5)).flagged);
+/// assert_eq!(sim.clock().remaining(Color::White), Some(Duration::from_secs(56)));
+/// assert!(!sim.play_at(Duration::from_secs(20)).flagged);
+/// assert_eq!(sim.clock().remaining(Color::Black), Some(Duration::from_secs(46)));
+///
+/// let ply = sim.play_at(Duration::from_secs(120));
+/// assert!(ply.flagged);
+/// assert_eq!(ply.remaining, Some(Duration::ZERO));
+/// assert_eq!(sim.flagged(), Some(Color::White));
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClockSimulation {
+    time_control: TimeControl,
+    clock: Clock,
+    turn: Color,
+    progress: ByColor<PeriodProgress>,
+    last: Duration,
+    flagged: Option<Color>,
+}
+
+impl ClockSimulation {
+    /// Starts clocks for a game with the given time control, where `turn`
+    /// makes the first move.
+    pub fn new(time_control: &TimeControl, turn: Color) -> ClockSimulation {
+        ClockSimulation {
+            time_control: time_control.clone(),
+            clock: Clock::new(time_control),
+            turn,
+            progress: ByColor::default(),
+            last: Duration::ZERO,
+            flagged: None,
+        }
+    }
+
+    /// The current state of the clocks.
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// The player whose clock is running.
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    /// The first player who ran out of time, if any.
+    pub fn flagged(&self) -> Option<Color> {
+        self.flagged
+    }
+
+    /// Simulates a move made at `timestamp`, measured from the start of the
+    /// simulation. The time spent is the difference to the timestamp of the
+    /// previous move.
+    pub fn play_at(&mut self, timestamp: Duration) -> ClockPly {
+        let spent = timestamp.saturating_sub(self.last);
+        self.last = self.last.max(timestamp);
+        self.simulate(spent)
+    }
+
+    /// Simulates a move that took `spent`.
+    pub fn play(&mut self, spent: Duration) -> ClockPly {
+        self.last += spent;
+        self.simulate(spent)
+    }
+
+    fn simulate(&mut self, spent: Duration) -> ClockPly {
+        let color = self.turn;
+        self.turn = !color;
+
+        let flagged = !self.clock.charge(
+            &self.time_control,
+            self.progress.get_mut(color),
+            color,
+            spent,
+        );
+        if flagged {
+            self.flagged.get_or_insert(color);
+        }
+
+        ClockPly {
+            color,
+            spent,
+            remaining: self.clock.remaining(color),
+            flagged,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_control() {
+        for tc in [
+            "?",
+            "-",
+            "*180",
+            "300",
+            "600+5",
+            "40/5400+30:1800+30",
+            "40/7200:20/3600:900",
+        ] {
+            assert_eq!(
+                tc.parse::<TimeControl>()
+                    .expect("valid time control")
+                    .to_string(),
+                tc
+            );
+        }
+
+        assert_eq!(
+            "40/5400+30:1800+30".parse::<TimeControl>().expect("valid"),
+            TimeControl::Periods(vec![
+                Period {
+                    moves: Some(40),
+                    base: Duration::from_secs(5400),
+                    increment: Duration::from_secs(30),
+                },
+                Period {
+                    moves: None,
+                    base: Duration::from_secs(1800),
+                    increment: Duration::from_secs(30),
+                },
+            ])
+        );
+
+        for invalid in ["", "+5", "40/", "1:", "*", "abc", "600+"] {
+            assert!(invalid.parse::<TimeControl>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut clock = Clock::new(&TimeControl::Unknown);
+        assert_eq!(clock.remaining(Color::Black), None);
+        assert!(!clock.update_from_comment(Color::Black, b"[%clk 0:01:x]"));
+        assert!(clock.update_from_comment(Color::Black, b"[%clk 0:01:02.25]"));
+        assert_eq!(
+            clock.remaining(Color::Black),
+            Some(Duration::from_millis(62_250))
+        );
+        assert_eq!(clock.remaining(Color::White), None);
+    }
+
+    #[test]
+    fn test_format_clk() {
+        for millis in [0, 100, 59_900, 3_600_000, 36_000_000 + 1_200] {
+            let remaining = Duration::from_millis(millis);
+            assert_eq!(parse_clk(format_clk(remaining).as_bytes()), Some(remaining));
+        }
+    }
+
+    #[test]
+    fn test_clock_simulation() {
+        let tc: TimeControl = "2/60+1:30".parse().expect("valid time control");
+        let mut sim = ClockSimulation::new(&tc, Color::Black);
+        for _ in 0..4 {
+            assert!(!sim.play(Duration::from_secs(20)).flagged);
+        }
+        // Both reached the second period after two moves.
+        assert_eq!(
+            sim.clock().remaining(Color::Black),
+            Some(Duration::from_secs(52))
+        );
+        assert_eq!(
+            sim.clock().remaining(Color::White),
+            Some(Duration::from_secs(52))
+        );
+        let ply = sim.play(Duration::from_secs(53));
+        assert_eq!(ply.color, Color::Black);
+        assert!(ply.flagged);
+        assert_eq!(sim.flagged(), Some(Color::Black));
+        assert!(sim.play(Duration::from_secs(10)).remaining.is_some());
+        assert_eq!(sim.flagged(), Some(Color::Black));
+
+        let mut sim = ClockSimulation::new(
+            &TimeControl::Sandclock(Duration::from_secs(60)),
+            Color::White,
+        );
+        sim.play_at(Duration::from_secs(10));
+        assert_eq!(
+            sim.clock().remaining(Color::White),
+            Some(Duration::from_secs(50))
+        );
+        assert_eq!(
+            sim.clock().remaining(Color::Black),
+            Some(Duration::from_secs(70))
+        );
+
+        let mut sim = ClockSimulation::new(&TimeControl::Unlimited, Color::White);
+        let ply = sim.play(Duration::from_secs(3600));
+        assert_eq!(ply.remaining, None);
+        assert!(!ply.flagged);
+    }
+}
@@ -19,6 +19,7 @@
 use std::{array, convert::identity, error::Error, fmt, mem, ops, str::FromStr};
 
 use crate::{
+    error::ErrorKind,
     role::{ByRole, Role},
     square::Rank,
     types::Piece,
@@ -170,6 +171,13 @@ impl fmt::Display for ParseColorError {
 
 impl Error for ParseColorError {}
 
+impl ParseColorError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidColor
+    }
+}
+
 impl FromStr for Color {
     type Err = ParseColorError;
 
@@ -0,0 +1,195 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers for coordinate training.
+//!
+//! Trainers ask for random squares, show the board from either side, and
+//! sometimes name squares from the perspective of black.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{
+//!     coordinates::{screen_position, square_at, SquareSampler},
+//!     Color, Square,
+//! };
+//!
+//! // The same seed always produces the same sequence of questions.
+//! let questions: Vec<Square> = SquareSampler::new(42).take(10).collect();
+//! assert_eq!(questions, SquareSampler::new(42).take(10).collect::<Vec<_>>());
+//!
+//! // Map between clicks and squares, with the board seen from black.
+//! assert_eq!(square_at(0, 0, Color::Black), Some(Square::H1));
+//! assert_eq!(screen_position(Square::A8, Color::Black), (7, 7));
+//! ```
+
+use crate::{Bitboard, Color, File, Rank, Square};
+
+/// Seeded random source of squares.
+///
+/// The same square is never asked twice in a row, unless it is the only
+/// candidate. The sequence depends only on the seed and the candidate
+/// squares, and is stable across platforms, but not necessarily across
+/// versions of this crate.
+#[derive(Debug, Clone)]
+pub struct SquareSampler {
+    state: u64,
+    squares: Bitboard,
+    last: Option<Square>,
+}
+
+impl SquareSampler {
+    /// Samples from all squares.
+    pub fn new(seed: u64) -> SquareSampler {
+        SquareSampler::with_squares(seed, Bitboard::FULL)
+    }
+
+    /// Samples only from the given `squares`, for example
+    /// [`Bitboard::LIGHT_SQUARES`].
+    pub fn with_squares(seed: u64, squares: Bitboard) -> SquareSampler {
+        SquareSampler {
+            state: seed,
+            squares,
+            last: None,
+        }
+    }
+
+    /// Candidate squares.
+    pub fn squares(&self) -> Bitboard {
+        self.squares
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // SplitMix64.
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Samples the next square, or `None` if there are no candidates.
+    pub fn next_square(&mut self) -> Option<Square> {
+        let mut candidates = self.squares;
+        if candidates.more_than_one() {
+            if let Some(last) = self.last {
+                candidates.discard(last);
+            }
+        }
+        let n = candidates.count() as u64;
+        if n == 0 {
+            return None;
+        }
+        let index = ((self.next_u64() >> 32) * n) >> 32;
+        let square = candidates.into_iter().nth(index as usize);
+        self.last = square;
+        square
+    }
+}
+
+impl Iterator for SquareSampler {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        self.next_square()
+    }
+}
+
+/// Gets the square at column `x` and row `y`, counted from the top left
+/// corner of a board seen from the side of `orientation`.
+pub fn square_at(x: u32, y: u32, orientation: Color) -> Option<Square> {
+    if x >= 8 || y >= 8 {
+        return None;
+    }
+    let square = Square::from_coords(File::new(x), Rank::new(7 - y));
+    Some(match orientation {
+        Color::White => square,
+        Color::Black => square.rotate_180(),
+    })
+}
+
+/// Gets the column and row of `square`, counted from the top left corner
+/// of a board seen from the side of `orientation`. Inverse of
+/// [`square_at()`].
+pub fn screen_position(square: Square, orientation: Color) -> (u32, u32) {
+    let square = match orientation {
+        Color::White => square,
+        Color::Black => square.rotate_180(),
+    };
+    (u32::from(square.file()), 7 - u32::from(square.rank()))
+}
+
+/// Names `square` from the perspective of `color`, counting ranks from its
+/// own side of the board, so that black's e2 is the square
+/// [`Square::E7`].
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{coordinates::relative_to, Color, Square};
+///
+/// assert_eq!(relative_to(Square::E2, Color::White), Square::E2);
+/// assert_eq!(relative_to(Square::E2, Color::Black), Square::E7);
+/// assert_eq!(relative_to(Square::A8, Color::Black), Square::A1);
+/// ```
+pub fn relative_to(square: Square, color: Color) -> Square {
+    match color {
+        Color::White => square,
+        Color::Black => square.flip_vertical(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_sampler() {
+        let mut sampler = SquareSampler::new(7);
+        let mut seen = Bitboard::EMPTY;
+        let mut last = None;
+        for _ in 0..1000 {
+            let square = sampler.next_square().expect("candidates");
+            assert_ne!(Some(square), last);
+            seen.add(square);
+            last = Some(square);
+        }
+        assert_eq!(seen, Bitboard::FULL);
+
+        let sampler = SquareSampler::with_squares(7, Bitboard::DARK_SQUARES);
+        assert!(sampler.take(100).all(Square::is_dark));
+
+        let mut sampler = SquareSampler::with_squares(7, Bitboard::from(Square::E4));
+        assert_eq!(sampler.next_square(), Some(Square::E4));
+        assert_eq!(sampler.next_square(), Some(Square::E4));
+
+        assert_eq!(SquareSampler::with_squares(7, Bitboard::EMPTY).next(), None);
+    }
+
+    #[test]
+    fn test_screen_position() {
+        assert_eq!(square_at(0, 0, Color::White), Some(Square::A8));
+        assert_eq!(square_at(7, 7, Color::White), Some(Square::H1));
+        assert_eq!(square_at(8, 0, Color::White), None);
+
+        for orientation in Color::ALL {
+            for square in Square::ALL {
+                let (x, y) = screen_position(square, orientation);
+                assert_eq!(square_at(x, y, orientation), Some(square));
+            }
+        }
+    }
+}
@@ -0,0 +1,429 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Aggregate statistics over collections of positions.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::corpus::CorpusStats;
+//!
+//! let corpus = "\
+//!     rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1\n\
+//!     rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1\n\
+//!     4k3/8/8/8/8/8/8/4K2R w K - bm O-O; id \"castle\";\n\
+//!     not a fen\n";
+//!
+//! let stats = CorpusStats::from_reader(corpus.as_bytes())?;
+//! assert_eq!(stats.positions(), 3);
+//! assert_eq!(stats.invalid(), 1);
+//! assert_eq!(stats.unique(), 2);
+//! assert_eq!(stats.phase_histogram()[24], 2);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    color::ByColor, fen::Fen, zobrist::ZobristHash, Board, ByRole, CastlingMode, Chess, Color,
+    Piece, Position, Role,
+};
+
+/// Game phase from 0 (only kings and pawns) to 24 (all pieces of the
+/// initial position), counting 1 for each knight and bishop, 2 for each
+/// rook and 4 for each queen. Promoted pieces can not push the phase
+/// beyond 24.
+pub fn phase(board: &Board) -> usize {
+    let phase = (board.knights() | board.bishops()).count()
+        + 2 * board.rooks().count()
+        + 4 * board.queens().count();
+    phase.min(24)
+}
+
+/// Streaming statistics over a corpus of positions.
+#[derive(Debug, Clone, Default)]
+pub struct CorpusStats {
+    positions: u64,
+    invalid: u64,
+    checks: u64,
+    material: HashMap<ByColor<ByRole<u8>>, u64>,
+    phases: [u64; 25],
+    seen: HashSet<u64>,
+}
+
+impl CorpusStats {
+    /// Creates empty statistics.
+    pub fn new() -> CorpusStats {
+        CorpusStats::default()
+    }
+
+    /// Reads a corpus with one FEN or EPD per line. Empty lines are
+    /// ignored. Positions are parsed as standard chess, allowing Chess960
+    /// castling rights.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying reader are passed through. Invalid
+    /// positions are not errors, but are [counted](CorpusStats::invalid).
+    pub fn from_reader<R: io::BufRead>(reader: R) -> io::Result<CorpusStats> {
+        let mut stats = CorpusStats::new();
+        for line in reader.split(b'\n') {
+            stats.add_line(&line?);
+        }
+        Ok(stats)
+    }
+
+    /// Adds a line with a FEN or EPD. EPD operations after the first four
+    /// fields are ignored.
+    pub fn add_line(&mut self, line: &[u8]) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return;
+        }
+
+        let fen = Fen::from_ascii(line).or_else(|_| {
+            let epd_end = line
+                .iter()
+                .enumerate()
+                .filter(|(_, ch)| **ch == b' ')
+                .map(|(idx, _)| idx)
+                .nth(3)
+                .unwrap_or(line.len());
+            Fen::from_ascii(&line[..epd_end])
+        });
+
+        match fen
+            .ok()
+            .and_then(|fen| fen.into_position::<Chess>(CastlingMode::Chess960).ok())
+        {
+            Some(pos) => self.add_position(&pos),
+            None => self.invalid += 1,
+        }
+    }
+
+    /// Adds a position.
+    pub fn add_position<P: Position + ZobristHash>(&mut self, pos: &P) {
+        self.positions += 1;
+        if pos.is_check() {
+            self.checks += 1;
+        }
+        *self.material.entry(pos.board().material()).or_insert(0) += 1;
+        self.phases[phase(pos.board())] += 1;
+        self.seen.insert(pos.zobrist_hash());
+    }
+
+    /// Adds all statistics from `other`, for example from a different thread.
+    pub fn merge(&mut self, other: &CorpusStats) {
+        self.positions += other.positions;
+        self.invalid += other.invalid;
+        self.checks += other.checks;
+        for (material, count) in &other.material {
+            *self.material.entry(*material).or_insert(0) += count;
+        }
+        for (phase, count) in self.phases.iter_mut().zip(other.phases) {
+            *phase += count;
+        }
+        self.seen.extend(&other.seen);
+    }
+
+    /// Number of valid positions.
+    pub fn positions(&self) -> u64 {
+        self.positions
+    }
+
+    /// Number of lines that could not be parsed, or that were not valid
+    /// positions.
+    pub fn invalid(&self) -> u64 {
+        self.invalid
+    }
+
+    /// Number of positions where the side to move is in check.
+    pub fn checks(&self) -> u64 {
+        self.checks
+    }
+
+    /// Fraction of positions where the side to move is in check.
+    pub fn check_rate(&self) -> f64 {
+        fraction(self.checks, self.positions)
+    }
+
+    /// Number of distinct positions, by 64-bit Zobrist hash.
+    pub fn unique(&self) -> u64 {
+        self.seen.len() as u64
+    }
+
+    /// Fraction of positions that are duplicates of an earlier position.
+    pub fn duplicate_rate(&self) -> f64 {
+        fraction(self.positions - self.unique(), self.positions)
+    }
+
+    /// Number of positions by material configuration.
+    pub fn material(&self) -> &HashMap<ByColor<ByRole<u8>>, u64> {
+        &self.material
+    }
+
+    /// Number of positions by [`phase()`].
+    pub fn phase_histogram(&self) -> &[u64; 25] {
+        &self.phases
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    Count {
+        color: Option<Color>,
+        role: Option<Role>,
+        min: u8,
+        max: u8,
+    },
+    Any(Vec<MaterialFilter>),
+}
+
+impl Condition {
+    fn matches(&self, material: &ByColor<ByRole<u8>>) -> bool {
+        match *self {
+            Condition::Count {
+                color,
+                role,
+                min,
+                max,
+            } => {
+                let mut count = 0u32;
+                for c in Color::ALL {
+                    if color.map_or(true, |color| color == c) {
+                        for r in Role::ALL {
+                            if role.map_or(true, |role| role == r) {
+                                count += u32::from(*material.get(c).get(r));
+                            }
+                        }
+                    }
+                }
+                u32::from(min) <= count && count <= u32::from(max)
+            }
+            Condition::Any(ref filters) => filters.iter().any(|f| f.matches_material(material)),
+        }
+    }
+}
+
+/// A cheap filter on the material of a position, to discard positions of a
+/// corpus before fully parsing them.
+///
+/// Conditions are combined with logical and. Use [`MaterialFilter::or()`]
+/// for alternatives. [`MaterialFilter::matches_fen()`] only counts the
+/// piece letters of the board part of a FEN or EPD, without validating or
+/// parsing anything else.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{corpus::MaterialFilter, Color, Role};
+///
+/// // Queenless middlegames with at least 12 pawns, or any endgame with at
+/// // most 6 pieces.
+/// let filter = MaterialFilter::new()
+///     .queens(false)
+///     .pawns(12..)
+///     .or(MaterialFilter::new().pieces(..=6));
+///
+/// assert!(filter.matches_fen(b"r3k2r/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/R3K2R w KQkq - 0 1"));
+/// assert!(filter.matches_fen(b"8/8/4k3/8/2QK4/8/8/8 w - - 0 1"));
+/// assert!(!filter.matches_fen(b"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+///
+/// let white_exchange_up = MaterialFilter::new()
+///     .count(Some(Color::White), Some(Role::Rook), 2..)
+///     .count(Some(Color::Black), Some(Role::Rook), ..=1);
+/// assert!(white_exchange_up.matches_fen(b"4k3/8/8/8/8/8/8/R3K2R w - -"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaterialFilter {
+    conditions: Vec<Condition>,
+}
+
+fn bounds<R: RangeBounds<u8>>(range: R) -> (u8, u8) {
+    let min = match range.start_bound() {
+        Bound::Included(&min) => min,
+        Bound::Excluded(&min) => min.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let max = match range.end_bound() {
+        Bound::Included(&max) => max,
+        Bound::Excluded(&0) => return (1, 0),
+        Bound::Excluded(&max) => max - 1,
+        Bound::Unbounded => u8::MAX,
+    };
+    (min, max)
+}
+
+impl MaterialFilter {
+    /// Creates a filter that accepts all positions.
+    pub fn new() -> MaterialFilter {
+        MaterialFilter::default()
+    }
+
+    /// Requires the number of pieces of the given color and role to be in
+    /// `range`. `None` counts all colors or all roles, respectively.
+    #[must_use]
+    pub fn count<R: RangeBounds<u8>>(
+        mut self,
+        color: Option<Color>,
+        role: Option<Role>,
+        range: R,
+    ) -> MaterialFilter {
+        let (min, max) = bounds(range);
+        self.conditions.push(Condition::Count {
+            color,
+            role,
+            min,
+            max,
+        });
+        self
+    }
+
+    /// Requires the total number of pieces, including kings and pawns, to
+    /// be in `range`.
+    #[must_use]
+    pub fn pieces<R: RangeBounds<u8>>(self, range: R) -> MaterialFilter {
+        self.count(None, None, range)
+    }
+
+    /// Requires the total number of pawns to be in `range`.
+    #[must_use]
+    pub fn pawns<R: RangeBounds<u8>>(self, range: R) -> MaterialFilter {
+        self.count(None, Some(Role::Pawn), range)
+    }
+
+    /// Requires queens to be present or absent.
+    #[must_use]
+    pub fn queens(self, present: bool) -> MaterialFilter {
+        if present {
+            self.count(None, Some(Role::Queen), 1..)
+        } else {
+            self.count(None, Some(Role::Queen), ..=0)
+        }
+    }
+
+    /// Creates a filter that accepts positions matching either `self` or
+    /// `other`.
+    #[must_use]
+    pub fn or(self, other: MaterialFilter) -> MaterialFilter {
+        MaterialFilter {
+            conditions: vec![Condition::Any(vec![self, other])],
+        }
+    }
+
+    /// Tests if the filter accepts the given material, as returned by
+    /// [`Board::material()`].
+    pub fn matches_material(&self, material: &ByColor<ByRole<u8>>) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(material))
+    }
+
+    /// Tests if the filter accepts the material on `board`.
+    pub fn matches_board(&self, board: &Board) -> bool {
+        self.matches_material(&board.material())
+    }
+
+    /// Tests if the filter accepts the material of a FEN or EPD, without
+    /// parsing it. Only piece letters up to the end of the board part are
+    /// counted, so positions that pass may still be invalid.
+    pub fn matches_fen(&self, fen: &[u8]) -> bool {
+        let mut material = ByColor::<ByRole<u8>>::default();
+        for &ch in fen
+            .iter()
+            .take_while(|&&ch| ch != b' ' && ch != b'_' && ch != b'[')
+        {
+            if let Some(piece) = Piece::from_char(char::from(ch)) {
+                let count = material.get_mut(piece.color).get_mut(piece.role);
+                *count = count.saturating_add(1);
+            }
+        }
+        self.matches_material(&material)
+    }
+}
+
+fn fraction(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpus_stats() {
+        let mut stats = CorpusStats::new();
+        stats.add_line(b"4k3/8/8/8/8/8/8/4KQ2 b - - 0 1\r");
+        stats.add_line(b"4k3/8/8/8/8/8/8/3QK3 w - -");
+        stats.add_line(b"4k3/8/8/8/8/8/8/3QK3 w - - 5 10");
+        stats.add_line(b"");
+        stats.add_line(b"4k3/8/8/8/8/8/8/3QK3 w - - 5 10 foo");
+        stats.add_line(b"8/8/8/8/8/8/8/8 w - - 0 1");
+
+        assert_eq!(stats.positions(), 4);
+        assert_eq!(stats.invalid(), 1);
+        assert_eq!(stats.checks(), 0);
+        assert_eq!(stats.unique(), 2);
+        assert!((stats.duplicate_rate() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(stats.phase_histogram()[4], 4);
+        assert_eq!(stats.material().len(), 1);
+
+        let mut merged = stats.clone();
+        merged.merge(&stats);
+        assert_eq!(merged.positions(), 8);
+        assert_eq!(merged.unique(), 2);
+        assert_eq!(merged.material().values().sum::<u64>(), 8);
+    }
+
+    #[test]
+    fn test_material_filter() {
+        let startpos = b"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let zh = b"r~nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[QQqq] w KQkq - 0 1";
+
+        assert!(MaterialFilter::new().matches_fen(b""));
+        assert!(MaterialFilter::new().pieces(32..=32).matches_fen(startpos));
+        assert!(MaterialFilter::new().pieces(32..=32).matches_fen(zh));
+        assert!(!MaterialFilter::new().pieces(..32).matches_fen(startpos));
+        assert!(!MaterialFilter::new()
+            .pieces(..0)
+            .matches_fen(b"8/8/8/8/8/8/8/8"));
+        assert!(MaterialFilter::new()
+            .pawns(16..=16)
+            .queens(true)
+            .matches_fen(startpos));
+        assert!(!MaterialFilter::new().queens(false).matches_fen(startpos));
+        assert!(MaterialFilter::new()
+            .count(Some(Color::Black), Some(Role::Knight), 2..=2)
+            .count(Some(Color::White), None, 16..=16)
+            .matches_fen(b"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR_w_KQkq_-_Nb8"));
+
+        let filter = MaterialFilter::new()
+            .queens(false)
+            .or(MaterialFilter::new().pawns(..=2));
+        let endgame = b"4k3/8/8/8/8/8/4P3/3QK3 w - -";
+        assert!(filter.matches_fen(endgame));
+        assert!(!filter.matches_fen(startpos));
+        assert!(filter.matches_board(&"4k3/8/8/8/8/8/4P3/3QK3".parse().expect("valid board")));
+    }
+}
@@ -0,0 +1,378 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Bookkeeping for engine-vs-engine games.
+//!
+//! An [`EngineGame`] validates the moves sent by engines, charges the time
+//! they used, ends the game on rule violations, time forfeits or by
+//! adjudication, and finally emits a PGN. Starting the engines and talking
+//! to them is left to the caller.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use shakmaty::{
+//!     clock::TimeControl,
+//!     engine_match::{EngineGame, Termination},
+//!     uci::Uci,
+//!     Chess, Color, Outcome,
+//! };
+//!
+//! let mut game = EngineGame::new(Chess::default(), "60+1".parse()?);
+//! game.push_tag("White", "Engine A");
+//! game.push_tag("Black", "Engine B");
+//!
+//! for uci in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+//!     // Send the position to the engine and wait for its move ...
+//!     game.play(&uci.parse::<Uci>()?, Duration::from_millis(1500))?;
+//! }
+//!
+//! assert_eq!(
+//!     game.outcome(),
+//!     Some(Outcome::Decisive { winner: Color::Black })
+//! );
+//! assert_eq!(game.termination(), Some(Termination::Normal));
+//! assert_eq!(game.remaining(Color::White), Some(Duration::from_secs(59)));
+//! assert!(game
+//!     .to_string()
+//!     .ends_with("\n2... Qh4# { [%clk 0:00:59] } 0-1\n\n"));
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{error::Error, fmt, io, time::Duration};
+
+use crate::{
+    clock::{format_clk, Clock, PeriodProgress, TimeControl},
+    color::{ByColor, Color},
+    error::ErrorKind,
+    fen::Fen,
+    pgn::PgnWriter,
+    position::{Outcome, Position},
+    san::SanPlus,
+    setup::Setup,
+    tablebase::{self, Tablebase},
+    types::EnPassantMode,
+    uci::Uci,
+    zobrist::ZobristHash,
+};
+
+/// Error when trying to continue a game that is already over.
+#[derive(Clone, Debug)]
+pub struct GameOverError;
+
+impl fmt::Display for GameOverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("game is already over")
+    }
+}
+
+impl Error for GameOverError {}
+
+impl GameOverError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::GameOver
+    }
+}
+
+/// Reason for the end of a game, as in the `Termination` tag of PGNs.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Termination {
+    /// Ended by the rules of the game, for example by checkmate, or by
+    /// resignation or agreement.
+    Normal,
+    /// A player ran out of time.
+    TimeForfeit,
+    /// A player tried to play an illegal move.
+    RulesInfraction,
+    /// Decided by the match manager, for example by consulting tablebases.
+    Adjudication,
+    /// A player stopped responding.
+    Abandoned,
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            Termination::Normal => "normal",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::RulesInfraction => "rules infraction",
+            Termination::Adjudication => "adjudication",
+            Termination::Abandoned => "abandoned",
+        })
+    }
+}
+
+/// A game between two engines.
+#[derive(Debug, Clone)]
+pub struct EngineGame<P> {
+    initial: P,
+    pos: P,
+    time_control: TimeControl,
+    clock: Clock,
+    progress: ByColor<PeriodProgress>,
+    tags: Vec<(String, String)>,
+    moves: Vec<(SanPlus, Option<Duration>)>,
+    hashes: Vec<u64>,
+    result: Option<(Outcome, Termination)>,
+}
+
+impl<P: Position + Clone + ZobristHash> EngineGame<P> {
+    /// Starts a game from `pos`.
+    pub fn new(pos: P, time_control: TimeControl) -> EngineGame<P> {
+        EngineGame {
+            initial: pos.clone(),
+            pos,
+            clock: Clock::new(&time_control),
+            time_control,
+            progress: ByColor::default(),
+            tags: Vec::new(),
+            moves: Vec::new(),
+            hashes: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Adds a tag for the PGN, like `White` or `Event`. The `Result`,
+    /// `SetUp`, `FEN`, `TimeControl` and `Termination` tags are added
+    /// automatically.
+    pub fn push_tag(&mut self, name: &str, value: &str) {
+        self.tags.push((name.to_owned(), value.to_owned()));
+    }
+
+    /// Gets the current position.
+    pub fn position(&self) -> &P {
+        &self.pos
+    }
+
+    /// Gets the remaining time of `color`, or `None` if the game is not
+    /// played with a clock.
+    pub fn remaining(&self, color: Color) -> Option<Duration> {
+        self.clock.remaining(color)
+    }
+
+    /// Gets the 64-bit Zobrist hashes of all earlier positions.
+    pub fn hash_history(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    /// Gets the outcome, or `None` if the game is not over.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.result.map(|(outcome, _)| outcome)
+    }
+
+    /// Gets the reason for the end of the game, or `None` if the game is
+    /// not over.
+    pub fn termination(&self) -> Option<Termination> {
+        self.result.map(|(_, termination)| termination)
+    }
+
+    /// Plays a move sent by the engine of the side to move, which used
+    /// `elapsed` time to think.
+    ///
+    /// The time is charged before the move is validated. If it exceeds the
+    /// remaining time, the engine loses on time (or draws if the opponent
+    /// has insufficient material to win). If the move is illegal, the
+    /// engine loses by rules infraction. Otherwise the move is played and
+    /// the game ends if the position is decided by the rules of the game,
+    /// including the 75-move rule and fivefold repetition.
+    ///
+    /// Returns the outcome if the game ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GameOverError`] if the game was already over.
+    pub fn play(&mut self, uci: &Uci, elapsed: Duration) -> Result<Option<Outcome>, GameOverError> {
+        if self.result.is_some() {
+            return Err(GameOverError);
+        }
+
+        let turn = self.pos.turn();
+        if !self.clock.charge(
+            &self.time_control,
+            self.progress.get_mut(turn),
+            turn,
+            elapsed,
+        ) {
+            let outcome = Outcome::from_timeout(&self.pos, turn);
+            return Ok(Some(self.end(outcome, Termination::TimeForfeit)));
+        }
+
+        let m = match uci.to_move(&self.pos) {
+            Ok(m) => m,
+            Err(_) => {
+                return Ok(Some(self.end(
+                    Outcome::Decisive { winner: !turn },
+                    Termination::RulesInfraction,
+                )))
+            }
+        };
+
+        self.hashes.push(self.pos.zobrist_hash());
+        let san_plus = SanPlus::from_move_and_play_unchecked(&mut self.pos, &m);
+        self.moves.push((san_plus, self.clock.remaining(turn)));
+
+        let no_tablebase: Option<&fn(&P) -> Option<_>> = None;
+        Ok(tablebase::adjudicate(&self.pos, &self.hashes, no_tablebase)
+            .map(|outcome| self.end(outcome, Termination::Normal)))
+    }
+
+    /// Adjudicates the game using a tablebase, if the current position is
+    /// decided according to [`tablebase::adjudicate()`].
+    ///
+    /// Returns the outcome if the game ended.
+    pub fn adjudicate<T: Tablebase<P>>(&mut self, tablebase: &T) -> Option<Outcome> {
+        if self.result.is_some() {
+            return self.outcome();
+        }
+        tablebase::adjudicate(&self.pos, &self.hashes, Some(tablebase))
+            .map(|outcome| self.end(outcome, Termination::Adjudication))
+    }
+
+    /// Ends the game, for example by resignation or engine crash, or
+    /// according to a custom adjudication rule. Does nothing if the game
+    /// is already over.
+    pub fn end(&mut self, outcome: Outcome, termination: Termination) -> Outcome {
+        self.result.get_or_insert((outcome, termination)).0
+    }
+}
+
+impl<P: Position + Clone> EngineGame<P> {
+    /// Writes the game as PGN, with the remaining time after each move
+    /// in a `[%clk]` comment.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn write_pgn<W: io::Write>(&self, writer: &mut PgnWriter<W>) -> io::Result<()> {
+        for (name, value) in &self.tags {
+            writer.tag(name.as_bytes(), value.as_bytes())?;
+        }
+        let result = match self.result {
+            Some((outcome, _)) => outcome.to_string(),
+            None => "*".to_owned(),
+        };
+        writer.tag(b"Result", result.as_bytes())?;
+        let setup = self.initial.clone().into_setup(EnPassantMode::Legal);
+        if setup != Setup::default() {
+            writer.tag(b"SetUp", b"1")?;
+            writer.tag(b"FEN", Fen::from_setup(setup).to_string().as_bytes())?;
+        }
+        writer.tag(b"TimeControl", self.time_control.to_string().as_bytes())?;
+        if let Some((_, termination)) = self.result {
+            writer.tag(b"Termination", termination.to_string().as_bytes())?;
+        }
+
+        for (san_plus, remaining) in &self.moves {
+            writer.san(san_plus)?;
+            if let Some(remaining) = remaining {
+                writer.comment(format!(" {} ", format_clk(*remaining)).as_bytes())?;
+            }
+        }
+        writer.outcome(self.result.map(|(outcome, _)| outcome))
+    }
+}
+
+impl<P: Position + Clone> fmt::Display for EngineGame<P> {
+    /// Formats the game as PGN.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = PgnWriter::new(Vec::new());
+        self.write_pgn(&mut writer).map_err(|_| fmt::Error)?;
+        f.write_str(&String::from_utf8_lossy(&writer.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chess;
+
+    fn play(game: &mut EngineGame<Chess>, uci: &str, millis: u64) -> Option<Outcome> {
+        game.play(
+            &uci.parse().expect("valid uci"),
+            Duration::from_millis(millis),
+        )
+        .expect("game not over")
+    }
+
+    #[test]
+    fn test_periods() {
+        let mut game = EngineGame::new(
+            Chess::default(),
+            "2/60+1:30".parse().expect("valid time control"),
+        );
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            assert_eq!(play(&mut game, uci, 10_000), None);
+        }
+        // Second period starts after 2 moves.
+        assert_eq!(
+            game.remaining(Color::White),
+            Some(Duration::from_secs(60 + 1 - 10 + 1 - 10 + 30))
+        );
+        assert_eq!(
+            play(&mut game, "e2e4", 72_001),
+            Some(Outcome::Decisive {
+                winner: Color::Black
+            })
+        );
+        assert_eq!(game.termination(), Some(Termination::TimeForfeit));
+        assert_eq!(game.remaining(Color::White), Some(Duration::ZERO));
+        assert!(game
+            .play(&"e2e4".parse().expect("uci"), Duration::ZERO)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sandclock_and_illegal_move() {
+        let mut game = EngineGame::new(
+            Chess::default(),
+            TimeControl::Sandclock(Duration::from_secs(10)),
+        );
+        play(&mut game, "e2e4", 3_000);
+        assert_eq!(game.remaining(Color::White), Some(Duration::from_secs(7)));
+        assert_eq!(game.remaining(Color::Black), Some(Duration::from_secs(13)));
+        assert_eq!(
+            play(&mut game, "e2e4", 0),
+            Some(Outcome::Decisive {
+                winner: Color::White
+            })
+        );
+        assert_eq!(game.termination(), Some(Termination::RulesInfraction));
+        assert!(game
+            .to_string()
+            .ends_with("1. e4 { [%clk 0:00:07] } 1-0\n\n"));
+    }
+
+    #[test]
+    fn test_adjudicate() {
+        let mut game = EngineGame::new(Chess::default(), TimeControl::Unlimited);
+        play(&mut game, "e2e4", 0);
+        assert_eq!(game.adjudicate(&|_: &Chess| None), None);
+        assert_eq!(
+            game.adjudicate(&|_: &Chess| Some(tablebase::Wdl::CursedWin)),
+            Some(Outcome::Draw)
+        );
+        game.end(
+            Outcome::Decisive {
+                winner: Color::White,
+            },
+            Termination::Abandoned,
+        );
+        assert_eq!(game.termination(), Some(Termination::Adjudication));
+        assert!(game.to_string().contains("[Termination \"adjudication\"]"));
+    }
+}
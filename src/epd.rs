@@ -0,0 +1,463 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Parse and write Extended Position Description (EPD) records with
+//! operations, as used by test suites like WAC or STS.
+//!
+//! An EPD record consists of the first four fields of a FEN, followed by
+//! operations like `bm Qg6;` or `id "WAC.001";`. Each operation has an
+//! opcode and any number of operands.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{epd::EpdRecord, san::San, Chess, CastlingMode, Position};
+//!
+//! let epd: EpdRecord = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";"
+//!     .parse()?;
+//! assert_eq!(epd.id(), Some("WAC.001"));
+//! assert_eq!(epd.best_moves()?, ["Qg6".parse::<San>()?]);
+//!
+//! let pos: Chess = epd.clone().into_setup().position(CastlingMode::Standard)?;
+//! assert!(epd.best_moves()?[0].to_move(&pos).is_ok());
+//!
+//! assert_eq!(
+//!     epd.to_string(),
+//!     "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";"
+//! );
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Write as _},
+    num::NonZeroU32,
+    str::FromStr,
+};
+
+use crate::{
+    error::ErrorKind,
+    fen::{self, ParseFenError},
+    san::{ParseSanError, San},
+    setup::Setup,
+};
+
+/// Errors that can occur when parsing an EPD record.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ParseEpdError {
+    /// The position fields are invalid.
+    InvalidFen(ParseFenError),
+    /// An operation is invalid, for example because of an invalid opcode
+    /// or an unterminated string operand.
+    InvalidOperation,
+}
+
+impl Display for ParseEpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ParseEpdError::InvalidFen(ref err) => Display::fmt(err, f),
+            ParseEpdError::InvalidOperation => f.write_str("invalid operation in epd"),
+        }
+    }
+}
+
+impl Error for ParseEpdError {}
+
+impl ParseEpdError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            ParseEpdError::InvalidFen(_) => ErrorKind::InvalidFen,
+            ParseEpdError::InvalidOperation => ErrorKind::InvalidEpd,
+        }
+    }
+}
+
+impl From<ParseFenError> for ParseEpdError {
+    fn from(err: ParseFenError) -> ParseEpdError {
+        ParseEpdError::InvalidFen(err)
+    }
+}
+
+/// An EPD operation, like `bm Qg6 Rxg7`.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Operation {
+    /// The opcode, starting with a letter, followed by letters, digits and
+    /// underscores.
+    pub opcode: String,
+    /// The operands, without quotes.
+    pub operands: Vec<String>,
+}
+
+impl Operation {
+    fn is_string(&self) -> bool {
+        let opcode = self.opcode.as_bytes();
+        self.opcode == "id"
+            || (opcode.len() == 2 && opcode[0] == b'c' && opcode[1].is_ascii_digit())
+    }
+}
+
+/// String operands of `id` and `c0` to `c9` are always quoted, other
+/// operands only if necessary. Quotes and backslashes in quoted operands
+/// are escaped with a backslash.
+impl Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.opcode)?;
+        for operand in &self.operands {
+            if self.is_string()
+                || operand.is_empty()
+                || operand.contains(|ch: char| ch == ';' || ch == '"' || ch.is_ascii_whitespace())
+            {
+                f.write_str(" \"")?;
+                for ch in operand.chars() {
+                    if ch == '"' || ch == '\\' {
+                        f.write_char('\\')?;
+                    }
+                    f.write_char(ch)?;
+                }
+                f.write_char('"')?;
+            } else {
+                write!(f, " {}", operand)?;
+            }
+        }
+        f.write_str(";")
+    }
+}
+
+fn is_opcode(opcode: &[u8]) -> bool {
+    opcode.first().map_or(false, u8::is_ascii_alphabetic)
+        && opcode
+            .iter()
+            .all(|ch| ch.is_ascii_alphanumeric() || *ch == b'_')
+}
+
+fn parse_operations(mut s: &[u8]) -> Result<Vec<Operation>, ParseEpdError> {
+    let skip_whitespace = |s: &mut &[u8]| {
+        while let Some((ch, tail)) = s.split_first() {
+            if !ch.is_ascii_whitespace() {
+                break;
+            }
+            *s = tail;
+        }
+    };
+    let take_word = |s: &mut &[u8]| -> String {
+        let end = s
+            .iter()
+            .position(|ch| *ch == b';' || ch.is_ascii_whitespace())
+            .unwrap_or(s.len());
+        let (word, tail) = s.split_at(end);
+        *s = tail;
+        String::from_utf8_lossy(word).into_owned()
+    };
+
+    let mut operations = Vec::new();
+    loop {
+        skip_whitespace(&mut s);
+        if s.is_empty() {
+            break;
+        }
+        let opcode = take_word(&mut s);
+        if !is_opcode(opcode.as_bytes()) {
+            return Err(ParseEpdError::InvalidOperation);
+        }
+        let mut operands = Vec::new();
+        loop {
+            skip_whitespace(&mut s);
+            match s.first() {
+                // The final semicolon is often omitted.
+                None => break,
+                Some(b';') => {
+                    s = &s[1..];
+                    break;
+                }
+                Some(b'"') => {
+                    // Quotes and backslashes can be escaped with a
+                    // backslash. Other backslashes are taken literally.
+                    let mut operand = Vec::new();
+                    s = &s[1..];
+                    loop {
+                        match *s {
+                            [] => return Err(ParseEpdError::InvalidOperation),
+                            [b'"', ref tail @ ..] => {
+                                s = tail;
+                                break;
+                            }
+                            [b'\\', escaped @ (b'"' | b'\\'), ref tail @ ..] => {
+                                operand.push(escaped);
+                                s = tail;
+                            }
+                            [ch, ref tail @ ..] => {
+                                operand.push(ch);
+                                s = tail;
+                            }
+                        }
+                    }
+                    operands.push(String::from_utf8_lossy(&operand).into_owned());
+                }
+                Some(_) => operands.push(take_word(&mut s)),
+            }
+        }
+        operations.push(Operation { opcode, operands });
+    }
+    Ok(operations)
+}
+
+/// An EPD record with operations.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct EpdRecord {
+    /// The position, given by the first four fields.
+    pub position: fen::Epd,
+    /// Operations in order of appearance.
+    pub operations: Vec<Operation>,
+}
+
+impl EpdRecord {
+    /// Parses an EPD record.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseEpdError`] if the position fields or any of the
+    /// operations are invalid.
+    pub fn from_ascii(epd: &[u8]) -> Result<EpdRecord, ParseEpdError> {
+        // Find the end of the four position fields, and the remaining
+        // checks if present.
+        let mut end = 0;
+        for field in 0..5 {
+            let start = epd[end..]
+                .iter()
+                .position(|ch| !ch.is_ascii_whitespace())
+                .map_or(epd.len(), |i| end + i);
+            let field_end = epd[start..]
+                .iter()
+                .position(u8::is_ascii_whitespace)
+                .map_or(epd.len(), |i| start + i);
+            let value = &epd[start..field_end];
+            if field == 4
+                && !(value.contains(&b'+')
+                    && value.iter().all(|ch| ch.is_ascii_digit() || *ch == b'+'))
+            {
+                break;
+            }
+            end = field_end;
+        }
+
+        Ok(EpdRecord {
+            position: fen::Epd::from_ascii(&epd[..end])?,
+            operations: parse_operations(&epd[end..])?,
+        })
+    }
+
+    /// Gets the operands of the first operation with the given opcode.
+    pub fn get(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|operation| operation.opcode == opcode)
+            .map(|operation| &operation.operands[..])
+    }
+
+    /// Replaces the operands of the first operation with the given opcode,
+    /// or appends a new operation.
+    pub fn set(&mut self, opcode: &str, operands: Vec<String>) {
+        match self
+            .operations
+            .iter_mut()
+            .find(|operation| operation.opcode == opcode)
+        {
+            Some(operation) => operation.operands = operands,
+            None => self.operations.push(Operation {
+                opcode: opcode.to_owned(),
+                operands,
+            }),
+        }
+    }
+
+    /// Removes all operations with the given opcode.
+    pub fn remove(&mut self, opcode: &str) {
+        self.operations
+            .retain(|operation| operation.opcode != opcode);
+    }
+
+    fn moves(&self, opcode: &str) -> Result<Vec<San>, ParseSanError> {
+        self.get(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|operand| operand.parse())
+            .collect()
+    }
+
+    fn number<T: FromStr>(&self, opcode: &str) -> Option<T> {
+        self.get(opcode)?.first()?.parse().ok()
+    }
+
+    /// Gets the best moves (`bm`), or an empty list.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseSanError`] if any of the moves is not valid SAN.
+    pub fn best_moves(&self) -> Result<Vec<San>, ParseSanError> {
+        self.moves("bm")
+    }
+
+    /// Gets the moves to avoid (`am`), or an empty list.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseSanError`] if any of the moves is not valid SAN.
+    pub fn avoid_moves(&self) -> Result<Vec<San>, ParseSanError> {
+        self.moves("am")
+    }
+
+    /// Gets the identifier (`id`).
+    pub fn id(&self) -> Option<&str> {
+        self.get("id")?.first().map(String::as_str)
+    }
+
+    /// Gets the centipawn evaluation (`ce`), from the point of view of the
+    /// side to move.
+    pub fn centipawns(&self) -> Option<i32> {
+        self.number("ce")
+    }
+
+    /// Gets the number of moves to a forced mate (`dm`).
+    pub fn direct_mate(&self) -> Option<u32> {
+        self.number("dm")
+    }
+
+    /// Gets the setup, with the move counters from the `hmvc` and `fmvn`
+    /// operations if present.
+    pub fn into_setup(self) -> Setup {
+        let halfmoves = self.number("hmvc");
+        let fullmoves = self.number::<NonZeroU32>("fmvn");
+        let mut setup = self.position.into_setup();
+        if let Some(halfmoves) = halfmoves {
+            setup.halfmoves = halfmoves;
+        }
+        if let Some(fullmoves) = fullmoves {
+            setup.fullmoves = fullmoves;
+        }
+        setup
+    }
+}
+
+impl From<fen::Epd> for EpdRecord {
+    fn from(position: fen::Epd) -> EpdRecord {
+        EpdRecord {
+            position,
+            operations: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for EpdRecord {
+    type Err = ParseEpdError;
+
+    fn from_str(epd: &str) -> Result<EpdRecord, ParseEpdError> {
+        EpdRecord::from_ascii(epd.as_bytes())
+    }
+}
+
+impl Display for EpdRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.position, f)?;
+        for operation in &self.operations {
+            write!(f, " {}", operation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epd() {
+        let epd: EpdRecord = "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Qd2 Be2; am f4; ce -15; dm 7; hmvc 3; fmvn 6; c0 \"two; words\"; noop"
+            .parse()
+            .expect("valid epd");
+        assert_eq!(
+            epd.best_moves().expect("valid san"),
+            ["Qd2".parse::<San>().unwrap(), "Be2".parse().unwrap()]
+        );
+        assert_eq!(
+            epd.avoid_moves().expect("valid san"),
+            ["f4".parse::<San>().unwrap()]
+        );
+        assert_eq!(epd.id(), None);
+        assert_eq!(epd.centipawns(), Some(-15));
+        assert_eq!(epd.direct_mate(), Some(7));
+        assert_eq!(epd.get("c0"), Some(&["two; words".to_owned()][..]));
+        assert_eq!(epd.get("noop"), Some(&[][..]));
+        assert_eq!(
+            epd.to_string(),
+            "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Qd2 Be2; am f4; ce -15; dm 7; hmvc 3; fmvn 6; c0 \"two; words\"; noop;"
+        );
+
+        let setup = epd.clone().into_setup();
+        assert_eq!(setup.halfmoves, 3);
+        assert_eq!(setup.fullmoves.get(), 6);
+
+        let mut epd = epd;
+        epd.set("id", vec!["test".to_owned()]);
+        epd.set("ce", vec!["20".to_owned()]);
+        epd.remove("noop");
+        assert!(epd
+            .to_string()
+            .ends_with("ce 20; dm 7; hmvc 3; fmvn 6; c0 \"two; words\"; id \"test\";"));
+        assert_eq!(
+            epd.to_string().parse::<EpdRecord>().expect("valid epd"),
+            epd
+        );
+
+        // Remaining checks are part of the position.
+        let epd: EpdRecord = "4k3/8/8/8/8/8/8/4K3 w - - 1+2 id \"3check\";"
+            .parse()
+            .expect("valid epd");
+        assert_eq!(epd.id(), Some("3check"));
+        assert_eq!(
+            epd.to_string(),
+            "4k3/8/8/8/8/8/8/4K3 w - - 1+2 id \"3check\";"
+        );
+
+        // Quotes and backslashes in string operands are escaped.
+        let mut epd: EpdRecord = "4k3/8/8/8/8/8/8/4K3 w - - c0 C:\\books\\wac.epd;"
+            .parse()
+            .expect("valid epd");
+        assert_eq!(epd.get("c0"), Some(&["C:\\books\\wac.epd".to_owned()][..]));
+        epd.set("c1", vec!["say \"hi\"".to_owned(), "a\"b".to_owned()]);
+        assert_eq!(
+            epd.to_string(),
+            "4k3/8/8/8/8/8/8/4K3 w - - c0 \"C:\\\\books\\\\wac.epd\"; c1 \"say \\\"hi\\\"\" \"a\\\"b\";"
+        );
+        assert_eq!(
+            epd.to_string().parse::<EpdRecord>().expect("valid epd"),
+            epd
+        );
+
+        assert_eq!(
+            "4k3/8/8/8/8/8/8/4K3 w - - id \"unterminated".parse::<EpdRecord>(),
+            Err(ParseEpdError::InvalidOperation)
+        );
+        assert_eq!(
+            "4k3/8/8/8/8/8/8/4K3 w - - 1bm e4;".parse::<EpdRecord>(),
+            Err(ParseEpdError::InvalidOperation)
+        );
+        assert!(matches!(
+            "4k3/8/8/8/8/8/8/4K3 x - - bm e4;".parse::<EpdRecord>(),
+            Err(ParseEpdError::InvalidFen(_))
+        ));
+    }
+}
@@ -0,0 +1,143 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+/// Machine-readable kind of an error of this crate.
+///
+/// Every error type has a `kind()` method, so that errors can be mapped
+/// to stable error codes without matching on their messages. More
+/// detailed information is available from the error types themselves,
+/// for example [`ParseFenError::kind()`](crate::fen::ParseFenError::kind()),
+/// which converts into an `ErrorKind`, or
+/// [`PositionError::kinds()`](crate::PositionError::kinds()).
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{san::San, Chess, ErrorKind};
+///
+/// let err = "Nf6".parse::<San>()?.to_move(&Chess::default()).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::IllegalMove);
+/// assert_eq!(err.kind().code(), "illegal_move");
+///
+/// assert_eq!("Nz9".parse::<San>().unwrap_err().kind(), ErrorKind::InvalidSan);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Invalid color name.
+    InvalidColor,
+    /// Invalid square name.
+    InvalidSquare,
+    /// Invalid variant name.
+    InvalidVariant,
+    /// Invalid game result.
+    InvalidOutcome,
+    /// Syntactically invalid FEN.
+    InvalidFen,
+    /// Syntactically invalid EPD operations.
+    InvalidEpd,
+    /// Syntactically invalid SAN.
+    InvalidSan,
+    /// Syntactically invalid UCI.
+    InvalidUci,
+    /// Syntactically invalid long algebraic notation.
+    InvalidLan,
+    /// Syntactically invalid ICCF numeric notation.
+    InvalidIccf,
+    /// Invalid time control.
+    InvalidTimeControl,
+    /// Invalid rating.
+    InvalidRating,
+    /// Invalid square highlight or arrow.
+    InvalidShape,
+    /// Invalid engine evaluation.
+    InvalidEval,
+    /// Invalid opening book.
+    InvalidBook,
+    /// Castling rights that can not be repaired.
+    InvalidCastling,
+    /// Setup that is not a legal position.
+    InvalidPosition,
+    /// Move that is not legal in the position.
+    IllegalMove,
+    /// Move notation that matches multiple legal moves.
+    AmbiguousMove,
+    /// Game is already over.
+    GameOver,
+    /// Sequence of moves does not reach the expected position.
+    UnexpectedPosition,
+}
+
+impl ErrorKind {
+    /// Gets a stable code for the kind, like `illegal_move`.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::InvalidColor => "invalid_color",
+            ErrorKind::InvalidSquare => "invalid_square",
+            ErrorKind::InvalidVariant => "invalid_variant",
+            ErrorKind::InvalidOutcome => "invalid_outcome",
+            ErrorKind::InvalidFen => "invalid_fen",
+            ErrorKind::InvalidEpd => "invalid_epd",
+            ErrorKind::InvalidSan => "invalid_san",
+            ErrorKind::InvalidUci => "invalid_uci",
+            ErrorKind::InvalidLan => "invalid_lan",
+            ErrorKind::InvalidIccf => "invalid_iccf",
+            ErrorKind::InvalidTimeControl => "invalid_time_control",
+            ErrorKind::InvalidRating => "invalid_rating",
+            ErrorKind::InvalidShape => "invalid_shape",
+            ErrorKind::InvalidEval => "invalid_eval",
+            ErrorKind::InvalidBook => "invalid_book",
+            ErrorKind::InvalidCastling => "invalid_castling",
+            ErrorKind::InvalidPosition => "invalid_position",
+            ErrorKind::IllegalMove => "illegal_move",
+            ErrorKind::AmbiguousMove => "ambiguous_move",
+            ErrorKind::GameOver => "game_over",
+            ErrorKind::UnexpectedPosition => "unexpected_position",
+        }
+    }
+
+    /// Tests if the error is about syntactically invalid input, as opposed
+    /// to well-formed input that violates the rules of the game.
+    pub fn is_syntax(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::InvalidColor
+                | ErrorKind::InvalidSquare
+                | ErrorKind::InvalidVariant
+                | ErrorKind::InvalidOutcome
+                | ErrorKind::InvalidFen
+                | ErrorKind::InvalidEpd
+                | ErrorKind::InvalidSan
+                | ErrorKind::InvalidUci
+                | ErrorKind::InvalidLan
+                | ErrorKind::InvalidIccf
+                | ErrorKind::InvalidTimeControl
+                | ErrorKind::InvalidRating
+                | ErrorKind::InvalidShape
+                | ErrorKind::InvalidEval
+                | ErrorKind::InvalidBook
+        )
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
@@ -0,0 +1,740 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Compact in-memory opening explorer.
+//!
+//! An [`Explorer`] maps 64-bit Zobrist keys of positions to per-move
+//! result counts. It is an open addressing hash table with linear probing,
+//! designed to hold millions of positions: Each position takes 16 bytes
+//! (at a load factor of at most 1/2, so up to 32 bytes), and each move
+//! record takes 20 bytes.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{explorer::Explorer, uci::Uci, Chess, Color, Outcome, Position};
+//!
+//! let mut explorer = Explorer::new();
+//!
+//! let pos = Chess::default();
+//! let m = "e2e4".parse::<Uci>()?.to_move(&pos)?;
+//! explorer.record(&pos, &m, Some(Outcome::Decisive { winner: Color::White }));
+//! explorer.record(&pos, &m, Some(Outcome::Draw));
+//!
+//! let moves = explorer.get(&pos);
+//! assert_eq!(moves[0].0, m);
+//! assert_eq!(moves[0].1.games(), 2);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Long-running aggregation jobs can checkpoint the explorer with
+//! [`Explorer::write_bin()`] and resume with [`Explorer::read_bin()`].
+
+use std::{convert::TryFrom, io};
+
+use crate::{
+    san::San,
+    uci::{IllegalUciError, Uci},
+    util::read_record,
+    zobrist::ZobristHash,
+    Color, Move, Outcome, Position, Role, Square,
+};
+
+/// A move packed into 16 bits, in the format of
+/// [`Uci::from_chess960()`].
+///
+/// Bits 0-5 are the target square, bits 6-11 the origin square, and bits
+/// 12-14 the promotion role. For drops, bit 15 is set, and bits 12-14 are
+/// the dropped role.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CompactMove(pub u16);
+
+impl CompactMove {
+    const DROP: u16 = 1 << 15;
+
+    /// Packs a [`Uci`]. Returns `None` for null moves.
+    pub fn from_uci(uci: &Uci) -> Option<CompactMove> {
+        Some(CompactMove(match *uci {
+            Uci::Normal {
+                from,
+                to,
+                promotion,
+            } => u16::from(to) | (u16::from(from) << 6) | (promotion.map_or(0, u16::from) << 12),
+            Uci::Put { role, to } => u16::from(to) | (u16::from(role) << 12) | CompactMove::DROP,
+            Uci::Null => return None,
+        }))
+    }
+
+    /// Packs a [`Move`].
+    pub fn from_move(m: &Move) -> CompactMove {
+        CompactMove::from_uci(&Uci::from_chess960(m)).expect("not a null move")
+    }
+
+    /// Unpacks to [`Uci`].
+    pub fn to_uci(self) -> Uci {
+        let to = Square::new(u32::from(self.0 & 63));
+        let role = Role::try_from((self.0 >> 12) & 7).ok();
+        match role {
+            Some(role) if self.0 & CompactMove::DROP != 0 => Uci::Put { role, to },
+            _ => Uci::Normal {
+                from: Square::new(u32::from((self.0 >> 6) & 63)),
+                to,
+                promotion: role,
+            },
+        }
+    }
+
+    /// Unpacks to a legal [`Move`] in `pos`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IllegalUciError`] if the move is not legal in `pos`.
+    pub fn to_move<P: Position>(self, pos: &P) -> Result<Move, IllegalUciError> {
+        self.to_uci().to_move(pos)
+    }
+}
+
+/// Game results for a move.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Counts {
+    /// Number of games won by white.
+    pub white: u32,
+    /// Number of drawn games.
+    pub draws: u32,
+    /// Number of games won by black.
+    pub black: u32,
+}
+
+impl Counts {
+    /// Counts for a single game with the given `outcome`.
+    pub fn from_outcome(outcome: Outcome) -> Counts {
+        let mut counts = Counts::default();
+        counts.add_outcome(outcome);
+        counts
+    }
+
+    /// Adds a game with the given `outcome`.
+    pub fn add_outcome(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Decisive {
+                winner: Color::White,
+            } => self.white = self.white.saturating_add(1),
+            Outcome::Decisive {
+                winner: Color::Black,
+            } => self.black = self.black.saturating_add(1),
+            Outcome::Draw => self.draws = self.draws.saturating_add(1),
+        }
+    }
+
+    /// Adds all games from `other`. Counts saturate at `u32::MAX`.
+    pub fn add(&mut self, other: Counts) {
+        self.white = self.white.saturating_add(other.white);
+        self.draws = self.draws.saturating_add(other.draws);
+        self.black = self.black.saturating_add(other.black);
+    }
+
+    /// Total number of games.
+    pub fn games(self) -> u64 {
+        u64::from(self.white) + u64::from(self.draws) + u64::from(self.black)
+    }
+
+    /// Number of games won by `color`.
+    pub fn wins(self, color: Color) -> u32 {
+        color.fold_wb(self.white, self.black)
+    }
+
+    /// Score of `color`, counting wins as 1 and draws as 1/2, from 0 to 1.
+    /// Returns `None` if there are no games.
+    pub fn score(self, color: Color) -> Option<f64> {
+        match self.games() {
+            0 => None,
+            games => {
+                Some((f64::from(self.wins(color)) + f64::from(self.draws) / 2.0) / games as f64)
+            }
+        }
+    }
+}
+
+/// Statistics for a move, as returned by [`Explorer::stats()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveStats {
+    /// The move.
+    pub m: Move,
+    /// Games where the move was played.
+    pub counts: Counts,
+    /// Games that continued from the position after the move, including
+    /// games that reached it with a different move order. Games that ended
+    /// in the position are not included.
+    pub transposed: Counts,
+    /// Score of the side that played the move. See [`Counts::score()`].
+    pub score: Option<f64>,
+}
+
+const NONE: u32 = u32::MAX;
+
+const RECORD_SIZE: usize = 22;
+
+#[derive(Debug, Copy, Clone)]
+struct Slot {
+    key: u64,
+    head: u32,
+}
+
+const EMPTY_SLOT: Slot = Slot { key: 0, head: NONE };
+
+#[derive(Debug, Copy, Clone)]
+struct Record {
+    counts: Counts,
+    next: u32,
+    m: CompactMove,
+}
+
+/// Map from positions (by Zobrist key) to move statistics.
+///
+/// See the [module level documentation](self) for memory usage.
+#[derive(Debug, Clone, Default)]
+pub struct Explorer {
+    slots: Vec<Slot>,
+    len: usize,
+    records: Vec<Record>,
+}
+
+impl Explorer {
+    /// Creates an empty explorer.
+    pub fn new() -> Explorer {
+        Explorer::default()
+    }
+
+    /// Creates an empty explorer with space for at least `positions`
+    /// positions before reallocating.
+    pub fn with_capacity(positions: usize) -> Explorer {
+        Explorer {
+            slots: vec![EMPTY_SLOT; (positions * 2).next_power_of_two()],
+            len: 0,
+            records: Vec::with_capacity(positions),
+        }
+    }
+
+    /// Number of positions.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tests if there are no positions.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of move records.
+    pub fn num_moves(&self) -> usize {
+        self.records.len()
+    }
+
+    fn find(&self, key: u64) -> Option<usize> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mask = self.slots.len() - 1;
+        let mut idx = key as usize & mask;
+        loop {
+            let slot = self.slots[idx];
+            if slot.head == NONE {
+                return None;
+            } else if slot.key == key {
+                return Some(idx);
+            }
+            idx = (idx + 1) & mask;
+        }
+    }
+
+    fn grow(&mut self) {
+        let capacity = (self.slots.len() * 2).max(16);
+        let old = std::mem::replace(&mut self.slots, vec![EMPTY_SLOT; capacity]);
+        let mask = self.slots.len() - 1;
+        for slot in old.into_iter().filter(|slot| slot.head != NONE) {
+            let mut idx = slot.key as usize & mask;
+            while self.slots[idx].head != NONE {
+                idx = (idx + 1) & mask;
+            }
+            self.slots[idx] = slot;
+        }
+    }
+
+    /// Adds `counts` for the move `m` in the position with Zobrist `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX - 1` move records would be stored.
+    pub fn insert(&mut self, key: u64, m: CompactMove, counts: Counts) {
+        if (self.len + 1) * 2 > self.slots.len() {
+            self.grow();
+        }
+
+        let new_record = u32::try_from(self.records.len())
+            .ok()
+            .filter(|idx| *idx != NONE)
+            .expect("too many move records");
+
+        let mask = self.slots.len() - 1;
+        let mut idx = key as usize & mask;
+        loop {
+            let slot = self.slots[idx];
+            if slot.head == NONE {
+                self.slots[idx] = Slot {
+                    key,
+                    head: new_record,
+                };
+                self.len += 1;
+                break;
+            } else if slot.key == key {
+                let mut record_idx = slot.head as usize;
+                loop {
+                    let record = &mut self.records[record_idx];
+                    if record.m == m {
+                        record.counts.add(counts);
+                        return;
+                    } else if record.next == NONE {
+                        record.next = new_record;
+                        break;
+                    }
+                    record_idx = record.next as usize;
+                }
+                break;
+            }
+            idx = (idx + 1) & mask;
+        }
+
+        self.records.push(Record {
+            counts,
+            next: NONE,
+            m,
+        });
+    }
+
+    /// Records a game with `outcome`, where the move `m` was played in `pos`.
+    /// Games with unknown outcome are not recorded.
+    pub fn record<P: Position + ZobristHash>(
+        &mut self,
+        pos: &P,
+        m: &Move,
+        outcome: Option<Outcome>,
+    ) {
+        if let Some(outcome) = outcome {
+            self.insert(
+                pos.zobrist_hash(),
+                CompactMove::from_move(m),
+                Counts::from_outcome(outcome),
+            );
+        }
+    }
+
+    /// Iterates over the packed moves and their counts in the position with
+    /// Zobrist `key`, in insertion order.
+    pub fn moves(&self, key: u64) -> Moves<'_> {
+        Moves {
+            records: &self.records,
+            next: self.find(key).map_or(NONE, |idx| self.slots[idx].head),
+        }
+    }
+
+    /// Gets the legal moves and their counts in `pos`, in insertion order.
+    /// Records that do not correspond to a legal move (due to hash
+    /// collisions) are skipped.
+    pub fn get<P: Position + ZobristHash>(&self, pos: &P) -> Vec<(Move, Counts)> {
+        self.moves(pos.zobrist_hash())
+            .filter_map(|(m, counts)| m.to_move(pos).ok().map(|m| (m, counts)))
+            .collect()
+    }
+
+    /// Total counts of all moves in the position with Zobrist `key`.
+    pub fn total(&self, key: u64) -> Counts {
+        let mut total = Counts::default();
+        for (_, counts) in self.moves(key) {
+            total.add(counts);
+        }
+        total
+    }
+
+    /// Iterates over all Zobrist keys, in arbitrary order.
+    pub fn keys(&self) -> impl Iterator<Item = u64> + '_ {
+        self.slots
+            .iter()
+            .filter(|slot| slot.head != NONE)
+            .map(|slot| slot.key)
+    }
+
+    /// Adds all records from `other`.
+    pub fn merge(&mut self, other: &Explorer) {
+        for key in other.keys() {
+            for (m, counts) in other.moves(key) {
+                self.insert(key, m, counts);
+            }
+        }
+    }
+
+    /// Removes all move records with fewer than `min_games` games, and
+    /// positions that are left without moves.
+    pub fn prune(&mut self, min_games: u64) {
+        let mut pruned = Explorer::new();
+        for key in self.keys() {
+            for (m, counts) in self.moves(key) {
+                if counts.games() >= min_games {
+                    pruned.insert(key, m, counts);
+                }
+            }
+        }
+        *self = pruned;
+    }
+
+    /// Gets statistics for the legal moves in `pos`, ordered by number of
+    /// games, most popular first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{explorer::Explorer, uci::Uci, Chess, Color, Outcome, Position};
+    ///
+    /// let mut explorer = Explorer::new();
+    /// for (line, outcome) in [
+    ///     (["e2e4", "e7e6", "d2d4", "d7d5"], Outcome::Draw),
+    ///     (["d2d4", "e7e6", "e2e4", "d7d5"], Outcome::Decisive { winner: Color::White }),
+    ///     (["d2d4", "e7e6", "e2e3", "d7d5"], Outcome::Decisive { winner: Color::Black }),
+    /// ] {
+    ///     let mut pos = Chess::default();
+    ///     for uci in line {
+    ///         let m = uci.parse::<Uci>()?.to_move(&pos)?;
+    ///         explorer.record(&pos, &m, Some(outcome));
+    ///         pos.play_unchecked(&m);
+    ///     }
+    /// }
+    ///
+    /// let mut pos = Chess::default();
+    /// let stats = explorer.stats(&pos);
+    /// assert_eq!(stats[0].m, "d2d4".parse::<Uci>()?.to_move(&pos)?);
+    /// assert_eq!(stats[0].counts.games(), 2);
+    /// assert_eq!(stats[0].score, Some(0.5));
+    ///
+    /// // 1. e4 e6 2. d4 transposes to 1. d4 e6 2. e4.
+    /// for uci in ["e2e4", "e7e6"] {
+    ///     pos.play_unchecked(&uci.parse::<Uci>()?.to_move(&pos)?);
+    /// }
+    /// let stats = explorer.stats(&pos);
+    /// assert_eq!(stats[0].counts.games(), 1);
+    /// assert_eq!(stats[0].transposed.games(), 2);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn stats<P: Position + ZobristHash + Clone>(&self, pos: &P) -> Vec<MoveStats> {
+        let mut stats: Vec<MoveStats> = self
+            .get(pos)
+            .into_iter()
+            .map(|(m, counts)| {
+                let mut after = pos.clone();
+                after.play_unchecked(&m);
+                MoveStats {
+                    transposed: self.total(after.zobrist_hash()),
+                    score: counts.score(pos.turn()),
+                    m,
+                    counts,
+                }
+            })
+            .collect();
+        stats.sort_by_key(|stats| std::cmp::Reverse(stats.counts.games()));
+        stats
+    }
+
+    /// Writes the [statistics](Explorer::stats()) of the moves in `pos` as
+    /// JSON, in the style of the Lichess opening explorer.
+    ///
+    /// The object has the fields `white`, `draws` and `black` with the
+    /// totals of the position, and `moves`, an array of objects with the
+    /// fields `uci` (using the castling mode of `pos`), `san`, `white`,
+    /// `draws`, `black`, `transposed` (number of games) and `score`
+    /// (`null` if there are no games).
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{explorer::Explorer, uci::Uci, Chess, Outcome};
+    ///
+    /// let pos = Chess::default();
+    /// let mut explorer = Explorer::new();
+    /// explorer.record(&pos, &"g1f3".parse::<Uci>()?.to_move(&pos)?, Some(Outcome::Draw));
+    ///
+    /// let mut json = Vec::new();
+    /// explorer.write_json(&pos, &mut json)?;
+    /// assert_eq!(
+    ///     String::from_utf8(json)?,
+    ///     r#"{"white":0,"draws":1,"black":0,"moves":[{"uci":"g1f3","san":"Nf3","white":0,"draws":1,"black":0,"transposed":0,"score":0.5}]}"#
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_json<P: Position + ZobristHash + Clone, W: io::Write>(
+        &self,
+        pos: &P,
+        mut writer: W,
+    ) -> io::Result<()> {
+        let total = self.total(pos.zobrist_hash());
+        write!(
+            writer,
+            r#"{{"white":{},"draws":{},"black":{},"moves":["#,
+            total.white, total.draws, total.black
+        )?;
+        for (i, stats) in self.stats(pos).into_iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            write!(
+                writer,
+                r#"{{"uci":"{}","san":"{}","white":{},"draws":{},"black":{},"transposed":{},"score":"#,
+                stats.m.to_uci(pos.castles().mode()),
+                San::from_move(pos, &stats.m),
+                stats.counts.white,
+                stats.counts.draws,
+                stats.counts.black,
+                stats.transposed.games(),
+            )?;
+            match stats.score {
+                Some(score) => write!(writer, "{}}}", score)?,
+                None => writer.write_all(b"null}")?,
+            }
+        }
+        writer.write_all(b"]}")
+    }
+
+    /// Writes all records, for example to checkpoint a long-running job.
+    ///
+    /// Each record is 22 bytes: the big-endian 64-bit key, 16-bit
+    /// [`CompactMove`], and 32-bit counts of white wins, draws and black
+    /// wins.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn write_bin<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        for key in self.keys() {
+            for (m, counts) in self.moves(key) {
+                let mut bytes = [0; RECORD_SIZE];
+                bytes[..8].copy_from_slice(&key.to_be_bytes());
+                bytes[8..10].copy_from_slice(&m.0.to_be_bytes());
+                bytes[10..14].copy_from_slice(&counts.white.to_be_bytes());
+                bytes[14..18].copy_from_slice(&counts.draws.to_be_bytes());
+                bytes[18..].copy_from_slice(&counts.black.to_be_bytes());
+                writer.write_all(&bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds all records written by [`Explorer::write_bin()`].
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying reader are passed through. Returns
+    /// [`io::ErrorKind::UnexpectedEof`] if the input is truncated. Records
+    /// before that are still added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{explorer::Explorer, uci::Uci, Chess, Outcome};
+    ///
+    /// let pos = Chess::default();
+    /// let m = "d2d4".parse::<Uci>()?.to_move(&pos)?;
+    /// let mut explorer = Explorer::new();
+    /// explorer.record(&pos, &m, Some(Outcome::Draw));
+    ///
+    /// let mut checkpoint = Vec::new();
+    /// explorer.write_bin(&mut checkpoint)?;
+    ///
+    /// let mut resumed = Explorer::new();
+    /// resumed.read_bin(&checkpoint[..])?;
+    /// assert_eq!(resumed.get(&pos), explorer.get(&pos));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn read_bin<R: io::Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut bytes = [0; RECORD_SIZE];
+        while read_record(&mut reader, &mut bytes)? {
+            let u32_at =
+                |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+            let mut key = [0; 8];
+            key.copy_from_slice(&bytes[..8]);
+            self.insert(
+                u64::from_be_bytes(key),
+                CompactMove(u16::from_be_bytes([bytes[8], bytes[9]])),
+                Counts {
+                    white: u32_at(10),
+                    draws: u32_at(14),
+                    black: u32_at(18),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the moves of a position in an [`Explorer`].
+#[derive(Debug, Clone)]
+pub struct Moves<'a> {
+    records: &'a [Record],
+    next: u32,
+}
+
+impl<'a> Iterator for Moves<'a> {
+    type Item = (CompactMove, Counts);
+
+    fn next(&mut self) -> Option<(CompactMove, Counts)> {
+        if self.next == NONE {
+            None
+        } else {
+            let record = &self.records[self.next as usize];
+            self.next = record.next;
+            Some((record.m, record.counts))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chess;
+
+    #[test]
+    fn test_compact_move() {
+        for uci in ["e2e4", "e7e8q", "e1h1", "N@f3", "a2a1n"] {
+            let uci: Uci = uci.parse().expect("valid uci");
+            assert_eq!(CompactMove::from_uci(&uci).expect("not null").to_uci(), uci);
+        }
+    }
+
+    #[test]
+    fn test_explorer() {
+        let mut explorer = Explorer::new();
+        let mut keys = Vec::new();
+        for i in 0..1000u64 {
+            // Colliding low bits.
+            let key = i << 40;
+            keys.push(key);
+            for j in 0..(i % 3 + 1) {
+                explorer.insert(
+                    key,
+                    CompactMove(j as u16),
+                    Counts::from_outcome(Outcome::Draw),
+                );
+                explorer.insert(
+                    key,
+                    CompactMove(j as u16),
+                    Counts::from_outcome(Outcome::Decisive {
+                        winner: Color::White,
+                    }),
+                );
+            }
+        }
+        assert_eq!(explorer.len(), 1000);
+        for (i, key) in keys.into_iter().enumerate() {
+            let moves: Vec<_> = explorer.moves(key).collect();
+            assert_eq!(moves.len(), i % 3 + 1);
+            assert!(moves.iter().all(|(_, counts)| counts.games() == 2));
+            assert_eq!(explorer.total(key).white, (i % 3 + 1) as u32);
+        }
+        assert_eq!(explorer.moves(1).count(), 0);
+
+        let mut merged = Explorer::new();
+        merged.merge(&explorer);
+        merged.merge(&explorer);
+        assert_eq!(merged.len(), 1000);
+        assert_eq!(merged.total(0).games(), 4);
+
+        let mut checkpoint = Vec::new();
+        merged.write_bin(&mut checkpoint).expect("write to vec");
+        assert_eq!(checkpoint.len(), merged.num_moves() * RECORD_SIZE);
+        let mut resumed = Explorer::new();
+        resumed.read_bin(&checkpoint[..]).expect("valid checkpoint");
+        assert_eq!(resumed.len(), 1000);
+        assert_eq!(resumed.num_moves(), merged.num_moves());
+        assert_eq!(resumed.total(0), merged.total(0));
+        assert_eq!(
+            Explorer::new()
+                .read_bin(&checkpoint[..30])
+                .expect_err("truncated")
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_counts_saturate() {
+        let mut counts = Counts {
+            white: u32::MAX - 1,
+            draws: 0,
+            black: 0,
+        };
+        counts.add(Counts::from_outcome(Outcome::Draw));
+        counts.add(counts);
+        counts.add_outcome(Outcome::Decisive {
+            winner: Color::White,
+        });
+        assert_eq!(counts.white, u32::MAX);
+        assert_eq!(counts.draws, 2);
+        assert_eq!(counts.games(), u64::from(u32::MAX) + 2);
+    }
+
+    #[test]
+    fn test_explorer_get() {
+        let mut explorer = Explorer::with_capacity(10);
+        let pos = Chess::default();
+        for uci in ["e2e4", "e2e5"] {
+            let uci: Uci = uci.parse().expect("valid uci");
+            explorer.insert(
+                pos.zobrist_hash(),
+                CompactMove::from_uci(&uci).expect("not null"),
+                Counts::from_outcome(Outcome::Draw),
+            );
+        }
+        let moves = explorer.get(&pos);
+        assert_eq!(moves.len(), 1); // Illegal move skipped
+        assert_eq!(Uci::from_standard(&moves[0].0).to_string(), "e2e4");
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut explorer = Explorer::new();
+        for (key, m, games) in [(1, 1, 5), (1, 2, 1), (2, 1, 2), (3, 1, 1)] {
+            for _ in 0..games {
+                explorer.insert(key, CompactMove(m), Counts::from_outcome(Outcome::Draw));
+            }
+        }
+        assert_eq!((explorer.len(), explorer.num_moves()), (3, 4));
+
+        explorer.prune(2);
+        assert_eq!((explorer.len(), explorer.num_moves()), (2, 2));
+        assert_eq!(explorer.total(1).games(), 5);
+        assert_eq!(explorer.total(2).games(), 2);
+        assert_eq!(explorer.moves(3).count(), 0);
+
+        assert_eq!(Counts::default().score(Color::White), None);
+        let counts = Counts {
+            white: 3,
+            draws: 2,
+            black: 5,
+        };
+        assert_eq!(counts.score(Color::White), Some(0.4));
+        assert_eq!(counts.score(Color::Black), Some(0.6));
+    }
+}
@@ -57,6 +57,11 @@
 //! assert_eq!(Epd::from_position(pos, EnPassantMode::Legal).to_string(),
 //!            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -");
 //! ```
+//!
+//! # Validation
+//!
+//! To report problems without rejecting the input outright, use
+//! [`validate()`] with one of the [`ValidationLevel`]s.
 
 use std::{
     char,
@@ -66,18 +71,37 @@ use std::{
     fmt,
     fmt::{Display, Write as _},
     num::NonZeroU32,
+    slice,
     str::FromStr,
 };
 
 use crate::{
-    Bitboard, Board, ByColor, ByRole, CastlingMode, Color, EnPassantMode, File, FromSetup, Piece,
-    Position, PositionError, Rank, RemainingChecks, Role, Setup, Square,
+    Bitboard, Board, ByColor, ByRole, CastlingMode, Color, EnPassantMode, ErrorKind, File,
+    FromSetup, Piece, Position, PositionError, PositionErrorKinds, Rank, RemainingChecks, Role,
+    Setup, Square,
 };
 
+/// Notation for castling rights in FENs.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CastlingFormat {
+    /// X-FEN, using `KQkq` for the outermost rooks, and file letters like
+    /// `Gb` only to disambiguate inner rooks. This is the default.
+    XFen,
+    /// Shredder-FEN, always using file letters like `HAha`.
+    Shredder,
+}
+
+impl Default for CastlingFormat {
+    fn default() -> CastlingFormat {
+        CastlingFormat::XFen
+    }
+}
+
 fn fmt_castling(
     f: &mut fmt::Formatter<'_>,
     board: &Board,
     castling_rights: Bitboard,
+    format: CastlingFormat,
 ) -> fmt::Result {
     let mut empty = true;
 
@@ -87,16 +111,17 @@ fn fmt_castling(
         let candidates = board.by_piece(color.rook()) & color.backrank();
 
         for rook in (candidates & castling_rights).into_iter().rev() {
-            f.write_char(
-                if Some(rook) == candidates.first() && king.map_or(false, |k| rook < k) {
-                    color.fold_wb('Q', 'q')
-                } else if Some(rook) == candidates.last() && king.map_or(false, |k| k < rook) {
-                    color.fold_wb('K', 'k')
-                } else {
-                    let file = rook.file();
-                    color.fold_wb(file.char().to_ascii_uppercase(), file.char())
-                },
-            )?;
+            f.write_char(if format == CastlingFormat::Shredder {
+                let file = rook.file();
+                color.fold_wb(file.char().to_ascii_uppercase(), file.char())
+            } else if Some(rook) == candidates.first() && king.map_or(false, |k| rook < k) {
+                color.fold_wb('Q', 'q')
+            } else if Some(rook) == candidates.last() && king.map_or(false, |k| k < rook) {
+                color.fold_wb('K', 'k')
+            } else {
+                let file = rook.file();
+                color.fold_wb(file.char().to_ascii_uppercase(), file.char())
+            })?;
             empty = false;
         }
     }
@@ -121,7 +146,7 @@ fn fmt_pockets(f: &mut fmt::Formatter<'_>, pockets: &ByColor<ByRole<u8>>) -> fmt
     f.write_char(']')
 }
 
-fn fmt_epd(f: &mut fmt::Formatter<'_>, setup: &Setup) -> fmt::Result {
+fn fmt_epd(f: &mut fmt::Formatter<'_>, setup: &Setup, format: CastlingFormat) -> fmt::Result {
     f.write_str(&setup.board.board_fen(setup.promoted))?;
     if let Some(ref pockets) = setup.pockets {
         fmt_pockets(f, pockets)?;
@@ -129,7 +154,7 @@ fn fmt_epd(f: &mut fmt::Formatter<'_>, setup: &Setup) -> fmt::Result {
     f.write_char(' ')?;
     f.write_char(setup.turn.char())?;
     f.write_char(' ')?;
-    fmt_castling(f, &setup.board, setup.castling_rights)?;
+    fmt_castling(f, &setup.board, setup.castling_rights, format)?;
     f.write_char(' ')?;
     match setup.ep_square {
         Some(ref ep_square) => Display::fmt(ep_square, f)?,
@@ -142,9 +167,11 @@ fn fmt_epd(f: &mut fmt::Formatter<'_>, setup: &Setup) -> fmt::Result {
     Ok(())
 }
 
-/// Errors that can occur when parsing a FEN.
-#[derive(Clone, Eq, PartialEq, Debug)]
-pub enum ParseFenError {
+/// Kind of a [`ParseFenError`], naming the invalid part of the FEN.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum ParseFenErrorKind {
+    /// The FEN is invalid as a whole, for example because it is empty or
+    /// has too many parts.
     InvalidFen,
     InvalidBoard,
     InvalidPocket,
@@ -156,19 +183,74 @@ pub enum ParseFenError {
     InvalidFullmoves,
 }
 
+impl From<ParseFenErrorKind> for ErrorKind {
+    fn from(_: ParseFenErrorKind) -> ErrorKind {
+        ErrorKind::InvalidFen
+    }
+}
+
+/// Errors that can occur when parsing a FEN.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::fen::{Fen, ParseFenErrorKind};
+///
+/// let err = "8/8/8/8/8/8/8/8 w KQx - 0 1".parse::<Fen>().unwrap_err();
+/// assert_eq!(err.kind(), ParseFenErrorKind::InvalidCastling);
+/// assert_eq!(err.offset(), 20);
+/// assert_eq!(err.to_string(), "invalid castling part in fen at byte 20");
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseFenError {
+    kind: ParseFenErrorKind,
+    offset: usize,
+}
+
+impl ParseFenError {
+    fn new(kind: ParseFenErrorKind, offset: usize) -> ParseFenError {
+        ParseFenError { kind, offset }
+    }
+
+    fn shifted(mut self, offset: usize) -> ParseFenError {
+        self.offset += offset;
+        self
+    }
+
+    /// Gets the kind of the error. Converts into
+    /// [`ErrorKind::InvalidFen`] for a machine-readable kind that is
+    /// shared with the other errors of this crate.
+    pub fn kind(&self) -> ParseFenErrorKind {
+        self.kind
+    }
+
+    /// Gets the byte offset of the error in the input. Points to the
+    /// offending character if known, otherwise to the start of the
+    /// invalid part.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Gets a human-readable description of the error, without the
+    /// offset.
+    pub fn message(&self) -> &'static str {
+        match self.kind {
+            ParseFenErrorKind::InvalidFen => "invalid fen",
+            ParseFenErrorKind::InvalidBoard => "invalid board part in fen",
+            ParseFenErrorKind::InvalidPocket => "invalid pocket in fen",
+            ParseFenErrorKind::InvalidTurn => "invalid turn part in fen",
+            ParseFenErrorKind::InvalidCastling => "invalid castling part in fen",
+            ParseFenErrorKind::InvalidEpSquare => "invalid ep square in fen",
+            ParseFenErrorKind::InvalidRemainingChecks => "invalid remaining checks in fen",
+            ParseFenErrorKind::InvalidHalfmoveClock => "invalid halfmove clock in fen",
+            ParseFenErrorKind::InvalidFullmoves => "invalid fullmove part in fen",
+        }
+    }
+}
+
 impl Display for ParseFenError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(match *self {
-            ParseFenError::InvalidFen => "invalid fen",
-            ParseFenError::InvalidBoard => "invalid board part in fen",
-            ParseFenError::InvalidPocket => "invalid pocket in fen",
-            ParseFenError::InvalidTurn => "invalid turn part in fen",
-            ParseFenError::InvalidCastling => "invalid castling part in fen",
-            ParseFenError::InvalidEpSquare => "invalid ep square in fen",
-            ParseFenError::InvalidRemainingChecks => "invalid remaining checks in fen",
-            ParseFenError::InvalidHalfmoveClock => "invalid halfmove clock in fen",
-            ParseFenError::InvalidFullmoves => "invalid fullmove part in fen",
-        })
+        write!(f, "{} at byte {}", self.message(), self.offset)
     }
 }
 
@@ -181,43 +263,45 @@ fn parse_board_fen(board_fen: &[u8]) -> Result<(Board, Bitboard), ParseFenError>
     let mut rank = 7i8;
     let mut file = 0i8;
 
-    let mut iter = board_fen.iter().copied().peekable();
+    let invalid = |offset| ParseFenError::new(ParseFenErrorKind::InvalidBoard, offset);
+
+    let mut iter = board_fen.iter().copied().enumerate().peekable();
 
-    while let Some(ch) = iter.next() {
+    while let Some((offset, ch)) = iter.next() {
         if ch == b'/' && file == 8 {
             file = 0;
             rank -= 1;
             if rank < 0 {
-                return Err(ParseFenError::InvalidBoard);
+                return Err(invalid(offset));
             }
         } else if (b'1'..=b'8').contains(&ch) {
             file += (ch - b'0') as i8;
             if file > 8 {
-                return Err(ParseFenError::InvalidBoard);
+                return Err(invalid(offset));
             }
         } else if let Some(piece) = Piece::from_char(char::from(ch)) {
             match (File::try_from(file), Rank::try_from(rank)) {
                 (Ok(f), Ok(r)) => {
                     let sq = Square::from_coords(f, r);
-                    let is_promoted = iter.peek() == Some(&b'~');
+                    let is_promoted = iter.peek().map(|(_, ch)| *ch) == Some(b'~');
                     if is_promoted {
                         promoted.add(sq);
                         iter.next();
                     }
                     board.set_piece_at(sq, piece);
                 }
-                _ => return Err(ParseFenError::InvalidBoard),
+                _ => return Err(invalid(offset)),
             }
             file += 1;
         } else {
-            return Err(ParseFenError::InvalidBoard);
+            return Err(invalid(offset));
         }
     }
 
     if rank == 0 && file == 8 {
         Ok((board, promoted))
     } else {
-        Err(ParseFenError::InvalidBoard)
+        Err(invalid(board_fen.len()))
     }
 }
 
@@ -266,41 +350,41 @@ impl Board {
         let mut fen = String::with_capacity(15);
 
         for rank in Rank::ALL.into_iter().rev() {
-            let mut empty = 0;
-
-            for file in File::ALL {
-                let square = Square::from_coords(file, rank);
-
-                empty = self.piece_at(square).map_or_else(
-                    || empty + 1,
-                    |piece| {
-                        if empty > 0 {
-                            fen.push(
-                                char::from_digit(empty, 10)
-                                    .expect("at most 8 empty squares on a rank"),
-                            );
-                        }
-                        fen.push(piece.char());
-                        if promoted.contains(square) {
-                            fen.push('~');
-                        }
-                        0
-                    },
-                );
+            push_rank(&mut fen, self, promoted, rank);
+            if rank > Rank::First {
+                fen.push('/');
+            }
+        }
+
+        fen
+    }
+}
+
+fn push_rank(fen: &mut String, board: &Board, promoted: Bitboard, rank: Rank) {
+    let mut empty = 0;
+
+    for file in File::ALL {
+        let square = Square::from_coords(file, rank);
 
-                if file == File::H && empty > 0 {
+        empty = board.piece_at(square).map_or_else(
+            || empty + 1,
+            |piece| {
+                if empty > 0 {
                     fen.push(
                         char::from_digit(empty, 10).expect("at most 8 empty squares on a rank"),
                     );
                 }
-
-                if file == File::H && rank > Rank::First {
-                    fen.push('/');
+                fen.push(piece.char());
+                if promoted.contains(square) {
+                    fen.push('~');
                 }
-            }
-        }
+                0
+            },
+        );
+    }
 
-        fen
+    if empty > 0 {
+        fen.push(char::from_digit(empty, 10).expect("at most 8 empty squares on a rank"));
     }
 }
 
@@ -352,20 +436,28 @@ impl Fen {
             .split(|ch| *ch == b' ' || *ch == b'_')
             .filter(|s| !s.is_empty());
 
-        let board_part = parts.next().ok_or(ParseFenError::InvalidFen)?;
+        // Byte offset of a part in the input.
+        let offset = |part: &[u8]| part.as_ptr() as usize - fen.as_ptr() as usize;
+
+        let board_part = parts
+            .next()
+            .ok_or_else(|| ParseFenError::new(ParseFenErrorKind::InvalidFen, fen.len()))?;
 
         let (board_part, pocket_part) = if board_part.ends_with(b"]") {
             // format: ...[pocket]
             let split_point = board_part
                 .iter()
                 .position(|ch| *ch == b'[')
-                .ok_or(ParseFenError::InvalidBoard)?;
+                .ok_or_else(|| {
+                    ParseFenError::new(ParseFenErrorKind::InvalidBoard, offset(board_part))
+                })?;
             let pocket_part = &board_part[(split_point + 1)..(board_part.len() - 1)];
             (&board_part[..split_point], Some(pocket_part))
         } else if let Some(split_point) = board_part
             .iter()
             .enumerate()
-            .filter_map(|(idx, ch)| (*ch == b'/').then(|| idx))
+            .filter(|(_, ch)| **ch == b'/')
+            .map(|(idx, _)| idx)
             .nth(7)
         {
             // format: .../pocket
@@ -377,18 +469,26 @@ impl Fen {
             (board_part, None)
         };
 
-        let (board, promoted) = parse_board_fen(board_part)?;
+        let (board, promoted) =
+            parse_board_fen(board_part).map_err(|err| err.shifted(offset(board_part)))?;
         result.board = board;
         result.promoted = promoted;
 
         if let Some(pocket_part) = pocket_part {
-            result.pockets = Some(parse_pockets(pocket_part).ok_or(ParseFenError::InvalidPocket)?);
+            result.pockets = Some(parse_pockets(pocket_part).ok_or_else(|| {
+                ParseFenError::new(ParseFenErrorKind::InvalidPocket, offset(pocket_part))
+            })?);
         }
 
         result.turn = match parts.next() {
             Some(b"w") | None => Color::White,
             Some(b"b") => Color::Black,
-            Some(_) => return Err(ParseFenError::InvalidTurn),
+            Some(turn_part) => {
+                return Err(ParseFenError::new(
+                    ParseFenErrorKind::InvalidTurn,
+                    offset(turn_part),
+                ))
+            }
         };
 
         match parts.next() {
@@ -397,6 +497,12 @@ impl Fen {
                 result.castling_rights = castling_part
                     .iter()
                     .map(|ch| {
+                        let invalid = || {
+                            ParseFenError::new(
+                                ParseFenErrorKind::InvalidCastling,
+                                offset(slice::from_ref(ch)),
+                            )
+                        };
                         let color = Color::from_white(ch.is_ascii_uppercase());
                         let rooks_and_kings = result.board.by_color(color)
                             & (result.board.rooks() | result.board.kings())
@@ -411,8 +517,7 @@ impl Fen {
                                 .filter(|sq| result.board.rooks().contains(*sq))
                                 .unwrap_or_else(|| Square::from_coords(File::A, color.backrank())),
                             file => Square::from_coords(
-                                File::from_char(char::from(file))
-                                    .ok_or(ParseFenError::InvalidCastling)?,
+                                File::from_char(char::from(file)).ok_or_else(invalid)?,
                                 color.backrank(),
                             ),
                         })
@@ -421,7 +526,10 @@ impl Fen {
 
                 for color in Color::ALL {
                     if (result.castling_rights & color.backrank()).count() > 2 {
-                        return Err(ParseFenError::InvalidCastling);
+                        return Err(ParseFenError::new(
+                            ParseFenErrorKind::InvalidCastling,
+                            offset(castling_part),
+                        ));
                     }
                 }
             }
@@ -430,8 +538,9 @@ impl Fen {
         match parts.next() {
             Some(b"-") | None => (),
             Some(ep_part) => {
-                result.ep_square =
-                    Some(Square::from_ascii(ep_part).map_err(|_| ParseFenError::InvalidEpSquare)?);
+                result.ep_square = Some(Square::from_ascii(ep_part).map_err(|_| {
+                    ParseFenError::new(ParseFenErrorKind::InvalidEpSquare, offset(ep_part))
+                })?);
             }
         }
 
@@ -447,13 +556,22 @@ impl Fen {
         };
 
         if let Some(halfmoves_part) = halfmoves_part {
-            result.halfmoves = btoi::btou_saturating(halfmoves_part)
-                .map_err(|_| ParseFenError::InvalidHalfmoveClock)?;
+            result.halfmoves = btoi::btou_saturating(halfmoves_part).map_err(|_| {
+                ParseFenError::new(
+                    if halfmoves_part.contains(&b'+') {
+                        ParseFenErrorKind::InvalidRemainingChecks
+                    } else {
+                        ParseFenErrorKind::InvalidHalfmoveClock
+                    },
+                    offset(halfmoves_part),
+                )
+            })?;
         }
 
         if let Some(fullmoves_part) = parts.next() {
-            let fullmoves = btoi::btou_saturating(fullmoves_part)
-                .map_err(|_| ParseFenError::InvalidFullmoves)?;
+            let fullmoves = btoi::btou_saturating(fullmoves_part).map_err(|_| {
+                ParseFenError::new(ParseFenErrorKind::InvalidFullmoves, offset(fullmoves_part))
+            })?;
             result.fullmoves = NonZeroU32::new(max(fullmoves, 1)).expect("non-zero fullmoves");
         }
 
@@ -470,10 +588,18 @@ impl Fen {
             None
         };
 
-        if last_part.is_some() {
-            Err(ParseFenError::InvalidFen)
-        } else {
-            Ok(Fen(result))
+        match last_part {
+            Some(last_part) if result.remaining_checks.is_none() && last_part.contains(&b'+') => {
+                Err(ParseFenError::new(
+                    ParseFenErrorKind::InvalidRemainingChecks,
+                    offset(last_part),
+                ))
+            }
+            Some(last_part) => Err(ParseFenError::new(
+                ParseFenErrorKind::InvalidFen,
+                offset(last_part),
+            )),
+            None => Ok(Fen(result)),
         }
     }
 
@@ -502,6 +628,29 @@ impl Fen {
     pub fn into_position<P: FromSetup>(self, mode: CastlingMode) -> Result<P, PositionError<P>> {
         P::from_setup(self.0, mode)
     }
+
+    /// Formats the FEN with the given notation for castling rights.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::fen::{CastlingFormat, Fen};
+    ///
+    /// let fen = Fen::default();
+    /// assert_eq!(
+    ///     fen.to_string_with(CastlingFormat::Shredder),
+    ///     "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"
+    /// );
+    /// assert_eq!(fen.to_string_with(CastlingFormat::XFen), fen.to_string());
+    /// ```
+    pub fn to_string_with(&self, format: CastlingFormat) -> String {
+        Formatted {
+            setup: &self.0,
+            format,
+            counters: true,
+        }
+        .to_string()
+    }
 }
 
 impl From<Setup> for Fen {
@@ -526,8 +675,28 @@ impl FromStr for Fen {
 
 impl Display for Fen {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt_epd(f, &self.0)?;
-        write!(f, " {} {}", self.0.halfmoves, self.0.fullmoves)
+        Formatted {
+            setup: &self.0,
+            format: CastlingFormat::XFen,
+            counters: true,
+        }
+        .fmt(f)
+    }
+}
+
+struct Formatted<'a> {
+    setup: &'a Setup,
+    format: CastlingFormat,
+    counters: bool,
+}
+
+impl Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_epd(f, self.setup, self.format)?;
+        if self.counters {
+            write!(f, " {} {}", self.setup.halfmoves, self.setup.fullmoves)?;
+        }
+        Ok(())
     }
 }
 
@@ -565,6 +734,16 @@ impl Epd {
     pub fn into_position<P: FromSetup>(self, mode: CastlingMode) -> Result<P, PositionError<P>> {
         P::from_setup(self.into_setup(), mode)
     }
+
+    /// Formats the EPD with the given notation for castling rights.
+    pub fn to_string_with(&self, format: CastlingFormat) -> String {
+        Formatted {
+            setup: &self.0,
+            format,
+            counters: false,
+        }
+        .to_string()
+    }
 }
 
 impl From<Setup> for Epd {
@@ -589,15 +768,333 @@ impl FromStr for Epd {
 
 impl Display for Epd {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt_epd(f, &self.0)
+        fmt_epd(f, &self.0, CastlingFormat::XFen)
     }
 }
 
+/// Writes FENs of successive positions, for example while replaying a
+/// game.
+///
+/// Only ranks of the board that changed since the previous call are
+/// formatted again, and the output buffer is reused, so that producing a
+/// FEN for every ply is much cheaper than going through
+/// [`Fen::from_position()`]. The output is identical.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{fen::FenWriter, uci::Uci, Chess, EnPassantMode, Position};
+///
+/// let mut pos = Chess::default();
+/// let mut writer = FenWriter::new();
+/// let mut fens = Vec::new();
+/// for uci in ["e2e4", "c7c5", "g1f3"] {
+///     let m = uci.parse::<Uci>()?.to_move(&pos)?;
+///     pos.play_unchecked(&m);
+///     fens.push(writer.fen(&pos, EnPassantMode::Legal).to_owned());
+/// }
+///
+/// assert_eq!(
+///     fens[2],
+///     "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+/// );
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FenWriter {
+    board: Option<(Board, Bitboard)>,
+    ranks: [String; 8],
+    format: CastlingFormat,
+    buffer: String,
+}
+
+impl FenWriter {
+    /// Creates a writer that uses X-FEN notation for castling rights.
+    pub fn new() -> FenWriter {
+        FenWriter::default()
+    }
+
+    /// Sets the notation for castling rights.
+    pub fn set_castling_format(&mut self, format: CastlingFormat) {
+        self.format = format;
+    }
+
+    /// Writes the FEN of `pos`.
+    pub fn fen<P: Position>(&mut self, pos: &P, mode: EnPassantMode) -> &str {
+        self.write(pos, mode, true)
+    }
+
+    /// Writes the EPD of `pos`, i.e., the FEN without move counters.
+    pub fn epd<P: Position>(&mut self, pos: &P, mode: EnPassantMode) -> &str {
+        self.write(pos, mode, false)
+    }
+
+    fn write<P: Position>(&mut self, pos: &P, mode: EnPassantMode, counters: bool) -> &str {
+        self.update_board(pos.board(), pos.promoted());
+
+        self.buffer.clear();
+        for (i, rank) in self.ranks.iter().rev().enumerate() {
+            if i > 0 {
+                self.buffer.push('/');
+            }
+            self.buffer.push_str(rank);
+        }
+        if let Some(pockets) = pos.pockets() {
+            write!(self.buffer, "{}", Pockets(pockets)).expect("write to string");
+        }
+        write!(
+            self.buffer,
+            " {} {} ",
+            pos.turn().char(),
+            Castling {
+                board: pos.board(),
+                castling_rights: pos.castles().castling_rights(),
+                format: self.format,
+            }
+        )
+        .expect("write to string");
+        match pos.ep_square(mode) {
+            Some(ep_square) => write!(self.buffer, "{}", ep_square).expect("write to string"),
+            None => self.buffer.push('-'),
+        }
+        if let Some(remaining_checks) = pos.remaining_checks() {
+            write!(self.buffer, " {}", remaining_checks).expect("write to string");
+        }
+        if counters {
+            write!(self.buffer, " {} {}", pos.halfmoves(), pos.fullmoves())
+                .expect("write to string");
+        }
+
+        &self.buffer
+    }
+
+    fn update_board(&mut self, board: &Board, promoted: Bitboard) {
+        let dirty = match self.board {
+            Some((ref prev, prev_promoted)) => {
+                let mut dirty = (prev.white() ^ board.white()) | (prev_promoted ^ promoted);
+                for role in Role::ALL {
+                    dirty |= prev.by_role(role) ^ board.by_role(role);
+                }
+                dirty
+            }
+            None => Bitboard::FULL,
+        };
+
+        if dirty.is_empty() {
+            return;
+        }
+
+        for rank in Rank::ALL {
+            if (dirty & Bitboard::from_rank(rank)).any() {
+                let fen = &mut self.ranks[usize::from(rank)];
+                fen.clear();
+                push_rank(fen, board, promoted, rank);
+            }
+        }
+
+        self.board = Some((board.clone(), promoted));
+    }
+}
+
+struct Castling<'a> {
+    board: &'a Board,
+    castling_rights: Bitboard,
+    format: CastlingFormat,
+}
+
+impl Display for Castling<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_castling(f, self.board, self.castling_rights, self.format)
+    }
+}
+
+struct Pockets<'a>(&'a ByColor<ByRole<u8>>);
+
+impl Display for Pockets<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_pockets(f, self.0)
+    }
+}
+
+/// How thoroughly to [`validate()`] a FEN. Each level includes the checks
+/// of the previous levels.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ValidationLevel {
+    /// Only check the syntax.
+    Syntax,
+    /// Check that the board is consistent with a game of standard chess:
+    /// one king for each side, no pawns on the backrank, and no more
+    /// pieces than possible with promotions.
+    Setup,
+    /// Check that the setup is a legal position, including checks,
+    /// castling rights and the en passant square.
+    Legal,
+}
+
+/// Problem found by [`validate()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Finding {
+    /// The FEN is syntactically invalid. Nothing else is checked.
+    Syntax(ParseFenError),
+    /// A side has no king.
+    MissingKing { color: Color },
+    /// A side has more than one king.
+    TooManyKings { color: Color },
+    /// A side has more than 8 pawns.
+    TooManyPawns { color: Color },
+    /// A side has more pieces than possible with promotions.
+    TooManyPieces { color: Color },
+    /// A side has pawns on the first or last rank.
+    PawnsOnBackrank { color: Color },
+    /// The setup is not a legal position.
+    Illegal(PositionErrorKinds),
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Finding::Syntax(ref err) => Display::fmt(err, f),
+            Finding::MissingKing { color } => write!(f, "{} has no king", color),
+            Finding::TooManyKings { color } => write!(f, "{} has too many kings", color),
+            Finding::TooManyPawns { color } => write!(f, "{} has too many pawns", color),
+            Finding::TooManyPieces { color } => write!(f, "{} has too many pieces", color),
+            Finding::PawnsOnBackrank { color } => write!(f, "{} has pawns on backrank", color),
+            Finding::Illegal(kinds) => write!(f, "illegal position: {:?}", kinds),
+        }
+    }
+}
+
+/// Validates a FEN up to the given `level`, using the rules of `P` for
+/// [`ValidationLevel::Legal`]. Returns all findings, or an empty list if
+/// the FEN is valid.
+///
+/// Findings at the [`ValidationLevel::Setup`] level are about standard
+/// chess. The FEN may still be loadable, for example in variants or for
+/// studies.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     fen::{validate, Finding, ValidationLevel},
+///     CastlingMode, Chess, Color,
+/// };
+///
+/// let fen = b"4k3/8/8/8/8/8/PPPPPPPP/QQQQK3 w - - 0 1";
+/// assert!(validate::<Chess>(fen, ValidationLevel::Syntax, CastlingMode::Standard).is_empty());
+/// assert_eq!(
+///     validate::<Chess>(fen, ValidationLevel::Setup, CastlingMode::Standard),
+///     [Finding::TooManyPieces { color: Color::White }]
+/// );
+/// ```
+pub fn validate<P: FromSetup>(
+    fen: &[u8],
+    level: ValidationLevel,
+    mode: CastlingMode,
+) -> Vec<Finding> {
+    let fen = match Fen::from_ascii(fen) {
+        Ok(fen) => fen,
+        Err(err) => return vec![Finding::Syntax(err)],
+    };
+
+    let mut findings = Vec::new();
+
+    if level >= ValidationLevel::Setup {
+        let board = &fen.0.board;
+        for color in Color::ALL {
+            let material = board.material_side(color);
+            match material.king {
+                0 => findings.push(Finding::MissingKing { color }),
+                1 => (),
+                _ => findings.push(Finding::TooManyKings { color }),
+            }
+            if material.pawn > 8 {
+                findings.push(Finding::TooManyPawns { color });
+            }
+            let promoted = material.knight.saturating_sub(2)
+                + material.bishop.saturating_sub(2)
+                + material.rook.saturating_sub(2)
+                + material.queen.saturating_sub(1);
+            if material.pawn + promoted > 8 || board.by_color(color).count() > 16 {
+                findings.push(Finding::TooManyPieces { color });
+            }
+            if (board.pawns() & board.by_color(color) & Bitboard::BACKRANKS).any() {
+                findings.push(Finding::PawnsOnBackrank { color });
+            }
+        }
+    }
+
+    if level >= ValidationLevel::Legal {
+        if let Err(err) = fen.into_position::<P>(mode) {
+            findings.push(Finding::Illegal(err.kinds()));
+        }
+    }
+
+    findings
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{Chess, EnPassantMode, Position};
 
+    #[test]
+    fn test_validate() {
+        let validate =
+            |fen: &str, level| validate::<Chess>(fen.as_bytes(), level, CastlingMode::Standard);
+
+        assert_eq!(
+            validate("8/8/8/8/8/8/8/8 x - - 0 1", ValidationLevel::Legal),
+            [Finding::Syntax(ParseFenError::new(
+                ParseFenErrorKind::InvalidTurn,
+                16
+            ))]
+        );
+        assert!(validate("4k3/8/8/8/8/8/8/4K3 w - - 0 1", ValidationLevel::Legal).is_empty());
+
+        let fen = "4k3/8/8/8/8/8/8/P3K2P w K - 0 1";
+        assert!(validate(fen, ValidationLevel::Syntax).is_empty());
+        assert_eq!(
+            validate(fen, ValidationLevel::Setup),
+            [Finding::PawnsOnBackrank {
+                color: Color::White
+            }]
+        );
+        assert_eq!(
+            validate(fen, ValidationLevel::Legal),
+            [
+                Finding::PawnsOnBackrank {
+                    color: Color::White
+                },
+                Finding::Illegal(
+                    PositionErrorKinds::PAWNS_ON_BACKRANK
+                        | PositionErrorKinds::INVALID_CASTLING_RIGHTS
+                ),
+            ]
+        );
+
+        assert_eq!(
+            validate(
+                "kk6/pppppppp/p7/8/8/8/8/8 w - - 0 1",
+                ValidationLevel::Setup
+            ),
+            [
+                Finding::MissingKing {
+                    color: Color::White
+                },
+                Finding::TooManyKings {
+                    color: Color::Black
+                },
+                Finding::TooManyPawns {
+                    color: Color::Black
+                },
+                Finding::TooManyPieces {
+                    color: Color::Black
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_legal_ep_square() {
         let original_epd = "4k3/8/8/8/3Pp3/8/8/3KR3 b - d3";
@@ -623,34 +1120,74 @@ mod tests {
 
     #[test]
     fn test_invalid_fen() {
-        assert_eq!("".parse::<Fen>().unwrap_err(), ParseFenError::InvalidFen);
+        let err = "".parse::<Fen>().unwrap_err();
+        assert_eq!(
+            (err.kind(), err.offset()),
+            (ParseFenErrorKind::InvalidFen, 0)
+        );
 
         assert_eq!(
             "8/8/8/8/8/8/8/8 w · - 0 1" // not ascii
                 .parse::<Fen>()
                 .unwrap_err(),
-            ParseFenError::InvalidCastling
+            ParseFenError::new(ParseFenErrorKind::InvalidCastling, 18)
         );
 
         assert_eq!(
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQQKBNR w cq - 0P1" // syntax
                 .parse::<Fen>()
                 .unwrap_err(),
-            ParseFenError::InvalidCastling
+            ParseFenError::new(ParseFenErrorKind::InvalidCastling, 51)
         );
 
         assert_eq!(
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w  - 0 1" // double space
                 .parse::<Fen>()
                 .unwrap_err(),
-            ParseFenError::InvalidEpSquare
+            ParseFenError::new(ParseFenErrorKind::InvalidEpSquare, 49)
         );
 
         assert_eq!(
             "4k2r/8/8/8/8/8/8/RR2K2R w KBQk - 0 1" // triple castling rights
                 .parse::<Fen>()
                 .unwrap_err(),
-            ParseFenError::InvalidCastling
+            ParseFenError::new(ParseFenErrorKind::InvalidCastling, 26)
+        );
+
+        assert_eq!(
+            "  4k3/8/8/8/8/8/8/4K2X w - - 0 1"
+                .parse::<Fen>()
+                .unwrap_err(),
+            ParseFenError::new(ParseFenErrorKind::InvalidBoard, 21)
+        );
+        assert_eq!(
+            "4k3/8/8/8/8/8/8 w - - 0 1".parse::<Fen>().unwrap_err(),
+            ParseFenError::new(ParseFenErrorKind::InvalidBoard, 15)
+        );
+        assert_eq!(
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1 x"
+                .parse::<Fen>()
+                .unwrap_err(),
+            ParseFenError::new(ParseFenErrorKind::InvalidFen, 30)
+        );
+        assert_eq!(
+            "4k3/8/8/8/8/8/8/4K3 w - - 3+x 0 1"
+                .parse::<Fen>()
+                .unwrap_err(),
+            ParseFenError::new(ParseFenErrorKind::InvalidRemainingChecks, 26)
+        );
+        assert_eq!(
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1 +0+x"
+                .parse::<Fen>()
+                .unwrap_err(),
+            ParseFenError::new(ParseFenErrorKind::InvalidRemainingChecks, 30)
+        );
+        assert_eq!(
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1 x"
+                .parse::<Fen>()
+                .unwrap_err()
+                .to_string(),
+            "invalid fen at byte 30"
         );
     }
 
@@ -684,6 +1221,55 @@ mod tests {
         assert_eq!(setup.pockets, Some(Default::default()));
     }
 
+    #[test]
+    fn test_castling_format() {
+        let fen: Fen = "1r2k2r/8/8/8/8/8/8/R3K1RR w GAhb - 0 1"
+            .parse()
+            .expect("valid fen");
+        assert_eq!(
+            fen.to_string_with(CastlingFormat::XFen),
+            "1r2k2r/8/8/8/8/8/8/R3K1RR w GQkq - 0 1"
+        );
+        assert_eq!(
+            fen.to_string_with(CastlingFormat::Shredder),
+            "1r2k2r/8/8/8/8/8/8/R3K1RR w GAhb - 0 1"
+        );
+        assert_eq!(
+            Epd::from(fen.into_setup()).to_string_with(CastlingFormat::Shredder),
+            "1r2k2r/8/8/8/8/8/8/R3K1RR w GAhb -"
+        );
+    }
+
+    #[test]
+    fn test_fen_writer() {
+        let mut pos = Chess::default();
+        let mut writer = FenWriter::new();
+        let mut shredder = FenWriter::new();
+        shredder.set_castling_format(CastlingFormat::Shredder);
+
+        let moves = "e2e4 d7d5 e4e5 f7f5 e5f6 g8h6 f6g7 b8c6 g7h8q e7e6 g1f3 d8e7 f1d3 e8d8 e1g1";
+        for uci in moves.split(' ') {
+            let m = uci
+                .parse::<crate::uci::Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal move");
+            pos.play_unchecked(&m);
+
+            let expected = Fen::from_position(pos.clone(), EnPassantMode::Legal);
+            assert_eq!(writer.fen(&pos, EnPassantMode::Legal), expected.to_string());
+            assert_eq!(
+                shredder.epd(&pos, EnPassantMode::Legal),
+                Epd::from(expected.into_setup()).to_string_with(CastlingFormat::Shredder)
+            );
+        }
+
+        assert_eq!(
+            writer.fen(&pos, EnPassantMode::Legal),
+            "r1bk1b1Q/ppp1q2p/2n1p2n/3p4/8/3B1N2/PPPP1PPP/RNBQ1RK1 b - - 5 8"
+        );
+    }
+
     #[test]
     fn test_remaining_checks() {
         let setup = "8/8/8/8/8/8/8/8 w - - 1+2 12 42"
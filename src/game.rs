@@ -0,0 +1,469 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Games with move history and variations.
+//!
+//! A [`Game`] keeps the starting position and a [`Chapter`] with the tree
+//! of moves, and tracks a current node. Playing a move that differs from
+//! the existing continuation starts a new variation. Comments, NAGs and
+//! clocks are stored on the [`Node`]s of the chapter, so everything that
+//! works on chapters, like merging or finding transpositions, also works
+//! on games.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use shakmaty::{game::Game, san::San, Chess, Position};
+//!
+//! let mut game = Game::new(Chess::default());
+//! for san in ["e4", "e5", "Nf3"] {
+//!     let m = san.parse::<San>()?.to_move(game.position())?;
+//!     game.play(&m)?;
+//! }
+//! game.node_mut().expect("node").set_clock(Some(Duration::from_secs(58)));
+//!
+//! // Go back and try another move, starting a variation.
+//! game.seek(2);
+//! let m = "Bc4".parse::<San>()?.to_move(game.position())?;
+//! game.play(&m)?;
+//! assert_eq!(game.ply(), 3);
+//! assert_eq!(game.mainline().count(), 3);
+//!
+//! // Make the variation the main line.
+//! assert!(game.promote_variation());
+//! let last = game.mainline().last().expect("node");
+//! assert_eq!(last.san_plus.to_string(), "Bc4");
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{io, time::Duration};
+
+use crate::{
+    clock::{ClockPly, ClockSimulation, TimeControl},
+    pgn::PgnWriter,
+    position::{PlayError, Position},
+    san::SanPlus,
+    study::{write_line, Chapter, Mainline, Node},
+    types::Move,
+};
+
+/// Iterator over a line of a [`Game`], following the main line after a
+/// given path. See [`Game::line()`].
+#[derive(Debug, Clone)]
+pub struct Line<'a> {
+    children: &'a [Node],
+    path: &'a [usize],
+}
+
+impl<'a> Iterator for Line<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        let index = match self.path.split_first() {
+            Some((&index, rest)) => {
+                self.path = rest;
+                index
+            }
+            None => 0,
+        };
+        let node = self.children.get(index)?;
+        self.children = &node.children;
+        Some(node)
+    }
+}
+
+/// A game with its starting position, a tree of moves, and a current node.
+///
+/// The current node is on the current line, which is remembered when
+/// seeking back, so that seeking forward returns to the same variation.
+#[derive(Debug, Clone)]
+pub struct Game<P> {
+    initial: P,
+    chapter: Chapter,
+    line: Vec<usize>,
+    ply: usize,
+    pos: P,
+}
+
+impl<P: Position + Clone + Default> Default for Game<P> {
+    fn default() -> Game<P> {
+        Game::new(P::default())
+    }
+}
+
+impl<P: Position + Clone> Game<P> {
+    /// Creates a game without moves, starting from `initial`.
+    pub fn new(initial: P) -> Game<P> {
+        Game::from_chapter(initial, Chapter::default())
+    }
+
+    /// Creates a game from the moves of `chapter`, for example read with a
+    /// [`ChapterBuilder`](crate::study::ChapterBuilder), starting from
+    /// `initial`. The current node is the starting position.
+    pub fn from_chapter(initial: P, chapter: Chapter) -> Game<P> {
+        Game {
+            pos: initial.clone(),
+            initial,
+            chapter,
+            line: Vec::new(),
+            ply: 0,
+        }
+    }
+
+    /// The starting position.
+    pub fn initial(&self) -> &P {
+        &self.initial
+    }
+
+    /// The position at the current node.
+    pub fn position(&self) -> &P {
+        &self.pos
+    }
+
+    /// The tree of moves.
+    pub fn chapter(&self) -> &Chapter {
+        &self.chapter
+    }
+
+    /// Returns the tree of moves, discarding the current node.
+    pub fn into_chapter(self) -> Chapter {
+        self.chapter
+    }
+
+    /// Number of plies from the starting position to the current node.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Indices of the continuations leading to the current node, as used
+    /// by [`Chapter::node()`].
+    pub fn path(&self) -> &[usize] {
+        &self.line[..self.ply]
+    }
+
+    fn children_at_mut(&mut self, path: &[usize]) -> &mut Vec<Node> {
+        let mut children = &mut self.chapter.children;
+        for &i in path {
+            children = &mut children[i].children;
+        }
+        children
+    }
+
+    /// The node of the last move played, or `None` at the starting
+    /// position.
+    pub fn node(&self) -> Option<&Node> {
+        self.chapter.node(self.path())
+    }
+
+    /// Mutable access to the node of the last move played, for example to
+    /// set its clock or add comments.
+    pub fn node_mut(&mut self) -> Option<&mut Node> {
+        let (&last, path) = self.path().split_last()?;
+        let path = path.to_vec();
+        self.children_at_mut(&path).get_mut(last)
+    }
+
+    /// Iterates over the main line from the starting position.
+    pub fn mainline(&self) -> Mainline<'_> {
+        self.chapter.mainline()
+    }
+
+    /// Iterates over the current line from the starting position: the
+    /// moves leading to the current node, followed by its main line.
+    pub fn line(&self) -> Line<'_> {
+        Line {
+            children: &self.chapter.children,
+            path: &self.line,
+        }
+    }
+
+    /// Plays a move at the current node. If the move is already a
+    /// continuation, follows it. Otherwise the move is added as a new
+    /// continuation, which becomes a variation if there was already a main
+    /// line.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PlayError`] if the move is not legal. The game is
+    /// unchanged.
+    pub fn play(&mut self, m: &Move) -> Result<(), PlayError<P>> {
+        let before = self.pos.clone();
+        self.pos = self.pos.clone().play(m)?;
+        let path = self.path().to_vec();
+        let children = self.children_at_mut(&path);
+        let index = match children
+            .iter()
+            .position(|child| child.san_plus.san.to_move(&before).as_ref() == Ok(m))
+        {
+            Some(index) => index,
+            None => {
+                children.push(Node::new(SanPlus::from_move(before, m)));
+                children.len() - 1
+            }
+        };
+        if self.line.get(self.ply) != Some(&index) {
+            self.line.truncate(self.ply);
+            self.line.push(index);
+        }
+        self.ply += 1;
+        Ok(())
+    }
+
+    /// Seeks to the node `ply` plies from the starting position on the
+    /// current line. Returns `false` and stays at the current node if the
+    /// line is shorter, or if it contains a move that is not legal.
+    pub fn seek(&mut self, ply: usize) -> bool {
+        let mut pos = self.initial.clone();
+        let mut nodes = 0;
+        for node in self.line().take(ply) {
+            match node.san_plus.san.to_move(&pos) {
+                Ok(m) => pos.play_unchecked(&m),
+                Err(_) => return false,
+            }
+            nodes += 1;
+        }
+        if nodes < ply {
+            return false;
+        }
+        self.pos = pos;
+        if self.line.len() < ply {
+            self.line.resize(ply, 0);
+        }
+        self.ply = ply;
+        true
+    }
+
+    /// Removes all continuations of the current node, including
+    /// variations.
+    pub fn truncate(&mut self) {
+        self.line.truncate(self.ply);
+        match self.node_mut() {
+            Some(node) => node.children.clear(),
+            None => self.chapter.children.clear(),
+        }
+    }
+
+    /// Promotes the variation that contains the current node, so that it
+    /// becomes the main line at the closest branching point. Returns
+    /// `false` if the current node is already on the main line.
+    pub fn promote_variation(&mut self) -> bool {
+        let depth = match self.path().iter().rposition(|&i| i != 0) {
+            Some(depth) => depth,
+            None => return false,
+        };
+        let index = self.line[depth];
+        let path = self.line[..depth].to_vec();
+        let children = self.children_at_mut(&path);
+        let variation = children.remove(index);
+        children.insert(0, variation);
+        self.line[depth] = 0;
+        true
+    }
+
+    /// Simulates the clocks along the current line, given the `timestamps`
+    /// of successive moves from the start of the game, and sets the clock
+    /// of each node to the remaining time. Returns the simulated moves,
+    /// which is fewer than the number of timestamps if the line is shorter.
+    ///
+    /// See [`ClockSimulation`] for details.
+    pub fn replay_clocks(
+        &mut self,
+        time_control: &TimeControl,
+        timestamps: &[Duration],
+    ) -> Vec<ClockPly> {
+        let mut sim = ClockSimulation::new(time_control, self.initial.turn());
+        let mut plies = Vec::with_capacity(timestamps.len());
+        let mut path = self.line.iter();
+        let mut children = &mut self.chapter.children;
+        for &timestamp in timestamps {
+            let index = path.next().copied().unwrap_or(0);
+            let node = match children.get_mut(index) {
+                Some(node) => node,
+                None => break,
+            };
+            let ply = sim.play_at(timestamp);
+            node.set_clock(ply.remaining);
+            plies.push(ply);
+            children = &mut node.children;
+        }
+        plies
+    }
+
+    /// Writes the moves with their annotations and variations as PGN
+    /// movetext, followed by the outcome at the end of the main line.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn write_pgn<W: io::Write>(&self, writer: &mut PgnWriter<W>) -> io::Result<()> {
+        writer.set_start(
+            self.initial.turn().is_black(),
+            self.initial.fullmoves().get(),
+        );
+        for comment in &self.chapter.comments {
+            writer.comment(comment.as_bytes())?;
+        }
+        write_line(writer, &self.chapter.children)?;
+        let mut pos = self.initial.clone();
+        for node in self.chapter.mainline() {
+            match node.san_plus.san.to_move(&pos) {
+                Ok(m) => pos.play_unchecked(&m),
+                Err(_) => return writer.outcome(self.chapter.outcome),
+            }
+        }
+        writer.outcome(pos.outcome().or(self.chapter.outcome))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pgn::PgnReader, san::San, study::ChapterBuilder, Chess, Color};
+
+    fn play(game: &mut Game<Chess>, sans: &str) {
+        for san in sans.split_whitespace() {
+            let m = san
+                .parse::<San>()
+                .expect("valid san")
+                .to_move(game.position())
+                .expect("legal move");
+            game.play(&m).expect("legal move");
+        }
+    }
+
+    fn pgn(game: &Game<Chess>) -> String {
+        let mut writer = PgnWriter::new(Vec::new());
+        game.write_pgn(&mut writer).expect("write pgn");
+        String::from_utf8(writer.into_inner())
+            .expect("utf-8")
+            .trim_end()
+            .to_owned()
+    }
+
+    #[test]
+    fn test_game() {
+        let mut game = Game::default();
+        play(&mut game, "e4 e5 Nf3 Nc6");
+        game.seek(2);
+        play(&mut game, "Bc4 Bc5");
+        game.node_mut()
+            .expect("node")
+            .set_clock(Some(Duration::from_secs(59)));
+        assert_eq!(game.path(), [0, 0, 1, 0]);
+
+        // Seeking back and forth stays on the variation.
+        assert!(game.seek(1));
+        assert_eq!(game.node().expect("node").san_plus.to_string(), "e4");
+        assert!(game.seek(4));
+        assert_eq!(game.path(), [0, 0, 1, 0]);
+        assert!(!game.seek(5));
+        assert!(game.seek(0));
+        assert!(game.node().is_none());
+        assert_eq!(*game.position(), Chess::default());
+
+        // Playing an existing move follows it.
+        play(&mut game, "e4");
+        assert_eq!(game.path(), [0]);
+        assert!(game.seek(4));
+        assert_eq!(game.path(), [0, 0, 1, 0]);
+
+        assert_eq!(
+            pgn(&game),
+            "1. e4 e5 2. Nf3 (2. Bc4 Bc5 { [%clk 0:00:59] }) 2... Nc6 *"
+        );
+
+        assert!(game.promote_variation());
+        assert!(!game.promote_variation());
+        assert_eq!(game.path(), [0, 0, 0, 0]);
+        assert_eq!(
+            pgn(&game),
+            "1. e4 e5 2. Bc4 (2. Nf3 Nc6) 2... Bc5 { [%clk 0:00:59] } *"
+        );
+
+        assert!(game.seek(2));
+        game.truncate();
+        assert_eq!(game.mainline().count(), 2);
+        assert!(!game.seek(3));
+        assert_eq!(pgn(&game), "1. e4 e5 *");
+    }
+
+    #[test]
+    fn test_illegal_move() {
+        let mut game = Game::<Chess>::default();
+        play(&mut game, "f3 e5 g4");
+        let m = "Qh4"
+            .parse::<San>()
+            .expect("valid san")
+            .to_move(game.position())
+            .expect("legal");
+        let before = game.position().clone();
+        game.seek(2);
+        assert!(game.play(&m).is_err());
+        assert_eq!(game.ply(), 2);
+        game.seek(3);
+        assert_eq!(*game.position(), before);
+        game.play(&m).expect("legal move");
+        assert_eq!(pgn(&game), "1. f3 e5 2. g4 Qh4# 0-1");
+    }
+
+    #[test]
+    fn test_replay_clocks() {
+        let mut game = Game::default();
+        play(&mut game, "e4 e5 Nf3 Nc6");
+        game.seek(2);
+        play(&mut game, "Bc4 Bc5");
+
+        let tc = "180+2".parse().expect("valid time control");
+        let timestamps = [3, 10, 150, 400, 500].map(Duration::from_secs);
+        let plies = game.replay_clocks(&tc, &timestamps);
+        assert_eq!(plies.len(), 4);
+        assert!(!plies[2].flagged);
+        assert!(plies[3].flagged);
+        assert_eq!(plies[3].color, Color::Black);
+
+        let secs = |secs| Some(Duration::from_secs(secs));
+        assert_eq!(
+            game.line().map(Node::clock).collect::<Vec<_>>(),
+            [secs(179), secs(175), secs(41), secs(0)]
+        );
+        assert_eq!(
+            game.mainline().map(Node::clock).collect::<Vec<_>>(),
+            [secs(179), secs(175), None, None]
+        );
+    }
+
+    #[test]
+    fn test_game_from_chapter() {
+        let movetext = b"1. e4 $1 { Best by test } e5 2. Nf3 (2. Bc4) 2... Nc6 1-0";
+        let chapter = PgnReader::new(&movetext[..])
+            .read_game(&mut ChapterBuilder::new())
+            .expect("io")
+            .expect("game");
+        let mut game = Game::from_chapter(Chess::default(), chapter);
+
+        assert!(game.seek(4));
+        play(&mut game, "Bb5");
+        assert_eq!(game.path(), [0, 0, 0, 0, 0]);
+        assert!(game.seek(2));
+        play(&mut game, "Bc4 Nf6");
+        assert_eq!(game.path(), [0, 0, 1, 0]);
+        assert_eq!(
+            pgn(&game),
+            "1. e4 $1 { Best by test } 1... e5 2. Nf3 (2. Bc4 Nf6) 2... Nc6 3. Bb5 1-0"
+        );
+    }
+}
@@ -0,0 +1,695 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Parse and write moves in long algebraic notation and ICCF numeric
+//! notation.
+//!
+//! Long algebraic notation always names the origin square, like `e2-e4`
+//! or `Ng1xf3`. ICCF numeric notation, used in correspondence chess, writes
+//! files and ranks as digits, like `5254` for `e2e4`.
+//!
+//! [`SpecialSan`] and [`SpecialUci`] write move kinds that [`Move`] does
+//! not represent, like gating moves (`Nf3/B`) and duck moves (`D@e4`).
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{
+//!     notation::{Iccf, Lan},
+//!     Chess, Position,
+//! };
+//!
+//! let mut pos = Chess::default();
+//!
+//! let m = "e2-e4".parse::<Lan>()?.to_move(&pos)?;
+//! assert_eq!(Iccf::from_move(&m).map(|iccf| iccf.to_string()), Some("5254".to_owned()));
+//! pos.play_unchecked(&m);
+//!
+//! let m = "7866".parse::<Iccf>()?.to_move(&pos)?;
+//! assert_eq!(Lan::from_move(&m).to_string(), "Ng8-f6");
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{
+    san::{ParseSanError, San},
+    uci::{ParseUciError, Uci},
+    CastlingSide, ErrorKind, File, Move, Position, Rank, Role, Square,
+};
+
+/// Error when parsing syntactically invalid long algebraic notation.
+#[derive(Clone, Debug)]
+pub struct ParseLanError;
+
+impl fmt::Display for ParseLanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid lan")
+    }
+}
+
+impl Error for ParseLanError {}
+
+impl ParseLanError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidLan
+    }
+}
+
+/// Error when parsing syntactically invalid ICCF numeric notation.
+#[derive(Clone, Debug)]
+pub struct ParseIccfError;
+
+impl fmt::Display for ParseIccfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid iccf")
+    }
+}
+
+impl Error for ParseIccfError {}
+
+impl ParseIccfError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidIccf
+    }
+}
+
+/// Error when a move in long algebraic or ICCF numeric notation is not
+/// legal in the position.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IllegalMoveError;
+
+impl fmt::Display for IllegalMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("illegal move")
+    }
+}
+
+impl Error for IllegalMoveError {}
+
+impl IllegalMoveError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::IllegalMove
+    }
+}
+
+/// A move in long algebraic notation, like `e2-e4`, `Ng1xf3`, `e7-e8=Q`
+/// or `O-O`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Lan {
+    Normal {
+        role: Role,
+        from: Square,
+        capture: bool,
+        to: Square,
+        promotion: Option<Role>,
+    },
+    Castle(CastlingSide),
+    Put {
+        role: Role,
+        to: Square,
+    },
+    Null,
+}
+
+impl Lan {
+    /// Parses a move in long algebraic notation. Check and checkmate
+    /// suffixes are accepted and ignored. The promotion sign `=` is
+    /// optional.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseLanError`] if `lan` is not syntactically valid.
+    pub fn from_ascii(mut lan: &[u8]) -> Result<Lan, ParseLanError> {
+        if let Some((b'+' | b'#', rest)) = lan.split_last() {
+            lan = rest;
+        }
+
+        match lan {
+            b"--" => return Ok(Lan::Null),
+            b"O-O" | b"0-0" => return Ok(Lan::Castle(CastlingSide::KingSide)),
+            b"O-O-O" | b"0-0-0" => return Ok(Lan::Castle(CastlingSide::QueenSide)),
+            _ => (),
+        }
+
+        if let [role, b'@', to @ ..] = lan {
+            return Ok(Lan::Put {
+                role: Role::from_char(char::from(*role))
+                    .filter(|_| role.is_ascii_uppercase())
+                    .ok_or(ParseLanError)?,
+                to: Square::from_ascii(to).map_err(|_| ParseLanError)?,
+            });
+        }
+
+        let (role, lan) = match lan.split_first() {
+            Some((&ch, rest)) if ch.is_ascii_uppercase() => {
+                (Role::from_char(char::from(ch)).ok_or(ParseLanError)?, rest)
+            }
+            _ => (Role::Pawn, lan),
+        };
+
+        if lan.len() < 5 {
+            return Err(ParseLanError);
+        }
+        let from = Square::from_ascii(&lan[0..2]).map_err(|_| ParseLanError)?;
+        let capture = match lan[2] {
+            b'-' => false,
+            b'x' => true,
+            _ => return Err(ParseLanError),
+        };
+        let to = Square::from_ascii(&lan[3..5]).map_err(|_| ParseLanError)?;
+        let promotion = match &lan[5..] {
+            [] => None,
+            [b'=', promotion] | [promotion] if role == Role::Pawn => Some(
+                Role::from_char(char::from(*promotion))
+                    .filter(|role| *role != Role::Pawn && promotion.is_ascii_uppercase())
+                    .ok_or(ParseLanError)?,
+            ),
+            _ => return Err(ParseLanError),
+        };
+
+        Ok(Lan::Normal {
+            role,
+            from,
+            capture,
+            to,
+            promotion,
+        })
+    }
+
+    /// Converts a move to long algebraic notation.
+    pub fn from_move(m: &Move) -> Lan {
+        match *m {
+            Move::Normal {
+                role,
+                from,
+                capture,
+                to,
+                promotion,
+            } => Lan::Normal {
+                role,
+                from,
+                capture: capture.is_some(),
+                to,
+                promotion,
+            },
+            Move::EnPassant { from, to } => Lan::Normal {
+                role: Role::Pawn,
+                from,
+                capture: true,
+                to,
+                promotion: None,
+            },
+            Move::Castle { king, rook } => Lan::Castle(CastlingSide::from_king_side(king < rook)),
+            Move::Put { role, to } => Lan::Put { role, to },
+        }
+    }
+
+    /// Tests if the move matches `m`. The capture marker is not required
+    /// to be accurate.
+    pub fn matches(&self, m: &Move) -> bool {
+        match (self, Lan::from_move(m)) {
+            (
+                Lan::Normal {
+                    role,
+                    from,
+                    to,
+                    promotion,
+                    ..
+                },
+                Lan::Normal {
+                    role: m_role,
+                    from: m_from,
+                    to: m_to,
+                    promotion: m_promotion,
+                    ..
+                },
+            ) => *role == m_role && *from == m_from && *to == m_to && *promotion == m_promotion,
+            (lan, other) => *lan == other,
+        }
+    }
+
+    /// Tries to convert the move to a legal [`Move`] in the context of a
+    /// position.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IllegalMoveError`] if the move is not legal.
+    pub fn to_move<P: Position>(&self, pos: &P) -> Result<Move, IllegalMoveError> {
+        pos.legal_moves()
+            .into_iter()
+            .find(|m| self.matches(m))
+            .ok_or(IllegalMoveError)
+    }
+}
+
+impl FromStr for Lan {
+    type Err = ParseLanError;
+
+    fn from_str(lan: &str) -> Result<Lan, ParseLanError> {
+        Lan::from_ascii(lan.as_bytes())
+    }
+}
+
+impl fmt::Display for Lan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Lan::Normal {
+                role,
+                from,
+                capture,
+                to,
+                promotion,
+            } => {
+                if role != Role::Pawn {
+                    write!(f, "{}", role.upper_char())?;
+                }
+                write!(f, "{}{}{}", from, if capture { 'x' } else { '-' }, to)?;
+                if let Some(promotion) = promotion {
+                    write!(f, "={}", promotion.upper_char())?;
+                }
+                Ok(())
+            }
+            Lan::Castle(CastlingSide::KingSide) => f.write_str("O-O"),
+            Lan::Castle(CastlingSide::QueenSide) => f.write_str("O-O-O"),
+            Lan::Put { role, to } => write!(f, "{}@{}", role.upper_char(), to),
+            Lan::Null => f.write_str("--"),
+        }
+    }
+}
+
+/// A move in ICCF numeric notation, like `5254` for `e2e4` or `57581` for
+/// `e7e8q`.
+///
+/// Files and ranks are written as digits from `1` to `8`. Promotions are
+/// suffixed with `1` (queen), `2` (rook), `3` (bishop) or `4` (knight).
+/// Castling is written as the king move, like `5171` for white castling
+/// short. Drops can not be represented.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub struct Iccf {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<Role>,
+}
+
+fn iccf_square(file: u8, rank: u8) -> Option<Square> {
+    let digit = |ch: u8| match ch {
+        b'1'..=b'8' => Some(u32::from(ch - b'1')),
+        _ => None,
+    };
+    Some(Square::from_coords(
+        File::new(digit(file)?),
+        Rank::new(digit(rank)?),
+    ))
+}
+
+impl Iccf {
+    /// Parses a move in ICCF numeric notation.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseIccfError`] if `iccf` is not syntactically valid.
+    pub fn from_ascii(iccf: &[u8]) -> Result<Iccf, ParseIccfError> {
+        let (from, to, promotion) = match *iccf {
+            [a, b, c, d] => (iccf_square(a, b), iccf_square(c, d), None),
+            [a, b, c, d, promotion] => (
+                iccf_square(a, b),
+                iccf_square(c, d),
+                Some(match promotion {
+                    b'1' => Role::Queen,
+                    b'2' => Role::Rook,
+                    b'3' => Role::Bishop,
+                    b'4' => Role::Knight,
+                    _ => return Err(ParseIccfError),
+                }),
+            ),
+            _ => return Err(ParseIccfError),
+        };
+        Ok(Iccf {
+            from: from.ok_or(ParseIccfError)?,
+            to: to.ok_or(ParseIccfError)?,
+            promotion,
+        })
+    }
+
+    /// Converts a move to ICCF numeric notation. Returns `None` for drops.
+    pub fn from_move(m: &Move) -> Option<Iccf> {
+        match Uci::from_standard(m) {
+            Uci::Normal {
+                from,
+                to,
+                promotion,
+            } => Some(Iccf {
+                from,
+                to,
+                promotion,
+            }),
+            Uci::Put { .. } | Uci::Null => None,
+        }
+    }
+
+    /// Tries to convert the move to a legal [`Move`] in the context of a
+    /// position.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IllegalMoveError`] if the move is not legal.
+    pub fn to_move<P: Position>(&self, pos: &P) -> Result<Move, IllegalMoveError> {
+        Uci::Normal {
+            from: self.from,
+            to: self.to,
+            promotion: self.promotion,
+        }
+        .to_move(pos)
+        .map_err(|_| IllegalMoveError)
+    }
+}
+
+impl FromStr for Iccf {
+    type Err = ParseIccfError;
+
+    fn from_str(iccf: &str) -> Result<Iccf, ParseIccfError> {
+        Iccf::from_ascii(iccf.as_bytes())
+    }
+}
+
+impl fmt::Display for Iccf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for square in [self.from, self.to] {
+            write!(
+                f,
+                "{}{}",
+                u32::from(square.file()) + 1,
+                u32::from(square.rank()) + 1
+            )?;
+        }
+        match self.promotion {
+            Some(Role::Queen) => f.write_str("1"),
+            Some(Role::Rook) => f.write_str("2"),
+            Some(Role::Bishop) => f.write_str("3"),
+            Some(Role::Knight) => f.write_str("4"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A move of a kind that [`Move`] does not represent, in Standard
+/// Algebraic Notation, like `Nf3/B` or `D@e4`.
+///
+/// None of the built-in variants have these moves. Check and checkmate
+/// suffixes are accepted and ignored when parsing.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[non_exhaustive]
+pub enum SpecialSan {
+    /// A normal move that also gates a piece onto the vacated origin
+    /// square, as in Seirawan chess, like `Nf3/B`.
+    Gate {
+        role: Role,
+        file: Option<File>,
+        rank: Option<Rank>,
+        capture: bool,
+        to: Square,
+        gate: Role,
+    },
+    /// Places or relocates the neutral duck, as in duck chess, like `D@e4`.
+    Duck { to: Square },
+}
+
+impl SpecialSan {
+    /// Parses a special move in Standard Algebraic Notation.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseSanError`] if `san` is not syntactically valid.
+    pub fn from_ascii(mut san: &[u8]) -> Result<SpecialSan, ParseSanError> {
+        if let Some((b'+' | b'#', rest)) = san.split_last() {
+            san = rest;
+        }
+
+        match san {
+            [b'D', b'@', to @ ..] => Ok(SpecialSan::Duck {
+                to: Square::from_ascii(to).map_err(|_| ParseSanError)?,
+            }),
+            [normal @ .., b'/', gate] => match San::from_ascii(normal)? {
+                San::Normal {
+                    role,
+                    file,
+                    rank,
+                    capture,
+                    to,
+                    promotion: None,
+                } => Ok(SpecialSan::Gate {
+                    role,
+                    file,
+                    rank,
+                    capture,
+                    to,
+                    gate: Role::from_char(char::from(*gate))
+                        .filter(|role| *role != Role::King && gate.is_ascii_uppercase())
+                        .ok_or(ParseSanError)?,
+                }),
+                _ => Err(ParseSanError),
+            },
+            _ => Err(ParseSanError),
+        }
+    }
+}
+
+impl FromStr for SpecialSan {
+    type Err = ParseSanError;
+
+    fn from_str(san: &str) -> Result<SpecialSan, ParseSanError> {
+        SpecialSan::from_ascii(san.as_bytes())
+    }
+}
+
+impl fmt::Display for SpecialSan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SpecialSan::Gate {
+                role,
+                file,
+                rank,
+                capture,
+                to,
+                gate,
+            } => {
+                let normal = San::Normal {
+                    role,
+                    file,
+                    rank,
+                    capture,
+                    to,
+                    promotion: None,
+                };
+                write!(f, "{}/{}", normal, gate.upper_char())
+            }
+            SpecialSan::Duck { to } => write!(f, "D@{}", to),
+        }
+    }
+}
+
+/// A move of a kind that [`Move`] does not represent, in UCI notation,
+/// like `g1f3b` or `D@e4`.
+///
+/// A gating move is written like a promotion, so `g1f3b` is only a gating
+/// move if the caller knows that the piece on `g1` is not a pawn.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+#[non_exhaustive]
+pub enum SpecialUci {
+    /// A normal move that also gates a piece onto the vacated origin
+    /// square, like `g1f3b`.
+    Gate {
+        from: Square,
+        to: Square,
+        gate: Role,
+    },
+    /// Places or relocates the neutral duck, like `D@e4`.
+    Duck { to: Square },
+}
+
+impl SpecialUci {
+    /// Parses a special move in UCI notation.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseUciError`] if `uci` is not syntactically valid.
+    pub fn from_ascii(uci: &[u8]) -> Result<SpecialUci, ParseUciError> {
+        match uci {
+            [b'D', b'@', to @ ..] => Ok(SpecialUci::Duck {
+                to: Square::from_ascii(to).map_err(|_| ParseUciError)?,
+            }),
+            [from @ .., gate] if from.len() == 4 => Ok(SpecialUci::Gate {
+                from: Square::from_ascii(&from[..2]).map_err(|_| ParseUciError)?,
+                to: Square::from_ascii(&from[2..]).map_err(|_| ParseUciError)?,
+                gate: Role::from_char(char::from(*gate))
+                    .filter(|role| *role != Role::King && gate.is_ascii_lowercase())
+                    .ok_or(ParseUciError)?,
+            }),
+            _ => Err(ParseUciError),
+        }
+    }
+}
+
+impl FromStr for SpecialUci {
+    type Err = ParseUciError;
+
+    fn from_str(uci: &str) -> Result<SpecialUci, ParseUciError> {
+        SpecialUci::from_ascii(uci.as_bytes())
+    }
+}
+
+impl fmt::Display for SpecialUci {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SpecialUci::Gate { from, to, gate } => write!(f, "{}{}{}", from, to, gate.char()),
+            SpecialUci::Duck { to } => write!(f, "D@{}", to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::Fen, CastlingMode, Chess};
+
+    #[test]
+    fn test_lan() {
+        for lan in [
+            "e2-e4", "Ng1xf3", "e7-e8=Q", "b2xa1=N", "O-O", "O-O-O", "Q@e4", "--",
+        ] {
+            assert_eq!(lan.parse::<Lan>().expect("valid lan").to_string(), lan);
+        }
+        assert_eq!("e7e8".parse::<Lan>().ok(), None);
+        assert_eq!("Ng1-f3=Q".parse::<Lan>().ok(), None);
+        assert_eq!("e7-e8P".parse::<Lan>().ok(), None);
+        assert_eq!(
+            "e7-e8q+".parse::<Lan>().ok(),
+            None,
+            "promotion must be uppercase"
+        );
+        assert_eq!(
+            "e7-e8Q#".parse::<Lan>().expect("valid lan").to_string(),
+            "e7-e8=Q"
+        );
+
+        let pos: Chess = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        for lan in ["e5-d6", "b7-a8=N", "O-O-O", "Ke1-f1"] {
+            let m = lan
+                .parse::<Lan>()
+                .expect("valid lan")
+                .to_move(&pos)
+                .expect("legal move");
+            assert!(Lan::from_move(&m).matches(&m));
+        }
+        let en_passant = "e5-d6"
+            .parse::<Lan>()
+            .expect("valid lan")
+            .to_move(&pos)
+            .expect("legal move");
+        assert_eq!(Lan::from_move(&en_passant).to_string(), "e5xd6");
+        assert_eq!(
+            "Ke1-g1".parse::<Lan>().expect("valid lan").to_move(&pos),
+            Err(IllegalMoveError)
+        );
+    }
+
+    #[test]
+    fn test_iccf() {
+        for iccf in ["5254", "7163", "57581", "21124"] {
+            assert_eq!(iccf.parse::<Iccf>().expect("valid iccf").to_string(), iccf);
+        }
+        for invalid in ["", "525", "5259", "0254", "52545", "525411"] {
+            assert!(invalid.parse::<Iccf>().is_err(), "{}", invalid);
+        }
+
+        let pos: Chess = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let castle = "5131"
+            .parse::<Iccf>()
+            .expect("valid iccf")
+            .to_move(&pos)
+            .expect("legal move");
+        assert_eq!(
+            castle,
+            Move::Castle {
+                king: Square::E1,
+                rook: Square::A1
+            }
+        );
+        assert_eq!(
+            Iccf::from_move(&castle)
+                .expect("encodable move")
+                .to_string(),
+            "5131"
+        );
+
+        let promotion = "27184"
+            .parse::<Iccf>()
+            .expect("valid iccf")
+            .to_move(&pos)
+            .expect("legal move");
+        assert_eq!(Lan::from_move(&promotion).to_string(), "b7xa8=N");
+        assert_eq!(
+            "27174".parse::<Iccf>().expect("valid iccf").to_move(&pos),
+            Err(IllegalMoveError)
+        );
+    }
+
+    #[test]
+    fn test_special_moves() {
+        for san in ["Nf3/B", "Rxa8/Q", "exd6/N", "Nbd2/R", "D@e4"] {
+            let result = san.parse::<SpecialSan>().expect("valid san").to_string();
+            assert_eq!(san, result);
+        }
+        assert_eq!(
+            "Ngf3/B+".parse::<SpecialSan>().expect("valid san"),
+            SpecialSan::Gate {
+                role: Role::Knight,
+                file: Some(File::G),
+                rank: None,
+                capture: false,
+                to: Square::F3,
+                gate: Role::Bishop,
+            }
+        );
+        for invalid in ["Nf3", "Nf3/K", "e8=Q/N", "Nf3/b", "D@e9", "O-O/R"] {
+            assert!(invalid.parse::<SpecialSan>().is_err(), "{}", invalid);
+        }
+
+        for uci in ["g1f3b", "e1g1r", "D@e4"] {
+            let result = uci.parse::<SpecialUci>().expect("valid uci").to_string();
+            assert_eq!(uci, result);
+        }
+        assert_eq!(
+            "D@e4".parse::<SpecialUci>().expect("valid uci"),
+            SpecialUci::Duck { to: Square::E4 }
+        );
+        for invalid in ["g1f3", "g1f3k", "g1f3B", "N@f3", "0000"] {
+            assert!(invalid.parse::<SpecialUci>().is_err(), "{}", invalid);
+        }
+    }
+}
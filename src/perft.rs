@@ -14,7 +14,33 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::position::Position;
+//! Count legal move paths, for testing move generation.
+//!
+//! # Examples
+//!
+//! Break down the node count by root move, to bisect move generation bugs
+//! against a reference implementation:
+//!
+//! ```
+//! use shakmaty::{perft::Divide, Chess};
+//!
+//! let divide = Divide::new(&Chess::default(), 2);
+//! assert_eq!(divide.nodes(), 400);
+//!
+//! let output = divide.to_string();
+//! assert!(output.contains("\ne2e4: 20\n"));
+//! assert!(output.ends_with("\n\nNodes searched: 400\n"));
+//! ```
+
+use std::fmt;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{
+    position::Position,
+    types::{CastlingMode, Move},
+};
 
 /// Counts legal move paths of a given length.
 ///
@@ -62,6 +88,114 @@ pub fn perft<P: Position + Clone>(pos: &P, depth: u32) -> u64 {
     }
 }
 
+/// Like [`perft()`], but distributes the work over the global
+/// [rayon](https://docs.rs/rayon) thread pool.
+///
+/// The first two plies are expanded in parallel, and each of the resulting
+/// subtrees is counted sequentially. At depths below 3 this just calls
+/// [`perft()`], because splitting would cost more than it saves.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{Chess, perft, perft_parallel};
+///
+/// let pos = Chess::default();
+/// assert_eq!(perft_parallel(&pos, 4), perft(&pos, 4));
+/// ```
+#[cfg(feature = "parallel")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "parallel")))]
+pub fn perft_parallel<P: Position + Clone + Send + Sync>(pos: &P, depth: u32) -> u64 {
+    if depth < 3 {
+        perft(pos, depth)
+    } else {
+        pos.legal_moves()
+            .par_iter()
+            .map(|m| {
+                let mut child = pos.clone();
+                child.play_unchecked(m);
+                child
+                    .legal_moves()
+                    .par_iter()
+                    .map(|m| {
+                        let mut grandchild = child.clone();
+                        grandchild.play_unchecked(m);
+                        perft(&grandchild, depth - 2)
+                    })
+                    .sum::<u64>()
+            })
+            .sum()
+    }
+}
+
+/// Counts legal move paths of a given length, for each legal move in
+/// `pos`. See [`perft()`].
+///
+/// The counts add up to `perft(pos, depth)`, except at depth 0, where
+/// there are no moves to break down.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{perft::divide, Chess};
+///
+/// let results = divide(&Chess::default(), 3);
+/// assert_eq!(results.len(), 20);
+/// assert_eq!(results.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+/// ```
+pub fn divide<P: Position + Clone>(pos: &P, depth: u32) -> Vec<(Move, u64)> {
+    if depth < 1 {
+        Vec::new()
+    } else {
+        pos.legal_moves()
+            .into_iter()
+            .map(|m| {
+                let mut child = pos.clone();
+                child.play_unchecked(&m);
+                let nodes = perft(&child, depth - 1);
+                (m, nodes)
+            })
+            .collect()
+    }
+}
+
+/// Results of [`divide()`], displayed like the output of `go perft` in
+/// Stockfish.
+///
+/// Moves are written in [UCI notation](crate::uci), one per line, followed
+/// by a blank line and the total node count.
+#[derive(Debug, Clone)]
+pub struct Divide {
+    /// Moves and the number of nodes below each of them.
+    pub moves: Vec<(Move, u64)>,
+    /// Castling mode used to write castling moves.
+    pub mode: CastlingMode,
+}
+
+impl Divide {
+    /// Computes the breakdown of `perft(pos, depth)`.
+    pub fn new<P: Position + Clone>(pos: &P, depth: u32) -> Divide {
+        Divide {
+            moves: divide(pos, depth),
+            mode: pos.castles().mode(),
+        }
+    }
+
+    /// Total number of nodes.
+    pub fn nodes(&self) -> u64 {
+        self.moves.iter().map(|&(_, nodes)| nodes).sum()
+    }
+}
+
+impl fmt::Display for Divide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (m, nodes) in &self.moves {
+            writeln!(f, "{}: {}", m.to_uci(self.mode), nodes)?;
+        }
+        write!(f, "\nNodes searched: {}\n", self.nodes())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,4 +207,33 @@ mod tests {
         assert_eq!(perft(&pos, 0), 1);
         assert_eq!(perft(&pos, 1), 20);
     }
+
+    #[test]
+    fn test_divide() {
+        let pos: Chess = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"
+            .parse::<crate::fen::Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        assert!(divide(&pos, 0).is_empty());
+        let divide = Divide::new(&pos, 2);
+        assert_eq!(divide.nodes(), perft(&pos, 2));
+        let output = divide.to_string();
+        assert!(output.contains("\ne1h1: 23\n"));
+        assert!(output.ends_with("\n\nNodes searched: 568\n"));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_perft_parallel() {
+        let pos: Chess = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+            .parse::<crate::fen::Fen>()
+            .expect("valid fen")
+            .into_position(crate::CastlingMode::Standard)
+            .expect("legal position");
+        for depth in 0..4 {
+            assert_eq!(perft_parallel(&pos, depth), perft(&pos, depth));
+        }
+        assert_eq!(perft_parallel(&pos, 3), 97_862);
+    }
 }
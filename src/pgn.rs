@@ -0,0 +1,1190 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Read and write games in Portable Game Notation.
+//!
+//! A [`PgnReader`] reads one game at a time from any [`io::BufRead`] and
+//! reports tags, moves, comments, NAGs and variations to a [`Visitor`].
+//! Each game is first read into an internal buffer of the reader. Slices
+//! passed to the visitor borrow from that buffer and are only valid during
+//! the callback.
+//!
+//! A game ends at its game termination marker (like `1-0` or `*`), at a
+//! blank line after movetext, or at the tags of the next game.
+//!
+//! The reader does not validate moves. Visitors are free to replay them on
+//! any [`Position`], and to skip games or variations they are not
+//! interested in.
+//!
+//! Custom formats that embed movetext can use the lower level [`Tokenizer`]
+//! directly.
+//!
+//! # Examples
+//!
+//! Replay the mainline of each game:
+//!
+//! ```
+//! use shakmaty::{
+//!     pgn::{PgnReader, Skip, Visitor},
+//!     san::SanPlus,
+//!     Chess, Position,
+//! };
+//!
+//! struct LastPosition {
+//!     pos: Chess,
+//! }
+//!
+//! impl Visitor for LastPosition {
+//!     type Result = Chess;
+//!
+//!     fn begin_game(&mut self) {
+//!         self.pos = Chess::default();
+//!     }
+//!
+//!     fn begin_variation(&mut self) -> Skip {
+//!         Skip(true) // stay in the mainline
+//!     }
+//!
+//!     fn san(&mut self, san_plus: SanPlus) {
+//!         if let Ok(m) = san_plus.san.to_move(&self.pos) {
+//!             self.pos.play_unchecked(&m);
+//!         }
+//!     }
+//!
+//!     fn end_game(&mut self) -> Chess {
+//!         std::mem::take(&mut self.pos)
+//!     }
+//! }
+//!
+//! let pgn = b"1. f3 e5 2. g4 (2. Kf2) 2... Qh4# 0-1";
+//!
+//! let mut reader = PgnReader::new(&pgn[..]);
+//! let mut visitor = LastPosition { pos: Chess::default() };
+//! let pos = reader.read_game(&mut visitor)?.expect("game");
+//! assert!(pos.is_checkmate());
+//! # Ok::<_, std::io::Error>(())
+//! ```
+//!
+//! Write a game:
+//!
+//! ```
+//! use shakmaty::{pgn::{Nag, PgnWriter}, uci::Uci, Chess, Outcome, Position};
+//!
+//! let mut pos = Chess::default();
+//! let mut moves = Vec::new();
+//! for uci in ["e2e4", "e7e5"] {
+//!     let m = uci.parse::<Uci>()?.to_move(&pos)?;
+//!     pos.play_unchecked(&m);
+//!     moves.push(m);
+//! }
+//!
+//! let mut writer = PgnWriter::new(Vec::new());
+//! writer.tag(b"Event", b"Casual game")?;
+//! writer.moves(Chess::default(), &moves)?;
+//! writer.nag(Nag::BLUNDER)?;
+//! writer.comment(b" resigns ")?;
+//! writer.outcome(Some(Outcome::Draw))?;
+//!
+//! assert_eq!(
+//!     String::from_utf8(writer.into_inner()).expect("ascii"),
+//!     "[Event \"Casual game\"]\n\n1. e4 e5 $4 { resigns } 1/2-1/2\n\n"
+//! );
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{borrow::Cow, fmt, io, str};
+
+use crate::{
+    fen::Fen,
+    position::{Outcome, Position},
+    san::{Annotation, SanPlus},
+    types::Move,
+};
+
+/// Tell the reader to skip over a game or variation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Skip(pub bool);
+
+/// A numeric annotation glyph like `$1` or `!`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Nag(pub u8);
+
+impl Nag {
+    /// `!`
+    pub const GOOD_MOVE: Nag = Nag(1);
+    /// `?`
+    pub const MISTAKE: Nag = Nag(2);
+    /// `!!`
+    pub const BRILLIANT_MOVE: Nag = Nag(3);
+    /// `??`
+    pub const BLUNDER: Nag = Nag(4);
+    /// `!?`
+    pub const SPECULATIVE_MOVE: Nag = Nag(5);
+    /// `?!`
+    pub const DUBIOUS_MOVE: Nag = Nag(6);
+
+    /// Parses a NAG like `$12`, or one of the move annotations `!`, `?`,
+    /// `!!`, `??`, `!?` and `?!`.
+    pub fn from_ascii(s: &[u8]) -> Option<Nag> {
+        match Annotation::from_ascii(s) {
+            Some(annotation) => Some(annotation.nag()),
+            None => Some(Nag(btoi::btou(s.strip_prefix(b"$")?).ok()?)),
+        }
+    }
+
+    /// Gets the corresponding move annotation suffix, like `!?` for `$5`.
+    pub fn annotation(self) -> Option<Annotation> {
+        Annotation::from_nag(self)
+    }
+}
+
+impl fmt::Display for Nag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
+/// The raw value of a tag, with escape sequences still in place.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RawTag<'a>(pub &'a [u8]);
+
+impl<'a> RawTag<'a> {
+    /// Gets the raw bytes, with escape sequences still in place.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Resolves the escape sequences `\"` and `\\`.
+    pub fn decode(&self) -> Cow<'a, [u8]> {
+        if !self.0.contains(&b'\\') {
+            return Cow::Borrowed(self.0);
+        }
+        let mut decoded = Vec::with_capacity(self.0.len());
+        let mut escaped = false;
+        for &ch in self.0 {
+            if ch == b'\\' && !escaped {
+                escaped = true;
+            } else {
+                decoded.push(ch);
+                escaped = false;
+            }
+        }
+        Cow::Owned(decoded)
+    }
+
+    /// Resolves escape sequences and decodes the value as UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`str::Utf8Error`] if the value is not valid UTF-8.
+    pub fn decode_utf8(&self) -> Result<Cow<'a, str>, str::Utf8Error> {
+        Ok(match self.decode() {
+            Cow::Borrowed(bytes) => Cow::Borrowed(str::from_utf8(bytes)?),
+            Cow::Owned(bytes) => {
+                Cow::Owned(String::from_utf8(bytes).map_err(|err| err.utf8_error())?)
+            }
+        })
+    }
+}
+
+/// Receives the contents of games from a [`PgnReader`].
+///
+/// All methods except [`Visitor::end_game()`] have default implementations
+/// that ignore the event.
+pub trait Visitor {
+    /// Value produced for each game.
+    type Result;
+
+    /// Called at the start of each game.
+    fn begin_game(&mut self) {}
+
+    /// Called before the first tag, even if there are no tags.
+    fn begin_tags(&mut self) {}
+
+    /// Called for each tag pair, like `[White "Deep Blue"]`.
+    fn tag(&mut self, _name: &[u8], _value: RawTag<'_>) {}
+
+    /// Called after the last tag. Return `Skip(true)` to skip the movetext
+    /// of this game.
+    fn end_tags(&mut self) -> Skip {
+        Skip(false)
+    }
+
+    /// Called for each move. Suffix annotations like `!?` are reported
+    /// separately, as [`Visitor::nag()`].
+    fn san(&mut self, _san_plus: SanPlus) {}
+
+    /// Called for each NAG or suffix annotation.
+    fn nag(&mut self, _nag: Nag) {}
+
+    /// Called for each comment, without the surrounding braces or the
+    /// leading semicolon.
+    fn comment(&mut self, _comment: &[u8]) {}
+
+    /// Called at the start of a variation, which is an alternative to the
+    /// last move. Return `Skip(true)` to skip the variation, including
+    /// nested variations.
+    fn begin_variation(&mut self) -> Skip {
+        Skip(false)
+    }
+
+    /// Called at the end of a variation that was not skipped.
+    fn end_variation(&mut self) {}
+
+    /// Called for the game termination marker. The marker `*` is reported
+    /// as `None`.
+    fn outcome(&mut self, _outcome: Option<Outcome>) {}
+
+    /// Called at the end of each game.
+    fn end_game(&mut self) -> Self::Result;
+}
+
+/// A token of PGN tags or movetext.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Token<'a> {
+    /// A tag pair like `[Event "?"]`.
+    Tag { name: &'a [u8], value: RawTag<'a> },
+    /// A move number like `12.` or `12...`.
+    MoveNumber(u32),
+    /// Any other word, usually a move. It is not validated, and may have
+    /// check and annotation suffixes, like `Qxf7#!`. Use [`parse_san()`]
+    /// to parse it.
+    San(&'a [u8]),
+    /// A NAG like `$12`.
+    Nag(Nag),
+    /// A comment, without the surrounding braces or the leading semicolon.
+    Comment(&'a [u8]),
+    /// `(`
+    StartVariation,
+    /// `)`
+    EndVariation,
+    /// A game termination marker. `*` is `None`.
+    Outcome(Option<Outcome>),
+}
+
+/// Splits tags and movetext into [tokens](Token).
+///
+/// The tokenizer knows nothing about the structure of games and never
+/// fails. It skips stray `]`, `}` and `"`, as well as NAGs and move numbers
+/// that overflow. Escaped lines starting with `%` are not recognized, and
+/// should be removed beforehand.
+///
+/// Useful to implement custom formats that embed movetext.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{pgn::{Nag, Token, Tokenizer}, Outcome};
+///
+/// let tokens: Vec<Token<'_>> = Tokenizer::new(b"1. e4 $1 { best by test } (1. d4) *").collect();
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::MoveNumber(1),
+///         Token::San(b"e4"),
+///         Token::Nag(Nag::GOOD_MOVE),
+///         Token::Comment(b" best by test "),
+///         Token::StartVariation,
+///         Token::MoveNumber(1),
+///         Token::San(b"d4"),
+///         Token::EndVariation,
+///         Token::Outcome(None),
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tokenizer<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Starts tokenizing `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Tokenizer<'a> {
+        Tokenizer { bytes, pos: 0 }
+    }
+
+    /// Gets the offset of the next unread byte.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Gets the bytes that have not been tokenized yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn take_while<F: Fn(u8) -> bool>(&mut self, pred: F) -> &'a [u8] {
+        let start = self.pos;
+        while self.peek().map_or(false, &pred) {
+            self.pos += 1;
+        }
+        &self.bytes[start..self.pos]
+    }
+
+    fn tag(&mut self) -> Token<'a> {
+        self.take_while(|ch| ch.is_ascii_whitespace());
+        let name = self.take_while(|ch| !ch.is_ascii_whitespace() && ch != b'"' && ch != b']');
+        self.take_while(|ch| ch.is_ascii_whitespace());
+        let value = if self.peek() == Some(b'"') {
+            self.pos += 1;
+            let start = self.pos;
+            let mut escaped = false;
+            while let Some(ch) = self.peek() {
+                if ch == b'"' && !escaped {
+                    break;
+                }
+                escaped = ch == b'\\' && !escaped;
+                self.pos += 1;
+            }
+            &self.bytes[start..self.pos]
+        } else {
+            self.take_while(|ch| ch != b']' && ch != b'\n')
+        };
+        self.take_while(|ch| ch != b']' && ch != b'\n');
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+        }
+        Token::Tag {
+            name,
+            value: RawTag(value),
+        }
+    }
+}
+
+fn is_delimiter(ch: u8) -> bool {
+    ch.is_ascii_whitespace() || b"(){};[]$\"".contains(&ch)
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        loop {
+            self.take_while(|ch| ch.is_ascii_whitespace());
+            let ch = self.peek()?;
+            self.pos += 1;
+            return Some(match ch {
+                b'[' => self.tag(),
+                b'{' => {
+                    let comment = self.take_while(|ch| ch != b'}');
+                    self.pos = (self.pos + 1).min(self.bytes.len());
+                    Token::Comment(comment)
+                }
+                b';' => {
+                    let comment = self.take_while(|ch| ch != b'\n');
+                    Token::Comment(comment.strip_suffix(b"\r").unwrap_or(comment))
+                }
+                b'(' => Token::StartVariation,
+                b')' => Token::EndVariation,
+                b'$' => match btoi::btou(self.take_while(|ch| ch.is_ascii_digit())) {
+                    Ok(nag) => Token::Nag(Nag(nag)),
+                    Err(_) => continue,
+                },
+                b'0'..=b'9' => {
+                    let start = self.pos - 1;
+                    self.take_while(|ch| ch.is_ascii_digit());
+                    let digits_end = self.pos;
+                    if self.peek() == Some(b'.') || self.peek().map_or(true, is_delimiter) {
+                        self.take_while(|ch| ch == b'.');
+                        match btoi::btou(&self.bytes[start..digits_end]) {
+                            Ok(number) => Token::MoveNumber(number),
+                            Err(_) => continue,
+                        }
+                    } else {
+                        self.take_while(|ch| !is_delimiter(ch));
+                        let word = &self.bytes[start..self.pos];
+                        match Outcome::from_ascii(word) {
+                            Ok(outcome) => Token::Outcome(Some(outcome)),
+                            Err(_) => Token::San(word),
+                        }
+                    }
+                }
+                b'*' if self.peek().map_or(true, is_delimiter) => Token::Outcome(None),
+                b']' | b'}' | b'"' => continue,
+                _ => {
+                    let start = self.pos - 1;
+                    self.take_while(|ch| !is_delimiter(ch));
+                    Token::San(&self.bytes[start..self.pos])
+                }
+            });
+        }
+    }
+}
+
+/// Parses a [`Token::San`], leniently. Returns the move if it is valid SAN,
+/// and the suffix annotation (like `!?`), if any.
+///
+/// Castling with zeros (`0-0`) is accepted.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::pgn::{parse_san, Nag};
+///
+/// let (san_plus, nag) = parse_san(b"Qxf7#!!");
+/// assert_eq!(san_plus.map(|san_plus| san_plus.to_string()), Some("Qxf7#".to_owned()));
+/// assert_eq!(nag, Some(Nag::BRILLIANT_MOVE));
+/// ```
+pub fn parse_san(token: &[u8]) -> (Option<SanPlus>, Option<Nag>) {
+    let split = token
+        .iter()
+        .rposition(|ch| *ch != b'!' && *ch != b'?')
+        .map_or(0, |last| last + 1);
+    let (san, annotation) = token.split_at(split);
+    let nag = if annotation.is_empty() {
+        None
+    } else {
+        Nag::from_ascii(annotation)
+    };
+    let san_plus = SanPlus::from_ascii(san).ok().or_else(|| {
+        // Tolerate castling with zeros, like 0-0-0.
+        if san.starts_with(b"0-0") {
+            let letters: Vec<u8> = san
+                .iter()
+                .map(|ch| if *ch == b'0' { b'O' } else { *ch })
+                .collect();
+            SanPlus::from_ascii(&letters).ok()
+        } else {
+            None
+        }
+    });
+    (san_plus, nag)
+}
+
+fn ends_with_termination(line: &[u8]) -> bool {
+    let end = line
+        .iter()
+        .rposition(|ch| !ch.is_ascii_whitespace())
+        .map_or(0, |i| i + 1);
+    let line = &line[..end];
+    [&b"1-0"[..], b"0-1", b"1/2-1/2", b"*"]
+        .iter()
+        .any(|result| {
+            line.strip_suffix(*result).map_or(false, |rest| {
+                rest.last().map_or(true, |ch| {
+                    ch.is_ascii_whitespace() || matches!(ch, b')' | b'}')
+                })
+            })
+        })
+}
+
+/// Reads games from a PGN file.
+///
+/// A game ends at its game termination marker, at a blank line after
+/// movetext, or at the tag section of the next game. Lines starting with
+/// `%` are ignored.
+#[derive(Debug)]
+pub struct PgnReader<R> {
+    reader: R,
+    game: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl<R: io::BufRead> PgnReader<R> {
+    /// Creates a reader. Wrap unbuffered sources like files in an
+    /// [`io::BufReader`].
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader,
+            game: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Gets the underlying reader. Data may have been buffered past the
+    /// end of the last game.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn fill_game(&mut self) -> io::Result<bool> {
+        self.game.clear();
+        self.game.append(&mut self.pending);
+
+        let mut line = Vec::new();
+        let mut in_comment = false;
+        let mut movetext = false;
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            if !in_comment {
+                let first = line.iter().copied().find(|ch| !ch.is_ascii_whitespace());
+                match (line.first(), first) {
+                    (Some(b'%'), _) => continue,
+                    (_, Some(b'[')) if movetext => {
+                        self.pending.extend_from_slice(&line);
+                        break;
+                    }
+                    (_, Some(b'[')) => {
+                        self.game.extend_from_slice(&line);
+                        continue;
+                    }
+                    (_, Some(_)) => movetext = true,
+                    (_, None) if movetext => break,
+                    (_, None) => (),
+                }
+            }
+
+            let mut code = line.len();
+            for (i, &ch) in line.iter().enumerate() {
+                match ch {
+                    b'}' if in_comment => in_comment = false,
+                    b'{' if !in_comment => in_comment = true,
+                    b';' if !in_comment => {
+                        code = i;
+                        break;
+                    }
+                    _ => (),
+                }
+            }
+
+            self.game.extend_from_slice(&line);
+
+            if movetext && !in_comment && ends_with_termination(&line[..code]) {
+                break;
+            }
+        }
+
+        Ok(self.game.iter().any(|ch| !ch.is_ascii_whitespace()))
+    }
+
+    /// Reads the next game, calling the methods of the visitor.
+    ///
+    /// Returns `Ok(None)` if there are no more games.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying reader are passed through. The reader is
+    /// lenient: Unparsable tokens are skipped.
+    pub fn read_game<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<Option<V::Result>> {
+        if !self.fill_game()? {
+            return Ok(None);
+        }
+
+        let mut tokens = Tokenizer::new(&self.game).peekable();
+
+        visitor.begin_game();
+        visitor.begin_tags();
+        while let Some(Token::Tag { name, value }) = tokens.peek() {
+            visitor.tag(name, *value);
+            tokens.next();
+        }
+        if visitor.end_tags().0 {
+            return Ok(Some(visitor.end_game()));
+        }
+
+        let mut depth = 0usize;
+        while let Some(token) = tokens.next() {
+            match token {
+                Token::San(token) => {
+                    let (san_plus, nag) = parse_san(token);
+                    if let Some(san_plus) = san_plus {
+                        visitor.san(san_plus);
+                        if let Some(nag) = nag {
+                            visitor.nag(nag);
+                        }
+                    }
+                }
+                Token::Nag(nag) => visitor.nag(nag),
+                Token::Comment(comment) => visitor.comment(comment),
+                Token::StartVariation => {
+                    if visitor.begin_variation().0 {
+                        let mut skipped = 1usize;
+                        for token in tokens.by_ref() {
+                            match token {
+                                Token::StartVariation => skipped += 1,
+                                Token::EndVariation => {
+                                    skipped -= 1;
+                                    if skipped == 0 {
+                                        break;
+                                    }
+                                }
+                                _ => (),
+                            }
+                        }
+                    } else {
+                        depth += 1;
+                    }
+                }
+                Token::EndVariation => {
+                    if depth > 0 {
+                        depth -= 1;
+                        visitor.end_variation();
+                    }
+                }
+                Token::Outcome(outcome) => visitor.outcome(outcome),
+                Token::MoveNumber(_) | Token::Tag { .. } => (),
+            }
+        }
+        for _ in 0..depth {
+            visitor.end_variation();
+        }
+
+        Ok(Some(visitor.end_game()))
+    }
+
+    /// Skips the next game without parsing its contents. Returns `false`
+    /// if there are no more games.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying reader are passed through.
+    pub fn skip_game(&mut self) -> io::Result<bool> {
+        self.fill_game()
+    }
+
+    /// Reads all remaining games.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying reader are passed through.
+    pub fn read_all<V: Visitor>(&mut self, visitor: &mut V) -> io::Result<()> {
+        while self.read_game(visitor)?.is_some() {}
+        Ok(())
+    }
+}
+
+/// Writes games in PGN export format.
+///
+/// Move numbers are inserted automatically, also after comments and
+/// variations. Lines are wrapped at 80 columns, but comments are never
+/// broken, so that they are read back exactly as written.
+#[derive(Debug)]
+pub struct PgnWriter<W> {
+    writer: W,
+    max_width: usize,
+    width: usize,
+    tags: bool,
+    movetext: bool,
+    ply: u32,
+    variations: Vec<u32>,
+    force_number: bool,
+    after_open: bool,
+    after_san: bool,
+    suffix_annotations: bool,
+}
+
+impl<W: io::Write> PgnWriter<W> {
+    /// Creates a writer.
+    pub fn new(writer: W) -> PgnWriter<W> {
+        PgnWriter {
+            writer,
+            max_width: 80,
+            width: 0,
+            tags: false,
+            movetext: false,
+            ply: 0,
+            variations: Vec::new(),
+            force_number: false,
+            after_open: false,
+            after_san: false,
+            suffix_annotations: false,
+        }
+    }
+
+    /// Writes NAGs `$1` to `$6` as suffixes like `!?`, if they directly
+    /// follow a move. Defaults to `false`.
+    pub fn set_suffix_annotations(&mut self, suffix_annotations: bool) {
+        self.suffix_annotations = suffix_annotations;
+    }
+
+    /// Sets the maximum line width of the movetext. Defaults to 80.
+    pub fn set_max_width(&mut self, max_width: usize) {
+        self.max_width = max_width;
+    }
+
+    /// Gets the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes a tag pair, escaping the value as needed. Tags must be
+    /// written before any movetext.
+    ///
+    /// A `FEN` tag also determines the move numbers of the following
+    /// movetext.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn tag(&mut self, name: &[u8], value: &[u8]) -> io::Result<()> {
+        if name == b"FEN" {
+            if let Ok(fen) = Fen::from_ascii(value) {
+                self.set_start(fen.0.turn.is_black(), fen.0.fullmoves.get());
+            }
+        }
+
+        self.tags = true;
+        self.writer.write_all(b"[")?;
+        self.writer.write_all(name)?;
+        self.writer.write_all(b" \"")?;
+        for &ch in value {
+            if ch == b'\\' || ch == b'"' {
+                self.writer.write_all(b"\\")?;
+            }
+            self.writer.write_all(&[ch])?;
+        }
+        self.writer.write_all(b"\"]\n")
+    }
+
+    /// Sets the move number of the first move, if the game does not start
+    /// with White to move in move 1. Not needed if there was a `FEN` tag.
+    pub fn set_start(&mut self, black: bool, fullmoves: u32) {
+        self.ply = fullmoves.saturating_sub(1) * 2 + u32::from(black);
+    }
+
+    fn token(&mut self, token: &[u8]) -> io::Result<()> {
+        self.token_with_suffix(token, 0)
+    }
+
+    /// Writes a token, breaking the line early enough for a suffix of up
+    /// to `suffix` bytes to be appended without a space.
+    fn token_with_suffix(&mut self, token: &[u8], suffix: usize) -> io::Result<()> {
+        if !self.movetext {
+            self.movetext = true;
+            if self.tags {
+                self.writer.write_all(b"\n")?;
+            }
+        }
+        if self.width > 0 && !self.after_open && token != b")" {
+            if self.width + 1 + token.len() + suffix > self.max_width {
+                self.writer.write_all(b"\n")?;
+                self.width = 0;
+            } else {
+                self.writer.write_all(b" ")?;
+                self.width += 1;
+            }
+        }
+        self.writer.write_all(token)?;
+        self.width += token.len();
+        self.after_open = token == b"(";
+        self.after_san = false;
+        Ok(())
+    }
+
+    /// Writes a move, preceded by its move number if needed.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn san(&mut self, san_plus: &SanPlus) -> io::Result<()> {
+        let fullmoves = self.ply / 2 + 1;
+        let token = if self.ply % 2 == 0 {
+            format!("{}. {}", fullmoves, san_plus)
+        } else if self.force_number || !self.movetext {
+            format!("{}... {}", fullmoves, san_plus)
+        } else {
+            san_plus.to_string()
+        };
+        // Leave room for suffix annotations like `!?`.
+        let suffix = if self.suffix_annotations { 2 } else { 0 };
+        self.token_with_suffix(token.as_bytes(), suffix)?;
+        self.ply += 1;
+        self.force_number = false;
+        self.after_san = true;
+        Ok(())
+    }
+
+    /// Writes a sequence of legal moves, starting from `pos`.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    ///
+    /// # Panics
+    ///
+    /// Illegal moves can corrupt the state of the position and may
+    /// (or may not) panic or cause panics on future calls.
+    pub fn moves<P: Position>(&mut self, mut pos: P, moves: &[Move]) -> io::Result<()> {
+        for m in moves {
+            self.san(&SanPlus::from_move_and_play_unchecked(&mut pos, m))?;
+        }
+        Ok(())
+    }
+
+    /// Writes a NAG.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn nag(&mut self, nag: Nag) -> io::Result<()> {
+        if self.suffix_annotations && self.after_san {
+            if let Some(annotation) = nag.annotation() {
+                self.writer.write_all(annotation.as_str().as_bytes())?;
+                self.width += annotation.as_str().len();
+                self.after_san = false;
+                return Ok(());
+            }
+        }
+        self.token(nag.to_string().as_bytes())
+    }
+
+    /// Writes a comment. The comment is written as given, so usually it
+    /// should start and end with a space. Any `}` is removed, because it
+    /// would end the comment early.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn comment(&mut self, comment: &[u8]) -> io::Result<()> {
+        let mut token = Vec::with_capacity(comment.len() + 2);
+        token.push(b'{');
+        token.extend(comment.iter().filter(|ch| **ch != b'}'));
+        token.push(b'}');
+        self.token(&token)?;
+        self.force_number = true;
+        Ok(())
+    }
+
+    /// Starts a variation, which is an alternative to the last move.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn begin_variation(&mut self) -> io::Result<()> {
+        self.token(b"(")?;
+        self.variations.push(self.ply);
+        self.ply = self.ply.saturating_sub(1);
+        self.force_number = true;
+        Ok(())
+    }
+
+    /// Ends the current variation.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn end_variation(&mut self) -> io::Result<()> {
+        if let Some(ply) = self.variations.pop() {
+            self.token(b")")?;
+            self.ply = ply;
+            self.force_number = true;
+        }
+        Ok(())
+    }
+
+    /// Closes open variations and writes the game termination marker,
+    /// using `*` if the outcome is unknown. Afterwards the next game can
+    /// be written.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn outcome(&mut self, outcome: Option<Outcome>) -> io::Result<()> {
+        while !self.variations.is_empty() {
+            self.end_variation()?;
+        }
+        match outcome {
+            Some(outcome) => self.token(outcome.to_string().as_bytes())?,
+            None => self.token(b"*")?,
+        }
+        self.writer.write_all(b"\n\n")?;
+
+        self.width = 0;
+        self.tags = false;
+        self.movetext = false;
+        self.ply = 0;
+        self.force_number = false;
+        self.after_open = false;
+        self.after_san = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::san::San;
+
+    #[derive(Default)]
+    struct Events {
+        events: Vec<String>,
+        skip_variations: bool,
+    }
+
+    impl Visitor for Events {
+        type Result = Vec<String>;
+
+        fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+            self.events.push(format!(
+                "[{} {}]",
+                String::from_utf8_lossy(name),
+                value.decode_utf8().expect("utf-8")
+            ));
+        }
+
+        fn san(&mut self, san_plus: SanPlus) {
+            self.events.push(san_plus.to_string());
+        }
+
+        fn nag(&mut self, nag: Nag) {
+            self.events.push(nag.to_string());
+        }
+
+        fn comment(&mut self, comment: &[u8]) {
+            self.events
+                .push(format!("{{{}}}", String::from_utf8_lossy(comment).trim()));
+        }
+
+        fn begin_variation(&mut self) -> Skip {
+            if !self.skip_variations {
+                self.events.push("(".to_owned());
+            }
+            Skip(self.skip_variations)
+        }
+
+        fn end_variation(&mut self) {
+            self.events.push(")".to_owned());
+        }
+
+        fn outcome(&mut self, outcome: Option<Outcome>) {
+            self.events
+                .push(outcome.map_or("*".to_owned(), |o| o.to_string()));
+        }
+
+        fn end_game(&mut self) -> Vec<String> {
+            std::mem::take(&mut self.events)
+        }
+    }
+
+    #[test]
+    fn test_read_games() {
+        let pgn = b"[Event \"A \\\"quoted\\\" event\"]\n\
+            [Site \"?\"]\n\
+            \n\
+            1. e4 $1 e5!? { a\n[%clk 0:01:00] comment } 2. Nf3 (2. f4 exf4 (2... d5)) ; rest\n\
+            2... Nc6 3.Bb5 0-0-0 1-0\n\
+            % escaped line\n\
+            \n\
+            [Event \"Second\"]\n\
+            1... e5 *\n";
+
+        let mut reader = PgnReader::new(&pgn[..]);
+        let mut visitor = Events::default();
+        assert_eq!(
+            reader.read_game(&mut visitor).expect("io").expect("game"),
+            [
+                "[Event A \"quoted\" event]",
+                "[Site ?]",
+                "e4",
+                "$1",
+                "e5",
+                "$5",
+                "{a\n[%clk 0:01:00] comment}",
+                "Nf3",
+                "(",
+                "f4",
+                "exf4",
+                "(",
+                "d5",
+                ")",
+                ")",
+                "{rest}",
+                "Nc6",
+                "Bb5",
+                "O-O-O",
+                "1-0",
+            ]
+        );
+
+        visitor.skip_variations = true;
+        assert_eq!(
+            reader.read_game(&mut visitor).expect("io").expect("game"),
+            ["[Event Second]", "e5", "*"]
+        );
+        assert!(reader.read_game(&mut visitor).expect("io").is_none());
+    }
+
+    #[test]
+    fn test_skip_variations() {
+        let mut reader = PgnReader::new(&b"1. e4 (1. d4 (1. c4) d5) (1. Nf3) e5 (1... c5"[..]);
+        let mut visitor = Events {
+            skip_variations: true,
+            ..Events::default()
+        };
+        assert_eq!(
+            reader.read_game(&mut visitor).expect("io").expect("game"),
+            ["e4", "e5"]
+        );
+    }
+
+    struct Rewrite {
+        writer: PgnWriter<Vec<u8>>,
+    }
+
+    impl Visitor for Rewrite {
+        type Result = ();
+
+        fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+            self.writer.tag(name, &value.decode()).expect("write");
+        }
+
+        fn san(&mut self, san_plus: SanPlus) {
+            self.writer.san(&san_plus).expect("write");
+        }
+
+        fn nag(&mut self, nag: Nag) {
+            self.writer.nag(nag).expect("write");
+        }
+
+        fn comment(&mut self, comment: &[u8]) {
+            self.writer.comment(comment).expect("write");
+        }
+
+        fn begin_variation(&mut self) -> Skip {
+            self.writer.begin_variation().expect("write");
+            Skip(false)
+        }
+
+        fn end_variation(&mut self) {
+            self.writer.end_variation().expect("write");
+        }
+
+        fn outcome(&mut self, outcome: Option<Outcome>) {
+            self.writer.outcome(outcome).expect("write");
+        }
+
+        fn end_game(&mut self) {}
+    }
+
+    #[test]
+    fn test_write_roundtrip() {
+        let pgn = b"[Event \"\\\"Quoted\\\"\"]\n\
+            [FEN \"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\"]\n\
+            \n\
+            1... e5 2. Nf3 $1 (2. f4 { King's gambit } exf4 (2... d5 3. exd5)) 2... Nc6 \
+            3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 \
+            10. d4 Nbd7 1/2-1/2\n\n\
+            1. d4 *\n\n";
+
+        let mut copy = Rewrite {
+            writer: PgnWriter::new(Vec::new()),
+        };
+        PgnReader::new(&pgn[..]).read_all(&mut copy).expect("io");
+        let written = copy.writer.into_inner();
+        assert!(written
+            .split(|ch| *ch == b'\n')
+            .all(|line| line.len() <= 80));
+        assert_eq!(
+            String::from_utf8(written.clone())
+                .expect("ascii")
+                .lines()
+                .nth(3),
+            Some(
+                "1... e5 2. Nf3 $1 (2. f4 { King's gambit } 2... exf4 (2... d5 3. exd5)) 2... Nc6"
+            )
+        );
+
+        let mut original = PgnReader::new(&pgn[..]);
+        let mut reread = PgnReader::new(&written[..]);
+        let mut visitor = Events::default();
+        for _ in 0..2 {
+            assert_eq!(
+                original.read_game(&mut visitor).expect("io"),
+                reread.read_game(&mut visitor).expect("io")
+            );
+        }
+        assert!(original.read_game(&mut visitor).expect("io").is_none());
+        assert!(reread.read_game(&mut visitor).expect("io").is_none());
+    }
+
+    #[test]
+    fn test_suffix_annotations() {
+        let pgn = b"1. e4! e5?! $18 2. Nf3 $1 $5 (2. f4 { Gambit } $3) *\n\n";
+
+        let mut copy = Rewrite {
+            writer: PgnWriter::new(Vec::new()),
+        };
+        copy.writer.set_suffix_annotations(true);
+        PgnReader::new(&pgn[..]).read_all(&mut copy).expect("io");
+        assert_eq!(
+            String::from_utf8(copy.writer.into_inner()).expect("ascii"),
+            "1. e4! e5?! $18 2. Nf3! $5 (2. f4 { Gambit } $3) *\n\n"
+        );
+
+        let mut copy = Rewrite {
+            writer: PgnWriter::new(Vec::new()),
+        };
+        copy.writer.set_suffix_annotations(true);
+        copy.writer.set_max_width(9);
+        PgnReader::new(&b"1. e4 e5?! 2. Nf3!! *\n\n"[..])
+            .read_all(&mut copy)
+            .expect("io");
+        let written = String::from_utf8(copy.writer.into_inner()).expect("ascii");
+        assert_eq!(written, "1. e4\ne5?!\n2. Nf3!!\n*\n\n");
+        assert!(written.lines().all(|line| line.len() <= 9));
+    }
+
+    #[test]
+    fn test_write_comment_with_brace() {
+        let mut writer = PgnWriter::new(Vec::new());
+        writer.comment(b" a } b ").expect("write");
+        writer.outcome(None).expect("write");
+        assert_eq!(writer.into_inner(), b"{ a  b } *\n\n");
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let tokens: Vec<_> = Tokenizer::new(b"12...Qxf7# $18 1/2-1/2 -- *").collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::MoveNumber(12),
+                Token::San(b"Qxf7#"),
+                Token::Nag(Nag(18)),
+                Token::Outcome(Some(Outcome::Draw)),
+                Token::San(b"--"),
+                Token::Outcome(None),
+            ]
+        );
+        assert_eq!(
+            parse_san(b"--").0.map(|san_plus| san_plus.san),
+            Some(San::Null)
+        );
+        assert_eq!(parse_san(b"e4??").1, Some(Nag::BLUNDER));
+    }
+
+    #[test]
+    fn test_read_games_without_tags() {
+        let pgn =
+            b"1. e4 e5 1-0\n\n1. d4 d5 0-1\n1. c4 { end } *\n1. Nf3\n\n1. g3 ; 1-0\n1... g6\n";
+
+        let mut reader = PgnReader::new(&pgn[..]);
+        let mut visitor = Events::default();
+        let mut games = Vec::new();
+        while let Some(game) = reader.read_game(&mut visitor).expect("io") {
+            games.push(game);
+        }
+        assert_eq!(
+            games,
+            [
+                vec!["e4", "e5", "1-0"],
+                vec!["d4", "d5", "0-1"],
+                vec!["c4", "{end}", "*"],
+                vec!["Nf3"],
+                vec!["g3", "{1-0}", "g6"],
+            ]
+        );
+    }
+}
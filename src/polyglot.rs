@@ -0,0 +1,260 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Create and merge Polyglot opening books.
+//!
+//! A Polyglot book is a sequence of 16 byte entries, sorted by the
+//! [Zobrist hash](crate::zobrist) of the position. Each entry consists of
+//! the big-endian 64-bit key, 16-bit move, 16-bit weight and 32-bit learn
+//! value.
+//!
+//! To aggregate statistics from game records, see
+//! [`book::GameBookBuilder`](crate::book::GameBookBuilder).
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{polyglot::BookBuilder, uci::Uci, Chess};
+//!
+//! let pos = Chess::default();
+//! let e4 = "e2e4".parse::<Uci>()?.to_move(&pos)?;
+//! let d4 = "d2d4".parse::<Uci>()?.to_move(&pos)?;
+//!
+//! let mut builder = BookBuilder::new();
+//! builder.insert(&pos, &e4, 10);
+//! builder.insert(&pos, &d4, 20);
+//!
+//! let mut other = BookBuilder::new();
+//! other.insert(&pos, &e4, 15);
+//!
+//! // Weights of duplicate entries are added up.
+//! let mut merged = Vec::new();
+//! builder.read_bin(&other.to_bin()[..])?;
+//! builder.write_bin(&mut merged)?;
+//! assert_eq!(merged.len(), 2 * 16);
+//! assert_eq!(&merged[8..12], &[0x03, 0x1c, 0, 25]); // e2e4, weight 25
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io;
+
+use crate::{position::Position, types::Move, util::read_record, zobrist::ZobristHash};
+
+/// An entry of a Polyglot opening book.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Entry {
+    /// Zobrist hash of the position.
+    pub key: u64,
+    /// The move, encoded with [`encode_move()`].
+    pub raw_move: u16,
+    /// Relative weight of the move in the position.
+    pub weight: u16,
+    /// Learning data, usually `0`.
+    pub learn: u32,
+}
+
+impl Entry {
+    /// Decodes an entry.
+    pub fn from_bytes(bytes: [u8; 16]) -> Entry {
+        let mut key = [0; 8];
+        key.copy_from_slice(&bytes[..8]);
+        Entry {
+            key: u64::from_be_bytes(key),
+            raw_move: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        }
+    }
+
+    /// Encodes the entry.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// Encodes a move in Polyglot format. Castling moves are encoded as the
+/// king capturing its own rook. Returns `None` for drops, which can not be
+/// represented.
+pub fn encode_move(m: &Move) -> Option<u16> {
+    let (from, to, promotion) = match *m {
+        Move::Normal {
+            from,
+            to,
+            promotion,
+            ..
+        } => (from, to, promotion.map_or(0, |role| u16::from(role) - 1)),
+        Move::EnPassant { from, to } => (from, to, 0),
+        Move::Castle { king, rook } => (king, rook, 0),
+        Move::Put { .. } => return None,
+    };
+    Some(u16::from(to) | (u16::from(from) << 6) | (promotion << 12))
+}
+
+/// Collects entries for a Polyglot opening book.
+///
+/// Entries can be added in any order, and can come from multiple sources.
+/// When written, entries are sorted by key, and by descending weight
+/// within each key. Duplicate moves in the same position are merged by
+/// adding up their weights (saturating at `u16::MAX`), keeping the learn
+/// value of the first occurrence.
+#[derive(Debug, Clone, Default)]
+pub struct BookBuilder {
+    entries: Vec<Entry>,
+}
+
+impl BookBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> BookBuilder {
+        BookBuilder::default()
+    }
+
+    /// Adds `m` in `pos` with the given `weight`. Drops are ignored.
+    pub fn insert<P: Position + ZobristHash>(&mut self, pos: &P, m: &Move, weight: u16) {
+        if let Some(raw_move) = encode_move(m) {
+            self.insert_entry(Entry {
+                key: pos.zobrist_hash(),
+                raw_move,
+                weight,
+                learn: 0,
+            });
+        }
+    }
+
+    /// Adds a raw entry.
+    pub fn insert_entry(&mut self, entry: Entry) {
+        self.entries.push(entry);
+    }
+
+    /// Adds all entries of an existing Polyglot book.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying reader are passed through. Returns
+    /// [`io::ErrorKind::UnexpectedEof`] if the book is truncated. Entries
+    /// before that are still added.
+    pub fn read_bin<R: io::Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut bytes = [0; 16];
+        while read_record(&mut reader, &mut bytes)? {
+            self.insert_entry(Entry::from_bytes(bytes));
+        }
+        Ok(())
+    }
+
+    /// Adds all entries of `other`.
+    pub fn merge(&mut self, other: &BookBuilder) {
+        self.entries.extend_from_slice(&other.entries);
+    }
+
+    /// Number of entries, including duplicates.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Tests if no entries have been added.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Sorts and deduplicates the entries.
+    pub fn finish(&mut self) -> &[Entry] {
+        self.entries
+            .sort_by_key(|entry| (entry.key, entry.raw_move));
+        self.entries.dedup_by(|entry, first| {
+            let duplicate = entry.key == first.key && entry.raw_move == first.raw_move;
+            if duplicate {
+                first.weight = first.weight.saturating_add(entry.weight);
+            }
+            duplicate
+        });
+        self.entries
+            .sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight), entry.raw_move));
+        &self.entries
+    }
+
+    /// Writes the book.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn write_bin<W: io::Write>(&mut self, mut writer: W) -> io::Result<()> {
+        for entry in self.finish() {
+            writer.write_all(&entry.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Gets the book as bytes, leaving `self` untouched.
+    pub fn to_bin(&self) -> Vec<u8> {
+        self.clone()
+            .finish()
+            .iter()
+            .flat_map(Entry::to_bytes)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_book_builder() {
+        let entry = |key, raw_move, weight, learn| Entry {
+            key,
+            raw_move,
+            weight,
+            learn,
+        };
+
+        let mut builder = BookBuilder::new();
+        builder.insert_entry(entry(2, 1, 1, 0));
+        builder.insert_entry(entry(1, 7, 40_000, 1));
+        builder.insert_entry(entry(1, 8, 5, 0));
+        builder.insert_entry(entry(1, 7, 40_000, 2));
+        builder.insert_entry(entry(1, 9, 10, 0));
+
+        let bin = builder.to_bin();
+        assert_eq!(bin.len(), 4 * 16);
+        assert_eq!(builder.len(), 5);
+
+        let mut reread = BookBuilder::new();
+        reread.read_bin(&bin[..]).expect("valid book");
+        assert_eq!(
+            reread.finish(),
+            [
+                entry(1, 7, u16::MAX, 1),
+                entry(1, 9, 10, 0),
+                entry(1, 8, 5, 0),
+                entry(2, 1, 1, 0),
+            ]
+        );
+
+        let mut truncated = BookBuilder::new();
+        assert_eq!(
+            truncated
+                .read_bin(&bin[..20])
+                .expect_err("truncated")
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(truncated.len(), 1);
+    }
+}
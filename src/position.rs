@@ -15,22 +15,27 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
+    cmp::max,
     error::Error,
     fmt,
     hash::{Hash, Hasher},
+    iter::FusedIterator,
     num::NonZeroU32,
     str::FromStr,
 };
 
+use arrayvec::ArrayVec;
 use bitflags::bitflags;
 
 use crate::{
     attacks,
     bitboard::{Bitboard, Direction},
     setup::{Castles, EnPassant, Setup},
+    zobrist::RepetitionTracker,
     Board, ByColor, ByRole, CastlingMode, CastlingSide, Color,
     Color::{Black, White},
-    EnPassantMode, Move, MoveList, Piece, Rank, RemainingChecks, Role, Square,
+    EnPassantMode, ErrorKind, MaterialChecks, Move, MoveList, Odds, Piece, Rank, RemainingChecks,
+    Role, Square,
 };
 
 /// Outcome of a game.
@@ -55,6 +60,37 @@ impl Outcome {
         }
     }
 
+    /// Adjudicates the game after `flagged` ran out of time in `pos`.
+    ///
+    /// The opponent wins, unless they
+    /// [have insufficient winning material](Position::has_insufficient_material),
+    /// in which case the game is drawn. This does not check if the game
+    /// was already over before the flag fell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, Color, Outcome};
+    ///
+    /// let pos: Chess = "8/8/4k3/8/8/8/8/4K2Q w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// assert_eq!(Outcome::from_timeout(&pos, Color::White), Outcome::Draw);
+    /// assert_eq!(
+    ///     Outcome::from_timeout(&pos, Color::Black),
+    ///     Outcome::Decisive { winner: Color::White }
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_timeout<P: Position>(pos: &P, flagged: Color) -> Outcome {
+        if pos.has_insufficient_material(!flagged) {
+            Outcome::Draw
+        } else {
+            Outcome::Decisive { winner: !flagged }
+        }
+    }
+
     pub fn from_ascii(bytes: &[u8]) -> Result<Outcome, ParseOutcomeError> {
         Ok(match bytes {
             b"1-0" => Outcome::Decisive { winner: White },
@@ -76,6 +112,56 @@ impl fmt::Display for Outcome {
     }
 }
 
+/// Rule that decided the [`Outcome`] of a game. See
+/// [`Position::outcome_with()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EndReason {
+    Checkmate,
+    Stalemate,
+    /// A variant specific game end, see [`Position::variant_outcome()`].
+    VariantEnd,
+    /// Neither side [has sufficient material to win](Position::is_insufficient_material()).
+    InsufficientMaterial,
+    /// 150 plies without capture or pawn move.
+    SeventyFiveMoves,
+    /// The position occurred for the fifth time since the last
+    /// irreversible move.
+    FivefoldRepetition,
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            EndReason::Checkmate => "checkmate",
+            EndReason::Stalemate => "stalemate",
+            EndReason::VariantEnd => "variant end",
+            EndReason::InsufficientMaterial => "insufficient material",
+            EndReason::SeventyFiveMoves => "75-move rule",
+            EndReason::FivefoldRepetition => "fivefold repetition",
+        })
+    }
+}
+
+/// Optional rules for [`Position::outcome_with()`], in addition to the
+/// rules that always apply.
+#[derive(Debug, Copy, Clone)]
+pub struct OutcomeRules<'a> {
+    /// Apply the 75-move rule. Defaults to `true`.
+    pub seventy_five_moves: bool,
+    /// Positions of the game so far, to apply the fivefold repetition
+    /// rule. Defaults to `None`.
+    pub repetitions: Option<&'a RepetitionTracker<u64>>,
+}
+
+impl<'a> Default for OutcomeRules<'a> {
+    fn default() -> OutcomeRules<'a> {
+        OutcomeRules {
+            seventy_five_moves: true,
+            repetitions: None,
+        }
+    }
+}
+
 /// Error when parsing the outcome of a game.
 #[derive(Debug, Clone)]
 pub enum ParseOutcomeError {
@@ -96,6 +182,13 @@ impl fmt::Display for ParseOutcomeError {
 
 impl Error for ParseOutcomeError {}
 
+impl ParseOutcomeError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidOutcome
+    }
+}
+
 impl FromStr for Outcome {
     type Err = ParseOutcomeError;
 
@@ -107,8 +200,8 @@ impl FromStr for Outcome {
 /// Error when trying to play an illegal move.
 #[derive(Debug)]
 pub struct PlayError<P> {
-    m: Move,
-    inner: P,
+    pub(crate) m: Move,
+    pub(crate) inner: P,
 }
 
 impl<P> PlayError<P> {
@@ -116,6 +209,16 @@ impl<P> PlayError<P> {
     pub fn into_inner(self) -> P {
         self.inner
     }
+
+    /// Gets the illegal move.
+    pub fn illegal_move(&self) -> &Move {
+        &self.m
+    }
+
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::IllegalMove
+    }
 }
 
 impl<P: fmt::Debug> fmt::Display for PlayError<P> {
@@ -126,6 +229,74 @@ impl<P: fmt::Debug> fmt::Display for PlayError<P> {
 
 impl<P: fmt::Debug> Error for PlayError<P> {}
 
+/// Information to take back a move. See [`Chess::play_and_record()`].
+#[derive(Debug, Clone)]
+pub struct Undo {
+    m: Move,
+    castles: Castles,
+    ep_square: Option<EnPassant>,
+    halfmoves: u32,
+}
+
+/// Lazy, staged generator of legal moves. See [`Position::moves_iter()`].
+///
+/// Yields all captures (including en passant and capturing promotions)
+/// first, and only generates the remaining moves once the captures are
+/// exhausted.
+#[derive(Debug, Clone)]
+pub struct MoveGen<'a, P> {
+    pos: &'a P,
+    moves: <MoveList as IntoIterator>::IntoIter,
+    quiets_pending: bool,
+}
+
+impl<'a, P: Position> MoveGen<'a, P> {
+    fn new(pos: &'a P) -> MoveGen<'a, P> {
+        MoveGen {
+            pos,
+            moves: pos.capture_moves().into_iter(),
+            quiets_pending: true,
+        }
+    }
+
+    /// Tests if all captures have been yielded, so that the remaining
+    /// moves are not captures.
+    pub fn is_quiet_stage(&self) -> bool {
+        !self.quiets_pending
+    }
+}
+
+impl<'a, P: Position> Iterator for MoveGen<'a, P> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        if let Some(m) = self.moves.next() {
+            return Some(m);
+        }
+        if self.quiets_pending {
+            self.quiets_pending = false;
+            self.moves = self.pos.quiet_moves().into_iter();
+            return self.moves.next();
+        }
+        None
+    }
+}
+
+impl<'a, P: Position> FusedIterator for MoveGen<'a, P> {}
+
+/// Whether a premove will be legal once the opponent has replied. See
+/// [`Position::premove_legality()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PremoveLegality {
+    /// The premove is legal after every possible reply.
+    Always,
+    /// The premove is legal after some, but not all possible replies.
+    Sometimes,
+    /// The premove is not legal after any possible reply, or the opponent
+    /// has no legal moves at all.
+    Never,
+}
+
 bitflags! {
     /// Reasons for a [`Setup`] not being a legal [`Position`].
     pub struct PositionErrorKinds: u32 {
@@ -195,7 +366,7 @@ pub struct PositionError<P> {
 }
 
 impl<P> PositionError<P> {
-    fn ignore(mut self, ignore: PositionErrorKinds) -> Result<P, Self> {
+    pub(crate) fn ignore(mut self, ignore: PositionErrorKinds) -> Result<P, Self> {
         self.errors -= ignore;
         match self {
             PositionError { pos, errors } if errors.is_empty() => Ok(pos),
@@ -225,15 +396,80 @@ impl<P> PositionError<P> {
         self.ignore(PositionErrorKinds::IMPOSSIBLE_MATERIAL)
     }
 
+    /// Alias of [`PositionError::ignore_impossible_material()`], named
+    /// after the only way material can be impossible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, PositionErrorKinds};
+    ///
+    /// // Eight queens next to eight pawns, and a triple check.
+    /// let fen: Fen = "3k4/2QQQ3/8/8/8/8/PPPPPPPP/QQQQQ1K1 b - - 0 1".parse()?;
+    /// let err = fen.into_position::<Chess>(CastlingMode::Standard).unwrap_err();
+    /// assert_eq!(
+    ///     err.kinds(),
+    ///     PositionErrorKinds::IMPOSSIBLE_MATERIAL | PositionErrorKinds::IMPOSSIBLE_CHECK
+    /// );
+    ///
+    /// // Opt into leniency for each kind of error separately.
+    /// let err = err.ignore_too_much_material().unwrap_err();
+    /// assert_eq!(err.kinds(), PositionErrorKinds::IMPOSSIBLE_CHECK);
+    /// let pos = err.ignore_impossible_check()?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn ignore_too_much_material(self) -> Result<P, Self> {
+        self.ignore_impossible_material()
+    }
+
+    /// Get the position despite [`PositionErrorKinds::IMPOSSIBLE_MATERIAL`],
+    /// as long as the board passes the given plausibility `checks`. Allows
+    /// importers to choose how strict to be about material.
+    ///
+    /// Only the pieces on the board are considered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, MaterialChecks};
+    ///
+    /// let fen: Fen = "4k3/8/8/8/8/8/PPPPPPPP/3BKB2 w - - 0 1".parse()?;
+    /// let err = fen.into_position::<Chess>(CastlingMode::Standard).unwrap_err();
+    /// let err = err
+    ///     .ignore_impossible_material_unless(MaterialChecks::all())
+    ///     .unwrap_err();
+    /// let pos = err.ignore_impossible_material_unless(
+    ///     MaterialChecks::TOO_MANY_PAWNS | MaterialChecks::UNACCOUNTED_PROMOTIONS,
+    /// )?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn ignore_impossible_material_unless(self, checks: MaterialChecks) -> Result<P, Self>
+    where
+        P: Position,
+    {
+        if self.pos.board().check_material(checks).is_empty() {
+            self.ignore_impossible_material()
+        } else {
+            Err(self)
+        }
+    }
+
     /// Get the position despite [`PositionErrorKinds::IMPOSSIBLE_CHECK`].
     /// Note that other programs may not work in such a situation.
     pub fn ignore_impossible_check(self) -> Result<P, Self> {
         self.ignore(PositionErrorKinds::IMPOSSIBLE_CHECK)
     }
 
+    /// Gets the reasons why the setup is not a legal position.
     pub fn kinds(&self) -> PositionErrorKinds {
         self.errors
     }
+
+    /// Gets the machine-readable kind of the error. See
+    /// [`PositionError::kinds()`] for the specific reasons.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidPosition
+    }
 }
 
 impl<P> fmt::Debug for PositionError<P> {
@@ -244,8 +480,12 @@ impl<P> fmt::Debug for PositionError<P> {
     }
 }
 
-impl<P> fmt::Display for PositionError<P> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl<P> PositionError<P> {
+    fn fmt_reasons(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        details: &dyn Fn(PositionErrorKinds) -> String,
+    ) -> fmt::Result {
         f.write_str("illegal position: ")?;
 
         let mut first = true;
@@ -255,6 +495,10 @@ impl<P> fmt::Display for PositionError<P> {
                     f.write_str(", ")?;
                 }
                 f.write_str(display)?;
+                let details = details(kind);
+                if !details.is_empty() {
+                    write!(f, " ({})", details)?;
+                }
                 first = false;
             }
             Ok(())
@@ -284,8 +528,134 @@ impl<P> fmt::Display for PositionError<P> {
     }
 }
 
+impl<P> fmt::Display for PositionError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_reasons(f, &|_| String::new())
+    }
+}
+
 impl<P> Error for PositionError<P> {}
 
+impl<P: Position> PositionError<P> {
+    /// Displays the error like [`Display`](fmt::Display), but also names
+    /// the offending pieces and squares, where possible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess};
+    ///
+    /// let err = "P3k3/8/8/8/8/8/8/4K3 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position::<Chess>(CastlingMode::Standard)
+    ///     .expect_err("illegal position");
+    /// assert_eq!(err.to_string(), "illegal position: pawns on backrank");
+    /// assert_eq!(
+    ///     err.detailed().to_string(),
+    ///     "illegal position: pawns on backrank (white pawn on a8)"
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn detailed(&self) -> impl fmt::Display + '_ {
+        DetailedPositionError(self)
+    }
+
+    fn details(&self, kind: PositionErrorKinds) -> String {
+        let board = self.pos.board();
+        match kind {
+            PositionErrorKinds::MISSING_KING => Color::ALL
+                .into_iter()
+                .filter(|&color| (board.kings() & board.by_color(color)).is_empty())
+                .map(|color| color.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            PositionErrorKinds::TOO_MANY_KINGS => describe_pieces(
+                board,
+                Color::ALL
+                    .into_iter()
+                    .map(|color| board.kings() & board.by_color(color))
+                    .filter(|kings| kings.more_than_one())
+                    .fold(Bitboard::EMPTY, |acc, kings| acc | kings),
+            ),
+            PositionErrorKinds::PAWNS_ON_BACKRANK => describe_pieces(
+                board,
+                Color::ALL.into_iter().fold(Bitboard::EMPTY, |acc, color| {
+                    // Like in Horde, a side without king may have pawns on
+                    // its own backrank.
+                    let backranks = if (board.kings() & board.by_color(color)).any() {
+                        Bitboard::BACKRANKS
+                    } else {
+                        Bitboard::from_rank((!color).backrank())
+                    };
+                    acc | (board.pawns() & board.by_color(color) & backranks)
+                }),
+            ),
+            PositionErrorKinds::OPPOSITE_CHECK => {
+                board
+                    .king_of(!self.pos.turn())
+                    .map_or(String::new(), |king| {
+                        describe_checkers(self.pos.king_attackers(
+                            king,
+                            self.pos.turn(),
+                            board.occupied(),
+                        ))
+                    })
+            }
+            PositionErrorKinds::IMPOSSIBLE_CHECK => describe_checkers(self.pos.checkers()),
+            PositionErrorKinds::IMPOSSIBLE_MATERIAL => Color::ALL
+                .into_iter()
+                .filter(|&color| !is_standard_material(board, color))
+                .map(|color| color.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::new(),
+        }
+    }
+}
+
+struct DetailedPositionError<'a, P>(&'a PositionError<P>);
+
+impl<P: Position> fmt::Display for DetailedPositionError<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_reasons(f, &|kind| self.0.details(kind))
+    }
+}
+
+fn describe_pieces(board: &Board, squares: Bitboard) -> String {
+    squares
+        .into_iter()
+        .filter_map(|sq| {
+            board.piece_at(sq).map(|piece| {
+                let role = match piece.role {
+                    Role::Pawn => "pawn",
+                    Role::Knight => "knight",
+                    Role::Bishop => "bishop",
+                    Role::Rook => "rook",
+                    Role::Queen => "queen",
+                    Role::King => "king",
+                };
+                format!("{} {} on {}", piece.color, role, sq)
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_checkers(checkers: Bitboard) -> String {
+    if checkers.is_empty() {
+        return String::new();
+    }
+    let squares = checkers
+        .into_iter()
+        .map(|sq| sq.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match checkers.count() {
+        1 => format!("checker on {}", squares),
+        n => format!("{} checkers: {}", n, squares),
+    }
+}
+
 /// Validate and set up a playable [`Position`]. All provided chess variants
 /// support this.
 pub trait FromSetup: Sized {
@@ -364,7 +734,7 @@ pub trait Position {
     /// Generates legal castling moves.
     fn castling_moves(&self, side: CastlingSide) -> MoveList {
         let mut moves = self.legal_moves();
-        moves.retain(|m| m.castling_side().map_or(false, |s| side == s));
+        moves.retain(|m| m.castling_side() == Some(side));
         moves
     }
 
@@ -382,6 +752,14 @@ pub trait Position {
         moves
     }
 
+    /// Generates moves that are not captures. Together with
+    /// [`Position::capture_moves()`], these are all legal moves.
+    fn quiet_moves(&self) -> MoveList {
+        let mut moves = self.legal_moves();
+        moves.retain(|m| !m.is_capture());
+        moves
+    }
+
     /// Generate promotion moves.
     fn promotion_moves(&self) -> MoveList {
         let mut moves = self.legal_moves();
@@ -389,6 +767,76 @@ pub trait Position {
         moves
     }
 
+    /// Generates moves that give check.
+    ///
+    /// The default implementation tests each legal move for a direct or
+    /// discovered attack on the king of the opponent.
+    fn checking_moves(&self) -> MoveList {
+        let mut moves = self.legal_moves();
+        match self.board().king_of(!self.turn()) {
+            Some(king) => moves.retain(|m| gives_check(self.board(), self.turn(), king, m)),
+            None => moves.clear(),
+        }
+        moves
+    }
+
+    /// Generates moves that get out of check. Empty if not in check.
+    fn evasion_moves(&self) -> MoveList {
+        if self.is_check() {
+            self.legal_moves()
+        } else {
+            MoveList::new()
+        }
+    }
+
+    /// Generates drops of pieces from the pocket, optionally restricted to
+    /// pieces of type `role`, to squares in `targets`. Only possible in
+    /// variants like Crazyhouse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "variant")]
+    /// # {
+    /// use shakmaty::{fen::Fen, variant::Crazyhouse, Bitboard, CastlingMode, Position, Rank, Role};
+    ///
+    /// let pos: Crazyhouse = "4k3/8/8/8/8/8/8/4K3[PN] w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    /// assert_eq!(pos.drop_moves(None, Bitboard::FULL).len(), 62 + 48);
+    /// assert_eq!(pos.drop_moves(Some(Role::Pawn), Bitboard::from(Rank::Eighth)).len(), 0);
+    /// assert_eq!(pos.drop_moves(Some(Role::Knight), Bitboard::from(Rank::Eighth)).len(), 7);
+    /// # }
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn drop_moves(&self, role: Option<Role>, targets: Bitboard) -> MoveList {
+        let mut moves = self.legal_moves();
+        moves.retain(|m| match *m {
+            Move::Put { role: r, to } => {
+                role.map_or(true, |role| role == r) && targets.contains(to)
+            }
+            _ => false,
+        });
+        moves
+    }
+
+    /// Tests if a move resets the halfmove clock, which counts towards
+    /// the 50-move rule (or 75-move rule).
+    ///
+    /// In standard chess, pawn moves and captures reset the halfmove clock.
+    /// The provided variants follow the same rule:
+    ///
+    /// * In Atomic chess, captures reset the clock, regardless of which
+    ///   pieces explode.
+    /// * In Crazyhouse, pawn drops reset the clock, but other drops do not.
+    /// * In Three-Check, checks do not reset the clock.
+    ///
+    /// [`play_unchecked()`](Position::play_unchecked) uses this to update
+    /// [`halfmoves()`](Position::halfmoves).
+    fn is_zeroing(&self, m: &Move) -> bool {
+        m.is_zeroing()
+    }
+
     /// Tests if a move is irreversible.
     ///
     /// In standard chess, pawn moves, captures, moves that destroy castling
@@ -446,11 +894,39 @@ pub trait Position {
     /// positions, is there a position with the same material configuration
     /// where `color` can win with a series of legal moves. If not, then
     /// `color` has insufficient winning material.
+    ///
+    /// Unlike [`is_insufficient_material()`](Position::is_insufficient_material),
+    /// this considers only one side, as needed to adjudicate timeouts: If a
+    /// player runs out of time, but the opponent has insufficient winning
+    /// material, the game is drawn. Each variant applies its own rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, Color, Position};
+    ///
+    /// let pos: Chess = "8/8/4k3/8/8/8/8/4K2Q w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// // White flagging against a bare king is a draw.
+    /// assert!(pos.has_insufficient_material(Color::Black));
+    /// assert!(!pos.has_insufficient_material(Color::White));
+    /// assert!(!pos.is_insufficient_material());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
     fn has_insufficient_material(&self, color: Color) -> bool;
 
     /// Tests special variant winning, losing and drawing conditions.
     fn variant_outcome(&self) -> Option<Outcome>;
 
+    /// Tests if the rules are symmetric under
+    /// [swapping colors](Setup::mirror()). This is the case for all
+    /// variants except Racing Kings and Horde.
+    fn is_color_symmetric(&self) -> bool {
+        true
+    }
+
     /// Plays a move. It is the callers responsibility to ensure the move is
     /// legal.
     ///
@@ -461,6 +937,28 @@ pub trait Position {
     /// [`Position::play()`] if you cannot guarantee legality.
     fn play_unchecked(&mut self, m: &Move);
 
+    /// Passes the turn to the opponent without moving, as used for null
+    /// move pruning. The en passant square is cleared, the halfmove clock is
+    /// incremented, and the fullmove number is incremented after Black.
+    ///
+    /// Unlike [`Position::swap_turn()`], this does not validate the
+    /// resulting position. It is the callers responsibility to ensure that
+    /// the side to move is not in check.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, Chess, EnPassantMode, Position};
+    ///
+    /// let mut pos = Chess::default();
+    /// pos.play_null_unchecked();
+    /// assert_eq!(
+    ///     Fen::from_position(pos, EnPassantMode::Legal).to_string(),
+    ///     "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 1 1"
+    /// );
+    /// ```
+    fn play_null_unchecked(&mut self);
+
     // Implementation note: Trait methods above this comment should be made
     // available for VariantPosition. The provided methods below this comment
     // are never overwritten in implementations, but for simplicity of use
@@ -487,17 +985,301 @@ pub trait Position {
         self.board().by_piece(role.of(!self.turn()))
     }
 
-    /// Tests a move for legality.
-    fn is_legal(&self, m: &Move) -> bool {
-        let moves = match *m {
-            Move::Normal { role, to, .. } | Move::Put { role, to } => self.san_candidates(role, to),
-            Move::EnPassant { to, .. } => self.san_candidates(Role::Pawn, to),
-            Move::Castle { king, rook } if king.file() < rook.file() => {
-                self.castling_moves(CastlingSide::KingSide)
-            }
-            Move::Castle { .. } => self.castling_moves(CastlingSide::QueenSide),
-        };
-        moves.contains(m)
+    /// Pieces of `attacker` that attack `square`.
+    ///
+    /// This only considers the usual piece movements on the current board.
+    /// To find the attacks that a king would have to deal with, according
+    /// to the rules of the variant, use [`Position::king_attackers()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Bitboard, Chess, Color, Position, Square};
+    ///
+    /// let pos = Chess::default();
+    /// assert_eq!(
+    ///     pos.attacks_to(Square::F3, Color::White),
+    ///     Bitboard::from(Square::E2) | Square::G1 | Square::G2
+    /// );
+    /// assert!(pos.is_attacked(Square::D6, Color::Black));
+    /// assert!(!pos.is_attacked(Square::D5, Color::Black));
+    /// ```
+    fn attacks_to(&self, square: Square, attacker: Color) -> Bitboard {
+        self.board()
+            .attacks_to(square, attacker, self.board().occupied())
+    }
+
+    /// Tests if `square` is attacked by any piece of `attacker`. See
+    /// [`Position::attacks_to()`].
+    fn is_attacked(&self, square: Square, attacker: Color) -> bool {
+        self.board()
+            .is_attacked(square, attacker, self.board().occupied())
+    }
+
+    /// Squares attacked by any piece of `attacker`. See
+    /// [`Position::attacks_to()`].
+    fn attacked_squares(&self, attacker: Color) -> Bitboard {
+        self.board()
+            .attacked_squares(attacker, self.board().occupied())
+    }
+
+    /// Pieces of `color` that are pinned to their own king by sliders of
+    /// the opponent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, Bitboard, CastlingMode, Chess, Color, Position, Square};
+    ///
+    /// let pos: Chess = "4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// assert_eq!(pos.pinned(Color::White), Bitboard::from(Square::E2));
+    /// assert_eq!(pos.pinners(Color::Black), Bitboard::from(Square::E7));
+    /// assert!(pos.pinned(Color::Black).is_empty());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn pinned(&self, color: Color) -> Bitboard {
+        self.board().king_of(color).map_or(Bitboard(0), |king| {
+            slider_blockers(self.board(), self.board().by_color(!color), king)
+                & self.board().by_color(color)
+        })
+    }
+
+    /// Sliders of `color` that pin pieces of the opponent to the
+    /// opponent's king. See [`Position::pinned()`].
+    fn pinners(&self, color: Color) -> Bitboard {
+        self.board().king_of(!color).map_or(Bitboard(0), |king| {
+            slider_pinners(self.board(), self.board().by_color(color), king)
+        })
+    }
+
+    /// Pieces of the side to move that would give discovered check when
+    /// moving off the line between one of our sliders and the opponent's
+    /// king.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, Bitboard, CastlingMode, Chess, Position, Square};
+    ///
+    /// let pos: Chess = "4k3/8/8/8/8/8/4N3/4R2K w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// assert_eq!(pos.discovered_check_candidates(), Bitboard::from(Square::E2));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn discovered_check_candidates(&self) -> Bitboard {
+        self.board()
+            .king_of(!self.turn())
+            .map_or(Bitboard(0), |king| {
+                slider_blockers(self.board(), self.us(), king) & self.us()
+            })
+    }
+
+    /// Tests a move for legality.
+    fn is_legal(&self, m: &Move) -> bool {
+        let moves = match *m {
+            Move::Normal { role, to, .. } | Move::Put { role, to } => self.san_candidates(role, to),
+            Move::EnPassant { to, .. } => self.san_candidates(Role::Pawn, to),
+            Move::Castle { king, rook } if king.file() < rook.file() => {
+                self.castling_moves(CastlingSide::KingSide)
+            }
+            Move::Castle { .. } => self.castling_moves(CastlingSide::QueenSide),
+        };
+        moves.contains(m)
+    }
+
+    /// Generates all legal moves in a stable order, that does not depend
+    /// on implementation details of the move generator.
+    ///
+    /// Moves are sorted by origin square, then by destination square
+    /// (the rook square for castling moves), then by promotion role.
+    /// Drops come last, sorted by role and then by destination square.
+    ///
+    /// The order of [`Position::legal_moves()`] is deterministic, but may
+    /// change between releases. Use this method for reproducible results,
+    /// for example with seeded random movers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{uci::Uci, CastlingMode, Chess, Position};
+    ///
+    /// let pos = Chess::default();
+    /// let moves = pos.sorted_legal_moves();
+    /// assert_eq!(Uci::from_move(&moves[0], CastlingMode::Standard).to_string(), "b1a3");
+    /// assert_eq!(Uci::from_move(&moves[19], CastlingMode::Standard).to_string(), "h2h4");
+    /// ```
+    fn sorted_legal_moves(&self) -> MoveList {
+        let mut moves = self.legal_moves();
+        moves.sort_unstable_by_key(|m| {
+            (
+                m.from().is_none(),
+                m.from(),
+                m.role(),
+                m.to(),
+                m.promotion(),
+            )
+        });
+        moves
+    }
+
+    /// Legal destination squares for the piece on `square`, taking into
+    /// account pins and checks. Empty if there is no piece of the side to
+    /// move on `square`.
+    ///
+    /// Castling moves contribute both the rook square and the target square
+    /// of the king.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Chess, Position, Square};
+    ///
+    /// let pos = Chess::default();
+    /// let dests = pos.safe_moves_from(Square::G1);
+    /// assert_eq!(dests.count(), 2);
+    /// assert!(dests.contains(Square::F3));
+    /// assert!(dests.contains(Square::H3));
+    /// ```
+    fn safe_moves_from(&self, square: Square) -> Bitboard {
+        let mut dests = Bitboard(0);
+        for m in self.legal_moves() {
+            if m.from() == Some(square) {
+                dests.add(m.to());
+                if let Some(side) = m.castling_side() {
+                    dests.add(side.king_to(self.turn()));
+                }
+            }
+        }
+        dests
+    }
+
+    /// Lazily generates legal moves in stages: captures first, then all
+    /// other moves.
+    ///
+    /// Useful for searches with early cutoffs, where the remaining moves
+    /// often do not need to be generated at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, Position};
+    ///
+    /// let pos: Chess = "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// let mut moves = pos.moves_iter();
+    /// assert!(moves.next().map_or(false, |m| m.is_capture()));
+    /// assert!(moves.all(|m| !m.is_capture()));
+    /// assert_eq!(pos.moves_iter().count(), pos.legal_moves().len());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn moves_iter(&self) -> MoveGen<'_, Self>
+    where
+        Self: Sized,
+    {
+        MoveGen::new(self)
+    }
+
+    /// Tests if `m`, queued as a premove by the side that is *not* to move,
+    /// will be legal after the opponent replies.
+    ///
+    /// Tries every legal reply. The premove is matched by piece, origin,
+    /// destination and promotion, but not by the captured piece, because
+    /// the reply can change what is on the target square.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Chess, Move, Position, PremoveLegality, Role, Square};
+    ///
+    /// let pos = Chess::default()
+    ///     .play(&Move::Normal {
+    ///         role: Role::Pawn,
+    ///         from: Square::E2,
+    ///         to: Square::E4,
+    ///         capture: None,
+    ///         promotion: None,
+    ///     })?;
+    ///
+    /// let premove = |from, to| Move::Normal {
+    ///     role: Role::Pawn,
+    ///     from,
+    ///     to,
+    ///     capture: None,
+    ///     promotion: None,
+    /// };
+    /// assert_eq!(pos.premove_legality(&premove(Square::D2, Square::D4)), PremoveLegality::Always);
+    /// assert_eq!(pos.premove_legality(&premove(Square::E4, Square::D5)), PremoveLegality::Sometimes);
+    /// assert_eq!(pos.premove_legality(&premove(Square::E4, Square::E6)), PremoveLegality::Never);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn premove_legality(&self, m: &Move) -> PremoveLegality
+    where
+        Self: Clone,
+    {
+        let key = |m: &Move| (m.role(), m.from(), m.to(), m.promotion());
+        let mut legal = false;
+        let mut illegal = false;
+        for reply in self.legal_moves() {
+            let mut child = self.clone();
+            child.play_unchecked(&reply);
+            if child.legal_moves().iter().any(|c| key(c) == key(m)) {
+                legal = true;
+            } else {
+                illegal = true;
+            }
+            if legal && illegal {
+                return PremoveLegality::Sometimes;
+            }
+        }
+        if legal {
+            PremoveLegality::Always
+        } else {
+            PremoveLegality::Never
+        }
+    }
+
+    /// Static exchange evaluation of a move, in centipawns.
+    ///
+    /// Plays out the sequence of captures on the target square, where both
+    /// sides always recapture with the least valuable piece, and may stop
+    /// capturing at any point. X-ray attackers are taken into account,
+    /// but pins are not. Uses the piece values 100 (pawn), 300 (knight and
+    /// bishop), 500 (rook) and 900 (queen).
+    ///
+    /// Drops are evaluated by the exchanges that can follow on the target
+    /// square, and castling moves are always `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Position};
+    ///
+    /// let pos: Chess = "4k3/8/3p4/4p3/8/5N2/8/4K3 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    /// let m = "f3e5".parse::<Uci>()?.to_move(&pos)?;
+    /// assert_eq!(pos.see(&m), 100 - 300);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn see(&self, m: &Move) -> i32 {
+        static_exchange(self.board(), self.turn(), Bitboard(0), m, false)
+    }
+
+    /// Like [`Position::see()`], but for drop variants like Crazyhouse.
+    ///
+    /// Each captured piece is not only lost by one side, but also goes to
+    /// the pocket of the other side, so captures count twice. Captured
+    /// [promoted](Position::promoted) pieces only count once plus the
+    /// value of a pawn, because they return to the pocket as pawns.
+    fn see_with_drops(&self, m: &Move) -> i32 {
+        static_exchange(self.board(), self.turn(), self.promoted(), m, true)
     }
 
     /// The en passant square, if it is the target of a
@@ -526,6 +1308,20 @@ pub trait Position {
     }
 
     /// Bitboard of pieces giving check.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, Bitboard, CastlingMode, Chess, Position, Square};
+    ///
+    /// let pos: Chess = "4k3/8/8/8/1b6/8/8/4K1n1 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// assert!(pos.is_check());
+    /// assert_eq!(pos.checkers(), Bitboard::from(Square::B4));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
     fn checkers(&self) -> Bitboard {
         self.our(Role::King).first().map_or(Bitboard(0), |king| {
             self.king_attackers(king, !self.turn(), self.board().occupied())
@@ -576,6 +1372,73 @@ pub trait Position {
         })
     }
 
+    /// The outcome of the game and the rule that decided it, or `None` if
+    /// the game is not over.
+    ///
+    /// The rules are applied in order: [variant end](Position::variant_outcome()),
+    /// checkmate, stalemate, insufficient material, and then as
+    /// configured in `rules`, the 75-move rule and fivefold repetition.
+    /// The repetition tracker should end with the hash of this position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{
+    ///     uci::Uci, zobrist::RepetitionTracker, Chess, EndReason, Outcome, OutcomeRules, Position,
+    /// };
+    ///
+    /// let mut pos = Chess::default();
+    /// let mut tracker = RepetitionTracker::new(&pos);
+    /// for _ in 0..4 {
+    ///     for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+    ///         let m = uci.parse::<Uci>()?.to_move(&pos)?;
+    ///         tracker.play_unchecked(&mut pos, &m);
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(pos.outcome_with(&OutcomeRules::default()), None);
+    ///
+    /// let rules = OutcomeRules {
+    ///     repetitions: Some(&tracker),
+    ///     ..OutcomeRules::default()
+    /// };
+    /// assert_eq!(
+    ///     pos.outcome_with(&rules),
+    ///     Some((Outcome::Draw, EndReason::FivefoldRepetition))
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn outcome_with(&self, rules: &OutcomeRules<'_>) -> Option<(Outcome, EndReason)> {
+        if let Some(outcome) = self.variant_outcome() {
+            return Some((outcome, EndReason::VariantEnd));
+        }
+        if self.is_checkmate() {
+            return Some((
+                Outcome::Decisive {
+                    winner: !self.turn(),
+                },
+                EndReason::Checkmate,
+            ));
+        }
+        Some((
+            Outcome::Draw,
+            if self.is_stalemate() {
+                EndReason::Stalemate
+            } else if self.is_insufficient_material() {
+                EndReason::InsufficientMaterial
+            } else if rules.seventy_five_moves && self.halfmoves() >= 150 {
+                EndReason::SeventyFiveMoves
+            } else if rules
+                .repetitions
+                .map_or(false, RepetitionTracker::is_fivefold)
+            {
+                EndReason::FivefoldRepetition
+            } else {
+                return None;
+            },
+        ))
+    }
+
     /// Plays a move.
     ///
     ///
@@ -613,6 +1476,212 @@ pub trait Position {
         setup.swap_turn();
         Self::from_setup(setup, mode)
     }
+
+    /// Maps the position to a canonical representative of all equivalent
+    /// positions under [swapping colors](Setup::mirror()) (if
+    /// [the rules are symmetric](Position::is_color_symmetric())) and
+    /// mirroring the board horizontally (if there are no castling rights).
+    ///
+    /// Useful to collapse symmetric duplicates, for example in databases.
+    /// White to move is preferred. Irrelevant en passant squares are
+    /// discarded. Halfmove clock and fullmove number are kept as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, Color, Position};
+    ///
+    /// let a: Chess = "8/8/8/2k5/8/8/3P4/4K3 b - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    /// let b: Chess = "3k4/4p3/8/8/5K2/8/8/8 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// assert_eq!(a.canonical().board(), b.canonical().board());
+    /// assert_eq!(a.canonical().turn(), Color::White);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    #[must_use]
+    fn canonical(&self) -> Self
+    where
+        Self: Clone + FromSetup,
+    {
+        let mode = self.castles().mode();
+        let setup = self.clone().into_setup(EnPassantMode::Legal);
+
+        let mut candidates = ArrayVec::<Setup, 4>::new();
+        if self.is_color_symmetric() {
+            let mut mirrored = setup.clone();
+            mirrored.mirror();
+            candidates.push(mirrored);
+        }
+        candidates.push(setup);
+        if candidates
+            .iter()
+            .all(|setup| setup.castling_rights.is_empty())
+        {
+            for i in 0..candidates.len() {
+                let mut flipped = candidates[i].clone();
+                flipped.flip_horizontal();
+                candidates.push(flipped);
+            }
+        }
+
+        candidates
+            .into_iter()
+            .filter_map(|setup| {
+                let key = canonical_key(&setup);
+                Self::from_setup(setup, mode).ok().map(|pos| (key, pos))
+            })
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+            .map_or_else(|| self.clone(), |(_, pos)| pos)
+    }
+
+    /// Tests if `other` is this position with the colors swapped and the
+    /// board [mirrored vertically](Setup::mirror()), so that the roles of
+    /// the players are reversed.
+    ///
+    /// Useful for testing evaluation symmetry, and to deduplicate
+    /// color-flipped training samples. Compares pieces, promoted pieces,
+    /// pockets, the side to move, castling rights, remaining checks, and
+    /// [legal](EnPassantMode::Legal) en passant squares. Halfmove clock and
+    /// fullmove number are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, Position};
+    ///
+    /// let a: Chess = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    /// let b: Chess = "rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// assert!(a.is_color_flipped_equal(&b));
+    /// assert!(!a.is_color_flipped_equal(&a));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    fn is_color_flipped_equal(&self, other: &Self) -> bool
+    where
+        Self: Sized,
+    {
+        self.board().is_mirror_of(other.board())
+            && self.promoted().flip_vertical() == other.promoted()
+            && self.pockets().map(|pockets| pockets.into_flipped()) == other.pockets().copied()
+            && self.turn() != other.turn()
+            && self.castles().castling_rights().flip_vertical() == other.castles().castling_rights()
+            && self.remaining_checks().map(|checks| checks.into_flipped())
+                == other.remaining_checks().copied()
+            && self.legal_ep_square().map(Square::flip_vertical) == other.legal_ep_square()
+    }
+}
+
+fn see_value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 100,
+        Role::Knight | Role::Bishop => 300,
+        Role::Rook => 500,
+        Role::Queen => 900,
+        Role::King => 0,
+    }
+}
+
+fn static_exchange(board: &Board, turn: Color, promoted: Bitboard, m: &Move, drops: bool) -> i32 {
+    // Value of capturing the piece on a square, given its role and if it
+    // was promoted.
+    let capture_value = |role: Role, promoted: bool| {
+        if !drops {
+            see_value(role)
+        } else if promoted {
+            see_value(role) + see_value(Role::Pawn)
+        } else {
+            2 * see_value(role)
+        }
+    };
+
+    let to = m.to();
+    let mut occupied = board.occupied();
+    let (mut gain, mut on_square) = match *m {
+        Move::Normal {
+            role,
+            from,
+            capture,
+            promotion,
+            ..
+        } => {
+            occupied.discard(from);
+            let captured =
+                capture.map_or(0, |captured| capture_value(captured, promoted.contains(to)));
+            match promotion {
+                Some(promotion) => (
+                    captured + see_value(promotion) - see_value(Role::Pawn),
+                    capture_value(promotion, true),
+                ),
+                None => (captured, capture_value(role, promoted.contains(from))),
+            }
+        }
+        Move::EnPassant { from, .. } => {
+            occupied.discard(from);
+            occupied.discard(Square::from_coords(to.file(), from.rank()));
+            (
+                capture_value(Role::Pawn, false),
+                capture_value(Role::Pawn, false),
+            )
+        }
+        Move::Put { role, .. } => {
+            occupied.add(to);
+            (0, capture_value(role, false))
+        }
+        Move::Castle { .. } => return 0,
+    };
+
+    let mut gains: ArrayVec<i32, 33> = ArrayVec::new();
+    gains.push(gain);
+    let mut color = !turn;
+    while !gains.is_full() {
+        let (sq, role) = match board.least_valuable_attacker(to, color, occupied) {
+            Some(attacker) => attacker,
+            None => break,
+        };
+        if role == Role::King && (board.attacks_to(to, !color, occupied) & occupied).any() {
+            break;
+        }
+        gain = on_square - gain;
+        gains.push(gain);
+        occupied.discard(sq);
+        on_square = capture_value(role, promoted.contains(sq));
+        color = !color;
+    }
+
+    while let Some(last) = gains.pop() {
+        match gains.last_mut() {
+            Some(previous) => *previous = -max(-*previous, last),
+            None => return last,
+        }
+    }
+    0
+}
+
+fn canonical_key(setup: &Setup) -> (bool, [u64; 9], Option<Square>) {
+    let board = &setup.board;
+    (
+        setup.turn.is_black(),
+        [
+            board.white().0,
+            board.black().0,
+            board.pawns().0,
+            board.knights().0,
+            board.bishops().0,
+            board.rooks().0,
+            board.queens().0,
+            board.kings().0,
+            setup.promoted.0,
+        ],
+        setup.ep_square,
+    )
 }
 
 /// A standard Chess position.
@@ -627,6 +1696,111 @@ pub struct Chess {
 }
 
 impl Chess {
+    /// The standard starting position, with `giver` giving `odds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Chess, Color, Odds, Position};
+    ///
+    /// let pos = Chess::odds(Odds::PawnAndMove, Color::Black);
+    /// assert_eq!(pos.turn(), Color::White);
+    /// assert_eq!(pos.board().pawns().count(), 15);
+    /// ```
+    pub fn odds(odds: Odds, giver: Color) -> Chess {
+        Chess::from_setup(Setup::odds(odds, giver), CastlingMode::Standard)
+            .expect("odds position is legal")
+    }
+
+    /// Plays a move, like [`Position::play_unchecked()`], and returns the
+    /// information needed to take it back with [`Chess::undo()`].
+    ///
+    /// Unlike cloning the position, only the move and the irreversible
+    /// parts of the state are recorded, and taking back the move only
+    /// touches the affected squares.
+    ///
+    /// # Panics
+    ///
+    /// Illegal moves can corrupt the state of the position and may
+    /// (or may not) panic or cause panics on future calls.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{uci::Uci, Chess, Position};
+    ///
+    /// let mut pos = Chess::default();
+    /// let m = "g1f3".parse::<Uci>()?.to_move(&pos)?;
+    ///
+    /// let undo = pos.play_and_record(&m);
+    /// assert_ne!(pos, Chess::default());
+    ///
+    /// pos.undo(undo);
+    /// assert_eq!(pos, Chess::default());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn play_and_record(&mut self, m: &Move) -> Undo {
+        let undo = Undo {
+            m: m.clone(),
+            castles: self.castles.clone(),
+            ep_square: self.ep_square,
+            halfmoves: self.halfmoves,
+        };
+        self.play_unchecked(m);
+        undo
+    }
+
+    /// Takes back the last move played with [`Chess::play_and_record()`].
+    ///
+    /// # Panics
+    ///
+    /// Undoing anything but the most recent move (that has not already
+    /// been taken back) corrupts the state of the position and may (or may
+    /// not) panic or cause panics on future calls.
+    pub fn undo(&mut self, undo: Undo) {
+        let color = !self.turn;
+
+        match undo.m {
+            Move::Normal {
+                role,
+                from,
+                capture,
+                to,
+                ..
+            } => {
+                self.board.discard_piece_at(to);
+                self.board.set_piece_at(from, role.of(color));
+                if let Some(capture) = capture {
+                    self.board.set_piece_at(to, capture.of(!color));
+                }
+            }
+            Move::EnPassant { from, to } => {
+                self.board.discard_piece_at(to);
+                self.board.set_piece_at(from, color.pawn());
+                self.board
+                    .set_piece_at(Square::from_coords(to.file(), from.rank()), (!color).pawn());
+            }
+            Move::Castle { king, rook } => {
+                let side = CastlingSide::from_queen_side(rook < king);
+                self.board
+                    .discard_piece_at(Square::from_coords(side.rook_to_file(), rook.rank()));
+                self.board
+                    .discard_piece_at(Square::from_coords(side.king_to_file(), king.rank()));
+                self.board.set_piece_at(king, color.king());
+                self.board.set_piece_at(rook, color.rook());
+            }
+            Move::Put { to, .. } => self.board.discard_piece_at(to),
+        }
+
+        if color.is_black() {
+            self.fullmoves = NonZeroU32::new(self.fullmoves.get() - 1).unwrap_or(self.fullmoves);
+        }
+        self.turn = color;
+        self.castles = undo.castles;
+        self.ep_square = undo.ep_square;
+        self.halfmoves = undo.halfmoves;
+    }
+
     #[cfg(feature = "variant")]
     fn gives_check(&self, m: &Move) -> bool {
         let mut pos = self.clone();
@@ -780,6 +1954,7 @@ impl Position for Chess {
     }
 
     fn play_unchecked(&mut self, m: &Move) {
+        let zeroing = self.is_zeroing(m);
         do_move(
             &mut self.board,
             &mut Bitboard(0),
@@ -788,10 +1963,20 @@ impl Position for Chess {
             &mut self.ep_square,
             &mut self.halfmoves,
             &mut self.fullmoves,
+            zeroing,
             m,
         );
     }
 
+    fn play_null_unchecked(&mut self) {
+        do_null_move(
+            &mut self.turn,
+            &mut self.ep_square,
+            &mut self.halfmoves,
+            &mut self.fullmoves,
+        );
+    }
+
     fn legal_moves(&self) -> MoveList {
         let mut moves = MoveList::new();
 
@@ -843,6 +2028,109 @@ impl Position for Chess {
         moves
     }
 
+    fn capture_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+
+        let king = self
+            .board()
+            .king_of(self.turn())
+            .expect("king in standard chess");
+
+        let has_ep = gen_en_passant(self.board(), self.turn(), self.ep_square, &mut moves);
+
+        let checkers = self.checkers();
+        if checkers.is_empty() {
+            let target = self.them();
+            gen_non_king(self, target, &mut moves);
+            gen_safe_king(self, king, target, &mut moves);
+        } else {
+            evasions(self, king, checkers, &mut moves);
+            moves.retain(|m| m.is_capture());
+        }
+
+        let blockers = slider_blockers(self.board(), self.them(), king);
+        if blockers.any() || has_ep {
+            moves.retain(|m| is_safe(self, king, m, blockers));
+        }
+
+        moves
+    }
+
+    fn quiet_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+
+        let king = self
+            .board()
+            .king_of(self.turn())
+            .expect("king in standard chess");
+
+        let checkers = self.checkers();
+        if checkers.is_empty() {
+            let target = !self.board().occupied();
+            gen_non_king(self, target, &mut moves);
+            gen_safe_king(self, king, target, &mut moves);
+            gen_castling_moves(
+                self,
+                &self.castles,
+                king,
+                CastlingSide::KingSide,
+                &mut moves,
+            );
+            gen_castling_moves(
+                self,
+                &self.castles,
+                king,
+                CastlingSide::QueenSide,
+                &mut moves,
+            );
+        } else {
+            evasions(self, king, checkers, &mut moves);
+            moves.retain(|m| !m.is_capture());
+        }
+
+        let blockers = slider_blockers(self.board(), self.them(), king);
+        if blockers.any() {
+            moves.retain(|m| is_safe(self, king, m, blockers));
+        }
+
+        moves
+    }
+
+    fn checking_moves(&self) -> MoveList {
+        let mut moves = self.legal_moves();
+        let king = self
+            .board()
+            .king_of(!self.turn())
+            .expect("king in standard chess");
+        let blockers = slider_blockers(self.board(), self.us(), king) & self.us();
+        moves.retain(|m| is_checking(self, king, m, blockers));
+        moves
+    }
+
+    fn evasion_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+
+        let checkers = self.checkers();
+        if checkers.is_empty() {
+            return moves;
+        }
+
+        let king = self
+            .board()
+            .king_of(self.turn())
+            .expect("king in standard chess");
+
+        let has_ep = gen_en_passant(self.board(), self.turn(), self.ep_square, &mut moves);
+        evasions(self, king, checkers, &mut moves);
+
+        let blockers = slider_blockers(self.board(), self.them(), king);
+        if blockers.any() || has_ep {
+            moves.retain(|m| is_safe(self, king, m, blockers));
+        }
+
+        moves
+    }
+
     fn en_passant_moves(&self) -> MoveList {
         let mut moves = MoveList::new();
 
@@ -1093,6 +2381,7 @@ pub(crate) mod variant {
         }
 
         fn play_unchecked(&mut self, m: &Move) {
+            let zeroing = self.is_zeroing(m);
             do_move(
                 &mut self.board,
                 &mut Bitboard(0),
@@ -1101,6 +2390,7 @@ pub(crate) mod variant {
                 &mut self.ep_square,
                 &mut self.halfmoves,
                 &mut self.fullmoves,
+                zeroing,
                 m,
             );
 
@@ -1129,6 +2419,15 @@ pub(crate) mod variant {
             }
         }
 
+        fn play_null_unchecked(&mut self) {
+            do_null_move(
+                &mut self.turn,
+                &mut self.ep_square,
+                &mut self.halfmoves,
+                &mut self.fullmoves,
+            );
+        }
+
         fn legal_moves(&self) -> MoveList {
             let mut moves = MoveList::new();
 
@@ -1166,7 +2465,17 @@ pub(crate) mod variant {
                     false
                 }
             });
-
+
+            moves
+        }
+
+        fn checking_moves(&self) -> MoveList {
+            let mut moves = self.legal_moves();
+            moves.retain(|m| {
+                let mut after = self.clone();
+                after.play_unchecked(m);
+                after.is_check()
+            });
             moves
         }
 
@@ -1356,6 +2665,7 @@ pub(crate) mod variant {
         }
 
         fn play_unchecked(&mut self, m: &Move) {
+            let zeroing = self.is_zeroing(m);
             do_move(
                 &mut self.board,
                 &mut Bitboard(0),
@@ -1364,10 +2674,20 @@ pub(crate) mod variant {
                 &mut self.ep_square,
                 &mut self.halfmoves,
                 &mut self.fullmoves,
+                zeroing,
                 m,
             );
         }
 
+        fn play_null_unchecked(&mut self) {
+            do_null_move(
+                &mut self.turn,
+                &mut self.ep_square,
+                &mut self.halfmoves,
+                &mut self.fullmoves,
+            );
+        }
+
         fn en_passant_moves(&self) -> MoveList {
             let mut moves = MoveList::new();
             gen_en_passant(self.board(), self.turn, self.ep_square, &mut moves);
@@ -1396,6 +2716,10 @@ pub(crate) mod variant {
             moves
         }
 
+        fn checking_moves(&self) -> MoveList {
+            MoveList::new()
+        }
+
         fn king_attackers(
             &self,
             _square: Square,
@@ -1492,6 +2816,10 @@ pub(crate) mod variant {
             self.chess.play_unchecked(m);
         }
 
+        fn play_null_unchecked(&mut self) {
+            self.chess.play_null_unchecked();
+        }
+
         fn legal_moves(&self) -> MoveList {
             if self.is_variant_end() {
                 MoveList::new()
@@ -1617,6 +2945,10 @@ pub(crate) mod variant {
             }
         }
 
+        fn play_null_unchecked(&mut self) {
+            self.chess.play_null_unchecked();
+        }
+
         fn legal_moves(&self) -> MoveList {
             if self.is_variant_end() {
                 MoveList::new()
@@ -1802,6 +3134,8 @@ pub(crate) mod variant {
         }
 
         fn play_unchecked(&mut self, m: &Move) {
+            let zeroing = self.is_zeroing(m);
+
             match *m {
                 Move::Normal {
                     capture: Some(capture),
@@ -1833,25 +3167,38 @@ pub(crate) mod variant {
                 &mut self.chess.ep_square,
                 &mut self.chess.halfmoves,
                 &mut self.chess.fullmoves,
+                zeroing,
                 m,
             );
         }
 
+        fn play_null_unchecked(&mut self) {
+            self.chess.play_null_unchecked();
+        }
+
         fn legal_moves(&self) -> MoveList {
             let mut moves = self.chess.legal_moves();
 
+            moves.extend(self.drop_moves(None, Bitboard::FULL));
+            moves
+        }
+
+        fn drop_moves(&self, role: Option<Role>, targets: Bitboard) -> MoveList {
+            let mut moves = MoveList::new();
+
             let pocket = self.our_pocket();
-            let targets = self.legal_put_squares();
+            let targets = targets & self.legal_put_squares();
+            let has = |r: Role| role.map_or(true, |role| role == r) && *pocket.get(r) > 0;
 
             for to in targets {
                 for role in [Role::Knight, Role::Bishop, Role::Rook, Role::Queen] {
-                    if *pocket.get(role) > 0 {
+                    if has(role) {
                         moves.push(Move::Put { role, to });
                     }
                 }
             }
 
-            if pocket.pawn > 0 {
+            if has(Role::Pawn) {
                 for to in targets & !Bitboard::BACKRANKS {
                     moves.push(Move::Put {
                         role: Role::Pawn,
@@ -2038,6 +3385,7 @@ pub(crate) mod variant {
         }
 
         fn play_unchecked(&mut self, m: &Move) {
+            let zeroing = self.is_zeroing(m);
             do_move(
                 &mut self.board,
                 &mut Bitboard(0),
@@ -2046,10 +3394,20 @@ pub(crate) mod variant {
                 &mut None,
                 &mut self.halfmoves,
                 &mut self.fullmoves,
+                zeroing,
                 m,
             );
         }
 
+        fn play_null_unchecked(&mut self) {
+            do_null_move(
+                &mut self.turn,
+                &mut None,
+                &mut self.halfmoves,
+                &mut self.fullmoves,
+            );
+        }
+
         fn legal_moves(&self) -> MoveList {
             let mut moves = MoveList::new();
 
@@ -2126,6 +3484,10 @@ pub(crate) mod variant {
                 None
             }
         }
+
+        fn is_color_symmetric(&self) -> bool {
+            false
+        }
     }
 
     /// A Horde position.
@@ -2261,6 +3623,7 @@ pub(crate) mod variant {
         }
 
         fn play_unchecked(&mut self, m: &Move) {
+            let zeroing = self.is_zeroing(m);
             do_move(
                 &mut self.board,
                 &mut Bitboard(0),
@@ -2269,10 +3632,20 @@ pub(crate) mod variant {
                 &mut self.ep_square,
                 &mut self.halfmoves,
                 &mut self.fullmoves,
+                zeroing,
                 m,
             );
         }
 
+        fn play_null_unchecked(&mut self) {
+            do_null_move(
+                &mut self.turn,
+                &mut self.ep_square,
+                &mut self.halfmoves,
+                &mut self.fullmoves,
+            );
+        }
+
         fn legal_moves(&self) -> MoveList {
             let mut moves = MoveList::new();
 
@@ -2573,6 +3946,10 @@ pub(crate) mod variant {
                 None
             }
         }
+
+        fn is_color_symmetric(&self) -> bool {
+            false
+        }
     }
 
     fn add_king_promotions(moves: &mut MoveList) {
@@ -2610,12 +3987,13 @@ fn do_move(
     ep_square: &mut Option<EnPassant>,
     halfmoves: &mut u32,
     fullmoves: &mut NonZeroU32,
+    zeroing: bool,
     m: &Move,
 ) {
     let color = *turn;
     ep_square.take();
 
-    *halfmoves = if m.is_zeroing() {
+    *halfmoves = if zeroing {
         0
     } else {
         halfmoves.saturating_add(1)
@@ -2682,6 +4060,21 @@ fn do_move(
     *turn = !color;
 }
 
+fn do_null_move(
+    turn: &mut Color,
+    ep_square: &mut Option<EnPassant>,
+    halfmoves: &mut u32,
+    fullmoves: &mut NonZeroU32,
+) {
+    ep_square.take();
+    *halfmoves = halfmoves.saturating_add(1);
+    if turn.is_black() {
+        *fullmoves =
+            NonZeroU32::new(fullmoves.get().saturating_add(1)).expect("fullmoves stay non-zero");
+    }
+    *turn = !*turn;
+}
+
 fn validate<P: Position>(pos: &P, ep_square: Option<EnPassant>) -> PositionErrorKinds {
     let mut errors = PositionErrorKinds::empty();
 
@@ -2751,7 +4144,7 @@ fn validate<P: Position>(pos: &P, ep_square: Option<EnPassant>) -> PositionError
     errors
 }
 
-fn is_standard_material(board: &Board, color: Color) -> bool {
+pub(crate) fn is_standard_material(board: &Board, color: Color) -> bool {
     let our = board.by_color(color);
     let promoted_pieces = (board.queens() & our).count().saturating_sub(1)
         + (board.rooks() & our).count().saturating_sub(2)
@@ -2775,10 +4168,9 @@ fn gen_non_king<P: Position>(pos: &P, target: Bitboard, moves: &mut MoveList) {
 
 fn gen_safe_king<P: Position>(pos: &P, king: Square, target: Bitboard, moves: &mut MoveList) {
     for to in attacks::king_attacks(king) & target {
-        if pos
+        if !pos
             .board()
-            .attacks_to(to, !pos.turn(), pos.board().occupied())
-            .is_empty()
+            .is_attacked(to, !pos.turn(), pos.board().occupied())
         {
             moves.push(Move::Normal {
                 role: Role::King,
@@ -2889,6 +4281,7 @@ enum KnightTag {}
 enum BishopTag {}
 enum RookTag {}
 enum QueenTag {}
+#[cfg(feature = "variant")]
 enum KingTag {}
 
 impl Stepper for KnightTag {
@@ -2898,6 +4291,7 @@ impl Stepper for KnightTag {
     }
 }
 
+#[cfg(feature = "variant")]
 impl Stepper for KingTag {
     const ROLE: Role = Role::King;
     fn attacks(from: Square) -> Bitboard {
@@ -3061,6 +4455,23 @@ fn slider_blockers(board: &Board, enemy: Bitboard, king: Square) -> Bitboard {
     blockers
 }
 
+fn slider_pinners(board: &Board, enemy: Bitboard, king: Square) -> Bitboard {
+    let snipers = (attacks::rook_attacks(king, Bitboard(0)) & board.rooks_and_queens())
+        | (attacks::bishop_attacks(king, Bitboard(0)) & board.bishops_and_queens());
+
+    let mut pinners = Bitboard(0);
+
+    for sniper in snipers & enemy {
+        let b = attacks::between(king, sniper) & board.occupied();
+
+        if b.single_square().is_some() && (b & enemy).is_empty() {
+            pinners.add(sniper);
+        }
+    }
+
+    pinners
+}
+
 fn is_safe<P: Position>(pos: &P, king: Square, m: &Move, blockers: Bitboard) -> bool {
     match *m {
         Move::Normal { from, to, .. } => {
@@ -3085,6 +4496,53 @@ fn is_safe<P: Position>(pos: &P, king: Square, m: &Move, blockers: Bitboard) ->
     }
 }
 
+fn is_checking<P: Position>(pos: &P, king: Square, m: &Move, blockers: Bitboard) -> bool {
+    match *m {
+        Move::Normal {
+            role,
+            from,
+            to,
+            promotion,
+            ..
+        } => {
+            let occupied = pos.board().occupied().without(from).with(to);
+            attacks::attacks(to, promotion.unwrap_or(role).of(pos.turn()), occupied).contains(king)
+                || (blockers.contains(from) && !attacks::aligned(from, to, king))
+        }
+        _ => gives_check(pos.board(), pos.turn(), king, m),
+    }
+}
+
+fn gives_check(board: &Board, turn: Color, king: Square, m: &Move) -> bool {
+    let mut board = board.clone();
+    match *m {
+        Move::Normal {
+            role,
+            from,
+            to,
+            promotion,
+            ..
+        } => {
+            board.discard_piece_at(from);
+            board.set_piece_at(to, promotion.unwrap_or(role).of(turn));
+        }
+        Move::EnPassant { from, to } => {
+            board.discard_piece_at(from);
+            board.discard_piece_at(Square::from_coords(to.file(), from.rank()));
+            board.set_piece_at(to, turn.pawn());
+        }
+        Move::Castle { king: from, rook } => {
+            let side = CastlingSide::from_queen_side(rook < from);
+            board.discard_piece_at(from);
+            board.discard_piece_at(rook);
+            board.set_piece_at(side.king_to(turn), turn.king());
+            board.set_piece_at(side.rook_to(turn), turn.rook());
+        }
+        Move::Put { role, to } => board.set_piece_at(to, role.of(turn)),
+    }
+    board.attacks_to(king, turn, board.occupied()).any()
+}
+
 fn filter_san_candidates(role: Role, to: Square, moves: &mut MoveList) {
     moves.retain(|m| match *m {
         Move::Normal { role: r, to: t, .. } | Move::Put { role: r, to: t } => to == t && role == r,
@@ -3096,7 +4554,7 @@ fn filter_san_candidates(role: Role, to: Square, moves: &mut MoveList) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::fen::Fen;
+    use crate::{fen::Fen, uci::Uci};
 
     struct _AssertObjectSafe(Box<dyn Position>);
 
@@ -3107,6 +4565,35 @@ mod tests {
             .expect("legal position")
     }
 
+    #[test]
+    fn test_position_error_display() {
+        for (fen, expected) in [
+            (
+                "P3k3/8/8/8/8/8/8/4K2p w - - 0 1",
+                "illegal position: pawns on backrank (black pawn on h1, white pawn on a8)",
+            ),
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                "illegal position: missing king (black)",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4RK2 w - - 0 1",
+                "illegal position: opposite check (checker on e1)",
+            ),
+            (
+                "3k4/2QQQ3/8/8/8/8/PPPPPPPP/QQQQQ1K1 b - - 0 1",
+                "illegal position: impossible check (3 checkers: c7, d7, e7), impossible material (white)",
+            ),
+        ] {
+            let err = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position::<Chess>(CastlingMode::Standard)
+                .expect_err("illegal position");
+            assert_eq!(err.detailed().to_string(), expected);
+        }
+    }
+
     #[test]
     fn test_most_known_legals() {
         let pos: Chess = setup_fen("R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1");
@@ -3133,6 +4620,233 @@ mod tests {
         assert_eq!(moves.len(), 1);
     }
 
+    #[test]
+    fn test_safe_moves_from() {
+        // Rook pinned along the e-file.
+        let pos: Chess = setup_fen("4r1k1/8/8/8/8/8/4R3/R3K3 w Q - 0 1");
+        assert_eq!(
+            pos.safe_moves_from(Square::E2),
+            attacks::between(Square::E1, Square::E8).without(Square::E2) | Square::E8
+        );
+        assert!(pos.safe_moves_from(Square::A1).contains(Square::D1));
+        assert!(pos.safe_moves_from(Square::E1).contains(Square::C1));
+        assert!(pos.safe_moves_from(Square::E1).contains(Square::A1));
+        assert!(pos.safe_moves_from(Square::E8).is_empty());
+        assert!(pos.safe_moves_from(Square::H4).is_empty());
+    }
+
+    #[test]
+    fn test_moves_iter() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/8/2k5/3Pp3/8/8/4K2R b K d3 0 1",
+            "4k3/8/8/1b6/8/3n4/8/4K3 w - - 0 1",
+        ] {
+            let pos: Chess = setup_fen(fen);
+            let legals = pos.legal_moves();
+            let mut captures = legals.clone();
+            captures.retain(|m| m.is_capture());
+            assert_eq!(pos.capture_moves().len(), captures.len(), "{}", fen);
+            let mut quiets = legals.clone();
+            quiets.retain(|m| !m.is_capture());
+            assert_eq!(pos.quiet_moves(), quiets, "{}", fen);
+
+            let mut moves = pos.moves_iter();
+            let staged: Vec<Move> = moves.by_ref().take(captures.len()).collect();
+            assert!(staged.iter().all(|m| m.is_capture()), "{}", fen);
+            assert!(!moves.is_quiet_stage());
+            let staged: Vec<Move> = staged.into_iter().chain(moves).collect();
+            assert_eq!(staged.len(), legals.len(), "{}", fen);
+            assert!(legals.iter().all(|m| staged.contains(m)), "{}", fen);
+        }
+    }
+
+    fn assert_checks_and_evasions<P: Position + FromSetup + Clone>(fen: &str) {
+        let pos: P = setup_fen(fen);
+        let mut expected = pos.legal_moves();
+        expected.retain(|m| {
+            let mut after = pos.clone();
+            after.play_unchecked(m);
+            after.is_check()
+        });
+        let checking = pos.checking_moves();
+        assert_eq!(checking.len(), expected.len(), "{}", fen);
+        assert!(expected.iter().all(|m| checking.contains(m)), "{}", fen);
+
+        let evasions = pos.evasion_moves();
+        if pos.is_check() {
+            assert_eq!(evasions.len(), pos.legal_moves().len(), "{}", fen);
+            assert!(evasions.iter().all(|m| pos.is_legal(m)), "{}", fen);
+        } else {
+            assert!(evasions.is_empty(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_checking_and_evasion_moves() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/8/2k5/3Pp3/8/8/4K2R b K d3 0 1",
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "4k3/8/8/1b6/8/3n4/8/4K3 w - - 0 1",
+            "3k4/8/8/2KPp2r/8/8/8/8 w - e6 0 1",
+        ] {
+            assert_checks_and_evasions::<Chess>(fen);
+        }
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_checking_moves_variants() {
+        use super::variant::{Antichess, Atomic, Crazyhouse};
+
+        assert_checks_and_evasions::<Atomic>("4k3/8/8/4K3/8/8/8/R7 w - - 0 1");
+        assert_checks_and_evasions::<Atomic>("r3k3/8/8/8/8/4p3/3P4/4K3 w - - 0 1");
+        assert_checks_and_evasions::<Crazyhouse>("4k3/8/8/8/8/8/8/4K3[NRb] w - - 0 1");
+        assert_checks_and_evasions::<Antichess>("4k3/8/8/8/8/8/8/4R3 w - - 0 1");
+    }
+
+    #[test]
+    fn test_premove_legality() {
+        let castle = Move::Castle {
+            king: Square::E1,
+            rook: Square::H1,
+        };
+        let pos: Chess = setup_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 1");
+        assert_eq!(pos.premove_legality(&castle), PremoveLegality::Always);
+        let pos: Chess = setup_fen("r3k3/8/8/8/8/8/8/4K2R b K - 0 1");
+        assert_eq!(pos.premove_legality(&castle), PremoveLegality::Sometimes);
+
+        // No replies after checkmate.
+        let pos: Chess = setup_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+        let m = Move::Normal {
+            role: Role::King,
+            from: Square::G1,
+            to: Square::F1,
+            capture: None,
+            promotion: None,
+        };
+        assert_eq!(pos.premove_legality(&m), PremoveLegality::Never);
+    }
+
+    #[test]
+    fn test_see() {
+        fn see<P: Position + FromSetup>(fen: &str, uci: &str, drops: bool) -> i32 {
+            let pos: P = setup_fen(fen);
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal move");
+            if drops {
+                pos.see_with_drops(&m)
+            } else {
+                pos.see(&m)
+            }
+        }
+
+        // Undefended, and defended behind a battery.
+        assert_eq!(
+            see::<Chess>("4k3/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5", false),
+            100
+        );
+        assert_eq!(
+            see::<Chess>("4k3/4r3/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5", false),
+            100
+        );
+        assert_eq!(
+            see::<Chess>("4k3/4r3/4r3/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5", false),
+            -400
+        );
+        assert_eq!(
+            see::<Chess>(
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5",
+                false
+            ),
+            -200
+        );
+
+        // King can only recapture undefended pieces.
+        assert_eq!(
+            see::<Chess>("4k3/3p4/8/8/8/8/3Q4/3RK3 w - - 0 1", "d2d7", false),
+            100
+        );
+        assert_eq!(
+            see::<Chess>("4k3/3p4/8/8/8/8/3Q4/4K3 w - - 0 1", "d2d7", false),
+            -800
+        );
+
+        // Promotions and quiet moves.
+        assert_eq!(
+            see::<Chess>("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q", false),
+            800
+        );
+        assert_eq!(
+            see::<Chess>("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q", false),
+            -100
+        );
+        assert_eq!(
+            see::<Chess>("4k3/8/3p4/8/8/8/8/4KN2 w - - 0 1", "f1e3", false),
+            0
+        );
+
+        // Captures count twice with drops.
+        assert_eq!(
+            see::<Chess>("4k3/8/3p4/4p3/8/5N2/8/4K3 w - - 0 1", "f3e5", true),
+            -400
+        );
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_see_crazyhouse() {
+        use super::variant::Crazyhouse;
+
+        let pos: Crazyhouse = setup_fen("4k3/3p4/4Q~3/8/8/8/8/4K3[N] b - - 0 1");
+        let m = "d7e6"
+            .parse::<Uci>()
+            .expect("valid uci")
+            .to_move(&pos)
+            .expect("legal");
+        assert_eq!(pos.see(&m), 900);
+        assert_eq!(pos.see_with_drops(&m), 1000);
+
+        let pos: Crazyhouse = setup_fen("4k3/8/3p4/8/8/8/8/4K3[N] w - - 0 1");
+        let m = "N@e5"
+            .parse::<Uci>()
+            .expect("valid uci")
+            .to_move(&pos)
+            .expect("legal");
+        assert_eq!(pos.see(&m), -300);
+        assert_eq!(pos.see_with_drops(&m), -600);
+    }
+
+    #[test]
+    fn test_sorted_legal_moves() {
+        let pos: Chess = setup_fen("r3k3/1P6/8/8/8/8/8/R3K2R w KQq - 0 1");
+        let moves = pos
+            .sorted_legal_moves()
+            .iter()
+            .map(|m| Uci::from_chess960(m).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(moves.len(), pos.legal_moves().len());
+        assert_eq!(
+            &moves[..],
+            &[
+                "a1b1", "a1c1", "a1d1", "a1a2", "a1a3", "a1a4", "a1a5", "a1a6", "a1a7", "a1a8",
+                "e1a1", "e1d1", "e1f1", "e1h1", "e1d2", "e1e2", "e1f2", "h1f1", "h1g1", "h1h2",
+                "h1h3", "h1h4", "h1h5", "h1h6", "h1h7", "h1h8", "b7a8n", "b7a8b", "b7a8r", "b7a8q",
+                "b7b8n", "b7b8b", "b7b8r", "b7b8q",
+            ][..]
+        );
+    }
+
     #[test]
     fn test_promotion() {
         let pos: Chess = setup_fen("3r3K/6PP/8/8/8/2k5/8/8 w - - 0 1");
@@ -3359,6 +5073,64 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_crazyhouse_halfmoves() {
+        use super::variant::Crazyhouse;
+
+        let mut pos: Crazyhouse = setup_fen("4k3/8/8/8/8/8/8/4K3[PNpn] w - - 10 20");
+        for (uci, zeroing, halfmoves) in [("N@c3", false, 11), ("p@c6", true, 0)] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal");
+            assert_eq!(pos.is_zeroing(&m), zeroing);
+            pos.play_unchecked(&m);
+            assert_eq!(pos.halfmoves(), halfmoves);
+        }
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_three_check_remaining_checks() {
+        use super::variant::ThreeCheck;
+
+        let mut pos: ThreeCheck = setup_fen("4k3/8/8/8/8/8/8/R3K2R w - - 3+2 0 1");
+        let remaining = |pos: &ThreeCheck| {
+            pos.remaining_checks()
+                .map(|checks| (u32::from(checks.white), u32::from(checks.black)))
+        };
+
+        for (uci, expected) in [("a1a8", (2, 2)), ("e8e7", (2, 2)), ("h1h7", (1, 2))] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal");
+            pos.play_unchecked(&m);
+            assert_eq!(remaining(&pos), Some(expected), "after {}", uci);
+        }
+        assert!(!pos.is_variant_end());
+
+        for uci in ["e7e6", "a8a6"] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal");
+            pos.play_unchecked(&m);
+        }
+        assert_eq!(remaining(&pos), Some((0, 2)));
+        assert!(pos.is_variant_end());
+        assert_eq!(
+            pos.variant_outcome(),
+            Some(Outcome::Decisive {
+                winner: Color::White
+            })
+        );
+    }
+
     #[test]
     fn test_aligned_checkers() {
         let res = "2Nq4/2K5/1b6/8/7R/3k4/7P/8 w - - 0 1"
@@ -3386,6 +5158,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_attacked_squares() {
+        let pos: Chess = "r1bqk2r/pp3ppp/2nb1n2/3p4/3P4/2NB1N2/PP3PPP/R1BQK2R w KQkq - 2 8"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+
+        for color in Color::ALL {
+            let mut expected = Bitboard::EMPTY;
+            for square in pos.board().by_color(color) {
+                expected |= pos.board().attacks_from(square);
+            }
+            assert_eq!(pos.attacked_squares(color), expected);
+
+            for square in Square::ALL {
+                assert_eq!(
+                    pos.is_attacked(square, color),
+                    expected.contains(square),
+                    "{} {}",
+                    square,
+                    color
+                );
+                assert_eq!(
+                    pos.attacks_to(square, color).any(),
+                    expected.contains(square)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_pins() {
+        let pos: Chess = "7k/4r2p/8/q7/1b1N4/6P1/1B1NPP2/4K2R w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+
+        assert_eq!(pos.pinned(White), Bitboard::from(Square::D2) | Square::E2);
+        assert_eq!(pos.pinners(Black), Bitboard::from(Square::B4) | Square::E7);
+        assert_eq!(pos.pinned(Black), Bitboard::from(Square::H7));
+        assert_eq!(pos.pinners(White), Bitboard::from(Square::H1));
+        assert_eq!(
+            pos.discovered_check_candidates(),
+            Bitboard::from(Square::D4)
+        );
+
+        let pos = pos.swap_turn().expect("swap turn");
+        assert!(pos.discovered_check_candidates().is_empty());
+    }
+
+    #[test]
+    fn test_undo() {
+        for fen in [
+            "r3k2r/1P4p1/8/2pP4/8/8/6P1/R3K2R w KQkq c6 0 20",
+            "r3k2r/6p1/8/8/2Pp4/8/1p4P1/R3K2R b KQkq c3 0 20",
+            "1rk3r1/8/8/8/8/8/8/1RK3R1 w GBgb - 0 1",
+        ] {
+            let pos: Chess = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position");
+
+            for m in pos.legal_moves() {
+                let mut after = pos.clone();
+                let undo = after.play_and_record(&m);
+                let expected = after.clone();
+
+                for reply in after.legal_moves() {
+                    let undo = after.play_and_record(&reply);
+                    after.undo(undo);
+                    assert_eq!(after, expected, "{} {} {}", fen, m, reply);
+                }
+
+                after.undo(undo);
+                assert_eq!(after, pos, "{} {}", fen, m);
+                assert_eq!(
+                    Fen::from_position(after, EnPassantMode::Always).to_string(),
+                    Fen::from_position(pos.clone(), EnPassantMode::Always).to_string()
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_swap_turn() {
         let pos: Chess = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"
@@ -3400,6 +5258,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_odds() {
+        for odds in [
+            Odds::PawnAndMove,
+            Odds::Pawn,
+            Odds::Knight,
+            Odds::Rook,
+            Odds::Queen,
+        ] {
+            for giver in Color::ALL {
+                let pos = Chess::odds(odds, giver);
+                assert_eq!(pos.board().occupied().count(), 31);
+                assert!(!pos.board().occupied().contains(odds.square(giver)));
+                assert_eq!(
+                    pos.turn().is_white(),
+                    odds != Odds::PawnAndMove || giver.is_black()
+                );
+            }
+        }
+
+        let pos = Chess::odds(Odds::Rook, Color::Black);
+        assert_eq!(pos.castles().castling_rights().count(), 3);
+        assert!(!pos.castles().has(Color::Black, CastlingSide::QueenSide));
+    }
+
+    #[test]
+    fn test_canonical() {
+        let canonical_fen = |fen: &str| {
+            let pos: Chess = setup_fen(fen);
+            Fen(pos.canonical().into_setup(EnPassantMode::Legal)).to_string()
+        };
+
+        // Black to move is mirrored.
+        assert_eq!(
+            canonical_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"),
+            "rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+
+        // Castling rights prevent horizontal mirroring.
+        assert_eq!(
+            canonical_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"),
+            "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"
+        );
+        assert_eq!(
+            canonical_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"),
+            canonical_fen("3k4/8/8/8/8/8/8/3K3R w - - 0 1")
+        );
+
+        // Legal en passant square is mirrored along.
+        assert_eq!(
+            canonical_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"),
+            canonical_fen("3k4/8/8/3pP3/8/8/8/3K4 w - d6 0 1")
+        );
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_canonical_asymmetric_variant() {
+        use crate::variant::RacingKings;
+
+        let pos: RacingKings = setup_fen("8/8/8/8/8/8/krbnNBRK/qrbnNBRQ b - - 0 1");
+        let canonical = pos.canonical();
+        assert_eq!(canonical.turn(), Color::Black);
+        assert_eq!(canonical.board().king_of(Color::White), Some(Square::A2));
+    }
+
     #[test]
     fn test_invalid_ep_square() {
         let fen: Fen = "4k3/8/8/8/8/8/8/4K3 w - e3 0 1".parse().expect("valid fen");
@@ -3429,4 +5353,33 @@ mod tests {
         assert!(pos.en_passant_moves().is_empty());
         assert_eq!(pos.legal_moves().len(), 2);
     }
+
+    #[test]
+    fn test_outcome_with() {
+        let rules = OutcomeRules::default();
+        let pos: Chess = setup_fen("8/8/4k3/8/8/8/R7/4K3 w - - 150 100");
+        assert_eq!(
+            pos.outcome_with(&rules),
+            Some((Outcome::Draw, EndReason::SeventyFiveMoves))
+        );
+        let lenient = OutcomeRules {
+            seventy_five_moves: false,
+            ..rules
+        };
+        assert_eq!(pos.outcome_with(&lenient), None);
+
+        // Checkmate takes precedence over the 75-move rule.
+        let pos: Chess = setup_fen("4k2R/8/4K3/8/8/8/8/8 b - - 150 100");
+        assert_eq!(
+            pos.outcome_with(&rules),
+            Some((Outcome::Decisive { winner: White }, EndReason::Checkmate))
+        );
+
+        let pos: Chess = setup_fen("8/8/4k3/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(
+            pos.outcome_with(&rules),
+            Some((Outcome::Draw, EndReason::InsufficientMaterial))
+        );
+        assert_eq!(pos.outcome(), pos.outcome_with(&lenient).map(|(o, _)| o));
+    }
 }
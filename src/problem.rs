@@ -0,0 +1,620 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Verify simple chess compositions.
+//!
+//! Supports directmates, where the side to move forces mate, selfmates,
+//! where the side to move forces the opponent to give mate, and helpmates,
+//! where the side to move cooperates with the opponent to get mated. The
+//! solver is a plain exhaustive search on top of the legal move
+//! generator, so it is only practical for problems with few moves.
+//!
+//! In variants, mate means any win according to the rules of the variant.
+//!
+//! Claimed proof games can be checked with [`verify_proof_game()`], and
+//! [`is_probably_reachable()`] cheaply rules out many impossible positions.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{
+//!     fen::Fen,
+//!     problem::{keys, Stipulation},
+//!     uci::Uci,
+//!     CastlingMode, Chess,
+//! };
+//!
+//! let pos: Chess = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"
+//!     .parse::<Fen>()?
+//!     .into_position(CastlingMode::Standard)?;
+//!
+//! let keys = keys(&pos, Stipulation::Directmate(1));
+//! assert_eq!(keys, ["a1a8".parse::<Uci>()?.to_move(&pos)?]);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{error::Error, fmt};
+
+use crate::{
+    position::is_standard_material,
+    san::{San, SanError},
+    Bitboard, Board, Color, EnPassantMode, ErrorKind, File, Move, Outcome, Position, Setup, Square,
+};
+
+/// Objective and number of moves of a composition.
+///
+/// Moves are counted for the side to move, so `Directmate(2)` means mate
+/// in two moves, answering one defense in between.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Stipulation {
+    /// The side to move forces mate (`#n`).
+    Directmate(u32),
+    /// The side to move forces the opponent to give mate (`s#n`).
+    Selfmate(u32),
+    /// The side to move and the opponent cooperate, so that the opponent
+    /// gives mate on its `n`-th move (`h#n`).
+    Helpmate(u32),
+}
+
+impl Stipulation {
+    /// Number of moves.
+    pub fn moves(self) -> u32 {
+        match self {
+            Stipulation::Directmate(n) | Stipulation::Selfmate(n) | Stipulation::Helpmate(n) => n,
+        }
+    }
+}
+
+impl fmt::Display for Stipulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Stipulation::Directmate(n) => write!(f, "#{}", n),
+            Stipulation::Selfmate(n) => write!(f, "s#{}", n),
+            Stipulation::Helpmate(n) => write!(f, "h#{}", n),
+        }
+    }
+}
+
+fn play<P: Position + Clone>(pos: &P, m: &Move) -> P {
+    let mut after = pos.clone();
+    after.play_unchecked(m);
+    after
+}
+
+fn is_mated<P: Position>(pos: &P) -> bool {
+    pos.outcome()
+        == Some(Outcome::Decisive {
+            winner: !pos.turn(),
+        })
+}
+
+fn is_directmate_key<P: Position + Clone>(pos: &P, m: &Move, n: u32) -> bool {
+    let after = play(pos, m);
+    if is_mated(&after) {
+        return true;
+    }
+    if n <= 1 || after.is_game_over() {
+        return false;
+    }
+    after.legal_moves().iter().all(|defense| {
+        let next = play(&after, defense);
+        next.legal_moves()
+            .iter()
+            .any(|m| is_directmate_key(&next, m, n - 1))
+    })
+}
+
+fn is_selfmate_key<P: Position + Clone>(pos: &P, m: &Move, n: u32) -> bool {
+    let after = play(pos, m);
+    if after.is_game_over() {
+        return false;
+    }
+    after.legal_moves().iter().all(|defense| {
+        let next = play(&after, defense);
+        is_mated(&next)
+            || (n > 1
+                && !next.is_game_over()
+                && next
+                    .legal_moves()
+                    .iter()
+                    .any(|m| is_selfmate_key(&next, m, n - 1)))
+    })
+}
+
+fn push_helpmates<P: Position + Clone>(
+    pos: &P,
+    n: u32,
+    line: &mut Vec<Move>,
+    solutions: &mut Vec<Vec<Move>>,
+) {
+    if pos.is_game_over() {
+        return;
+    }
+    for m in pos.legal_moves() {
+        let after = play(pos, &m);
+        if after.is_game_over() {
+            continue;
+        }
+        line.push(m);
+        for reply in after.legal_moves() {
+            let next = play(&after, &reply);
+            line.push(reply);
+            if n <= 1 {
+                if is_mated(&next) {
+                    solutions.push(line.clone());
+                }
+            } else {
+                push_helpmates(&next, n - 1, line, solutions);
+            }
+            line.pop();
+        }
+        line.pop();
+    }
+}
+
+/// Finds all solutions of a helpmate in `n` moves.
+///
+/// Each solution is the complete sequence of `2 * n` moves, starting with
+/// the side to move, that ends with the side to move being mated. Lines
+/// where the game ends early are not solutions.
+pub fn helpmates<P: Position + Clone>(pos: &P, n: u32) -> Vec<Vec<Move>> {
+    let mut solutions = Vec::new();
+    if n > 0 {
+        push_helpmates(pos, n, &mut Vec::new(), &mut solutions);
+    }
+    solutions
+}
+
+/// Finds all first moves (keys) of the side to move that fulfill the
+/// stipulation.
+///
+/// Directmates and selfmates are fulfilled in at most the given number of
+/// moves. A stipulation with `0` moves has no keys.
+pub fn keys<P: Position + Clone>(pos: &P, stipulation: Stipulation) -> Vec<Move> {
+    let n = stipulation.moves();
+    if n == 0 {
+        return Vec::new();
+    }
+    match stipulation {
+        Stipulation::Directmate(_) => pos
+            .legal_moves()
+            .into_iter()
+            .filter(|m| is_directmate_key(pos, m, n))
+            .collect(),
+        Stipulation::Selfmate(_) => pos
+            .legal_moves()
+            .into_iter()
+            .filter(|m| is_selfmate_key(pos, m, n))
+            .collect(),
+        Stipulation::Helpmate(_) => {
+            let mut keys: Vec<Move> = Vec::new();
+            for solution in helpmates(pos, n) {
+                if !keys.contains(&solution[0]) {
+                    keys.push(solution[0].clone());
+                }
+            }
+            keys
+        }
+    }
+}
+
+/// Error when a proof game does not reach the target position. See
+/// [`verify_proof_game()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ProofGameError {
+    /// The proof game does not have the required number of plies.
+    Length { expected: usize, actual: usize },
+    /// The move at index `ply` (starting at `0`) is illegal or ambiguous.
+    Move { ply: usize, error: SanError },
+    /// The pieces on `squares` differ from the target position.
+    Board { squares: Bitboard },
+    /// The board matches, but the side to move, castling rights or en
+    /// passant square differ from the target position.
+    State,
+}
+
+impl fmt::Display for ProofGameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ProofGameError::Length { expected, actual } => {
+                write!(f, "proof game has {} plies, expected {}", actual, expected)
+            }
+            ProofGameError::Move { ply, ref error } => {
+                write!(f, "{} at ply {}", error, ply + 1)
+            }
+            ProofGameError::Board { squares } => {
+                f.write_str("board differs from target on")?;
+                for square in squares {
+                    write!(f, " {}", square)?;
+                }
+                Ok(())
+            }
+            ProofGameError::State => {
+                f.write_str("side to move, castling rights or en passant square differ from target")
+            }
+        }
+    }
+}
+
+impl Error for ProofGameError {}
+
+impl ProofGameError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            ProofGameError::Move { ref error, .. } => error.kind(),
+            _ => ErrorKind::UnexpectedPosition,
+        }
+    }
+}
+
+/// Verifies that `moves`, played from the initial position, reach the
+/// `target` position in exactly `plies` plies.
+///
+/// Besides the board, the side to move, castling rights and the legal en
+/// passant square of the target have to match. Move counters are ignored.
+/// Returns the final position.
+///
+/// # Errors
+///
+/// Returns [`ProofGameError`] describing the first problem found.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     fen::Fen,
+///     problem::{verify_proof_game, ProofGameError},
+///     san::San,
+///     Chess, Square,
+/// };
+///
+/// let target = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq -"
+///     .parse::<Fen>()?
+///     .into_setup();
+///
+/// let moves = ["e4", "e5"].map(|san| san.parse::<San>().unwrap());
+/// let pos: Chess = verify_proof_game(&moves, &target, 2)?;
+///
+/// let moves = ["e3", "e5"].map(|san| san.parse::<San>().unwrap());
+/// let err = verify_proof_game::<Chess>(&moves, &target, 2).unwrap_err();
+/// assert_eq!(err.to_string(), "board differs from target on e3 e4");
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn verify_proof_game<P: Position + Default>(
+    moves: &[San],
+    target: &Setup,
+    plies: usize,
+) -> Result<P, ProofGameError> {
+    if moves.len() != plies {
+        return Err(ProofGameError::Length {
+            expected: plies,
+            actual: moves.len(),
+        });
+    }
+
+    let mut pos = P::default();
+    for (ply, san) in moves.iter().enumerate() {
+        let m = san
+            .to_move(&pos)
+            .map_err(|error| ProofGameError::Move { ply, error })?;
+        pos.play_unchecked(&m);
+    }
+
+    let mut squares = Bitboard::EMPTY;
+    for square in Square::ALL {
+        if pos.board().piece_at(square) != target.board.piece_at(square) {
+            squares.add(square);
+        }
+    }
+    if squares.any() {
+        return Err(ProofGameError::Board { squares });
+    }
+
+    if pos.turn() != target.turn
+        || pos.castles().castling_rights() != target.castling_rights
+        || pos.ep_square(EnPassantMode::Legal) != target.ep_square
+    {
+        return Err(ProofGameError::State);
+    }
+
+    Ok(pos)
+}
+
+/// Reason why a board can not be reached from the initial position of
+/// standard chess. See [`unreachable_reasons()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Unreachable {
+    /// A side has pawns on the first or last rank.
+    PawnsOnBackrank { color: Color },
+    /// A side has more than 8 pawns.
+    TooManyPawns { color: Color },
+    /// A side has more pieces than possible with promotions.
+    TooManyPieces { color: Color },
+    /// The pawns of a side can not have reached their squares, regardless
+    /// of captures.
+    ImpossiblePawns { color: Color },
+    /// The pawns of a side need at least `required` captures to reach
+    /// their files, but only `available` pieces of the opponent are
+    /// missing.
+    PawnCaptures {
+        color: Color,
+        required: usize,
+        available: usize,
+    },
+}
+
+/// Minimum number of captures needed to bring the pawns of `color` from
+/// their starting files, or `None` if the pawn structure is impossible.
+fn required_pawn_captures(board: &Board, color: Color) -> Option<usize> {
+    let pawns: Vec<Square> = (board.pawns() & board.by_color(color))
+        .into_iter()
+        .collect();
+    if pawns.len() > 8 {
+        return None;
+    }
+
+    // Exact assignment of pawns to distinct starting files, indexed by the
+    // set of starting files used so far.
+    let mut best = [None; 256];
+    best[0] = Some(0);
+    for mask in 0..256usize {
+        let cost = match best[mask] {
+            Some(cost) => cost,
+            None => continue,
+        };
+        let pawn = match pawns.get(mask.count_ones() as usize) {
+            Some(&pawn) => pawn,
+            None => continue,
+        };
+        let max_captures = pawn.rank().distance(color.backrank()).checked_sub(1)?;
+        for file in File::ALL {
+            let captures = file.distance(pawn.file());
+            let next = mask | 1 << usize::from(file);
+            if next != mask && captures <= max_captures {
+                let cost = Some(cost + captures as usize);
+                if best[next].map_or(true, |best| cost < Some(best)) {
+                    best[next] = cost;
+                }
+            }
+        }
+    }
+
+    (0..256usize)
+        .filter(|mask| mask.count_ones() as usize == pawns.len())
+        .filter_map(|mask| best[mask])
+        .min()
+}
+
+/// Finds reasons why `board` can not be reached from the initial position
+/// of standard chess.
+///
+/// The checks are heuristics about material and pawn structure, so an
+/// empty result does not prove that the board is reachable. Every reason
+/// returned is conclusive, though. This is useful to filter corrupted
+/// positions from databases.
+///
+/// Pawn captures are counted by assigning each pawn to a distinct starting
+/// file, where a pawn can not have captured more often than it advanced.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     fen::Fen,
+///     problem::{unreachable_reasons, Unreachable},
+///     Color,
+/// };
+///
+/// // Tripled pawns, but no black piece has been captured.
+/// let setup = "rnbqkbnr/pppppppp/8/8/P7/P7/P2PPPPP/RNBQKBNR w KQkq -"
+///     .parse::<Fen>()?
+///     .into_setup();
+///
+/// assert_eq!(
+///     unreachable_reasons(&setup.board),
+///     [Unreachable::PawnCaptures {
+///         color: Color::White,
+///         required: 3,
+///         available: 0,
+///     }]
+/// );
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn unreachable_reasons(board: &Board) -> Vec<Unreachable> {
+    let mut reasons = Vec::new();
+
+    for color in Color::ALL {
+        let pawns = board.pawns() & board.by_color(color);
+        if (pawns & Bitboard::BACKRANKS).any() {
+            reasons.push(Unreachable::PawnsOnBackrank { color });
+        }
+        if pawns.count() > 8 {
+            reasons.push(Unreachable::TooManyPawns { color });
+        } else if !is_standard_material(board, color) {
+            reasons.push(Unreachable::TooManyPieces { color });
+        }
+    }
+
+    if reasons.is_empty() {
+        for color in Color::ALL {
+            let available = 16usize.saturating_sub(board.by_color(!color).count());
+            match required_pawn_captures(board, color) {
+                None => reasons.push(Unreachable::ImpossiblePawns { color }),
+                Some(required) if required > available => reasons.push(Unreachable::PawnCaptures {
+                    color,
+                    required,
+                    available,
+                }),
+                Some(_) => (),
+            }
+        }
+    }
+
+    reasons
+}
+
+/// Tests if `board` may be reachable from the initial position of standard
+/// chess, according to the heuristics of [`unreachable_reasons()`].
+pub fn is_probably_reachable(board: &Board) -> bool {
+    unreachable_reasons(board).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::Fen, uci::Uci, CastlingMode, Chess};
+
+    fn setup(fen: &str) -> Chess {
+        fen.parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position")
+    }
+
+    fn uci(pos: &Chess, uci: &str) -> Move {
+        uci.parse::<Uci>()
+            .expect("valid uci")
+            .to_move(pos)
+            .expect("legal move")
+    }
+
+    #[test]
+    fn test_directmate() {
+        let pos = setup("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+        assert_eq!(keys(&pos, Stipulation::Directmate(1)), [uci(&pos, "h1h8")]);
+        assert!(keys(&pos, Stipulation::Directmate(2)).contains(&uci(&pos, "h1h8")));
+        assert_eq!(keys(&pos, Stipulation::Directmate(0)), []);
+    }
+
+    #[test]
+    fn test_selfmate() {
+        // 1. Qg3+ Kxg3#, discovering check from the rook.
+        let pos = setup("8/8/8/7r/1p5k/1Q6/4Pp2/7K w - - 0 1");
+        assert_eq!(keys(&pos, Stipulation::Selfmate(1)), [uci(&pos, "b3g3")]);
+        assert_eq!(keys(&pos, Stipulation::Directmate(1)), []);
+    }
+
+    #[test]
+    fn test_helpmate() {
+        let pos = setup("k7/8/1K6/8/8/8/8/7R b - - 0 1");
+        let solutions = helpmates(&pos, 1);
+        assert_eq!(solutions.len(), 1);
+        let after = play(&pos, &solutions[0][0]);
+        assert_eq!(solutions[0], [uci(&pos, "a8b8"), uci(&after, "h1h8")]);
+        assert_eq!(keys(&pos, Stipulation::Helpmate(1)), [uci(&pos, "a8b8")]);
+
+        for solution in helpmates(&pos, 2) {
+            assert_eq!(solution.len(), 4);
+            let mut pos = pos.clone();
+            for m in &solution {
+                pos.play_unchecked(m);
+            }
+            assert!(pos.is_checkmate());
+        }
+    }
+
+    #[test]
+    fn test_verify_proof_game() {
+        let target = "rnbq1bnr/ppppkppp/8/4p3/4P3/8/PPPPKPPP/RNBQ1BNR w - -"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_setup();
+        let sans = |line: &str| -> Vec<San> {
+            line.split(' ')
+                .map(|san| san.parse().expect("valid san"))
+                .collect()
+        };
+
+        assert!(verify_proof_game::<Chess>(&sans("e4 e5 Ke2 Ke7"), &target, 4).is_ok());
+        assert_eq!(
+            verify_proof_game::<Chess>(&sans("e4 e5 Ke2 Nc6"), &target, 4),
+            Err(ProofGameError::Board {
+                squares: Bitboard::from(Square::B8) | Square::C6 | Square::E7 | Square::E8
+            })
+        );
+        assert_eq!(
+            verify_proof_game::<Chess>(&sans("e4 e5"), &target, 3),
+            Err(ProofGameError::Length {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            verify_proof_game::<Chess>(&sans("e4 e5 Ke3"), &target, 3)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::IllegalMove
+        );
+
+        let start = Setup::default();
+        assert!(verify_proof_game::<Chess>(&sans("Nf3 Nf6 Ng1 Ng8"), &start, 4).is_ok());
+        assert_eq!(
+            verify_proof_game::<Chess>(&sans("Nf3 Nf6 Rg1 Ng8 Rh1 Nf6 Ng1 Ng8"), &start, 8),
+            Err(ProofGameError::State)
+        );
+    }
+
+    #[test]
+    fn test_unreachable_reasons() {
+        let board = |fen: &str| fen.parse::<Fen>().expect("valid fen").into_setup().board;
+
+        assert!(is_probably_reachable(&Board::default()));
+        assert!(is_probably_reachable(&Board::empty()));
+        assert_eq!(
+            unreachable_reasons(&board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNP")),
+            [Unreachable::PawnsOnBackrank {
+                color: Color::White
+            }]
+        );
+        assert_eq!(
+            unreachable_reasons(&board("qqqqkqqq/pppppppp/8/8/8/8/8/4K3")),
+            [Unreachable::TooManyPieces {
+                color: Color::Black
+            }]
+        );
+
+        // Doubled pawns after capturing the missing h-pawn.
+        assert!(is_probably_reachable(&board(
+            "rnbqkbnr/ppppppp1/8/8/8/P7/P1PPPPPP/RNBQKBNR"
+        )));
+        assert_eq!(
+            unreachable_reasons(&board("rnbqkbnr/pppppppp/8/8/8/P7/P1PPPPPP/RNBQKBNR")),
+            [Unreachable::PawnCaptures {
+                color: Color::White,
+                required: 1,
+                available: 0,
+            }]
+        );
+
+        // The pawn on a3 can not come from the h-file.
+        assert_eq!(
+            unreachable_reasons(&board("rnbqkbnr/8/8/8/8/P7/PPPPPPP1/RNBQKBNR")),
+            [Unreachable::ImpossiblePawns {
+                color: Color::White
+            }]
+        );
+
+        // Black pawns count from the other side.
+        assert_eq!(
+            unreachable_reasons(&board("4k3/pp6/p7/8/8/8/8/4K3")),
+            [Unreachable::ImpossiblePawns {
+                color: Color::Black
+            }]
+        );
+        assert!(is_probably_reachable(&board("4k3/p7/p7/p7/8/8/8/4K3")));
+    }
+}
@@ -0,0 +1,151 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Player ratings, as in the `WhiteElo` and `BlackElo` tags of PGNs.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::rating::Rating;
+//!
+//! assert_eq!(Rating::from_tag(b"2850"), Some(2850));
+//! assert_eq!(Rating::from_tag(b"1500?"), Some(1500));
+//! assert_eq!(Rating::from_tag(b"?"), None);
+//! assert_eq!(Rating::from_tag(b""), None);
+//! ```
+
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::error::ErrorKind;
+
+/// Error when parsing an invalid rating.
+#[derive(Clone, Debug)]
+pub struct ParseRatingError;
+
+impl fmt::Display for ParseRatingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid rating")
+    }
+}
+
+impl Error for ParseRatingError {}
+
+impl ParseRatingError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidRating
+    }
+}
+
+/// A rating, possibly marked as provisional.
+///
+/// Provisional ratings are based on only a few games. Some servers mark them
+/// with a trailing `?` (for example `1500?`), others with a trailing `P`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Rating {
+    /// Rating points.
+    pub value: u16,
+    /// Rating is provisional.
+    pub provisional: bool,
+}
+
+impl Rating {
+    /// Parses a rating tag value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Ok(None)` for unknown ratings (empty, `?` or `-`), and
+    /// [`ParseRatingError`] if the value is not a valid rating.
+    pub fn from_ascii(s: &[u8]) -> Result<Option<Rating>, ParseRatingError> {
+        if matches!(s, b"" | b"?" | b"-") {
+            return Ok(None);
+        }
+        let (digits, provisional) = match s.split_last() {
+            Some((b'?' | b'P' | b'p', digits)) => (digits, true),
+            _ => (s, false),
+        };
+        Ok(Some(Rating {
+            value: btoi::btou(digits).map_err(|_| ParseRatingError)?,
+            provisional,
+        }))
+    }
+
+    /// Leniently parses a rating tag value, ignoring the provisional marker.
+    /// Unknown and invalid values are `None`.
+    pub fn from_tag(s: &[u8]) -> Option<u16> {
+        Rating::from_ascii(s)
+            .ok()
+            .flatten()
+            .map(|rating| rating.value)
+    }
+}
+
+impl From<u16> for Rating {
+    fn from(value: u16) -> Rating {
+        Rating {
+            value,
+            provisional: false,
+        }
+    }
+}
+
+impl From<Rating> for u16 {
+    fn from(rating: Rating) -> u16 {
+        rating.value
+    }
+}
+
+impl FromStr for Rating {
+    type Err = ParseRatingError;
+
+    fn from_str(s: &str) -> Result<Rating, ParseRatingError> {
+        Rating::from_ascii(s.as_bytes())?.ok_or(ParseRatingError)
+    }
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)?;
+        if self.provisional {
+            f.write_str("?")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rating() {
+        assert_eq!(Rating::from_ascii(b"-").expect("unknown"), None);
+        assert_eq!(
+            Rating::from_ascii(b"1234P").expect("valid"),
+            Some(Rating {
+                value: 1234,
+                provisional: true
+            })
+        );
+        assert!(Rating::from_ascii(b"12a").is_err());
+        assert!(Rating::from_ascii(b"70000").is_err());
+        assert!("?".parse::<Rating>().is_err());
+        for rating in ["0", "2850", "1500?"] {
+            assert_eq!(rating.parse::<Rating>().expect("valid").to_string(), rating);
+        }
+        assert_eq!(Rating::from_tag(b"x"), None);
+    }
+}
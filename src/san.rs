@@ -62,7 +62,9 @@
 
 use std::{error::Error, fmt, str::FromStr};
 
-use crate::{CastlingSide, File, Move, MoveList, Outcome, Position, Rank, Role, Square};
+use crate::{
+    pgn::Nag, CastlingSide, ErrorKind, File, Move, MoveList, Outcome, Position, Rank, Role, Square,
+};
 
 /// Error when parsing a syntactially invalid SAN.
 #[derive(Clone, Debug)]
@@ -76,6 +78,13 @@ impl fmt::Display for ParseSanError {
 
 impl Error for ParseSanError {}
 
+impl ParseSanError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidSan
+    }
+}
+
 /// `IllegalSan` or `AmbiguousSan`.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum SanError {
@@ -96,6 +105,16 @@ impl fmt::Display for SanError {
 
 impl Error for SanError {}
 
+impl SanError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            SanError::IllegalSan => ErrorKind::IllegalMove,
+            SanError::AmbiguousSan => ErrorKind::AmbiguousMove,
+        }
+    }
+}
+
 /// A move in Standard Algebraic Notation.
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum San {
@@ -460,7 +479,7 @@ impl San {
                 }
                 _ => false,
             },
-            San::Castle(side) => m.castling_side().map_or(false, |s| side == s),
+            San::Castle(side) => m.castling_side() == Some(side),
             San::Put { role, to } => match *m {
                 Move::Put { role: r, to: t } => r == role && to == t,
                 _ => false,
@@ -639,6 +658,233 @@ impl fmt::Display for SanPlus {
     }
 }
 
+fn figurine(ch: char) -> char {
+    match ch {
+        'K' => '♚',
+        'Q' => '♛',
+        'R' => '♜',
+        'B' => '♝',
+        'N' => '♞',
+        ch => ch,
+    }
+}
+
+fn from_figurine(ch: char) -> char {
+    match ch {
+        '♔' | '♚' => 'K',
+        '♕' | '♛' => 'Q',
+        '♖' | '♜' => 'R',
+        '♗' | '♝' => 'B',
+        '♘' | '♞' => 'N',
+        ch => ch,
+    }
+}
+
+impl San {
+    /// Writes the SAN with Unicode figurines instead of piece letters, like
+    /// `♞f3`. See [`FigurineSan`].
+    pub fn to_figurine_string(&self) -> String {
+        self.to_string().chars().map(figurine).collect()
+    }
+}
+
+impl SanPlus {
+    /// Writes the SAN and possible check or checkmate suffix with Unicode
+    /// figurines instead of piece letters, like `♛xf7#`. See
+    /// [`FigurineSan`].
+    pub fn to_figurine_string(&self) -> String {
+        self.to_string().chars().map(figurine).collect()
+    }
+}
+
+/// A [`SanPlus`] that is displayed and parsed with Unicode figurines
+/// instead of piece letters, like `♞xe5+`.
+///
+/// Figurines are the same for both colors. The solid glyphs (`♚♛♜♝♞`) are
+/// written. Parsing accepts both solid and outlined glyphs (`♔♕♖♗♘`), as
+/// well as plain piece letters.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::san::{FigurineSan, SanPlus};
+///
+/// let san: FigurineSan = "♞xe5+".parse()?;
+/// assert_eq!(san.0, "Nxe5+".parse::<SanPlus>()?);
+/// assert_eq!(san.to_string(), "♞xe5+");
+///
+/// assert_eq!("exd8=♕".parse::<FigurineSan>()?.to_string(), "exd8=♛");
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct FigurineSan(pub SanPlus);
+
+impl FromStr for FigurineSan {
+    type Err = ParseSanError;
+
+    fn from_str(san: &str) -> Result<FigurineSan, ParseSanError> {
+        let ascii: String = san.chars().map(from_figurine).collect();
+        SanPlus::from_ascii(ascii.as_bytes()).map(FigurineSan)
+    }
+}
+
+impl fmt::Display for FigurineSan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_figurine_string())
+    }
+}
+
+/// Move annotation suffix, like `!?`. Each corresponds to one of the
+/// first six [`Nag`]s.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Annotation {
+    /// `!`, `$1`
+    GoodMove,
+    /// `?`, `$2`
+    Mistake,
+    /// `!!`, `$3`
+    BrilliantMove,
+    /// `??`, `$4`
+    Blunder,
+    /// `!?`, `$5`
+    SpeculativeMove,
+    /// `?!`, `$6`
+    DubiousMove,
+}
+
+impl Annotation {
+    pub const ALL: [Annotation; 6] = [
+        Annotation::GoodMove,
+        Annotation::Mistake,
+        Annotation::BrilliantMove,
+        Annotation::Blunder,
+        Annotation::SpeculativeMove,
+        Annotation::DubiousMove,
+    ];
+
+    /// Parses a suffix like `!?`.
+    pub fn from_ascii(s: &[u8]) -> Option<Annotation> {
+        Annotation::ALL
+            .into_iter()
+            .find(|annotation| annotation.as_str().as_bytes() == s)
+    }
+
+    /// Gets the suffix, like `!?`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Annotation::GoodMove => "!",
+            Annotation::Mistake => "?",
+            Annotation::BrilliantMove => "!!",
+            Annotation::Blunder => "??",
+            Annotation::SpeculativeMove => "!?",
+            Annotation::DubiousMove => "?!",
+        }
+    }
+
+    /// Gets the annotation corresponding to `nag`, if any.
+    pub fn from_nag(nag: Nag) -> Option<Annotation> {
+        Annotation::ALL
+            .into_iter()
+            .find(|annotation| annotation.nag() == nag)
+    }
+
+    /// Gets the corresponding NAG, like `$5` for `!?`.
+    pub fn nag(self) -> Nag {
+        match self {
+            Annotation::GoodMove => Nag::GOOD_MOVE,
+            Annotation::Mistake => Nag::MISTAKE,
+            Annotation::BrilliantMove => Nag::BRILLIANT_MOVE,
+            Annotation::Blunder => Nag::BLUNDER,
+            Annotation::SpeculativeMove => Nag::SPECULATIVE_MOVE,
+            Annotation::DubiousMove => Nag::DUBIOUS_MOVE,
+        }
+    }
+}
+
+impl From<Annotation> for Nag {
+    fn from(annotation: Annotation) -> Nag {
+        annotation.nag()
+    }
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A [`SanPlus`] and a possible move annotation suffix, like `Nf3!?`.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{pgn::Nag, san::{Annotation, AnnotatedSan}};
+///
+/// let san: AnnotatedSan = "exd5+?!".parse()?;
+/// assert_eq!(san.san_plus.to_string(), "exd5+");
+/// assert_eq!(san.annotation, Some(Annotation::DubiousMove));
+/// assert_eq!(san.annotation.map(Nag::from), Some(Nag::DUBIOUS_MOVE));
+/// assert_eq!(san.to_string(), "exd5+?!");
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct AnnotatedSan {
+    pub san_plus: SanPlus,
+    pub annotation: Option<Annotation>,
+}
+
+impl AnnotatedSan {
+    /// Parses a SAN, possible check and checkmate suffix, and possible
+    /// move annotation suffix.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseSanError`] if `san` is not syntactically valid,
+    /// including unknown annotation suffixes like `!!!`.
+    pub fn from_ascii(san: &[u8]) -> Result<AnnotatedSan, ParseSanError> {
+        let split = san
+            .iter()
+            .rposition(|ch| *ch != b'!' && *ch != b'?')
+            .map_or(0, |last| last + 1);
+        let (san, annotation) = san.split_at(split);
+        Ok(AnnotatedSan {
+            san_plus: SanPlus::from_ascii(san)?,
+            annotation: if annotation.is_empty() {
+                None
+            } else {
+                Some(Annotation::from_ascii(annotation).ok_or(ParseSanError)?)
+            },
+        })
+    }
+}
+
+impl From<SanPlus> for AnnotatedSan {
+    fn from(san_plus: SanPlus) -> AnnotatedSan {
+        AnnotatedSan {
+            san_plus,
+            annotation: None,
+        }
+    }
+}
+
+impl FromStr for AnnotatedSan {
+    type Err = ParseSanError;
+
+    fn from_str(san: &str) -> Result<AnnotatedSan, ParseSanError> {
+        AnnotatedSan::from_ascii(san.as_bytes())
+    }
+}
+
+impl fmt::Display for AnnotatedSan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.san_plus)?;
+        if let Some(annotation) = self.annotation {
+            write!(f, "{}", annotation)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem;
@@ -664,6 +910,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_figurine_san() {
+        for (ascii, figurine) in [
+            ("e4", "e4"),
+            ("Nf3", "♞f3"),
+            ("O-O-O+", "O-O-O+"),
+            ("bxa8=R#", "bxa8=♜#"),
+            ("Kxh1", "♚xh1"),
+            ("Q@b3", "♛@b3"),
+            ("Bba5", "♝ba5"),
+        ] {
+            let san = ascii.parse::<SanPlus>().expect("valid san");
+            assert_eq!(san.to_figurine_string(), figurine);
+            assert_eq!(
+                san.san.to_figurine_string(),
+                figurine.trim_end_matches(['+', '#'])
+            );
+            assert_eq!(
+                figurine
+                    .parse::<FigurineSan>()
+                    .expect("valid figurine san")
+                    .0,
+                san
+            );
+        }
+        assert_eq!(
+            "♘c3".parse::<FigurineSan>().expect("outlined").to_string(),
+            "♞c3"
+        );
+        assert!("♟e4".parse::<FigurineSan>().is_err());
+    }
+
+    #[test]
+    fn test_annotated_san() {
+        for san in ["e4", "Nf3!", "O-O-O+??", "exd8=Q#!!", "K@b3?"] {
+            let result = san.parse::<AnnotatedSan>().expect("valid san").to_string();
+            assert_eq!(san, result);
+        }
+        for invalid in ["e4!!!", "e4?!?", "!", "e4 !"] {
+            assert!(invalid.parse::<AnnotatedSan>().is_err(), "{}", invalid);
+        }
+        for annotation in Annotation::ALL {
+            assert_eq!(Annotation::from_nag(annotation.nag()), Some(annotation));
+            assert_eq!(
+                Nag::from_ascii(annotation.as_str().as_bytes()),
+                Some(annotation.nag())
+            );
+        }
+        assert_eq!(Annotation::from_nag(Nag(7)), None);
+    }
+
     #[test]
     fn test_pawn_capture_without_file() {
         let san = "f6".parse::<San>().expect("valid san");
@@ -0,0 +1,530 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Serde support. Human-readable formats use the usual notation, like
+//! `"e4"` for squares, `"Q"` for pieces and FENs for boards. Other formats
+//! use compact integers:
+//!
+//! * `Square`: Index from `0` (a1) to `63` (h8).
+//! * `Color`: `0` for black, `1` for white.
+//! * `Role`: `1` (pawn) to `6` (king).
+//! * `Piece`: Role, plus `8` for white pieces.
+//! * `Bitboard`: Always an integer.
+//! * `Board`: White, black, and the pieces of each role, as 8 bitboards.
+//! * `Outcome`: `0` for draws, `1` if white wins, `2` if black wins.
+//! * `Fen`: Always a string.
+//!
+//! `Move`, `ByColor` and `ByRole` are serialized like their definitions.
+
+use std::{convert::TryFrom, fmt, marker::PhantomData, str::FromStr};
+
+use serde::{
+    de::{self, Unexpected},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    fen::Fen, Bitboard, Board, ByColor, ByRole, Color, Move, Outcome, Piece, Role, Square,
+};
+
+struct FromStrVisitor<T> {
+    expecting: &'static str,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: FromStr> de::Visitor<'de> for FromStrVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+fn deserialize_str<'de, D, T>(deserializer: D, expecting: &'static str) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+{
+    deserializer.deserialize_str(FromStrVisitor {
+        expecting,
+        marker: PhantomData,
+    })
+}
+
+fn deserialize_u8<'de, D, T, F>(deserializer: D, expecting: &str, f: F) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    F: FnOnce(u8) -> Option<T>,
+{
+    let n = u8::deserialize(deserializer)?;
+    f(n).ok_or_else(|| de::Error::invalid_value(Unexpected::Unsigned(u64::from(n)), &expecting))
+}
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u8(u8::from(*self))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        if deserializer.is_human_readable() {
+            deserialize_str(deserializer, "a square name like e4")
+        } else {
+            deserialize_u8(deserializer, "a square index", |n| Square::try_from(n).ok())
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u8(u8::from(self.is_white()))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        if deserializer.is_human_readable() {
+            deserialize_str(deserializer, "white or black")
+        } else {
+            deserialize_u8(deserializer, "0 or 1", |n| match n {
+                0 => Some(Color::Black),
+                1 => Some(Color::White),
+                _ => None,
+            })
+        }
+    }
+}
+
+struct RoleChar(Role);
+
+impl FromStr for RoleChar {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<RoleChar, ()> {
+        let mut chars = s.chars();
+        match (chars.next().and_then(Role::from_char), chars.next()) {
+            (Some(role), None) => Ok(RoleChar(role)),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Serialize for Role {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_char(self.char())
+        } else {
+            serializer.serialize_u8(u8::from(*self))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Role {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Role, D::Error> {
+        if deserializer.is_human_readable() {
+            deserialize_str(deserializer, "a role like q").map(|RoleChar(role)| role)
+        } else {
+            deserialize_u8(deserializer, "a role from 1 to 6", |n| {
+                Role::try_from(n).ok()
+            })
+        }
+    }
+}
+
+struct PieceChar(Piece);
+
+impl FromStr for PieceChar {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<PieceChar, ()> {
+        let mut chars = s.chars();
+        match (chars.next().and_then(Piece::from_char), chars.next()) {
+            (Some(piece), None) => Ok(PieceChar(piece)),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Serialize for Piece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_char(self.char())
+        } else {
+            serializer.serialize_u8(u8::from(self.role) | if self.color.is_white() { 8 } else { 0 })
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Piece {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Piece, D::Error> {
+        if deserializer.is_human_readable() {
+            deserialize_str(deserializer, "a piece like Q").map(|PieceChar(piece)| piece)
+        } else {
+            deserialize_u8(deserializer, "a piece code", |n| {
+                Role::try_from(n & 7)
+                    .ok()
+                    .filter(|_| n & !15 == 0)
+                    .map(|role| role.of(Color::from_white(n & 8 != 0)))
+            })
+        }
+    }
+}
+
+impl Serialize for Bitboard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bitboard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bitboard, D::Error> {
+        u64::deserialize(deserializer).map(Bitboard)
+    }
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            (
+                self.white(),
+                self.black(),
+                self.pawns(),
+                self.knights(),
+                self.bishops(),
+                self.rooks(),
+                self.queens(),
+                self.kings(),
+            )
+                .serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        if deserializer.is_human_readable() {
+            deserialize_str(deserializer, "a board fen")
+        } else {
+            let (white, black, pawns, knights, bishops, rooks, queens, kings) =
+                <(
+                    Bitboard,
+                    Bitboard,
+                    Bitboard,
+                    Bitboard,
+                    Bitboard,
+                    Bitboard,
+                    Bitboard,
+                    Bitboard,
+                )>::deserialize(deserializer)?;
+            let mut board = Board::empty();
+            let mut occupied = Bitboard(0);
+            for (role, bitboard) in Role::ALL
+                .into_iter()
+                .zip([pawns, knights, bishops, rooks, queens, kings])
+            {
+                if (occupied & bitboard).any() {
+                    return Err(de::Error::custom("overlapping roles in board"));
+                }
+                occupied |= bitboard;
+                for sq in bitboard {
+                    board.set_piece_at(sq, role.of(Color::from_white(white.contains(sq))));
+                }
+            }
+            if (white & black).any() || white | black != occupied {
+                return Err(de::Error::custom("inconsistent colors in board"));
+            }
+            Ok(board)
+        }
+    }
+}
+
+impl Serialize for Outcome {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u8(match *self {
+                Outcome::Draw => 0,
+                Outcome::Decisive {
+                    winner: Color::White,
+                } => 1,
+                Outcome::Decisive {
+                    winner: Color::Black,
+                } => 2,
+            })
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Outcome {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Outcome, D::Error> {
+        if deserializer.is_human_readable() {
+            deserialize_str(deserializer, "1-0, 0-1 or 1/2-1/2")
+        } else {
+            deserialize_u8(deserializer, "0, 1 or 2", |n| match n {
+                0 => Some(Outcome::Draw),
+                1 => Some(Outcome::Decisive {
+                    winner: Color::White,
+                }),
+                2 => Some(Outcome::Decisive {
+                    winner: Color::Black,
+                }),
+                _ => None,
+            })
+        }
+    }
+}
+
+impl Serialize for Fen {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fen {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Fen, D::Error> {
+        deserialize_str(deserializer, "a fen")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Move", rename = "Move")]
+enum MoveDef {
+    Normal {
+        role: Role,
+        from: Square,
+        capture: Option<Role>,
+        to: Square,
+        promotion: Option<Role>,
+    },
+    EnPassant {
+        from: Square,
+        to: Square,
+    },
+    Castle {
+        king: Square,
+        rook: Square,
+    },
+    Put {
+        role: Role,
+        to: Square,
+    },
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoveDef::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        MoveDef::deserialize(deserializer)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ByColor", rename = "ByColor")]
+struct ByColorDef<T> {
+    black: T,
+    white: T,
+}
+
+impl<T: Serialize> Serialize for ByColor<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ByColorDef::serialize(self, serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ByColor<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByColor<T>, D::Error> {
+        ByColorDef::deserialize(deserializer)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ByRole", rename = "ByRole")]
+struct ByRoleDef<T> {
+    pawn: T,
+    knight: T,
+    bishop: T,
+    rook: T,
+    queen: T,
+    king: T,
+}
+
+impl<T: Serialize> Serialize for ByRole<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ByRoleDef::serialize(self, serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ByRole<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByRole<T>, D::Error> {
+        ByRoleDef::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_test::{assert_de_tokens_error, assert_tokens, Compact, Configure, Readable, Token};
+
+    use super::*;
+
+    #[test]
+    fn test_readable() {
+        assert_tokens(&Square::E4.readable(), &[Token::Str("e4")]);
+        assert_tokens(&Color::White.readable(), &[Token::Str("white")]);
+        assert_tokens(&Role::Queen.readable(), &[Token::Char('q')]);
+        assert_tokens(&Color::White.queen().readable(), &[Token::Char('Q')]);
+        assert_tokens(&Bitboard(42).readable(), &[Token::U64(42)]);
+        assert_tokens(
+            &Board::default().readable(),
+            &[Token::Str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR")],
+        );
+        assert_tokens(&Outcome::Draw.readable(), &[Token::Str("1/2-1/2")]);
+        assert_tokens(
+            &Fen::default(),
+            &[Token::Str(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            )],
+        );
+        assert_de_tokens_error::<Readable<Square>>(
+            &[Token::Str("e9")],
+            "invalid value: string \"e9\", expected a square name like e4",
+        );
+    }
+
+    #[test]
+    fn test_compact() {
+        assert_tokens(&Square::E4.compact(), &[Token::U8(28)]);
+        assert_tokens(&Color::Black.compact(), &[Token::U8(0)]);
+        assert_tokens(&Role::King.compact(), &[Token::U8(6)]);
+        assert_tokens(&Color::White.rook().compact(), &[Token::U8(12)]);
+        assert_tokens(&Color::Black.pawn().compact(), &[Token::U8(1)]);
+        assert_tokens(
+            &Outcome::Decisive {
+                winner: Color::Black,
+            }
+            .compact(),
+            &[Token::U8(2)],
+        );
+        assert_de_tokens_error::<Compact<Piece>>(
+            &[Token::U8(7)],
+            "invalid value: integer `7`, expected a piece code",
+        );
+
+        let board = Board::default();
+        let tokens = [
+            Token::Tuple { len: 8 },
+            Token::U64(board.white().0),
+            Token::U64(board.black().0),
+            Token::U64(board.pawns().0),
+            Token::U64(board.knights().0),
+            Token::U64(board.bishops().0),
+            Token::U64(board.rooks().0),
+            Token::U64(board.queens().0),
+            Token::U64(board.kings().0),
+            Token::TupleEnd,
+        ];
+        assert_tokens(&board.clone().compact(), &tokens);
+        let mut invalid = tokens;
+        invalid[1] = Token::U64(board.white().0 | board.black().0);
+        assert_de_tokens_error::<Compact<Board>>(&invalid, "inconsistent colors in board");
+    }
+
+    #[test]
+    fn test_derived() {
+        let m = Move::Normal {
+            role: Role::Pawn,
+            from: Square::E7,
+            capture: Some(Role::Rook),
+            to: Square::F8,
+            promotion: Some(Role::Queen),
+        };
+        assert_tokens(
+            &m.readable(),
+            &[
+                Token::StructVariant {
+                    name: "Move",
+                    variant: "Normal",
+                    len: 5,
+                },
+                Token::Str("role"),
+                Token::Char('p'),
+                Token::Str("from"),
+                Token::Str("e7"),
+                Token::Str("capture"),
+                Token::Some,
+                Token::Char('r'),
+                Token::Str("to"),
+                Token::Str("f8"),
+                Token::Str("promotion"),
+                Token::Some,
+                Token::Char('q'),
+                Token::StructVariantEnd,
+            ],
+        );
+
+        let material: ByColor<ByRole<u8>> = Board::default().material();
+        let side = |name| {
+            [
+                Token::Str(name),
+                Token::Struct {
+                    name: "ByRole",
+                    len: 6,
+                },
+                Token::Str("pawn"),
+                Token::U8(8),
+                Token::Str("knight"),
+                Token::U8(2),
+                Token::Str("bishop"),
+                Token::U8(2),
+                Token::Str("rook"),
+                Token::U8(2),
+                Token::Str("queen"),
+                Token::U8(1),
+                Token::Str("king"),
+                Token::U8(1),
+                Token::StructEnd,
+            ]
+        };
+        let mut tokens = vec![Token::Struct {
+            name: "ByColor",
+            len: 2,
+        }];
+        tokens.extend(side("black"));
+        tokens.extend(side("white"));
+        tokens.push(Token::StructEnd);
+        assert_tokens(&material, &tokens);
+    }
+}
@@ -14,11 +14,12 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::num::NonZeroU32;
+use std::{error::Error, fmt, num::NonZeroU32};
 
 use crate::{
-    attacks, Bitboard, Board, ByColor, ByRole, CastlingMode, CastlingSide, Color, File, FromSetup,
-    PositionError, Rank, RemainingChecks, Square,
+    attacks, Bitboard, Board, ByColor, ByRole, CastlingMode, CastlingSide, Color, ErrorKind, File,
+    FromSetup, Odds, Piece, Position, PositionError, PositionErrorKinds, Rank, RemainingChecks,
+    Square,
 };
 
 /// A not necessarily legal position.
@@ -108,14 +109,149 @@ impl Setup {
         }
     }
 
+    /// The standard starting position, with `giver` giving `odds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, Color, Odds, Setup};
+    ///
+    /// let setup = Setup::odds(Odds::Rook, Color::White);
+    /// assert_eq!(
+    ///     Fen(setup).to_string(),
+    ///     "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/1NBQKBNR w Kkq - 0 1"
+    /// );
+    /// ```
+    pub fn odds(odds: Odds, giver: Color) -> Setup {
+        let mut setup = Setup::default();
+        let square = odds.square(giver);
+        setup.board.discard_piece_at(square);
+        setup.castling_rights.discard(square);
+        if odds == Odds::PawnAndMove {
+            setup.turn = !giver;
+        }
+        setup
+    }
+
     pub fn swap_turn(&mut self) {
         self.turn = !self.turn;
         self.ep_square = None;
     }
 
+    /// Swap colors and mirror the board vertically, so that the resulting
+    /// setup is equivalent with the roles of the players reversed (in
+    /// variants where the rules are symmetric).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{Color, Setup};
+    ///
+    /// let mut setup = Setup::default();
+    /// setup.mirror();
+    /// assert_eq!(setup.turn, Color::Black);
+    /// setup.turn = Color::White;
+    /// assert_eq!(setup, Setup::default());
+    /// ```
+    pub fn mirror(&mut self) {
+        self.board.flip_vertical();
+        self.board.swap_colors();
+        self.promoted = self.promoted.flip_vertical();
+        if let Some(ref mut pockets) = self.pockets {
+            pockets.flip();
+        }
+        self.turn = !self.turn;
+        self.castling_rights = self.castling_rights.flip_vertical();
+        self.ep_square = self.ep_square.map(Square::flip_vertical);
+        if let Some(ref mut remaining_checks) = self.remaining_checks {
+            remaining_checks.flip();
+        }
+    }
+
+    /// Mirror the board horizontally. Note that castling rights are
+    /// mirrored as well, which changes their meaning.
+    pub fn flip_horizontal(&mut self) {
+        self.board.flip_horizontal();
+        self.promoted = self.promoted.flip_horizontal();
+        self.castling_rights = self.castling_rights.flip_horizontal();
+        self.ep_square = self.ep_square.map(Square::flip_horizontal);
+    }
+
     pub fn position<P: FromSetup>(self, mode: CastlingMode) -> Result<P, PositionError<P>> {
         P::from_setup(self, mode)
     }
+
+    /// Suggests changes that fix some of the reasons why the setup is not a
+    /// legal position `P`. The repairs can be applied selectively with
+    /// [`Repair::apply()`]. Returns an empty list if the setup is legal,
+    /// or if there are no suggestions.
+    ///
+    /// Each suggested repair fixes at least one
+    /// [`PositionErrorKinds::INVALID_EP_SQUARE`],
+    /// [`PositionErrorKinds::INVALID_CASTLING_RIGHTS`], or
+    /// [`PositionErrorKinds::IMPOSSIBLE_CHECK`] on its own, except that all
+    /// invalid castling rights need to be discarded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, CastlingMode, Chess, Repair, Square};
+    ///
+    /// let mut setup = "4k3/8/8/8/8/8/8/4K2R w KQ e6 0 1".parse::<Fen>()?.into_setup();
+    /// let repairs = setup.repairs::<Chess>(CastlingMode::Standard);
+    /// assert_eq!(
+    ///     repairs,
+    ///     [Repair::ClearEpSquare, Repair::DiscardCastlingRight(Square::A1)]
+    /// );
+    ///
+    /// for repair in repairs {
+    ///     repair.apply(&mut setup);
+    /// }
+    /// assert!(setup.position::<Chess>(CastlingMode::Standard).is_ok());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn repairs<P: FromSetup + Position>(&self, mode: CastlingMode) -> Vec<Repair> {
+        let err = match P::from_setup(self.clone(), mode) {
+            Ok(_) => return Vec::new(),
+            Err(err) => err,
+        };
+        let errors = err.kinds();
+
+        let improves = |repair: Repair| {
+            let mut setup = self.clone();
+            repair.apply(&mut setup);
+            let after = P::from_setup(setup, mode)
+                .map_or_else(|err| err.kinds(), |_| PositionErrorKinds::empty());
+            errors.contains(after) && after != errors
+        };
+
+        let mut repairs = Vec::new();
+
+        if self.ep_square.is_some()
+            && errors.intersects(
+                PositionErrorKinds::INVALID_EP_SQUARE | PositionErrorKinds::IMPOSSIBLE_CHECK,
+            )
+            && improves(Repair::ClearEpSquare)
+        {
+            repairs.push(Repair::ClearEpSquare);
+        }
+
+        if errors.contains(PositionErrorKinds::INVALID_CASTLING_RIGHTS) {
+            for rook in self.castling_rights & !err.pos.castles().castling_rights() {
+                repairs.push(Repair::DiscardCastlingRight(rook));
+            }
+        }
+
+        if errors.contains(PositionErrorKinds::IMPOSSIBLE_CHECK) {
+            for checker in err.pos.checkers() {
+                if improves(Repair::RemovePiece(checker)) {
+                    repairs.push(Repair::RemovePiece(checker));
+                }
+            }
+        }
+
+        repairs
+    }
 }
 
 impl Default for Setup {
@@ -128,6 +264,184 @@ impl Default for Setup {
     }
 }
 
+/// Builds a [`Setup`] step by step, starting from an empty board.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{Chess, CastlingMode, Color::*, PositionErrorKinds, SetupBuilder, Square};
+///
+/// let builder = SetupBuilder::new()
+///     .piece(Square::E1, White.king())
+///     .piece(Square::E8, Black.king())
+///     .piece(Square::E4, White.queen())
+///     .turn(Black)
+///     .ep_square(Some(Square::E3));
+///
+/// // The en passant square is invalid, but can be downgraded to a warning.
+/// let (pos, warnings) = builder
+///     .position::<Chess>(CastlingMode::Standard, PositionErrorKinds::INVALID_EP_SQUARE)?;
+/// assert_eq!(warnings, PositionErrorKinds::INVALID_EP_SQUARE);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SetupBuilder {
+    setup: Setup,
+}
+
+impl SetupBuilder {
+    /// Starts with [`Setup::empty()`].
+    pub fn new() -> SetupBuilder {
+        SetupBuilder {
+            setup: Setup::empty(),
+        }
+    }
+
+    /// Puts a piece on the square, replacing any previous piece.
+    #[must_use]
+    pub fn piece(mut self, square: Square, piece: Piece) -> SetupBuilder {
+        self.setup.board.set_piece_at(square, piece);
+        self
+    }
+
+    /// Removes any piece from the square.
+    #[must_use]
+    pub fn empty_square(mut self, square: Square) -> SetupBuilder {
+        self.setup.board.discard_piece_at(square);
+        self.setup.promoted.discard(square);
+        self
+    }
+
+    /// Marks the piece on the square as promoted, as tracked in Crazyhouse.
+    #[must_use]
+    pub fn promoted(mut self, square: Square) -> SetupBuilder {
+        self.setup.promoted.add(square);
+        self
+    }
+
+    #[must_use]
+    pub fn pockets(mut self, pockets: Option<ByColor<ByRole<u8>>>) -> SetupBuilder {
+        self.setup.pockets = pockets;
+        self
+    }
+
+    #[must_use]
+    pub fn turn(mut self, turn: Color) -> SetupBuilder {
+        self.setup.turn = turn;
+        self
+    }
+
+    /// Sets the castling rights in terms of the corresponding rook
+    /// positions. See [`Setup::castling_rights`].
+    #[must_use]
+    pub fn castling(mut self, rooks: Bitboard) -> SetupBuilder {
+        self.setup.castling_rights = rooks;
+        self
+    }
+
+    #[must_use]
+    pub fn ep_square(mut self, ep_square: Option<Square>) -> SetupBuilder {
+        self.setup.ep_square = ep_square;
+        self
+    }
+
+    #[must_use]
+    pub fn remaining_checks(
+        mut self,
+        remaining_checks: Option<ByColor<RemainingChecks>>,
+    ) -> SetupBuilder {
+        self.setup.remaining_checks = remaining_checks;
+        self
+    }
+
+    #[must_use]
+    pub fn halfmoves(mut self, halfmoves: u32) -> SetupBuilder {
+        self.setup.halfmoves = halfmoves;
+        self
+    }
+
+    #[must_use]
+    pub fn fullmoves(mut self, fullmoves: NonZeroU32) -> SetupBuilder {
+        self.setup.fullmoves = fullmoves;
+        self
+    }
+
+    /// Gets the setup.
+    pub fn build(self) -> Setup {
+        self.setup
+    }
+
+    /// Sets up a position, downgrading the reasons in `allow` to warnings.
+    /// Returns the position together with the reasons that were ignored.
+    ///
+    /// Only [`PositionErrorKinds::INVALID_CASTLING_RIGHTS`],
+    /// [`PositionErrorKinds::INVALID_EP_SQUARE`],
+    /// [`PositionErrorKinds::IMPOSSIBLE_MATERIAL`] (for example more than
+    /// 8 pawns), and [`PositionErrorKinds::IMPOSSIBLE_CHECK`] can be
+    /// downgraded. Other reasons are always errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PositionError`] if the setup is not a legal position,
+    /// even when ignoring the reasons in `allow`.
+    pub fn position<P: FromSetup>(
+        self,
+        mode: CastlingMode,
+        allow: PositionErrorKinds,
+    ) -> Result<(P, PositionErrorKinds), PositionError<P>> {
+        let allow = allow
+            & (PositionErrorKinds::INVALID_CASTLING_RIGHTS
+                | PositionErrorKinds::INVALID_EP_SQUARE
+                | PositionErrorKinds::IMPOSSIBLE_MATERIAL
+                | PositionErrorKinds::IMPOSSIBLE_CHECK);
+        match P::from_setup(self.setup, mode) {
+            Ok(pos) => Ok((pos, PositionErrorKinds::empty())),
+            Err(err) => {
+                let warnings = err.kinds() & allow;
+                err.ignore(warnings).map(|pos| (pos, warnings))
+            }
+        }
+    }
+}
+
+impl Default for SetupBuilder {
+    fn default() -> SetupBuilder {
+        SetupBuilder::new()
+    }
+}
+
+impl From<Setup> for SetupBuilder {
+    fn from(setup: Setup) -> SetupBuilder {
+        SetupBuilder { setup }
+    }
+}
+
+/// Change to a [`Setup`], suggested by [`Setup::repairs()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Repair {
+    /// Clear the en passant square.
+    ClearEpSquare,
+    /// Discard the castling right associated with the rook on the square.
+    DiscardCastlingRight(Square),
+    /// Remove the piece on the square, for example an impossible checker.
+    RemovePiece(Square),
+}
+
+impl Repair {
+    /// Applies the change to the setup.
+    pub fn apply(self, setup: &mut Setup) {
+        match self {
+            Repair::ClearEpSquare => setup.ep_square = None,
+            Repair::DiscardCastlingRight(rook) => setup.castling_rights.discard(rook),
+            Repair::RemovePiece(square) => {
+                setup.board.discard_piece_at(square);
+                setup.promoted.discard(square);
+                setup.castling_rights.discard(square);
+            }
+        }
+    }
+}
+
 /// Castling paths and unmoved rooks.
 #[derive(Clone, Debug)]
 pub struct Castles {
@@ -234,6 +548,96 @@ impl Castles {
         }
     }
 
+    /// Reconstructs Chess960 castling rights from the castling part of a
+    /// possibly sloppy FEN, given only the `board`.
+    ///
+    /// Each character in `rights` is either `K`/`Q` (the outermost rook on
+    /// the king or queen side), a file letter from `A` to `H` (the rook on
+    /// that file), or `-`. Lowercase characters are rights of black. The side
+    /// of a rook is always decided relative to the actual position of the
+    /// king, and repeated rights are merged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RepairCastlingError`] if a right can not be matched to a
+    /// king and rook on the back rank, or if two different rooks claim the
+    /// same side.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{fen::Fen, Castles, CastlingSide, Color, Square};
+    ///
+    /// // K denotes the outermost rook, even though it is not on the h-file.
+    /// let fen: Fen = "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w - - 0 1".parse()?;
+    /// let castles = Castles::repair(&fen.0.board, b"KQbg")?;
+    /// assert_eq!(castles.rook(Color::White, CastlingSide::KingSide), Some(Square::G1));
+    /// assert_eq!(castles.rook(Color::Black, CastlingSide::QueenSide), Some(Square::B8));
+    /// assert_eq!(castles.castling_rights().count(), 4);
+    ///
+    /// assert!(Castles::repair(&fen.0.board, b"Kc").is_err());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn repair(board: &Board, rights: &[u8]) -> Result<Castles, RepairCastlingError> {
+        let mut claimed: ByColor<[Option<Square>; 2]> = ByColor::default();
+
+        for &ch in rights {
+            if ch == b'-' {
+                continue;
+            }
+
+            let color = Color::from_white(ch.is_ascii_uppercase());
+            let king = board
+                .king_of(color)
+                .filter(|king| king.rank() == color.backrank())
+                .ok_or(RepairCastlingError::NoKing { color })?;
+            if king.file() == File::A || king.file() == File::H {
+                return Err(RepairCastlingError::KingCannotCastle { color });
+            }
+            let rooks = board.rooks() & board.by_color(color) & color.backrank();
+
+            let (side, rook) = match ch.to_ascii_lowercase() {
+                b'k' => (
+                    CastlingSide::KingSide,
+                    rooks.last().filter(|rook| king < *rook),
+                ),
+                b'q' => (
+                    CastlingSide::QueenSide,
+                    rooks.first().filter(|rook| *rook < king),
+                ),
+                file => {
+                    let file = File::from_char(char::from(file))
+                        .ok_or(RepairCastlingError::InvalidRight { ch: char::from(ch) })?;
+                    let square = Square::from_coords(file, color.backrank());
+                    (
+                        CastlingSide::from_queen_side(file < king.file()),
+                        Some(square).filter(|sq| rooks.contains(*sq)),
+                    )
+                }
+            };
+
+            let rook = rook.ok_or(RepairCastlingError::NoRook { color, side })?;
+            let slot = &mut claimed.get_mut(color)[side as usize];
+            if slot.map_or(false, |existing| existing != rook) {
+                return Err(RepairCastlingError::Conflict { color, side });
+            }
+            *slot = Some(rook);
+        }
+
+        let mut setup = Setup::empty();
+        setup.board = board.clone();
+        for color in Color::ALL {
+            for rook in claimed.get(color).iter().flatten() {
+                setup.castling_rights.add(*rook);
+            }
+        }
+
+        // Every claimed rook has been matched to a king that can castle
+        // towards it, so all rights are accepted.
+        Ok(Castles::from_setup(&setup, CastlingMode::Chess960)
+            .expect("repaired castling rights are consistent"))
+    }
+
     pub fn any(&self) -> bool {
         self.mask.any()
     }
@@ -326,6 +730,62 @@ impl Castles {
     }
 }
 
+/// Error when castling rights can not be repaired. See [`Castles::repair()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RepairCastlingError {
+    /// Character that does not denote a castling right.
+    InvalidRight { ch: char },
+    /// No king on the back rank that could castle.
+    NoKing { color: Color },
+    /// King on the a-file or h-file, which can not castle.
+    KingCannotCastle { color: Color },
+    /// No rook that matches the claimed castling right.
+    NoRook { color: Color, side: CastlingSide },
+    /// Two different rooks claim the same side.
+    Conflict { color: Color, side: CastlingSide },
+}
+
+impl fmt::Display for RepairCastlingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RepairCastlingError::InvalidRight { ch } => {
+                write!(f, "invalid castling right {:?}", ch)
+            }
+            RepairCastlingError::NoKing { color } => {
+                write!(f, "no {} king that could castle", color)
+            }
+            RepairCastlingError::KingCannotCastle { color } => {
+                write!(f, "{} king on the a-file or h-file can not castle", color)
+            }
+            RepairCastlingError::NoRook { color, side } => {
+                write!(f, "no {} rook for castling {}", color, side_name(side))
+            }
+            RepairCastlingError::Conflict { color, side } => write!(
+                f,
+                "conflicting {} castling rights {}",
+                color,
+                side_name(side)
+            ),
+        }
+    }
+}
+
+fn side_name(side: CastlingSide) -> &'static str {
+    match side {
+        CastlingSide::KingSide => "kingside",
+        CastlingSide::QueenSide => "queenside",
+    }
+}
+
+impl Error for RepairCastlingError {}
+
+impl RepairCastlingError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidCastling
+    }
+}
+
 /// En passant square on the third or sixth rank.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct EnPassant(pub Square);
@@ -379,3 +839,132 @@ impl EnPassant {
         self.0.xor(Square::A2)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::Fen, Chess};
+
+    struct _AssertSendSync<T: Clone + Eq + Send + Sync + 'static>(T);
+    struct _AssertSetupOwned(_AssertSendSync<Setup>);
+
+    fn board(fen: &str) -> Board {
+        fen.parse::<Fen>().expect("valid fen").0.board
+    }
+
+    #[test]
+    fn test_setup_builder() {
+        let builder = SetupBuilder::new()
+            .piece(Square::E1, Color::White.king())
+            .piece(Square::H1, Color::White.rook())
+            .piece(Square::E8, Color::Black.king())
+            .castling(Bitboard::from(Square::H1) | Square::A1);
+
+        let setup = builder.clone().build();
+        assert_eq!(
+            Fen::from_setup(setup).to_string(),
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1"
+        );
+
+        assert_eq!(
+            builder
+                .clone()
+                .position::<Chess>(CastlingMode::Standard, PositionErrorKinds::empty())
+                .expect_err("invalid castling rights")
+                .kinds(),
+            PositionErrorKinds::INVALID_CASTLING_RIGHTS
+        );
+        let (pos, warnings) = builder
+            .clone()
+            .position::<Chess>(CastlingMode::Standard, PositionErrorKinds::all())
+            .expect("castling rights downgraded");
+        assert_eq!(warnings, PositionErrorKinds::INVALID_CASTLING_RIGHTS);
+        assert_eq!(pos.castles().castling_rights(), Bitboard::from(Square::H1));
+
+        // Missing kings can not be downgraded.
+        assert!(builder
+            .empty_square(Square::E8)
+            .position::<Chess>(CastlingMode::Standard, PositionErrorKinds::all())
+            .is_err());
+    }
+
+    #[test]
+    fn test_repairs() {
+        let setup = |fen: &str| fen.parse::<Fen>().expect("valid fen").into_setup();
+
+        assert!(Setup::default()
+            .repairs::<Chess>(CastlingMode::Standard)
+            .is_empty());
+
+        let mut checked = setup("4k3/4r3/8/8/8/3n1n2/8/4K3 w - - 0 1");
+        let repairs = checked.repairs::<Chess>(CastlingMode::Standard);
+        assert_eq!(
+            repairs,
+            [
+                Repair::RemovePiece(Square::D3),
+                Repair::RemovePiece(Square::F3),
+                Repair::RemovePiece(Square::E7),
+            ]
+        );
+        repairs[2].apply(&mut checked);
+        assert!(checked.position::<Chess>(CastlingMode::Standard).is_ok());
+
+        let pushed = setup("4k2R/8/8/8/3Pp3/8/8/6K1 b - d3 0 1");
+        assert_eq!(
+            pushed.repairs::<Chess>(CastlingMode::Standard),
+            [Repair::ClearEpSquare, Repair::RemovePiece(Square::H8)]
+        );
+    }
+
+    #[test]
+    fn test_repair_castles() {
+        let standard = board("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1");
+        let castles = Castles::repair(&standard, b"KQkqHA-").expect("repaired");
+        assert_eq!(castles.castling_rights(), Bitboard::CORNERS);
+
+        let inner = board("rr2k3/8/8/8/8/8/8/4K1RR w - - 0 1");
+        let castles = Castles::repair(&inner, b"Gb").expect("repaired");
+        assert_eq!(
+            castles.rook(Color::White, CastlingSide::KingSide),
+            Some(Square::G1)
+        );
+        assert_eq!(
+            castles.rook(Color::Black, CastlingSide::QueenSide),
+            Some(Square::B8)
+        );
+        assert_eq!(
+            Castles::repair(&inner, b"GH").unwrap_err(),
+            RepairCastlingError::Conflict {
+                color: Color::White,
+                side: CastlingSide::KingSide
+            }
+        );
+        assert_eq!(
+            Castles::repair(&inner, b"Q").unwrap_err(),
+            RepairCastlingError::NoRook {
+                color: Color::White,
+                side: CastlingSide::QueenSide
+            }
+        );
+        assert_eq!(
+            Castles::repair(&inner, b"x").unwrap_err(),
+            RepairCastlingError::InvalidRight { ch: 'x' }
+        );
+
+        let corner_king = board("4k3/8/8/8/8/8/8/K6R w - - 0 1");
+        assert_eq!(
+            Castles::repair(&corner_king, b"K").unwrap_err(),
+            RepairCastlingError::KingCannotCastle {
+                color: Color::White
+            }
+        );
+
+        let black_corner_king = board("k6r/8/8/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(
+            Castles::repair(&black_corner_king, b"Qk").unwrap_err(),
+            RepairCastlingError::KingCannotCastle {
+                color: Color::Black
+            }
+        );
+    }
+}
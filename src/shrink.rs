@@ -0,0 +1,152 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Shrink move sequences that reproduce a bug.
+//!
+//! Discrepancies in move generation or hashing are often found after long
+//! random games. [`shrink_moves()`] searches for a shorter sequence of
+//! legal moves that still has the same property.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{shrink::shrink_moves, uci::Uci, Chess, Position, Role, Square};
+//!
+//! let pos = Chess::default();
+//! let mut moves = Vec::new();
+//! let mut after = pos.clone();
+//! for uci in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "d2d3", "f8c5"] {
+//!     let m = uci.parse::<Uci>()?.to_move(&after)?;
+//!     after.play_unchecked(&m);
+//!     moves.push(m);
+//! }
+//!
+//! // Find a shorter game where the white bishop reaches c4.
+//! let shrunk = shrink_moves(&pos, &moves, |pos, _| {
+//!     pos.board().role_at(Square::C4) == Some(Role::Bishop)
+//! });
+//! assert_eq!(shrunk.len(), 3);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::{position::Position, types::Move};
+
+/// Plays `moves` from `pos`, or returns `None` if any of them is illegal.
+fn replay<P: Position + Clone>(pos: &P, moves: &[Move]) -> Option<P> {
+    let mut pos = pos.clone();
+    for m in moves {
+        if !pos.is_legal(m) {
+            return None;
+        }
+        pos.play_unchecked(m);
+    }
+    Some(pos)
+}
+
+/// Finds a short sequence of legal moves from `pos` that still satisfies
+/// `predicate`.
+///
+/// The predicate is called with the position after playing a candidate
+/// sequence, and the candidate sequence itself. Only subsequences of
+/// `moves` that are legal from `pos` are considered.
+///
+/// First finds the shortest prefix satisfying the predicate, then greedily
+/// removes chunks of moves, halving the chunk size down to single moves.
+/// The result is not necessarily the shortest possible sequence, but no
+/// single move can be removed from it. If `moves` itself is illegal or
+/// does not satisfy the predicate, it is returned unchanged.
+pub fn shrink_moves<P, F>(pos: &P, moves: &[Move], mut predicate: F) -> Vec<Move>
+where
+    P: Position + Clone,
+    F: FnMut(&P, &[Move]) -> bool,
+{
+    let mut test = |candidate: &[Move]| {
+        replay(pos, candidate).map_or(false, |after| predicate(&after, candidate))
+    };
+
+    if !test(moves) {
+        return moves.to_vec();
+    }
+
+    let len = (0..moves.len())
+        .find(|&len| test(&moves[..len]))
+        .unwrap_or(moves.len());
+    let mut moves = moves[..len].to_vec();
+
+    let mut chunk = moves.len() / 2;
+    while chunk > 0 {
+        let mut i = 0;
+        while i < moves.len() {
+            let end = (i + chunk).min(moves.len());
+            let candidate: Vec<Move> = moves[..i].iter().chain(&moves[end..]).cloned().collect();
+            if test(&candidate) {
+                moves = candidate;
+            } else {
+                i += chunk;
+            }
+        }
+        chunk /= 2;
+    }
+
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::Fen, uci::Uci, CastlingMode, Chess};
+
+    #[test]
+    fn test_shrink_moves() {
+        let pos = Chess::default();
+        let mut moves = Vec::new();
+        let mut after = pos.clone();
+        for uci in [
+            "g1f3", "g8f6", "e2e4", "b8c6", "d2d4", "e7e5", "f1b5", "a7a6", "b5c6", "d7c6",
+        ] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&after)
+                .expect("legal");
+            after.play_unchecked(&m);
+            moves.push(m);
+        }
+
+        // Trading the bishop for the knight needs both to get to c6.
+        let shrunk = shrink_moves(&pos, &moves, |pos, _| {
+            pos.board().bishops().count() == 3 && pos.board().knights().count() == 3
+        });
+        assert_eq!(
+            shrunk
+                .iter()
+                .map(|m| m.to_uci(CastlingMode::Standard).to_string())
+                .collect::<Vec<_>>(),
+            ["e2e4", "b8c6", "f1b5", "a7a6", "b5c6", "d7c6"]
+        );
+
+        // Unchanged if the property does not hold.
+        assert_eq!(shrink_moves(&pos, &moves, |_, _| false), moves);
+
+        // Empty sequence if the property holds from the start.
+        let pos: Chess = "4k3/8/8/8/8/8/8/4K3 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal");
+        assert!(shrink_moves(&pos, &[], |pos, _| pos.is_insufficient_material()).is_empty());
+    }
+}
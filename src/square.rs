@@ -15,8 +15,6 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-    cmp::max,
-    convert::TryInto,
     error::Error,
     fmt::{self, Write as _},
     mem,
@@ -25,7 +23,7 @@ use std::{
     str,
 };
 
-use crate::util::overflow_error;
+use crate::{error::ErrorKind, util::overflow_error};
 
 macro_rules! from_repr_u8_impl {
     ($from:ty, $($t:ty)+) => {
@@ -122,7 +120,7 @@ impl File {
     ///
     /// Panics if the index is not in the range `0..=7`.
     #[inline]
-    pub fn new(index: u32) -> File {
+    pub const fn new(index: u32) -> File {
         assert!(index < 8);
         unsafe { File::new_unchecked(index) }
     }
@@ -134,59 +132,60 @@ impl File {
     /// It is the callers responsibility to ensure the index is in the range
     /// `0..=7`.
     #[inline]
-    pub unsafe fn new_unchecked(index: u32) -> File {
+    pub const unsafe fn new_unchecked(index: u32) -> File {
         debug_assert!(index < 8);
         unsafe { mem::transmute(index as u8) }
     }
 
     #[inline]
-    pub fn from_char(ch: char) -> Option<File> {
-        if ('a'..='h').contains(&ch) {
-            Some(File::new(u32::from(ch as u8 - b'a')))
+    pub const fn from_char(ch: char) -> Option<File> {
+        if 'a' <= ch && ch <= 'h' {
+            Some(File::new((ch as u8 - b'a') as u32))
         } else {
             None
         }
     }
 
     #[inline]
-    pub fn char(self) -> char {
-        char::from(b'a' + u8::from(self))
+    pub const fn char(self) -> char {
+        (b'a' + self as u8) as char
     }
 
     #[inline]
-    pub fn upper_char(self) -> char {
-        char::from(b'A' + u8::from(self))
+    pub const fn upper_char(self) -> char {
+        (b'A' + self as u8) as char
     }
 
     #[must_use]
     #[inline]
-    pub fn offset(self, delta: i32) -> Option<File> {
-        i32::from(self)
-            .checked_add(delta)
-            .and_then(|index| index.try_into().ok())
+    pub const fn offset(self, delta: i32) -> Option<File> {
+        match (self as i32).checked_add(delta) {
+            Some(index) if 0 <= index && index < 8 => Some(File::new(index as u32)),
+            _ => None,
+        }
     }
 
     #[inline]
-    pub fn distance(self, other: File) -> u32 {
-        u32::from(self).abs_diff(u32::from(other))
+    pub const fn distance(self, other: File) -> u32 {
+        (self as u32).abs_diff(other as u32)
     }
 
     #[must_use]
     #[inline]
-    pub fn flip_horizontal(self) -> File {
-        File::new(7 - u32::from(self))
+    pub const fn flip_horizontal(self) -> File {
+        File::new(7 - self as u32)
     }
 
     #[must_use]
     #[inline]
-    pub fn flip_diagonal(self) -> Rank {
-        Rank::new(u32::from(self))
+    pub const fn flip_diagonal(self) -> Rank {
+        Rank::new(self as u32)
     }
 
     #[must_use]
     #[inline]
-    pub fn flip_anti_diagonal(self) -> Rank {
-        Rank::new(7 - u32::from(self))
+    pub const fn flip_anti_diagonal(self) -> Rank {
+        Rank::new(7 - self as u32)
     }
 
     /// `A`, ..., `H`.
@@ -243,7 +242,7 @@ impl Rank {
     ///
     /// Panics if the index is not in the range `0..=7`.
     #[inline]
-    pub fn new(index: u32) -> Rank {
+    pub const fn new(index: u32) -> Rank {
         assert!(index < 8);
         unsafe { Rank::new_unchecked(index) }
     }
@@ -255,54 +254,55 @@ impl Rank {
     /// It is the callers responsibility to ensure the index is in the range
     /// `0..=7`.
     #[inline]
-    pub unsafe fn new_unchecked(index: u32) -> Rank {
+    pub const unsafe fn new_unchecked(index: u32) -> Rank {
         debug_assert!(index < 8);
         unsafe { mem::transmute(index as u8) }
     }
 
     #[inline]
-    pub fn from_char(ch: char) -> Option<Rank> {
-        if ('1'..='8').contains(&ch) {
-            Some(Rank::new(u32::from(ch as u8 - b'1')))
+    pub const fn from_char(ch: char) -> Option<Rank> {
+        if '1' <= ch && ch <= '8' {
+            Some(Rank::new((ch as u8 - b'1') as u32))
         } else {
             None
         }
     }
 
     #[inline]
-    pub fn char(self) -> char {
-        char::from(b'1' + u8::from(self))
+    pub const fn char(self) -> char {
+        (b'1' + self as u8) as char
     }
 
     #[must_use]
     #[inline]
-    pub fn offset(self, delta: i32) -> Option<Rank> {
-        i32::from(self)
-            .checked_add(delta)
-            .and_then(|index| index.try_into().ok())
+    pub const fn offset(self, delta: i32) -> Option<Rank> {
+        match (self as i32).checked_add(delta) {
+            Some(index) if 0 <= index && index < 8 => Some(Rank::new(index as u32)),
+            _ => None,
+        }
     }
 
     #[inline]
-    pub fn distance(self, other: Rank) -> u32 {
-        u32::from(self).abs_diff(u32::from(other))
+    pub const fn distance(self, other: Rank) -> u32 {
+        (self as u32).abs_diff(other as u32)
     }
 
     #[must_use]
     #[inline]
-    pub fn flip_vertical(self) -> Rank {
-        Rank::new(7 - u32::from(self))
+    pub const fn flip_vertical(self) -> Rank {
+        Rank::new(7 - self as u32)
     }
 
     #[must_use]
     #[inline]
-    pub fn flip_diagonal(self) -> File {
-        File::new(u32::from(self))
+    pub const fn flip_diagonal(self) -> File {
+        File::new(self as u32)
     }
 
     #[must_use]
     #[inline]
-    pub fn flip_anti_diagonal(self) -> File {
-        File::new(7 - u32::from(self))
+    pub const fn flip_anti_diagonal(self) -> File {
+        File::new(7 - self as u32)
     }
 
     /// `First`, ..., `Eighth`.
@@ -349,6 +349,13 @@ impl fmt::Display for ParseSquareError {
 
 impl Error for ParseSquareError {}
 
+impl ParseSquareError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidSquare
+    }
+}
+
 /// A square of the chessboard.
 #[rustfmt::skip]
 #[allow(missing_docs)]
@@ -407,10 +414,10 @@ impl Square {
     /// assert_eq!(Square::from_coords(File::A, Rank::First), Square::A1);
     /// ```
     #[inline]
-    pub fn from_coords(file: File, rank: Rank) -> Square {
+    pub const fn from_coords(file: File, rank: Rank) -> Square {
         // Safety: Files and ranks are represented with 3 bits each, and all
         // 6 bit values are in the range 0..=63.
-        unsafe { Square::new_unchecked(u32::from(file) | (u32::from(rank) << 3)) }
+        unsafe { Square::new_unchecked(file as u32 | ((rank as u32) << 3)) }
     }
 
     /// Parses a square name.
@@ -458,8 +465,8 @@ impl Square {
     /// assert_eq!(Square::B2.file(), File::B);
     /// ```
     #[inline]
-    pub fn file(self) -> File {
-        File::new(u32::from(self) & 7)
+    pub const fn file(self) -> File {
+        File::new(self as u32 & 7)
     }
 
     /// Gets the rank.
@@ -473,8 +480,8 @@ impl Square {
     /// assert_eq!(Square::B2.rank(), Rank::Second);
     /// ```
     #[inline]
-    pub fn rank(self) -> Rank {
-        Rank::new(u32::from(self) >> 3)
+    pub const fn rank(self) -> Rank {
+        Rank::new(self as u32 >> 3)
     }
 
     /// Gets file and rank.
@@ -488,7 +495,7 @@ impl Square {
     /// assert_eq!(Square::H8.coords(), (File::H, Rank::Eighth));
     /// ```
     #[inline]
-    pub fn coords(self) -> (File, Rank) {
+    pub const fn coords(self) -> (File, Rank) {
         (self.file(), self.rank())
     }
 
@@ -506,10 +513,11 @@ impl Square {
     /// ```
     #[must_use]
     #[inline]
-    pub fn offset(self, delta: i32) -> Option<Square> {
-        i32::from(self)
-            .checked_add(delta)
-            .and_then(|index| index.try_into().ok())
+    pub const fn offset(self, delta: i32) -> Option<Square> {
+        match (self as i32).checked_add(delta) {
+            Some(index) if 0 <= index && index < 64 => Some(Square::new(index as u32)),
+            _ => None,
+        }
     }
 
     /// Calculates the offset from a square index without checking for
@@ -521,18 +529,18 @@ impl Square {
     /// offset for `self`.
     #[must_use]
     #[inline]
-    pub unsafe fn offset_unchecked(self, delta: i32) -> Square {
+    pub const unsafe fn offset_unchecked(self, delta: i32) -> Square {
         debug_assert!(-64 < delta && delta < 64);
-        unsafe { Square::new_unchecked((i32::from(self) + delta) as u32) }
+        unsafe { Square::new_unchecked((self as i32 + delta) as u32) }
     }
 
     /// Return the bitwise XOR of the numeric square representations. For some
     /// operands this is a useful geometric transformation.
     #[must_use]
     #[inline]
-    pub fn xor(self, other: Square) -> Square {
+    pub const fn xor(self, other: Square) -> Square {
         // Safety: 6 bit value XOR 6 bit value -> 6 bit value.
-        unsafe { Square::new_unchecked(u32::from(self) ^ u32::from(other)) }
+        unsafe { Square::new_unchecked(self as u32 ^ other as u32) }
     }
 
     /// Flip the square horizontally.
@@ -546,7 +554,7 @@ impl Square {
     #[must_use]
     #[inline]
     #[allow(clippy::unusual_byte_groupings)]
-    pub fn flip_horizontal(self) -> Square {
+    pub const fn flip_horizontal(self) -> Square {
         self.xor(Square::H1)
     }
 
@@ -561,7 +569,7 @@ impl Square {
     #[must_use]
     #[inline]
     #[allow(clippy::unusual_byte_groupings)]
-    pub fn flip_vertical(self) -> Square {
+    pub const fn flip_vertical(self) -> Square {
         self.xor(Square::A8)
     }
 
@@ -575,11 +583,11 @@ impl Square {
     /// ```
     #[must_use]
     #[inline]
-    pub fn flip_diagonal(self) -> Square {
+    pub const fn flip_diagonal(self) -> Square {
         // See https://www.chessprogramming.org/Flipping_Mirroring_and_Rotating#Diagonal.
         // Safety: We are selecting 32 - 26 = 6 bits with the shift, and all
         // 6 bits values are in the range 0..=63.
-        unsafe { Square::new_unchecked(u32::from(self).wrapping_mul(0x2080_0000) >> 26) }
+        unsafe { Square::new_unchecked((self as u32).wrapping_mul(0x2080_0000) >> 26) }
     }
 
     /// Flip at the h1-a8 diagonal.
@@ -592,7 +600,7 @@ impl Square {
     /// ```
     #[must_use]
     #[inline]
-    pub fn flip_anti_diagonal(self) -> Square {
+    pub const fn flip_anti_diagonal(self) -> Square {
         self.flip_diagonal().rotate_180()
     }
 
@@ -606,7 +614,7 @@ impl Square {
     /// ```
     #[must_use]
     #[inline]
-    pub fn rotate_90(self) -> Square {
+    pub const fn rotate_90(self) -> Square {
         self.flip_diagonal().flip_vertical()
     }
 
@@ -621,7 +629,7 @@ impl Square {
     #[must_use]
     #[inline]
     #[allow(clippy::unusual_byte_groupings)]
-    pub fn rotate_180(self) -> Square {
+    pub const fn rotate_180(self) -> Square {
         self.xor(Square::H8)
     }
 
@@ -635,7 +643,7 @@ impl Square {
     /// ```
     #[must_use]
     #[inline]
-    pub fn rotate_270(self) -> Square {
+    pub const fn rotate_270(self) -> Square {
         self.flip_diagonal().flip_horizontal()
     }
 
@@ -648,8 +656,8 @@ impl Square {
     /// assert!(!Square::D8.is_light());
     /// ```
     #[inline]
-    pub fn is_light(self) -> bool {
-        (u32::from(self.rank()) + u32::from(self.file())) % 2 == 1
+    pub const fn is_light(self) -> bool {
+        (self.rank() as u32 + self.file() as u32) % 2 == 1
     }
 
     /// Tests is the square is a dark square.
@@ -661,8 +669,8 @@ impl Square {
     /// assert!(!Square::E8.is_dark());
     /// ```
     #[inline]
-    pub fn is_dark(self) -> bool {
-        (u32::from(self.rank()) + u32::from(self.file())) % 2 == 0
+    pub const fn is_dark(self) -> bool {
+        (self.rank() as u32 + self.file() as u32) % 2 == 0
     }
 
     /// The distance between the two squares, i.e. the number of king steps
@@ -673,11 +681,14 @@ impl Square {
     ///
     /// assert_eq!(Square::A2.distance(Square::B5), 3);
     /// ```
-    pub fn distance(self, other: Square) -> u32 {
-        max(
-            self.file().distance(other.file()),
-            self.rank().distance(other.rank()),
-        )
+    pub const fn distance(self, other: Square) -> u32 {
+        let files = self.file().distance(other.file());
+        let ranks = self.rank().distance(other.rank());
+        if files > ranks {
+            files
+        } else {
+            ranks
+        }
     }
 }
 
@@ -0,0 +1,897 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Studies: collections of annotated game trees.
+//!
+//! A [`Study`] holds multiple [`Chapter`]s, each a tree of moves with
+//! variations, comments and NAGs. This is how Lichess structures its
+//! studies: they are exported as one PGN game per chapter, with the
+//! `StudyName` and `ChapterName` tags identifying them.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{pgn::PgnWriter, study::Study};
+//!
+//! let pgn = b"[Event \"Openings: Italian\"]\n\
+//!     [Annotator \"Coach\"]\n\
+//!     [StudyName \"Openings\"]\n\
+//!     [ChapterName \"Italian\"]\n\
+//!     \n\
+//!     1. e4 e5 2. Nf3 Nc6 3. Bc4 { The Italian. } (3. Bb5) *\n\
+//!     \n\
+//!     [Event \"Openings: Sicilian\"]\n\
+//!     [Annotator \"Coach\"]\n\
+//!     [StudyName \"Openings\"]\n\
+//!     [ChapterName \"Sicilian\"]\n\
+//!     \n\
+//!     1. e4 c5 *\n";
+//!
+//! let study = Study::read_pgn(&pgn[..])?;
+//! assert_eq!(study.name, "Openings");
+//! assert_eq!(study.tags, [("Annotator".to_owned(), "Coach".to_owned())]);
+//! assert_eq!(study.chapters.len(), 2);
+//!
+//! let italian = study.chapter("Italian").expect("chapter");
+//! let mainline: Vec<String> = italian.mainline().map(|node| node.san_plus.to_string()).collect();
+//! assert_eq!(mainline, ["e4", "e5", "Nf3", "Nc6", "Bc4"]);
+//!
+//! let mut writer = PgnWriter::new(Vec::new());
+//! study.write_pgn(&mut writer)?;
+//! assert_eq!(Study::read_pgn(&writer.into_inner()[..])?, study);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{collections::HashMap, io, mem, time::Duration};
+
+use crate::{
+    annotation::{accuracy, format_eval, parse_eval, remove_eval, Eval, Score},
+    clock::{format_clk, parse_clk, remove_clk},
+    color::{ByColor, Color},
+    pgn::{Nag, PgnReader, PgnWriter, RawTag, Skip, Visitor},
+    position::{Outcome, Position},
+    san::SanPlus,
+    zobrist::ZobristHash,
+};
+
+/// A move in a game tree, with its annotations and continuations.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Node {
+    /// The move leading to this node.
+    pub san_plus: SanPlus,
+    /// Comments before the move. Only the first move of a variation can
+    /// have starting comments in PGN.
+    pub starting_comments: Vec<String>,
+    /// NAGs of the move.
+    pub nags: Vec<Nag>,
+    /// Comments after the move.
+    pub comments: Vec<String>,
+    /// Continuations. The first one is the main line, the others are
+    /// variations.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// Creates a node without annotations and continuations.
+    pub fn new(san_plus: SanPlus) -> Node {
+        Node {
+            san_plus,
+            starting_comments: Vec::new(),
+            nags: Vec::new(),
+            comments: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Gets the engine evaluation from the `[%eval]` command in the
+    /// comments after the move.
+    pub fn eval(&self) -> Option<Eval> {
+        self.comments
+            .iter()
+            .find_map(|comment| parse_eval(comment.as_bytes()))
+    }
+
+    /// Replaces the engine evaluation in the comments after the move, or
+    /// removes it if `eval` is `None`. Comments that become empty are
+    /// removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{annotation::{Eval, Score}, san::SanPlus, study::Node};
+    ///
+    /// let mut node = Node::new("e4".parse::<SanPlus>()?);
+    /// node.comments.push(" Best by test. ".to_owned());
+    ///
+    /// let mut eval = Eval::new(Score::Centipawns(25));
+    /// eval.depth = Some(30);
+    /// node.set_eval(Some(eval.clone()));
+    /// assert_eq!(node.comments, [" [%eval 0.25,30] Best by test. "]);
+    /// assert_eq!(node.eval(), Some(eval));
+    ///
+    /// node.set_eval(None);
+    /// assert_eq!(node.comments, ["  Best by test. "]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_eval(&mut self, eval: Option<Eval>) {
+        for comment in &mut self.comments {
+            if parse_eval(comment.as_bytes()).is_some() || comment.contains("[%engine ") {
+                *comment = remove_eval(comment);
+            }
+        }
+        self.comments.retain(|comment| !comment.trim().is_empty());
+        if let Some(eval) = eval {
+            self.insert_command(&format_eval(&eval));
+        }
+    }
+
+    /// Gets the remaining time of the player who made the move from the
+    /// `[%clk]` command in the comments after the move.
+    pub fn clock(&self) -> Option<Duration> {
+        self.comments
+            .iter()
+            .find_map(|comment| parse_clk(comment.as_bytes()))
+    }
+
+    /// Replaces the `[%clk]` command in the comments after the move, or
+    /// removes it if `clock` is `None`. Comments that become empty are
+    /// removed.
+    pub fn set_clock(&mut self, clock: Option<Duration>) {
+        for comment in &mut self.comments {
+            if comment.contains("[%clk ") {
+                *comment = remove_clk(comment);
+            }
+        }
+        self.comments.retain(|comment| !comment.trim().is_empty());
+        if let Some(clock) = clock {
+            self.insert_command(&format_clk(clock));
+        }
+    }
+
+    fn insert_command(&mut self, command: &str) {
+        match self.comments.first_mut() {
+            Some(comment) if comment.starts_with(char::is_whitespace) => {
+                comment.insert_str(0, &format!(" {}", command));
+            }
+            Some(comment) => comment.insert_str(0, &format!(" {} ", command)),
+            None => self.comments.push(format!(" {} ", command)),
+        }
+    }
+
+    /// Iterates over the main line, starting with this node.
+    pub fn mainline(&self) -> Mainline<'_> {
+        Mainline {
+            children: std::slice::from_ref(self),
+        }
+    }
+
+    fn write_pgn<W: io::Write>(&self, writer: &mut PgnWriter<W>) -> io::Result<()> {
+        for comment in &self.starting_comments {
+            writer.comment(comment.as_bytes())?;
+        }
+        writer.san(&self.san_plus)?;
+        for &nag in &self.nags {
+            writer.nag(nag)?;
+        }
+        for comment in &self.comments {
+            writer.comment(comment.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the main line of a game tree. See [`Chapter::mainline()`]
+/// and [`Node::mainline()`].
+#[derive(Debug, Clone)]
+pub struct Mainline<'a> {
+    children: &'a [Node],
+}
+
+impl<'a> Iterator for Mainline<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        let node = self.children.first()?;
+        self.children = &node.children;
+        Some(node)
+    }
+}
+
+pub(crate) fn write_line<W: io::Write>(
+    writer: &mut PgnWriter<W>,
+    mut children: &[Node],
+) -> io::Result<()> {
+    while let Some((main, variations)) = children.split_first() {
+        main.write_pgn(writer)?;
+        for variation in variations {
+            writer.begin_variation()?;
+            variation.write_pgn(writer)?;
+            write_line(writer, &variation.children)?;
+            writer.end_variation()?;
+        }
+        children = &main.children;
+    }
+    Ok(())
+}
+
+fn children_at<'a>(mut children: &'a mut Vec<Node>, path: &[usize]) -> &'a mut Vec<Node> {
+    for &i in path {
+        children = &mut children[i].children;
+    }
+    children
+}
+
+fn extend_unique<T: PartialEq>(items: &mut Vec<T>, others: Vec<T>) {
+    for other in others {
+        if !items.contains(&other) {
+            items.push(other);
+        }
+    }
+}
+
+fn visit_positions<P, F>(children: &[Node], pos: &P, path: &mut Vec<usize>, f: &mut F)
+where
+    P: Position + Clone,
+    F: FnMut(&[usize], &P),
+{
+    for (i, child) in children.iter().enumerate() {
+        if let Ok(m) = child.san_plus.san.to_move(pos) {
+            let mut after = pos.clone();
+            after.play_unchecked(&m);
+            path.push(i);
+            f(path, &after);
+            visit_positions(&child.children, &after, path, f);
+            path.pop();
+        }
+    }
+}
+
+fn merge_children<P: Position + Clone + ZobristHash>(
+    root: &mut Vec<Node>,
+    path: &[usize],
+    pos: &P,
+    others: Vec<Node>,
+    index: &mut HashMap<u64, Vec<usize>>,
+) {
+    for mut other in others {
+        let m = match other.san_plus.san.to_move(pos) {
+            Ok(m) => m,
+            Err(_) => {
+                children_at(root, path).push(other);
+                continue;
+            }
+        };
+        let mut after = pos.clone();
+        after.play_unchecked(&m);
+
+        let grandchildren = mem::take(&mut other.children);
+        let children = children_at(root, path);
+        let mut child_path = path.to_vec();
+        match children
+            .iter()
+            .position(|child| child.san_plus.san.to_move(pos).as_ref() == Ok(&m))
+        {
+            Some(i) => {
+                let child = &mut children[i];
+                extend_unique(&mut child.starting_comments, other.starting_comments);
+                extend_unique(&mut child.nags, other.nags);
+                extend_unique(&mut child.comments, other.comments);
+                child_path.push(i);
+            }
+            None => {
+                children.push(other);
+                child_path.push(children.len() - 1);
+                match index.get(&after.zobrist_hash()) {
+                    Some(transposition) => child_path = transposition.clone(),
+                    None => {
+                        index.insert(after.zobrist_hash(), child_path.clone());
+                    }
+                }
+            }
+        }
+        merge_children(root, &child_path, &after, grandchildren, index);
+    }
+}
+
+fn find_tag<'a>(tags: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|(tag, _)| tag == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Nodes of a [`Chapter`] that reach the same position. See
+/// [`Chapter::transpositions()`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Transposition {
+    /// Zobrist hash of the position.
+    pub hash: u64,
+    /// Paths of the nodes, as used by [`Chapter::node()`].
+    pub paths: Vec<Vec<usize>>,
+}
+
+impl Transposition {
+    /// The path of the first occurrence of the position, which the other
+    /// occurrences link to. See [`Chapter::transposition_links()`].
+    pub fn target(&self) -> &[usize] {
+        &self.paths[0]
+    }
+}
+
+/// A game tree with tags, for example a chapter of a [`Study`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Chapter {
+    /// Tag pairs, in order.
+    pub tags: Vec<(String, String)>,
+    /// Comments before the first move.
+    pub comments: Vec<String>,
+    /// First moves. The first one is the main line, the others are
+    /// variations.
+    pub children: Vec<Node>,
+    /// Game termination marker. `None` for `*`.
+    pub outcome: Option<Outcome>,
+}
+
+impl Chapter {
+    /// Creates an empty chapter with the given `ChapterName`.
+    pub fn new(name: &str) -> Chapter {
+        let mut chapter = Chapter::default();
+        chapter.set_tag("ChapterName", name);
+        chapter
+    }
+
+    /// Gets the name of the chapter from the `ChapterName` tag.
+    pub fn name(&self) -> Option<&str> {
+        self.tag("ChapterName")
+    }
+
+    /// Gets the value of the first tag with the given name.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        find_tag(&self.tags, name)
+    }
+
+    /// Sets a tag, replacing the value of an existing tag with the same
+    /// name, or appending it otherwise.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => *existing = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// Removes all tags with the given name, returning the value of the
+    /// first.
+    pub fn remove_tag(&mut self, name: &str) -> Option<String> {
+        let value = self.tag(name).map(str::to_owned);
+        self.tags.retain(|(tag, _)| tag != name);
+        value
+    }
+
+    /// Iterates over the main line.
+    pub fn mainline(&self) -> Mainline<'_> {
+        Mainline {
+            children: &self.children,
+        }
+    }
+
+    /// Gets the accuracy of both players from the `[%eval]` annotations of
+    /// the main line, given `turn` and the evaluation of the starting
+    /// position. See [`annotation::accuracy()`](crate::annotation::accuracy()).
+    ///
+    /// Returns `None` if a move of the main line is not evaluated.
+    pub fn accuracy(&self, turn: Color, initial: Score) -> Option<ByColor<Option<f64>>> {
+        let mut scores = vec![initial];
+        for node in self.mainline() {
+            scores.push(node.eval()?.score);
+        }
+        Some(accuracy(turn, &scores))
+    }
+
+    /// Gets the node at `path`, which lists the indices of the children to
+    /// follow from the start of the game.
+    pub fn node(&self, path: &[usize]) -> Option<&Node> {
+        let (&last, parent) = path.split_last()?;
+        let mut children = &self.children;
+        for &i in parent {
+            children = &children.get(i)?.children;
+        }
+        children.get(last)
+    }
+
+    /// Gets the moves leading to the node at `path`.
+    pub fn line(&self, path: &[usize]) -> Option<Vec<&SanPlus>> {
+        let mut children = &self.children;
+        let mut line = Vec::with_capacity(path.len());
+        for &i in path {
+            let node = children.get(i)?;
+            line.push(&node.san_plus);
+            children = &node.children;
+        }
+        Some(line)
+    }
+
+    /// Finds positions that are reached at more than one node, starting
+    /// from `pos`.
+    ///
+    /// Positions are compared by their Zobrist hash, which includes the
+    /// side to move, so transposing nodes always have the same ply parity.
+    /// The starting position has the empty path. Groups are ordered by
+    /// their first occurrence in a depth-first traversal, with main lines
+    /// first. Moves that are not legal are skipped, including their
+    /// continuations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{pgn::PgnReader, study::ChapterBuilder, Chess};
+    ///
+    /// let pgn = b"1. d4 Nf6 2. c4 (2. Nf3 e6 3. c4) 2... e6 3. Nf3 *";
+    /// let chapter = PgnReader::new(&pgn[..])
+    ///     .read_game(&mut ChapterBuilder::new())?
+    ///     .expect("game");
+    ///
+    /// let transpositions = chapter.transpositions(&Chess::default());
+    /// assert_eq!(transpositions.len(), 1);
+    /// let paths = &transpositions[0].paths;
+    /// assert_eq!(paths, &[vec![0, 0, 0, 0, 0], vec![0, 0, 1, 0, 0]]);
+    ///
+    /// // This position also arises via ...
+    /// let via: Vec<String> = chapter
+    ///     .line(&paths[1])
+    ///     .expect("node")
+    ///     .into_iter()
+    ///     .map(|san_plus| san_plus.to_string())
+    ///     .collect();
+    /// assert_eq!(via, ["d4", "Nf6", "Nf3", "e6", "c4"]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn transpositions<P: Position + Clone + ZobristHash>(&self, pos: &P) -> Vec<Transposition> {
+        let mut transpositions = vec![Transposition {
+            hash: pos.zobrist_hash(),
+            paths: vec![Vec::new()],
+        }];
+        let mut index = HashMap::new();
+        index.insert(transpositions[0].hash, 0);
+        visit_positions(
+            &self.children,
+            pos,
+            &mut Vec::new(),
+            &mut |path, after: &P| {
+                let hash = after.zobrist_hash();
+                let i = *index.entry(hash).or_insert_with(|| {
+                    transpositions.push(Transposition {
+                        hash,
+                        paths: Vec::new(),
+                    });
+                    transpositions.len() - 1
+                });
+                transpositions[i].paths.push(path.to_vec());
+            },
+        );
+        transpositions.retain(|transposition| transposition.paths.len() > 1);
+        transpositions
+    }
+
+    /// Links each node that transposes into a position that occurred
+    /// before to the path of the first occurrence, starting from `pos`.
+    /// See [`Chapter::transpositions()`] for the order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{pgn::PgnReader, study::ChapterBuilder, Chess};
+    ///
+    /// let pgn = b"1. d4 Nf6 2. c4 (2. Nf3 e6 3. c4) 2... e6 3. Nf3 *";
+    /// let chapter = PgnReader::new(&pgn[..])
+    ///     .read_game(&mut ChapterBuilder::new())?
+    ///     .expect("game");
+    ///
+    /// let links = chapter.transposition_links(&Chess::default());
+    /// assert_eq!(links.len(), 1);
+    /// assert_eq!(links[&vec![0, 0, 1, 0, 0]], [0, 0, 0, 0, 0]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn transposition_links<P: Position + Clone + ZobristHash>(
+        &self,
+        pos: &P,
+    ) -> HashMap<Vec<usize>, Vec<usize>> {
+        let mut links = HashMap::new();
+        for transposition in self.transpositions(pos) {
+            let target = transposition.target().to_vec();
+            for path in transposition.paths.into_iter().skip(1) {
+                links.insert(path, target.clone());
+            }
+        }
+        links
+    }
+
+    /// Merges the moves and annotations of `other` into this chapter.
+    /// Both chapters must start from `pos`.
+    ///
+    /// Moves are matched by playing them, not by their SAN, so `Nbd2` and
+    /// `Nd2` are the same move. Comments and NAGs of matching moves are
+    /// concatenated, skipping duplicates. New moves are appended as
+    /// variations. If a new move transposes into a position that already
+    /// occurs in this chapter, the move is added, but its continuations are
+    /// merged into the existing occurrence instead of duplicating the
+    /// line. Moves that are not legal are appended without merging their
+    /// continuations.
+    ///
+    /// Tags of `other` are added if they are not already present, and the
+    /// outcome is taken from `other` if unknown.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::{pgn::PgnWriter, study::Study, Chess};
+    ///
+    /// let pgn = b"[Event \"Queen's pawn\"]\n1. d4 Nf6 2. c4 e6 *\n\n\
+    ///     [Event \"English\"]\n1. c4 Nf6 { English } 2. d4 g6 *\n";
+    /// let mut study = Study::read_pgn(&pgn[..])?;
+    /// let other = study.chapters.pop().expect("second chapter");
+    /// let chapter = &mut study.chapters[0];
+    /// chapter.merge(other, &Chess::default());
+    ///
+    /// let mut writer = PgnWriter::new(Vec::new());
+    /// chapter.write_pgn(&mut writer)?;
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner())?,
+    ///     "[Event \"Queen's pawn\"]\n\n\
+    ///      1. d4 (1. c4 Nf6 { English } 2. d4) 1... Nf6 2. c4 e6 (2... g6) *\n\n"
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn merge<P: Position + Clone + ZobristHash>(&mut self, other: Chapter, pos: &P) {
+        for (name, value) in other.tags {
+            if self.tag(&name).is_none() {
+                self.tags.push((name, value));
+            }
+        }
+        extend_unique(&mut self.comments, other.comments);
+        self.outcome = self.outcome.or(other.outcome);
+
+        let mut index = HashMap::new();
+        index.insert(pos.zobrist_hash(), Vec::new());
+        visit_positions(
+            &self.children,
+            pos,
+            &mut Vec::new(),
+            &mut |path, after: &P| {
+                index
+                    .entry(after.zobrist_hash())
+                    .or_insert_with(|| path.to_vec());
+            },
+        );
+        merge_children(&mut self.children, &[], pos, other.children, &mut index);
+    }
+
+    /// Writes the chapter as a PGN game.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn write_pgn<W: io::Write>(&self, writer: &mut PgnWriter<W>) -> io::Result<()> {
+        self.write_pgn_with(writer, &[])
+    }
+
+    fn write_pgn_with<W: io::Write>(
+        &self,
+        writer: &mut PgnWriter<W>,
+        extra_tags: &[(String, String)],
+    ) -> io::Result<()> {
+        for (name, value) in &self.tags {
+            writer.tag(name.as_bytes(), value.as_bytes())?;
+        }
+        for (name, value) in extra_tags {
+            if self.tag(name).is_none() {
+                writer.tag(name.as_bytes(), value.as_bytes())?;
+            }
+        }
+        for comment in &self.comments {
+            writer.comment(comment.as_bytes())?;
+        }
+        write_line(writer, &self.children)?;
+        writer.outcome(self.outcome)
+    }
+}
+
+/// A [`Visitor`] that reads each game into a [`Chapter`].
+#[derive(Debug, Default)]
+pub struct ChapterBuilder {
+    chapter: Chapter,
+    path: Vec<usize>,
+    variations: Vec<Vec<usize>>,
+    starting_comments: Vec<String>,
+    variation_start: bool,
+}
+
+impl ChapterBuilder {
+    /// Creates a builder.
+    pub fn new() -> ChapterBuilder {
+        ChapterBuilder::default()
+    }
+
+    fn node_mut(&mut self) -> Option<&mut Node> {
+        let (&last, parent) = self.path.split_last()?;
+        Some(&mut children_at(&mut self.chapter.children, parent)[last])
+    }
+}
+
+impl Visitor for ChapterBuilder {
+    type Result = Chapter;
+
+    fn begin_game(&mut self) {
+        *self = ChapterBuilder::default();
+    }
+
+    fn tag(&mut self, name: &[u8], value: RawTag<'_>) {
+        self.chapter.tags.push((
+            String::from_utf8_lossy(name).into_owned(),
+            String::from_utf8_lossy(&value.decode()).into_owned(),
+        ));
+    }
+
+    fn san(&mut self, san_plus: SanPlus) {
+        let mut node = Node::new(san_plus);
+        node.starting_comments = mem::take(&mut self.starting_comments);
+        let children = children_at(&mut self.chapter.children, &self.path);
+        children.push(node);
+        let index = children.len() - 1;
+        self.path.push(index);
+        self.variation_start = false;
+    }
+
+    fn nag(&mut self, nag: Nag) {
+        if let Some(node) = self.node_mut() {
+            node.nags.push(nag);
+        }
+    }
+
+    fn comment(&mut self, comment: &[u8]) {
+        let comment = String::from_utf8_lossy(comment).into_owned();
+        if self.variation_start {
+            self.starting_comments.push(comment);
+        } else if let Some(node) = self.node_mut() {
+            node.comments.push(comment);
+        } else {
+            self.chapter.comments.push(comment);
+        }
+    }
+
+    fn begin_variation(&mut self) -> Skip {
+        if self.path.is_empty() {
+            return Skip(true);
+        }
+        self.variations.push(self.path.clone());
+        self.path.pop();
+        self.variation_start = true;
+        Skip(false)
+    }
+
+    fn end_variation(&mut self) {
+        if let Some(path) = self.variations.pop() {
+            self.path = path;
+        }
+        self.starting_comments.clear();
+        self.variation_start = false;
+    }
+
+    fn outcome(&mut self, outcome: Option<Outcome>) {
+        self.chapter.outcome = outcome;
+    }
+
+    fn end_game(&mut self) -> Chapter {
+        mem::take(&mut self.chapter)
+    }
+}
+
+/// A named collection of chapters with shared tags.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Study {
+    /// Name of the study, exported as the `StudyName` tag.
+    pub name: String,
+    /// Tags shared by all chapters. Tags of a chapter take precedence.
+    pub tags: Vec<(String, String)>,
+    /// Chapters, in order.
+    pub chapters: Vec<Chapter>,
+}
+
+impl Study {
+    /// Creates an empty study.
+    pub fn new(name: &str) -> Study {
+        Study {
+            name: name.to_owned(),
+            ..Study::default()
+        }
+    }
+
+    /// Gets the first chapter with the given name.
+    pub fn chapter(&self, name: &str) -> Option<&Chapter> {
+        self.chapters
+            .iter()
+            .find(|chapter| chapter.name() == Some(name))
+    }
+
+    /// Gets the first chapter with the given name, mutably.
+    pub fn chapter_mut(&mut self, name: &str) -> Option<&mut Chapter> {
+        self.chapters
+            .iter_mut()
+            .find(|chapter| chapter.name() == Some(name))
+    }
+
+    /// Reads a study from PGN, with one chapter per game.
+    ///
+    /// The name of the study is taken from the `StudyName` tag of the
+    /// first chapter. `StudyName` tags are removed from all chapters. If
+    /// there are at least two chapters, tags that all chapters have in
+    /// common (with the same value) are moved to [`Study::tags`].
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying reader are passed through.
+    pub fn read_pgn<R: io::BufRead>(reader: R) -> io::Result<Study> {
+        let mut reader = PgnReader::new(reader);
+        let mut builder = ChapterBuilder::new();
+        let mut study = Study::default();
+        while let Some(mut chapter) = reader.read_game(&mut builder)? {
+            if let Some(name) = chapter.remove_tag("StudyName") {
+                if study.chapters.is_empty() {
+                    study.name = name;
+                }
+            }
+            study.chapters.push(chapter);
+        }
+
+        if let Some((first, others)) = study.chapters.split_first() {
+            if !others.is_empty() {
+                study.tags = first
+                    .tags
+                    .iter()
+                    .filter(|(name, value)| {
+                        others
+                            .iter()
+                            .all(|chapter| chapter.tag(name) == Some(value.as_str()))
+                    })
+                    .cloned()
+                    .collect();
+            }
+        }
+        for chapter in &mut study.chapters {
+            for (name, _) in &study.tags {
+                chapter.remove_tag(name);
+            }
+        }
+
+        Ok(study)
+    }
+
+    /// Writes all chapters as PGN games. Shared tags are written for each
+    /// chapter that does not override them, followed by the `StudyName`.
+    ///
+    /// # Errors
+    ///
+    /// Errors from the underlying writer are passed through.
+    pub fn write_pgn<W: io::Write>(&self, writer: &mut PgnWriter<W>) -> io::Result<()> {
+        let mut tags = self.tags.clone();
+        if !self.name.is_empty() {
+            tags.push(("StudyName".to_owned(), self.name.clone()));
+        }
+        for chapter in &self.chapters {
+            chapter.write_pgn_with(writer, &tags)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chapter_round_trip() {
+        let pgn = "[Event \"Test\"]\n\
+            [StudyName \"Study\"]\n\
+            \n\
+            { Intro } 1. e4 $1 { Best } (1. d4 d5 (1... Nf6 { Indian }) 2. c4) (1. c4)\n\
+            1... e5 2. Nf3 ({ Gambit } 2. f4 exf4) 2... Nc6 1-0\n\
+            \n";
+
+        let mut reader = PgnReader::new(pgn.as_bytes());
+        let chapter = reader
+            .read_game(&mut ChapterBuilder::new())
+            .expect("io")
+            .expect("game");
+        assert_eq!(chapter.tag("StudyName"), Some("Study"));
+        assert_eq!(chapter.comments, [" Intro "]);
+        assert_eq!(chapter.children.len(), 3);
+        assert_eq!(chapter.children[0].nags, [Nag::GOOD_MOVE]);
+        assert_eq!(chapter.children[1].children.len(), 2);
+        let f4 = &chapter.children[0].children[0].children[1];
+        assert_eq!(f4.starting_comments, [" Gambit "]);
+        assert_eq!(chapter.mainline().count(), 4);
+        assert_eq!(chapter.node(&[0, 0, 1]), Some(f4));
+        assert_eq!(chapter.node(&[0, 5]), None);
+        assert_eq!(chapter.line(&[]), Some(Vec::new()));
+        assert!(chapter.transpositions(&crate::Chess::default()).is_empty());
+
+        let mut writer = PgnWriter::new(Vec::new());
+        chapter.write_pgn(&mut writer).expect("write");
+        assert_eq!(String::from_utf8(writer.into_inner()).expect("utf-8"), pgn);
+
+        let study = Study::read_pgn(pgn.as_bytes()).expect("study");
+        assert_eq!(study.name, "Study");
+        assert!(study.tags.is_empty());
+        assert_eq!(study.chapters[0].tag("StudyName"), None);
+        assert_eq!(study.chapters[0].tag("Event"), Some("Test"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let read = |pgn: &str| {
+            PgnReader::new(pgn.as_bytes())
+                .read_game(&mut ChapterBuilder::new())
+                .expect("io")
+                .expect("game")
+        };
+
+        let mut chapter = read("1. e4 { a } e5 2. Nf3 $1 *");
+        chapter.merge(
+            read("[Site \"?\"]\n1. e4 { b } e5 2. Ngf3 $1 $5 { c } Nc6 1-0"),
+            &crate::Chess::default(),
+        );
+        assert_eq!(chapter.tag("Site"), Some("?"));
+        assert_eq!(
+            chapter.outcome.map(|o| o.to_string()).as_deref(),
+            Some("1-0")
+        );
+        let mainline: Vec<_> = chapter.mainline().collect();
+        assert_eq!(mainline.len(), 4);
+        assert_eq!(mainline[0].comments, [" a ", " b "]);
+        assert_eq!(mainline[2].nags, [Nag::GOOD_MOVE, Nag::SPECULATIVE_MOVE]);
+        assert_eq!(mainline[2].san_plus.to_string(), "Nf3");
+        assert!(mainline.iter().all(|node| node.children.len() <= 1));
+        assert_eq!(chapter.accuracy(Color::White, Score::Centipawns(15)), None);
+
+        let chapter = read("1. e4 { [%eval 0.3] } e5 { [%eval 0.25] } 2. Qh5 { [%eval -0.5] } *");
+        let accuracy = chapter
+            .accuracy(Color::White, Score::Centipawns(15))
+            .expect("evaluated");
+        assert!(accuracy.white < accuracy.black);
+    }
+
+    #[test]
+    fn test_transposition_links() {
+        let pgn = "1. Nf3 Nf6 2. Ng1 Ng8 3. Nc3 Nc6 (3... e6 4. e3 Nc6 5. Nf3) 4. e3 e6 *";
+        let chapter = PgnReader::new(pgn.as_bytes())
+            .read_game(&mut ChapterBuilder::new())
+            .expect("io")
+            .expect("game");
+
+        let links = chapter.transposition_links(&crate::Chess::default());
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[&vec![0, 0, 0, 0]], Vec::<usize>::new());
+        assert_eq!(
+            links[&vec![0, 0, 0, 0, 0, 1, 0, 0]],
+            [0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        let transpositions = chapter.transpositions(&crate::Chess::default());
+        for transposition in &transpositions {
+            for path in &transposition.paths[1..] {
+                assert_eq!(links[path], transposition.target());
+            }
+            assert!(!links.contains_key(transposition.target()));
+        }
+    }
+}
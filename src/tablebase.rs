@@ -0,0 +1,628 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Types for endgame tablebase results.
+//!
+//! [`Wdl`] and [`Dtz`] values are always from the point of view of the side
+//! to move. Use [`Dtz::real_wdl()`] to combine a distance to zeroing with
+//! the current halfmove clock, to find out if a tablebase win can actually
+//! be converted under the 50-move rule.
+//!
+//! To exhaustively generate small endgames, [`positions()`] enumerates all
+//! legal positions with a given material configuration.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{tablebase::Wdl, Color, Outcome};
+//!
+//! // A cursed win is only a draw under the 50-move rule.
+//! assert_eq!(Wdl::CursedWin.outcome(Color::White), Outcome::Draw);
+//! assert_eq!(
+//!     Wdl::Win.outcome(Color::White),
+//!     Outcome::Decisive { winner: Color::White }
+//! );
+//! ```
+
+use std::{
+    marker::PhantomData,
+    ops::{Add, Neg},
+};
+
+use crate::{
+    color::{ByColor, Color},
+    position::{FromSetup, Outcome, Position},
+    role::{ByRole, Role},
+    setup::Setup,
+    square::Square,
+    types::{CastlingMode, Piece},
+    zobrist::ZobristHash,
+};
+
+/// 5-valued evaluation of a position in the context of the 50-move drawing
+/// rule, always from the point of view of the side to move.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Wdl {
+    /// Unconditional loss.
+    Loss = -2,
+    /// Loss that can be saved by the 50-move rule.
+    BlessedLoss = -1,
+    /// Unconditional draw.
+    Draw = 0,
+    /// Win that can be frustrated by the 50-move rule.
+    CursedWin = 1,
+    /// Unconditional win.
+    Win = 2,
+}
+
+impl Wdl {
+    /// Converts an [`Outcome`] to a [`Wdl`] from the point of view of `pov`.
+    ///
+    /// Decisive outcomes are unconditional, so they are never converted to
+    /// [`Wdl::CursedWin`] or [`Wdl::BlessedLoss`].
+    pub fn from_outcome(outcome: Outcome, pov: Color) -> Wdl {
+        match outcome {
+            Outcome::Draw => Wdl::Draw,
+            Outcome::Decisive { winner } if winner == pov => Wdl::Win,
+            Outcome::Decisive { .. } => Wdl::Loss,
+        }
+    }
+
+    /// Converts the evaluation to an [`Outcome`] under the 50-move rule,
+    /// given that `turn` is the side to move.
+    ///
+    /// Cursed wins and blessed losses are draws.
+    pub fn outcome(self, turn: Color) -> Outcome {
+        match self {
+            Wdl::Win => Outcome::Decisive { winner: turn },
+            Wdl::Loss => Outcome::Decisive { winner: !turn },
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => Outcome::Draw,
+        }
+    }
+
+    /// Converts the evaluation to an [`Outcome`] without the 50-move rule,
+    /// given that `turn` is the side to move.
+    ///
+    /// Cursed wins and blessed losses are decisive.
+    pub fn outcome_ignoring_50_move_rule(self, turn: Color) -> Outcome {
+        match self {
+            Wdl::Win | Wdl::CursedWin => Outcome::Decisive { winner: turn },
+            Wdl::Loss | Wdl::BlessedLoss => Outcome::Decisive { winner: !turn },
+            Wdl::Draw => Outcome::Draw,
+        }
+    }
+
+    /// Gets the [`Wdl`] from the point of view of `pov`, given that `turn`
+    /// is the side to move.
+    #[must_use]
+    pub fn relative_to(self, turn: Color, pov: Color) -> Wdl {
+        if turn == pov {
+            self
+        } else {
+            -self
+        }
+    }
+
+    /// Tests if the side to move wins, at least without the 50-move rule.
+    pub fn is_win(self) -> bool {
+        matches!(self, Wdl::Win | Wdl::CursedWin)
+    }
+
+    /// Tests if the side to move loses, at least without the 50-move rule.
+    pub fn is_loss(self) -> bool {
+        matches!(self, Wdl::Loss | Wdl::BlessedLoss)
+    }
+
+    /// Converts a [`Dtz`] value of a position where the halfmove clock was
+    /// just reset to a [`Wdl`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::tablebase::{Dtz, Wdl};
+    ///
+    /// assert_eq!(Wdl::from_dtz_after_zeroing(Dtz(42)), Wdl::Win);
+    /// assert_eq!(Wdl::from_dtz_after_zeroing(Dtz(101)), Wdl::CursedWin);
+    /// assert_eq!(Wdl::from_dtz_after_zeroing(Dtz(-100)), Wdl::Loss);
+    /// ```
+    pub fn from_dtz_after_zeroing(dtz: Dtz) -> Wdl {
+        match dtz.0 {
+            n if n < -100 => Wdl::BlessedLoss,
+            n if n < 0 => Wdl::Loss,
+            0 => Wdl::Draw,
+            n if n <= 100 => Wdl::Win,
+            _ => Wdl::CursedWin,
+        }
+    }
+
+    /// Gets `1` for wins, `-1` for losses and `0` for draws, counting
+    /// cursed wins and blessed losses as draws.
+    pub fn signum(self) -> i32 {
+        match self {
+            Wdl::Win => 1,
+            Wdl::Loss => -1,
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => 0,
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+}
+
+/// Distance to zeroing of the halfmove clock, from the point of view of the
+/// side to move.
+///
+/// Positive values for wins, negative values for losses, and zero for
+/// draws. The absolute value is the number of plies until the next capture
+/// or pawn move (zeroing move), assuming optimal play. Values with an
+/// absolute value above 100 are cursed wins or blessed losses.
+///
+/// Tablebases may round distances, so that the value is only accurate up
+/// to one ply. The helpers in this module treat the value as exact, so
+/// results near the 100 ply boundary are only as accurate as the input.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Dtz(pub i32);
+
+impl Dtz {
+    /// Converts a [`Wdl`] of a position that is one ply before a zeroing
+    /// move to the corresponding [`Dtz`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::tablebase::{Dtz, Wdl};
+    ///
+    /// assert_eq!(Dtz::before_zeroing(Wdl::Win), Dtz(1));
+    /// assert_eq!(Dtz::before_zeroing(Wdl::BlessedLoss), Dtz(-101));
+    /// ```
+    pub fn before_zeroing(wdl: Wdl) -> Dtz {
+        match wdl {
+            Wdl::Loss => Dtz(-1),
+            Wdl::BlessedLoss => Dtz(-101),
+            Wdl::Draw => Dtz(0),
+            Wdl::CursedWin => Dtz(101),
+            Wdl::Win => Dtz(1),
+        }
+    }
+
+    /// Increases the absolute value by `plies`, keeping draws at zero.
+    #[must_use]
+    pub fn add_plies(self, plies: i32) -> Dtz {
+        Dtz(self.0.signum() * (self.0.abs() + plies))
+    }
+
+    /// Gets the [`Wdl`] under the 50-move rule, given the current
+    /// `halfmoves` clock.
+    ///
+    /// A win is only a real win if the next zeroing move can be reached
+    /// before the halfmove clock exceeds 100 plies. Otherwise the position
+    /// is a [cursed win](Wdl::CursedWin) (or respectively a
+    /// [blessed loss](Wdl::BlessedLoss)), which is a draw when the 50-move
+    /// rule is claimed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty::tablebase::{Dtz, Wdl};
+    ///
+    /// assert_eq!(Dtz(20).real_wdl(80), Wdl::Win);
+    /// assert_eq!(Dtz(20).real_wdl(81), Wdl::CursedWin);
+    /// assert_eq!(Dtz(-20).real_wdl(81), Wdl::BlessedLoss);
+    /// assert_eq!(Dtz(0).real_wdl(99), Wdl::Draw);
+    /// ```
+    pub fn real_wdl(self, halfmoves: u32) -> Wdl {
+        let plies = u64::from(self.0.unsigned_abs()) + u64::from(halfmoves);
+        match self.0 {
+            0 => Wdl::Draw,
+            n if n > 0 && plies <= 100 => Wdl::Win,
+            n if n > 0 => Wdl::CursedWin,
+            _ if plies <= 100 => Wdl::Loss,
+            _ => Wdl::BlessedLoss,
+        }
+    }
+
+    /// Tests if the side to move can claim a draw under the 50-move rule
+    /// before the game is decided, given the current `halfmoves` clock.
+    pub fn is_drawn_by_50_move_rule(self, halfmoves: u32) -> bool {
+        self.real_wdl(halfmoves).signum() == 0
+    }
+}
+
+impl Neg for Dtz {
+    type Output = Dtz;
+
+    #[inline]
+    fn neg(self) -> Dtz {
+        Dtz(-self.0)
+    }
+}
+
+impl Add for Dtz {
+    type Output = Dtz;
+
+    #[inline]
+    fn add(self, other: Dtz) -> Dtz {
+        Dtz(self.0 + other.0)
+    }
+}
+
+impl From<Dtz> for i32 {
+    #[inline]
+    fn from(dtz: Dtz) -> i32 {
+        dtz.0
+    }
+}
+
+impl From<Wdl> for Dtz {
+    /// Equivalent to [`Dtz::before_zeroing()`].
+    fn from(wdl: Wdl) -> Dtz {
+        Dtz::before_zeroing(wdl)
+    }
+}
+
+macro_rules! from_wdl_impl {
+    ($($t:ty)+) => {
+        $(impl From<Wdl> for $t {
+            #[inline]
+            fn from(wdl: Wdl) -> $t {
+                wdl as $t
+            }
+        })+
+    }
+}
+
+from_wdl_impl! { i8 i16 i32 i64 i128 isize }
+
+/// A source of [`Wdl`] evaluations for positions of type `P`.
+///
+/// Closures `Fn(&P) -> Option<Wdl>` are tablebases.
+pub trait Tablebase<P> {
+    /// Probes the evaluation of `pos`, assuming that the halfmove clock was
+    /// just reset. Returns `None` if the position is not covered.
+    fn probe_wdl(&self, pos: &P) -> Option<Wdl>;
+}
+
+impl<P, F> Tablebase<P> for F
+where
+    F: Fn(&P) -> Option<Wdl>,
+{
+    fn probe_wdl(&self, pos: &P) -> Option<Wdl> {
+        self(pos)
+    }
+}
+
+/// Decides if a game can be adjudicated, as a match manager would.
+///
+/// `hash_history` contains the 64-bit [Zobrist hashes](ZobristHash) of all
+/// earlier positions of the game, oldest first, not including `pos`
+/// itself. The rules are applied in order:
+///
+/// 1. The [outcome](Position::outcome()) of the position: checkmate,
+///    stalemate, insufficient material and variant specific game ends.
+/// 2. The 75-move rule: A draw after 150 plies without capture or pawn
+///    move.
+/// 3. Fivefold repetition: A draw if the position occurred four times
+///    before, since the last capture or pawn move.
+/// 4. The tablebase, if any. A [`Wdl`] can be trusted to be decisive
+///    only right after a zeroing move, so wins and losses are
+///    adjudicated only when the halfmove clock is `0`. Draws, cursed
+///    wins and blessed losses are always adjudicated as draws.
+///
+/// Returns `None` if the game should continue.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{fen::Fen, tablebase::{adjudicate, Wdl}, CastlingMode, Chess, Outcome};
+///
+/// let pos: Chess = "8/8/8/8/8/2k5/8/K1q5 w - - 0 1"
+///     .parse::<Fen>()?
+///     .into_position(CastlingMode::Standard)?;
+///
+/// let no_tablebase: Option<&fn(&Chess) -> Option<Wdl>> = None;
+/// assert_eq!(adjudicate(&pos, &[], no_tablebase), None);
+///
+/// let tablebase = |_: &Chess| Some(Wdl::Loss);
+/// assert_eq!(
+///     adjudicate(&pos, &[], Some(&tablebase)),
+///     Some(Outcome::Decisive { winner: shakmaty::Color::Black })
+/// );
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn adjudicate<P, T>(pos: &P, hash_history: &[u64], tablebase: Option<&T>) -> Option<Outcome>
+where
+    P: Position + ZobristHash,
+    T: Tablebase<P>,
+{
+    if let Some(outcome) = pos.outcome() {
+        return Some(outcome);
+    }
+
+    let halfmoves = pos.halfmoves();
+    if halfmoves >= 150 {
+        return Some(Outcome::Draw);
+    }
+
+    let reversible = hash_history.len().min(halfmoves as usize);
+    let hash = pos.zobrist_hash::<u64>();
+    let repetitions = hash_history[hash_history.len() - reversible..]
+        .iter()
+        .filter(|h| **h == hash)
+        .count();
+    if repetitions >= 4 {
+        return Some(Outcome::Draw);
+    }
+
+    match tablebase?.probe_wdl(pos)? {
+        wdl @ (Wdl::Win | Wdl::Loss) if halfmoves == 0 => Some(wdl.outcome(pos.turn())),
+        Wdl::Win | Wdl::Loss => None,
+        Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => Some(Outcome::Draw),
+    }
+}
+
+/// Iterator over all legal positions with a given material configuration.
+/// Created by [`positions()`].
+#[derive(Debug)]
+pub struct Positions<P> {
+    pieces: Vec<Piece>,
+    squares: Vec<u8>,
+    turn: Color,
+    done: bool,
+    _marker: PhantomData<fn() -> P>,
+}
+
+/// Enumerates all legal positions with exactly the given material, for
+/// exhaustive tests of endgames or to generate small bitbases.
+///
+/// Positions are without castling rights and en passant squares. Each
+/// placement of pieces is generated once with each side to move.
+/// Placements that differ only by swapping identical pieces are
+/// generated only once.
+///
+/// The number of candidate placements grows exponentially with the number
+/// of pieces, so this is only feasible for a handful of pieces.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{tablebase::positions, ByColor, ByRole, Chess};
+///
+/// let kings = ByColor {
+///     white: ByRole { king: 1, ..ByRole::default() },
+///     black: ByRole { king: 1, ..ByRole::default() },
+/// };
+/// assert_eq!(positions::<Chess>(&kings).count(), 2 * 3612);
+/// ```
+pub fn positions<P: FromSetup>(material: &ByColor<ByRole<u8>>) -> Positions<P> {
+    let mut pieces = Vec::new();
+    for color in Color::ALL {
+        for role in Role::ALL {
+            for _ in 0..*material.get(color).get(role) {
+                pieces.push(Piece { color, role });
+            }
+        }
+    }
+    let mut positions = Positions {
+        squares: vec![0; pieces.len()],
+        done: pieces.len() > 64,
+        pieces,
+        turn: Color::White,
+        _marker: PhantomData,
+    };
+    if !positions.done && !positions.is_valid_placement() {
+        positions.done = !positions.next_placement();
+    }
+    positions
+}
+
+impl<P> Positions<P> {
+    fn is_valid_placement(&self) -> bool {
+        self.squares.iter().enumerate().all(|(i, &sq)| {
+            self.squares[..i].iter().all(|&other| other != sq)
+                && (i == 0 || self.pieces[i - 1] != self.pieces[i] || self.squares[i - 1] < sq)
+        })
+    }
+
+    fn next_placement(&mut self) -> bool {
+        loop {
+            let mut i = self.squares.len();
+            loop {
+                if i == 0 {
+                    return false;
+                }
+                i -= 1;
+                self.squares[i] += 1;
+                if self.squares[i] < 64 {
+                    break;
+                }
+                self.squares[i] = 0;
+            }
+            if self.is_valid_placement() {
+                return true;
+            }
+        }
+    }
+
+    fn setup(&self) -> Setup {
+        let mut setup = Setup::empty();
+        for (&piece, &sq) in self.pieces.iter().zip(&self.squares) {
+            setup.board.set_piece_at(Square::new(u32::from(sq)), piece);
+        }
+        setup.turn = self.turn;
+        setup
+    }
+}
+
+impl<P: FromSetup> Iterator for Positions<P> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<P> {
+        while !self.done {
+            let setup = self.setup();
+            self.turn = !self.turn;
+            if self.turn == Color::White {
+                self.done = !self.next_placement();
+            }
+            if let Ok(pos) = P::from_setup(setup, CastlingMode::Standard) {
+                return Some(pos);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wdl_outcome_roundtrip() {
+        for outcome in [
+            Outcome::Draw,
+            Outcome::Decisive {
+                winner: Color::White,
+            },
+            Outcome::Decisive {
+                winner: Color::Black,
+            },
+        ] {
+            for turn in Color::ALL {
+                assert_eq!(Wdl::from_outcome(outcome, turn).outcome(turn), outcome);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wdl_neg() {
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(Wdl::Win.relative_to(Color::White, Color::Black), Wdl::Loss);
+        assert_eq!(i32::from(-Wdl::Win), -2);
+    }
+
+    #[test]
+    fn test_dtz_real_wdl() {
+        for wdl in [
+            Wdl::Loss,
+            Wdl::BlessedLoss,
+            Wdl::Draw,
+            Wdl::CursedWin,
+            Wdl::Win,
+        ] {
+            let dtz = Dtz::before_zeroing(wdl);
+            assert_eq!(Wdl::from_dtz_after_zeroing(dtz), wdl);
+            assert_eq!(dtz.real_wdl(0), wdl);
+        }
+        assert_eq!(Dtz(1).add_plies(99).real_wdl(0), Wdl::Win);
+        assert_eq!(Dtz(1).add_plies(99).real_wdl(1), Wdl::CursedWin);
+        assert_eq!(Dtz(0).add_plies(10), Dtz(0));
+        assert!(Dtz(-60).is_drawn_by_50_move_rule(41));
+        assert!(!Dtz(-60).is_drawn_by_50_move_rule(40));
+    }
+
+    #[test]
+    fn test_positions() {
+        use crate::Chess;
+
+        let mut material = ByColor::<ByRole<u8>>::default();
+        assert_eq!(positions::<Chess>(&material).count(), 0);
+
+        material.white.king = 1;
+        material.black.king = 1;
+        let kings: Vec<Chess> = positions(&material).collect();
+        assert_eq!(kings.len(), 2 * 3612);
+        assert!(kings
+            .iter()
+            .all(|pos| pos.board().king_of(pos.turn()).is_some()));
+
+        // The side not to move is never in check.
+        material.white.queen = 1;
+        assert!(positions::<Chess>(&material).all(|pos| {
+            let their_king = pos.board().king_of(!pos.turn()).expect("king");
+            pos.king_attackers(their_king, pos.turn(), pos.board().occupied())
+                .is_empty()
+        }));
+
+        material.white.king = 0;
+        assert_eq!(positions::<Chess>(&material).count(), 0);
+    }
+
+    #[test]
+    fn test_adjudicate() {
+        use crate::{fen::Fen, CastlingMode, Chess, Move, Role, Square};
+
+        let no_tablebase: Option<&fn(&Chess) -> Option<Wdl>> = None;
+        let draw = |_: &Chess| Some(Wdl::Draw);
+
+        let mut pos = Chess::default();
+        assert_eq!(adjudicate(&pos, &[], no_tablebase), None);
+        assert_eq!(adjudicate(&pos, &[], Some(&draw)), Some(Outcome::Draw));
+
+        // Shuffle knights back and forth.
+        let mut history = Vec::new();
+        for (from, to) in [
+            (Square::G1, Square::F3),
+            (Square::G8, Square::F6),
+            (Square::F3, Square::G1),
+            (Square::F6, Square::G8),
+        ]
+        .iter()
+        .cycle()
+        .take(16)
+        {
+            history.push(pos.zobrist_hash::<u64>());
+            pos.play_unchecked(&Move::Normal {
+                role: Role::Knight,
+                from: *from,
+                to: *to,
+                capture: None,
+                promotion: None,
+            });
+            if history.len() < 16 {
+                assert_eq!(adjudicate(&pos, &history, no_tablebase), None);
+            }
+        }
+        assert_eq!(
+            adjudicate(&pos, &history, no_tablebase),
+            Some(Outcome::Draw)
+        );
+
+        let pos: Chess = "8/8/8/8/8/2k5/8/K1q5 w - - 150 100"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        assert_eq!(adjudicate(&pos, &[], no_tablebase), Some(Outcome::Draw));
+
+        let pos: Chess = "8/8/8/8/8/2k5/8/K1q5 w - - 1 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let loss = |_: &Chess| Some(Wdl::Loss);
+        assert_eq!(adjudicate(&pos, &[], Some(&loss)), None);
+    }
+}
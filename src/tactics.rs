@@ -0,0 +1,329 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Simple tactical analysis for annotations and tutoring.
+//!
+//! [`threats()`] finds what the opponent threatens, and [`motifs()`] labels
+//! the tactical motifs of a move, using the theme names of the Lichess
+//! puzzle database.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::{fen::Fen, tactics::threats, uci::Uci, CastlingMode, Chess, Position};
+//!
+//! // Black to move must deal with the threat of Qxf7#. Bxf7+ would also
+//! // win a pawn.
+//! let pos: Chess = "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 3 3"
+//!     .parse::<Fen>()?
+//!     .into_position(CastlingMode::Standard)?;
+//! let opponent = pos.clone().swap_turn()?;
+//!
+//! let threats = threats(&pos);
+//! assert_eq!(threats.mates, ["h5f7".parse::<Uci>()?.to_move(&opponent)?]);
+//! assert_eq!(threats.winning_captures, ["c4f7".parse::<Uci>()?.to_move(&opponent)?]);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::{attacks, Bitboard, Move, Outcome, Position, Role};
+
+/// Moves the opponent could play if it were their turn, grouped by
+/// severity. See [`threats()`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Threats {
+    /// Moves that would immediately win the game, usually by checkmate.
+    pub mates: Vec<Move>,
+    /// Captures that would win material according to
+    /// [static exchange evaluation](Position::see()), ordered by the
+    /// expected gain, highest first.
+    pub winning_captures: Vec<Move>,
+}
+
+impl Threats {
+    /// Tests if there are no threats.
+    pub fn is_empty(&self) -> bool {
+        self.mates.is_empty() && self.winning_captures.is_empty()
+    }
+}
+
+/// Finds the threats of the opponent, by passing the turn to the opponent
+/// and looking at their moves.
+///
+/// There are no threats if the side to move is in check, because passing
+/// the turn is not possible in that case.
+pub fn threats<P: Position + Clone>(pos: &P) -> Threats {
+    let mut threats = Threats::default();
+    if pos.is_check() || pos.is_game_over() {
+        return threats;
+    }
+
+    let mut opponent = pos.clone();
+    opponent.play_null_unchecked();
+
+    let mut captures = Vec::new();
+    for m in opponent.legal_moves() {
+        let mut after = opponent.clone();
+        after.play_unchecked(&m);
+        if after.outcome()
+            == Some(Outcome::Decisive {
+                winner: opponent.turn(),
+            })
+        {
+            threats.mates.push(m);
+        } else if m.is_capture() {
+            let gain = opponent.see(&m);
+            if gain > 0 {
+                captures.push((gain, m));
+            }
+        }
+    }
+
+    captures.sort_by_key(|&(gain, _)| -gain);
+    threats.winning_captures = captures.into_iter().map(|(_, m)| m).collect();
+    threats
+}
+
+/// Tactical motif of a move. See [`motifs()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Motif {
+    /// The moved piece attacks two or more pieces, each of them the king,
+    /// more valuable than the moved piece, or undefended.
+    Fork,
+    /// The moved piece pins a piece to its king.
+    Pin,
+    /// The moved piece attacks a piece, and behind it a less valuable
+    /// piece on the same line.
+    Skewer,
+    /// The move uncovers an attack of another piece on the king or on a
+    /// piece other than a pawn.
+    DiscoveredAttack,
+    /// The move mates a king on its backrank, that is blocked in by its
+    /// own pieces.
+    BackRankMate,
+}
+
+impl Motif {
+    /// Name of the motif in the Lichess puzzle themes.
+    pub fn theme(self) -> &'static str {
+        match self {
+            Motif::Fork => "fork",
+            Motif::Pin => "pin",
+            Motif::Skewer => "skewer",
+            Motif::DiscoveredAttack => "discoveredAttack",
+            Motif::BackRankMate => "backRankMate",
+        }
+    }
+}
+
+fn value(role: Role) -> u32 {
+    match role {
+        Role::Pawn => 1,
+        Role::Knight | Role::Bishop => 3,
+        Role::Rook => 5,
+        Role::Queen => 9,
+        Role::King => 100,
+    }
+}
+
+/// Labels the tactical motifs of the legal move `m` in `pos`.
+///
+/// The motifs are detected from the position right after the move, using
+/// only the attacks of the pieces. Whether the motif actually wins
+/// anything is not checked.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     fen::Fen,
+///     tactics::{motifs, Motif},
+///     uci::Uci,
+///     CastlingMode, Chess,
+/// };
+///
+/// let pos: Chess = "r3k3/8/8/3N4/8/8/8/4K3 w - - 0 1"
+///     .parse::<Fen>()?
+///     .into_position(CastlingMode::Standard)?;
+/// let m = "d5c7".parse::<Uci>()?.to_move(&pos)?;
+///
+/// assert_eq!(motifs(&pos, &m), [Motif::Fork]);
+/// assert_eq!(Motif::Fork.theme(), "fork");
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn motifs<P: Position + Clone>(pos: &P, m: &Move) -> Vec<Motif> {
+    let us = pos.turn();
+    let mut after = pos.clone();
+    after.play_unchecked(m);
+
+    let board = after.board();
+    let occupied = board.occupied();
+    let them = board.by_color(!us);
+    let to = match m.castling_side() {
+        Some(side) => side.rook_to(us),
+        None => m.to(),
+    };
+    let piece = match board.piece_at(to) {
+        Some(piece) => piece,
+        None => return Vec::new(),
+    };
+    let targets = board.attacks_from(to) & them;
+
+    let mut motifs = Vec::new();
+
+    let forked = targets
+        .into_iter()
+        .filter(|&sq| {
+            board.role_at(sq).map_or(false, |role| {
+                role == Role::King
+                    || value(role) > value(piece.role)
+                    || !board.is_attacked(sq, !us, occupied)
+            })
+        })
+        .count();
+    if forked >= 2 {
+        motifs.push(Motif::Fork);
+    }
+
+    if after.pinners(us).contains(to) {
+        motifs.push(Motif::Pin);
+    }
+
+    if board.sliders().contains(to) {
+        let skewer = targets.into_iter().any(|front| {
+            let xray = attacks::attacks(to, piece, occupied.without(front))
+                & attacks::ray(to, front)
+                & !board.attacks_from(to);
+            let front_value = board.role_at(front).map_or(0, value);
+            (xray & them)
+                .into_iter()
+                .filter_map(|behind| board.role_at(behind))
+                .any(|role| value(role) < front_value)
+        });
+        if skewer {
+            motifs.push(Motif::Skewer);
+        }
+    }
+
+    let discovered = m.from().map_or(false, |_| {
+        (board.sliders() & board.by_color(us))
+            .without(to)
+            .into_iter()
+            .any(|slider| {
+                board.piece_at(slider).map_or(false, |slider_piece| {
+                    let uncovered = attacks::attacks(slider, slider_piece, occupied)
+                        & !attacks::attacks(slider, slider_piece, pos.board().occupied())
+                        & them
+                        & !board.pawns();
+                    uncovered.any()
+                })
+            })
+    });
+    if discovered {
+        motifs.push(Motif::DiscoveredAttack);
+    }
+
+    if after.is_checkmate() {
+        if let Some(king) = board.king_of(!us) {
+            let backrank = Bitboard::from_rank((!us).backrank());
+            let escapes = attacks::king_attacks(king) & !backrank;
+            if backrank.contains(king)
+                && (after.checkers() & !backrank).is_empty()
+                && (escapes & !them).is_empty()
+            {
+                motifs.push(Motif::BackRankMate);
+            }
+        }
+    }
+
+    motifs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::Fen, uci::Uci, CastlingMode, Chess};
+
+    fn setup(fen: &str) -> Chess {
+        fen.parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position")
+    }
+
+    fn moves(pos: &Chess, ucis: &[&str]) -> Vec<Move> {
+        let mut opponent = pos.clone();
+        opponent.play_null_unchecked();
+        ucis.iter()
+            .map(|uci| {
+                uci.parse::<Uci>()
+                    .expect("valid uci")
+                    .to_move(&opponent)
+                    .expect("legal move")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_threats() {
+        assert!(threats(&Chess::default()).is_empty());
+
+        // Rook and knight hanging, ordered by value.
+        let pos = setup("4k3/8/8/3n4/2r1P3/8/8/2R1K3 b - - 0 1");
+        let threats = threats(&pos);
+        assert!(threats.mates.is_empty());
+        assert_eq!(threats.winning_captures, moves(&pos, &["c1c4", "e4d5"]));
+
+        // Back rank mate.
+        let pos = setup("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1");
+        assert_eq!(super::threats(&pos).mates, moves(&pos, &["a1a8"]));
+
+        // No threats while in check.
+        let pos = setup("6k1/5ppp/8/8/8/8/8/R2r2K1 w - - 0 1");
+        assert!(super::threats(&pos).is_empty());
+    }
+
+    #[test]
+    fn test_motifs() {
+        for (fen, uci, expected) in [
+            (
+                "r3k3/8/8/3N4/8/8/8/4K3 w - - 0 1",
+                "d5c7",
+                &[Motif::Fork][..],
+            ),
+            ("4k3/4n3/8/8/8/8/8/R5K1 w - - 0 1", "a1e1", &[Motif::Pin]),
+            ("r7/8/2k5/8/8/8/8/1B2K3 w - - 0 1", "b1e4", &[Motif::Skewer]),
+            (
+                "q3k3/8/8/8/N7/8/8/R3K3 w - - 0 1",
+                "a4c5",
+                &[Motif::DiscoveredAttack],
+            ),
+            (
+                "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+                "a1a8",
+                &[Motif::BackRankMate],
+            ),
+            ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a2", &[]),
+        ] {
+            let pos = setup(fen);
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal move");
+            assert_eq!(motifs(&pos, &m), expected, "{} {}", fen, uci);
+        }
+    }
+}
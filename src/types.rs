@@ -22,7 +22,7 @@ use std::{
 use crate::{
     color::{ByColor, Color},
     role::Role,
-    square::{File, Square},
+    square::{File, Rank, Square},
     util::overflow_error,
 };
 
@@ -326,6 +326,39 @@ pub enum EnPassantMode {
     Always,
 }
 
+/// Common handicaps in odds games, where the stronger player starts without
+/// some material.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum Odds {
+    /// Without the f-pawn. The opponent moves first.
+    PawnAndMove,
+    /// Without the f-pawn.
+    Pawn,
+    /// Without the queenside knight.
+    Knight,
+    /// Without the queenside rook, and thus without queenside castling.
+    Rook,
+    /// Without the queen.
+    Queen,
+}
+
+impl Odds {
+    /// Square of the removed piece, for the player giving odds.
+    pub fn square(self, giver: Color) -> Square {
+        let file = match self {
+            Odds::PawnAndMove | Odds::Pawn => File::F,
+            Odds::Knight => File::B,
+            Odds::Rook => File::A,
+            Odds::Queen => File::D,
+        };
+        let rank = match self {
+            Odds::PawnAndMove | Odds::Pawn => Rank::Second,
+            Odds::Knight | Odds::Rook | Odds::Queen => Rank::First,
+        };
+        Square::from_coords(file, giver.relative_rank(rank))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem;
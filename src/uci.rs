@@ -77,7 +77,11 @@
 
 use std::{error::Error, fmt, str::FromStr};
 
-use crate::{CastlingMode, CastlingSide, Move, Position, Rank, Role, Square};
+use crate::{
+    pgn::parse_san,
+    san::{ParseSanError, SanPlus},
+    CastlingMode, CastlingSide, ErrorKind, Move, Outcome, Position, Rank, Role, Square,
+};
 
 /// Error when parsing an invalid UCI.
 #[derive(Clone, Debug)]
@@ -91,6 +95,13 @@ impl fmt::Display for ParseUciError {
 
 impl Error for ParseUciError {}
 
+impl ParseUciError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidUci
+    }
+}
+
 /// Error when UCI is illegal.
 #[derive(Clone, Debug)]
 pub struct IllegalUciError;
@@ -103,6 +114,13 @@ impl fmt::Display for IllegalUciError {
 
 impl Error for IllegalUciError {}
 
+impl IllegalUciError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::IllegalMove
+    }
+}
+
 /// A move as represented in the UCI protocol.
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 pub enum Uci {
@@ -155,7 +173,7 @@ impl Uci {
     /// # Examples
     ///
     /// ```
-    /// use shakmaty::{Square, uci::Uci};
+    /// use shakmaty::{Role, Square, uci::Uci};
     ///
     /// let uci = Uci::from_ascii(b"e4e5")?;
     ///
@@ -164,6 +182,10 @@ impl Uci {
     ///     to: Square::E5,
     ///     promotion: None,
     /// });
+    ///
+    /// // Drops, as in Crazyhouse.
+    /// let uci = Uci::from_ascii(b"N@f3")?;
+    /// assert_eq!(uci, Uci::Put { role: Role::Knight, to: Square::F3 });
     /// # Ok::<_, Box<dyn std::error::Error>>(())
     /// ```
     pub fn from_ascii(uci: &[u8]) -> Result<Uci, ParseUciError> {
@@ -178,6 +200,9 @@ impl Uci {
         let to = Square::from_ascii(&uci[2..4]).map_err(|_| ParseUciError)?;
 
         if uci[1] == b'@' {
+            if uci.len() != 4 {
+                return Err(ParseUciError);
+            }
             Ok(Uci::Put {
                 role: Role::from_char(char::from(uci[0])).ok_or(ParseUciError)?,
                 to,
@@ -361,6 +386,155 @@ impl Move {
     }
 }
 
+/// Move notation for [`convert_lines()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Notation {
+    /// Standard Algebraic Notation, like `Nf3`.
+    San,
+    /// UCI notation, like `g1f3`.
+    Uci,
+}
+
+/// Error when a move of a line can not be converted. See
+/// [`convert_lines()`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConvertError {
+    index: usize,
+    kind: ErrorKind,
+    message: String,
+}
+
+impl ConvertError {
+    /// Gets the index of the offending move in the line, starting at `0`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Gets the machine-readable kind of the error, like
+    /// [`ErrorKind::IllegalMove`].
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can not convert move {}: {}",
+            self.index + 1,
+            self.message
+        )
+    }
+}
+
+impl Error for ConvertError {}
+
+fn is_move_number(token: &str) -> bool {
+    token.ends_with('.')
+        && token
+            .trim_end_matches('.')
+            .bytes()
+            .all(|ch| ch.is_ascii_digit())
+}
+
+fn convert_line<P: Position + Clone>(
+    pos: &P,
+    line: &str,
+    from: Notation,
+    to: Notation,
+) -> Result<String, ConvertError> {
+    let mode = pos.castles().mode();
+    let mut pos = pos.clone();
+    let mut converted = String::with_capacity(line.len() + line.len() / 4);
+    let tokens = line.split_ascii_whitespace().filter(|token| {
+        from == Notation::Uci
+            || !(is_move_number(token) || *token == "*" || token.parse::<Outcome>().is_ok())
+    });
+    for (index, token) in tokens.enumerate() {
+        let error = |kind, message: String| ConvertError {
+            index,
+            kind,
+            message,
+        };
+        let m = match from {
+            Notation::San => parse_san(token.as_bytes())
+                .0
+                .ok_or_else(|| error(ErrorKind::InvalidSan, ParseSanError.to_string()))?
+                .san
+                .to_move(&pos)
+                .map_err(|err| error(err.kind(), err.to_string()))?,
+            Notation::Uci => Uci::from_ascii(token.as_bytes())
+                .map_err(|err| error(err.kind(), err.to_string()))?
+                .to_move(&pos)
+                .map_err(|err| error(err.kind(), err.to_string()))?,
+        };
+        if !converted.is_empty() {
+            converted.push(' ');
+        }
+        match to {
+            Notation::San => {
+                converted
+                    .push_str(&SanPlus::from_move_and_play_unchecked(&mut pos, &m).to_string());
+            }
+            Notation::Uci => {
+                converted.push_str(&m.to_uci(mode).to_string());
+                pos.play_unchecked(&m);
+            }
+        }
+    }
+    Ok(converted)
+}
+
+/// Converts lines of moves from one notation to another. Each line is a
+/// sequence of moves, separated by whitespace, starting from `pos`.
+///
+/// Returns one result for each line of the input, with the converted
+/// moves separated by single spaces. SAN input may contain move numbers
+/// (like `1.` or `12...`), game results and annotation suffixes, which are
+/// skipped. UCI output uses the castling mode of `pos`.
+///
+/// # Errors
+///
+/// A line fails with [`ConvertError`] at the first move that can not be
+/// parsed or is illegal. Other lines are not affected.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     fen::Fen,
+///     uci::{convert_lines, Notation},
+///     CastlingMode, Chess, ErrorKind,
+/// };
+///
+/// let pos: Chess = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+///     .parse::<Fen>()?
+///     .into_position(CastlingMode::Standard)?;
+///
+/// let input = "3. Bb5 a6 4. Ba4\nBc4 Nf6 Ng5!?\nBc4 Nf3";
+/// let converted = convert_lines(&pos, input, Notation::San, Notation::Uci);
+/// assert_eq!(converted[0].as_deref(), Ok("f1b5 a7a6 b5a4"));
+/// assert_eq!(converted[1].as_deref(), Ok("f1c4 g8f6 f3g5"));
+/// let err = converted[2].as_ref().unwrap_err();
+/// assert_eq!((err.index(), err.kind()), (1, ErrorKind::IllegalMove));
+///
+/// let converted = convert_lines(&pos, "f1c4 g8f6", Notation::Uci, Notation::San);
+/// assert_eq!(converted[0].as_deref(), Ok("Bc4 Nf6"));
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn convert_lines<P: Position + Clone>(
+    pos: &P,
+    input: &str,
+    from: Notation,
+    to: Notation,
+) -> Vec<Result<String, ConvertError>> {
+    input
+        .lines()
+        .map(|line| convert_line(pos, line, from, to))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -436,8 +610,15 @@ mod tests {
             .expect("P@d7+")
             .to_move(&pos)
             .expect("legal");
+        assert_eq!(p_at_d7.to_uci(CastlingMode::Standard).to_string(), "P@d7");
         pos.play_unchecked(&p_at_d7);
         assert!(pos.is_check());
+
+        // Only pieces in the pocket can be dropped.
+        let n_at_f6 = "N@f6".parse::<Uci>().expect("N@f6");
+        assert!(n_at_f6.to_move(&pos).is_err());
+        assert!(n_at_f6.to_move(&Chess::default()).is_err());
+        assert!("P@d7q".parse::<Uci>().is_err());
     }
 
     #[test]
@@ -481,4 +662,46 @@ mod tests {
             "nbkr1nbr/ppp1pppp/3p4/8/5Pq1/6N1/PPPPPBPP/NBQR1RK1 b - - 5 4"
         );
     }
+
+    #[test]
+    fn test_convert_lines() {
+        let pos = Chess::default();
+        let converted = convert_lines(
+            &pos,
+            "1. e4 e5 2. Nf3 1-0\n\n1. d4 Nf6 2. c4 e6 3. Nc3 Bb4 4. Qc2 O-O 5. a3 Bxc3+ *",
+            Notation::San,
+            Notation::Uci,
+        );
+        assert_eq!(
+            converted,
+            [
+                Ok("e2e4 e7e5 g1f3".to_owned()),
+                Ok(String::new()),
+                Ok("d2d4 g8f6 c2c4 e7e6 b1c3 f8b4 d1c2 e8g8 a2a3 b4c3".to_owned()),
+            ]
+        );
+        assert_eq!(
+            convert_lines(
+                &pos,
+                "d2d4 g8f6 c2c4 e7e6 b1c3 f8b4 d1c2 e8g8 a2a3 b4c3",
+                Notation::Uci,
+                Notation::San
+            ),
+            [Ok("d4 Nf6 c4 e6 Nc3 Bb4 Qc2 O-O a3 Bxc3+".to_owned())]
+        );
+
+        let err = convert_lines(&pos, "e2e4 e7e9", Notation::Uci, Notation::San)
+            .pop()
+            .expect("line")
+            .unwrap_err();
+        assert_eq!((err.index(), err.kind()), (1, ErrorKind::InvalidUci));
+        assert_eq!(err.to_string(), "can not convert move 2: invalid uci");
+
+        let err = convert_lines(&pos, "e4 Nf6 Ke3", Notation::San, Notation::Uci)
+            .pop()
+            .expect("line")
+            .unwrap_err();
+        assert_eq!((err.index(), err.kind()), (2, ErrorKind::IllegalMove));
+        assert_eq!(err.to_string(), "can not convert move 3: illegal san");
+    }
 }
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::{convert::TryFrom as _, num::TryFromIntError};
+use std::{convert::TryFrom as _, io, num::TryFromIntError};
 
 pub(crate) fn overflow_error() -> TryFromIntError {
     // This is a hack to construct TryFromIntError despite its private
@@ -23,3 +23,20 @@ pub(crate) fn overflow_error() -> TryFromIntError {
     // that something more specific than "overflow" will be added.
     u32::try_from(u64::MAX).unwrap_err()
 }
+
+/// Fills `buf` with a fixed size record. Returns `Ok(false)` if the reader
+/// is exhausted before the first byte, and [`io::ErrorKind::UnexpectedEof`]
+/// if it is exhausted in the middle of the record.
+pub(crate) fn read_record<R: io::Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
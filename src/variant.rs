@@ -19,7 +19,7 @@
 //! These are games played with normal chess pieces but special rules.
 //! Every chess variant implements [`FromSetup`] and [`Position`].
 
-use std::{num::NonZeroU32, str};
+use std::{error::Error, fmt, num::NonZeroU32, str, str::FromStr};
 
 pub use crate::position::{
     variant::{Antichess, Atomic, Crazyhouse, Horde, KingOfTheHill, RacingKings, ThreeCheck},
@@ -28,8 +28,8 @@ pub use crate::position::{
 use crate::{
     zobrist::{ZobristHash, ZobristValue},
     Bitboard, Board, ByColor, ByRole, Castles, CastlingMode, CastlingSide, Color, EnPassantMode,
-    FromSetup, Move, MoveList, Outcome, Position, PositionError, RemainingChecks, Role, Setup,
-    Square,
+    ErrorKind, FromSetup, Move, MoveList, Outcome, Position, PositionError, RemainingChecks, Role,
+    Setup, Square,
 };
 
 /// Discriminant of [`VariantPosition`].
@@ -107,6 +107,41 @@ impl Default for Variant {
     }
 }
 
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.uci())
+    }
+}
+
+/// Error when parsing an unknown variant name.
+#[derive(Clone, Debug)]
+pub struct ParseVariantError;
+
+impl fmt::Display for ParseVariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid variant")
+    }
+}
+
+impl Error for ParseVariantError {}
+
+impl ParseVariantError {
+    /// Gets the machine-readable kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidVariant
+    }
+}
+
+/// Parses the name used by the `UCI_Variant` option of chess engines. See
+/// [`Variant::from_uci()`].
+impl FromStr for Variant {
+    type Err = ParseVariantError;
+
+    fn from_str(s: &str) -> Result<Variant, ParseVariantError> {
+        Variant::from_uci(s).ok_or(ParseVariantError)
+    }
+}
+
 /// Dynamically dispatched chess variant [`Position`].
 #[allow(missing_docs)]
 #[derive(Debug, Clone)]
@@ -183,6 +218,7 @@ impl VariantPosition {
         }
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn from_setup(
         variant: Variant,
         setup: Setup,
@@ -228,6 +264,7 @@ impl VariantPosition {
         }
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn swap_turn(self) -> Result<VariantPosition, PositionError<VariantPosition>> {
         let mode = self.castles().mode();
         let variant = self.variant();
@@ -331,9 +368,24 @@ impl Position for VariantPosition {
     fn capture_moves(&self) -> MoveList {
         self.borrow().capture_moves()
     }
+    fn quiet_moves(&self) -> MoveList {
+        self.borrow().quiet_moves()
+    }
     fn promotion_moves(&self) -> MoveList {
         self.borrow().promotion_moves()
     }
+    fn checking_moves(&self) -> MoveList {
+        self.borrow().checking_moves()
+    }
+    fn evasion_moves(&self) -> MoveList {
+        self.borrow().evasion_moves()
+    }
+    fn drop_moves(&self, role: Option<Role>, targets: Bitboard) -> MoveList {
+        self.borrow().drop_moves(role, targets)
+    }
+    fn is_zeroing(&self, m: &Move) -> bool {
+        self.borrow().is_zeroing(m)
+    }
     fn is_irreversible(&self, m: &Move) -> bool {
         self.borrow().is_irreversible(m)
     }
@@ -349,9 +401,15 @@ impl Position for VariantPosition {
     fn variant_outcome(&self) -> Option<Outcome> {
         self.borrow().variant_outcome()
     }
+    fn is_color_symmetric(&self) -> bool {
+        self.borrow().is_color_symmetric()
+    }
     fn play_unchecked(&mut self, m: &Move) {
         self.borrow_mut().play_unchecked(m)
     }
+    fn play_null_unchecked(&mut self) {
+        self.borrow_mut().play_null_unchecked()
+    }
 }
 
 impl ZobristHash for VariantPosition {
@@ -433,4 +491,17 @@ mod tests {
             .expect("legal move");
         assert_eq!(pos.variant(), Variant::Chess);
     }
+
+    #[test]
+    fn test_variant_names() {
+        for variant in Variant::ALL {
+            assert_eq!(variant.to_string().parse::<Variant>().ok(), Some(variant));
+            assert_eq!(VariantPosition::new(variant).variant(), variant);
+        }
+        assert_eq!(
+            "kingofthehill".parse::<Variant>().ok(),
+            Some(Variant::KingOfTheHill)
+        );
+        assert!("standard".parse::<Variant>().is_err());
+    }
 }
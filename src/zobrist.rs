@@ -31,15 +31,72 @@
 //! assert_eq!(pos.zobrist_hash::<u64>(), 0x463b96181691fc9c);
 //! ```
 
-use std::{cell::Cell, num::NonZeroU32, ops::BitXorAssign};
+use std::{cell::Cell, io, num::NonZeroU32, ops::BitXorAssign};
 
 use crate::{
-    color::ByColor, Bitboard, Board, ByRole, Castles, CastlingMode, CastlingSide, Chess, Color,
-    EnPassantMode, File, FromSetup, Move, MoveList, Outcome, Piece, Position, PositionError,
-    RemainingChecks, Role, Setup, Square,
+    color::ByColor, util::read_record, Bitboard, Board, ByRole, Castles, CastlingMode,
+    CastlingSide, Chess, Color, EnPassantMode, File, FromSetup, Move, MoveList, Outcome, Piece,
+    Position, PositionError, RemainingChecks, Role, Setup, Square,
 };
 
 /// Integer type that can be returned as a Zobrist hash.
+///
+/// The keys are truncations of 128-bit random numbers, so a `u64` hash is
+/// the lower half of the `u128` hash of the same position. The upper half
+/// is an independent 64-bit key, for example for the verification tier
+/// of a transposition table.
+///
+/// Downstream crates can implement this trait for their own types, for
+/// example by deriving keys from the `u128` or `u64` implementations.
+///
+/// # Examples
+///
+/// ```
+/// use std::ops::BitXorAssign;
+/// use shakmaty::{
+///     zobrist::{ZobristHash, ZobristValue},
+///     CastlingSide, Chess, Color, File, Piece, RemainingChecks, Role, Square,
+/// };
+///
+/// #[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+/// struct TtKey(u64);
+///
+/// impl BitXorAssign for TtKey {
+///     fn bitxor_assign(&mut self, rhs: TtKey) {
+///         self.0 ^= rhs.0;
+///     }
+/// }
+///
+/// impl ZobristValue for TtKey {
+///     fn zobrist_for_piece(square: Square, piece: Piece) -> TtKey {
+///         TtKey(u64::zobrist_for_piece(square, piece))
+///     }
+///     fn zobrist_for_white_turn() -> TtKey {
+///         TtKey(u64::zobrist_for_white_turn())
+///     }
+///     fn zobrist_for_castling_right(color: Color, side: CastlingSide) -> TtKey {
+///         TtKey(u64::zobrist_for_castling_right(color, side))
+///     }
+///     fn zobrist_for_en_passant_file(file: File) -> TtKey {
+///         TtKey(u64::zobrist_for_en_passant_file(file))
+///     }
+///     fn zobrist_for_remaining_checks(color: Color, remaining: RemainingChecks) -> TtKey {
+///         TtKey(u64::zobrist_for_remaining_checks(color, remaining))
+///     }
+///     fn zobrist_for_promoted(square: Square) -> TtKey {
+///         TtKey(u64::zobrist_for_promoted(square))
+///     }
+///     fn zobrist_for_pocket(color: Color, role: Role, pieces: u8) -> TtKey {
+///         TtKey(u64::zobrist_for_pocket(color, role, pieces))
+///     }
+/// }
+///
+/// let pos = Chess::default();
+/// assert_eq!(pos.zobrist_hash::<TtKey>(), TtKey(pos.zobrist_hash()));
+///
+/// let hash: u128 = pos.zobrist_hash();
+/// assert_eq!(hash as u64, pos.zobrist_hash::<u64>());
+/// ```
 pub trait ZobristValue: BitXorAssign + Default + Copy {
     fn zobrist_for_piece(square: Square, piece: Piece) -> Self;
     fn zobrist_for_white_turn() -> Self;
@@ -48,6 +105,18 @@ pub trait ZobristValue: BitXorAssign + Default + Copy {
     fn zobrist_for_remaining_checks(color: Color, remaining: RemainingChecks) -> Self;
     fn zobrist_for_promoted(square: Square) -> Self;
     fn zobrist_for_pocket(color: Color, role: Role, pieces: u8) -> Self;
+    /// Key for the square of the duck, in variants with a neutral blocker
+    /// piece. Defaults to an empty key, for types that do not distinguish
+    /// such positions.
+    fn zobrist_for_duck(_square: Square) -> Self {
+        Self::default()
+    }
+    /// Key for the right of `color` to gate a piece from the pocket onto the
+    /// back rank square on `file`, in variants like Seirawan chess. Defaults
+    /// to an empty key, for types that do not distinguish such positions.
+    fn zobrist_for_gating_right(_color: Color, _file: File) -> Self {
+        Self::default()
+    }
 }
 
 macro_rules! zobrist_value_impl {
@@ -97,17 +166,126 @@ macro_rules! zobrist_value_impl {
                     <$t>::default()
                 }
             }
+
+            fn zobrist_for_duck(square: Square) -> $t {
+                DUCK_MASKS[usize::from(square)] as $t
+            }
+
+            fn zobrist_for_gating_right(color: Color, file: File) -> $t {
+                GATING_MASKS[color as usize * 8 + usize::from(file)] as $t
+            }
         })+
     }
 }
 
 zobrist_value_impl! { u8 u16 u32 u64 u128 }
 
+/// A 32-bit position fingerprint for memory constrained indexes, such as
+/// Bloom filters over billions of positions.
+///
+/// Unlike a `u32` Zobrist hash, which is simply the lowest 32 bits of the
+/// 128-bit keys (and therefore also the lowest 32 bits of the Polyglot
+/// compatible `u64` hash), each key of the fingerprint folds all 128 bits
+/// of the corresponding key together with XOR. So the fingerprint carries
+/// information from the upper 64 bits, that are independent of the `u64`
+/// hash, and can be combined with it as an additional check. Since folding
+/// is linear, the fingerprint of a position is the folded 128-bit hash,
+/// and it can be updated incrementally like any other [`ZobristValue`].
+///
+/// Fingerprints are stable, just like the other hashes.
+///
+/// # Collisions
+///
+/// Two distinct positions have the same fingerprint with probability
+/// about 2<sup>-32</sup>. Among `n` distinct positions, about
+/// n<sup>2</sup> / 2<sup>33</sup> pairs collide, so collisions become likely
+/// beyond about 77,000 positions, and a set of 1 billion positions has
+/// roughly 116 million colliding pairs. Use fingerprints only where false
+/// positives are acceptable or can be verified, and use `u64` or `u128`
+/// hashes as keys otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     zobrist::{Fingerprint32, ZobristHash},
+///     Chess,
+/// };
+///
+/// let pos = Chess::default();
+/// let fingerprint = pos.zobrist_hash::<Fingerprint32>();
+/// assert_eq!(fingerprint, Fingerprint32::fold(pos.zobrist_hash::<u128>()));
+/// assert_ne!(fingerprint.0, pos.zobrist_hash::<u32>());
+/// ```
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Fingerprint32(pub u32);
+
+impl Fingerprint32 {
+    /// Folds a 128-bit Zobrist hash into a fingerprint.
+    pub const fn fold(hash: u128) -> Fingerprint32 {
+        Fingerprint32((hash ^ (hash >> 32) ^ (hash >> 64) ^ (hash >> 96)) as u32)
+    }
+}
+
+impl BitXorAssign for Fingerprint32 {
+    fn bitxor_assign(&mut self, rhs: Fingerprint32) {
+        self.0 ^= rhs.0;
+    }
+}
+
+impl From<Fingerprint32> for u32 {
+    fn from(fingerprint: Fingerprint32) -> u32 {
+        fingerprint.0
+    }
+}
+
+impl ZobristValue for Fingerprint32 {
+    fn zobrist_for_piece(square: Square, piece: Piece) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_piece(square, piece))
+    }
+
+    fn zobrist_for_white_turn() -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_white_turn())
+    }
+
+    fn zobrist_for_castling_right(color: Color, side: CastlingSide) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_castling_right(color, side))
+    }
+
+    fn zobrist_for_en_passant_file(file: File) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_en_passant_file(file))
+    }
+
+    fn zobrist_for_remaining_checks(color: Color, remaining: RemainingChecks) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_remaining_checks(color, remaining))
+    }
+
+    fn zobrist_for_promoted(square: Square) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_promoted(square))
+    }
+
+    fn zobrist_for_pocket(color: Color, role: Role, pieces: u8) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_pocket(color, role, pieces))
+    }
+
+    fn zobrist_for_duck(square: Square) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_duck(square))
+    }
+
+    fn zobrist_for_gating_right(color: Color, file: File) -> Fingerprint32 {
+        Fingerprint32::fold(u128::zobrist_for_gating_right(color, file))
+    }
+}
+
 /// Supports Zobrist hashing.
 pub trait ZobristHash {
     /// Computes the Zobrist hash of the position from scratch. Hash includes
     /// the position, except halfmove clock and fullmove number.
     ///
+    /// The hash does not depend on the [`CastlingMode`], or on the notation
+    /// of castling rights in the FEN, so it can be used as a transposition
+    /// key across sources.
+    ///
     /// Warning: Zobrist hashes have good collision resistance, but can be
     /// forged efficiently. In this implementation, impossible positions with
     /// more than standard material in Crazyhouse pockets are particularly
@@ -135,6 +313,16 @@ pub trait ZobristHash {
     ) -> Option<V> {
         None
     }
+
+    /// Computes the Zobrist hash of the [canonical](Position::canonical())
+    /// representative of the position, so that positions that are
+    /// equivalent under symmetry have the same hash.
+    fn canonical_zobrist_hash<V: ZobristValue>(&self) -> V
+    where
+        Self: Position + Clone + FromSetup,
+    {
+        self.canonical().zobrist_hash()
+    }
 }
 
 impl ZobristHash for Chess {
@@ -249,6 +437,19 @@ impl<P: ZobristHash, V: ZobristValue> Zobrist<P, V> {
             zobrist
         }
     }
+
+    /// Tests if the incrementally maintained hash (if any) matches a
+    /// hash computed from scratch. A mismatch would indicate a bug in the
+    /// incremental updates, for example when debugging a custom
+    /// [`ZobristValue`] or search code that handles moves unusually.
+    pub fn validate(&self) -> bool
+    where
+        V: PartialEq,
+    {
+        self.zobrist
+            .get()
+            .map_or(true, |zobrist| zobrist == self.pos.zobrist_hash())
+    }
 }
 
 impl<P: Default, V: ZobristValue> Default for Zobrist<P, V> {
@@ -318,6 +519,18 @@ impl<P: Position + ZobristHash, V: ZobristValue> Position for Zobrist<P, V> {
     fn promotion_moves(&self) -> MoveList {
         self.pos.promotion_moves()
     }
+    fn checking_moves(&self) -> MoveList {
+        self.pos.checking_moves()
+    }
+    fn evasion_moves(&self) -> MoveList {
+        self.pos.evasion_moves()
+    }
+    fn drop_moves(&self, role: Option<Role>, targets: Bitboard) -> MoveList {
+        self.pos.drop_moves(role, targets)
+    }
+    fn is_zeroing(&self, m: &Move) -> bool {
+        self.pos.is_zeroing(m)
+    }
     fn is_irreversible(&self, m: &Move) -> bool {
         self.pos.is_irreversible(m)
     }
@@ -333,6 +546,9 @@ impl<P: Position + ZobristHash, V: ZobristValue> Position for Zobrist<P, V> {
     fn variant_outcome(&self) -> Option<Outcome> {
         self.pos.variant_outcome()
     }
+    fn is_color_symmetric(&self) -> bool {
+        self.pos.is_color_symmetric()
+    }
 
     fn play_unchecked(&mut self, m: &Move) {
         self.zobrist.set(
@@ -347,8 +563,181 @@ impl<P: Position + ZobristHash, V: ZobristValue> Position for Zobrist<P, V> {
                 .and_then(|value| self.pos.finalize_incremental_zobrist_hash(value, m)),
         );
     }
+
+    fn play_null_unchecked(&mut self) {
+        if let Some(mut value) = self.zobrist.get() {
+            value ^= V::zobrist_for_white_turn();
+            if let Some(sq) = self.pos.legal_ep_square() {
+                value ^= V::zobrist_for_en_passant_file(sq.file());
+            }
+            self.zobrist.set(Some(value));
+        }
+        self.pos.play_null_unchecked();
+    }
 }
 
+/// Records the Zobrist hashes of the positions in a game, to detect
+/// repetitions.
+///
+/// Positions can only repeat since the last irreversible move (see
+/// [`Position::is_irreversible()`]), so earlier positions are not
+/// considered.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{uci::Uci, zobrist::RepetitionTracker, Chess};
+///
+/// let mut pos = Chess::default();
+/// let mut tracker = RepetitionTracker::<u64>::new(&pos);
+///
+/// for _ in 0..2 {
+///     for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+///         let m = uci.parse::<Uci>()?.to_move(&pos)?;
+///         tracker.play_unchecked(&mut pos, &m);
+///     }
+/// }
+///
+/// assert_eq!(tracker.count_repetitions(), 3);
+/// assert!(tracker.is_threefold());
+/// assert!(!tracker.is_fivefold());
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RepetitionTracker<V> {
+    hashes: Vec<(V, bool)>,
+}
+
+impl<V: ZobristValue + PartialEq> RepetitionTracker<V> {
+    /// Starts tracking from `pos`.
+    pub fn new<P: ZobristHash>(pos: &P) -> RepetitionTracker<V> {
+        RepetitionTracker::from_hash(pos.zobrist_hash())
+    }
+
+    /// Starts tracking from a position with the given hash.
+    pub fn from_hash(hash: V) -> RepetitionTracker<V> {
+        RepetitionTracker {
+            hashes: vec![(hash, true)],
+        }
+    }
+
+    /// Records the position reached by a move. `irreversible` tells if the
+    /// move was irreversible, so that earlier positions can not repeat.
+    pub fn push(&mut self, hash: V, irreversible: bool) {
+        self.hashes.push((hash, irreversible));
+    }
+
+    /// Plays a move in `pos` and records the resulting position.
+    ///
+    /// # Panics
+    ///
+    /// Illegal moves can corrupt the state of the position and may
+    /// (or may not) panic or cause panics on future calls.
+    pub fn play_unchecked<P: Position + ZobristHash>(&mut self, pos: &mut P, m: &Move) {
+        let irreversible = pos.is_irreversible(m);
+        pos.play_unchecked(m);
+        self.push(pos.zobrist_hash(), irreversible);
+    }
+
+    /// Forgets the last recorded position, for example to take back a
+    /// move. The starting position is never removed.
+    pub fn pop(&mut self) -> Option<V> {
+        if self.hashes.len() > 1 {
+            self.hashes.pop().map(|(hash, _)| hash)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the hash of the current position.
+    pub fn current(&self) -> V {
+        self.hashes.last().expect("starting position").0
+    }
+
+    /// Counts how often the current position occurred, including the
+    /// current occurrence.
+    pub fn count_repetitions(&self) -> usize {
+        let current = self.current();
+        let mut count = 0;
+        for &(hash, irreversible) in self.hashes.iter().rev() {
+            if hash == current {
+                count += 1;
+            }
+            if irreversible {
+                break;
+            }
+        }
+        count
+    }
+
+    /// Tests if the current position occurred at least three times, so
+    /// that a draw can be claimed.
+    pub fn is_threefold(&self) -> bool {
+        self.count_repetitions() >= 3
+    }
+
+    /// Tests if the current position occurred at least five times, which
+    /// is an automatic draw.
+    pub fn is_fivefold(&self) -> bool {
+        self.count_repetitions() >= 5
+    }
+}
+
+macro_rules! repetition_tracker_bin_impl {
+    ($($t:ty)+) => {
+        $(impl RepetitionTracker<$t> {
+            /// Writes the recorded history, for example to checkpoint a
+            /// long-running job.
+            ///
+            /// Each record is the big-endian hash, followed by a byte that
+            /// is `1` if the position was reached by an irreversible move
+            /// (or is the starting position), and `0` otherwise.
+            ///
+            /// # Errors
+            ///
+            /// Errors from the underlying writer are passed through.
+            pub fn write_bin<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+                for &(hash, irreversible) in &self.hashes {
+                    writer.write_all(&hash.to_be_bytes())?;
+                    writer.write_all(&[u8::from(irreversible)])?;
+                }
+                Ok(())
+            }
+
+            /// Reads a history written by
+            /// [`RepetitionTracker::write_bin()`].
+            ///
+            /// # Errors
+            ///
+            /// Errors from the underlying reader are passed through. Returns
+            /// [`io::ErrorKind::UnexpectedEof`] if the input is empty or
+            /// truncated, and [`io::ErrorKind::InvalidData`] for invalid
+            /// flag bytes.
+            pub fn read_bin<R: io::Read>(mut reader: R) -> io::Result<RepetitionTracker<$t>> {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                let mut bytes = [0; SIZE + 1];
+                let mut hashes = Vec::new();
+                while read_record(&mut reader, &mut bytes)? {
+                    let mut hash = [0; SIZE];
+                    hash.copy_from_slice(&bytes[..SIZE]);
+                    let irreversible = match bytes[SIZE] {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(io::ErrorKind::InvalidData.into()),
+                    };
+                    hashes.push((<$t>::from_be_bytes(hash), irreversible));
+                }
+                if hashes.is_empty() {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                Ok(RepetitionTracker { hashes })
+            }
+        })+
+    }
+}
+
+repetition_tracker_bin_impl! { u8 u16 u32 u64 u128 }
+
 fn hash_board<V: ZobristValue>(board: &Board) -> V {
     let mut zobrist = V::default();
     for (sq, piece) in board.clone() {
@@ -398,7 +787,128 @@ fn hash_position<P: Position, V: ZobristValue>(pos: &P) -> V {
     zobrist
 }
 
+/// Computes the Zobrist hash of a [`Setup`], including promoted pieces,
+/// pockets and remaining checks, without validating it as a position.
+///
+/// The en passant square is hashed as given, so the hash agrees with
+/// [`ZobristHash::zobrist_hash()`] of the corresponding position if the
+/// setup was created with [`EnPassantMode::Legal`]. Castling rights are
+/// assigned to a side by comparing the file of the rook with the file of
+/// the king on the backrank, or the e-file if there is none.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     fen::Fen,
+///     zobrist::{zobrist_hash_setup, ZobristHash},
+///     CastlingMode, Chess, EnPassantMode, Position,
+/// };
+///
+/// let setup = Fen::default().into_setup();
+/// assert_eq!(zobrist_hash_setup::<u64>(&setup), 0x463b96181691fc9c);
+///
+/// let pos: Chess = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"
+///     .parse::<Fen>()?
+///     .into_position(CastlingMode::Standard)?;
+/// assert_eq!(
+///     zobrist_hash_setup::<u64>(&pos.clone().into_setup(EnPassantMode::Legal)),
+///     pos.zobrist_hash::<u64>()
+/// );
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+pub fn zobrist_hash_setup<V: ZobristValue>(setup: &Setup) -> V {
+    let mut zobrist = hash_board(&setup.board);
+
+    for sq in setup.promoted {
+        zobrist ^= V::zobrist_for_promoted(sq);
+    }
+
+    if let Some(ref pockets) = setup.pockets {
+        for (color, pocket) in pockets.as_ref().zip_color() {
+            for role in Role::ALL {
+                zobrist ^= V::zobrist_for_pocket(color, role, *pocket.get(role));
+            }
+        }
+    }
+
+    if setup.turn == Color::White {
+        zobrist ^= V::zobrist_for_white_turn();
+    }
+
+    for color in Color::ALL {
+        let backrank = Bitboard::from_rank(color.backrank());
+        let king_file = (setup.board.kings() & setup.board.by_color(color) & backrank)
+            .first()
+            .map_or(File::E, Square::file);
+        let rooks = setup.castling_rights & backrank;
+        for side in CastlingSide::ALL {
+            if rooks
+                .into_iter()
+                .any(|rook| side.is_king_side() == (rook.file() > king_file))
+            {
+                zobrist ^= V::zobrist_for_castling_right(color, side);
+            }
+        }
+    }
+
+    if let Some(sq) = setup.ep_square {
+        zobrist ^= V::zobrist_for_en_passant_file(sq.file());
+    }
+
+    if let Some(ref remaining_checks) = setup.remaining_checks {
+        for (color, remaining) in remaining_checks.as_ref().zip_color() {
+            zobrist ^= V::zobrist_for_remaining_checks(color, *remaining);
+        }
+    }
+
+    zobrist
+}
+
+/// The 781 random numbers of the Polyglot opening book format, in the
+/// order of the specification: 768 for pieces, 4 for castling rights, 8
+/// for en passant files, and 1 for White to move.
+///
+/// These are the keys of the `u64` Zobrist hash, for use with other
+/// implementations or custom hashing schemes.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty::{
+///     zobrist::{ZobristValue, POLYGLOT},
+///     Color, Piece, Role, Square,
+/// };
+///
+/// // Piece keys are indexed by 64 * (2 * (role - 1) + color) + square,
+/// // where black is 0 and white is 1.
+/// let piece = Piece { color: Color::White, role: Role::Knight };
+/// assert_eq!(POLYGLOT[64 * 3 + 1], u64::zobrist_for_piece(Square::B1, piece));
+/// assert_eq!(POLYGLOT[780], u64::zobrist_for_white_turn());
+/// ```
+pub static POLYGLOT: [u64; 781] = polyglot_table();
+
+const fn polyglot_table() -> [u64; 781] {
+    let mut table = [0; 781];
+    let mut i = 0;
+    while i < 768 {
+        table[i] = PIECE_MASKS[i] as u64;
+        i += 1;
+    }
+    while i < 772 {
+        table[i] = CASTLING_RIGHT_MASKS[i - 768] as u64;
+        i += 1;
+    }
+    while i < 780 {
+        table[i] = EN_PASSANT_FILE_MASKS[i - 772] as u64;
+        i += 1;
+    }
+    table[780] = WHITE_TURN_MASK as u64;
+    table
+}
+
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
     use crate::{fen::Fen, uci::Uci, Chess};
@@ -455,6 +965,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_polyglot_table() {
+        let pos = Chess::default();
+        let mut hash = POLYGLOT[780];
+        for (square, piece) in pos.board().clone() {
+            hash ^= POLYGLOT[64 * (2 * (usize::from(piece.role) - 1) + piece.color as usize)
+                + usize::from(square)];
+        }
+        for key in &POLYGLOT[768..772] {
+            hash ^= key;
+        }
+        assert_eq!(hash, pos.zobrist_hash::<u64>());
+        assert_eq!(POLYGLOT[0], 0x9d39_247e_3377_6d41);
+        assert_eq!(POLYGLOT[772], 0x70cc_73d9_0bc2_6e24);
+    }
+
     #[test]
     fn test_incremental() {
         let moves = [
@@ -486,6 +1012,134 @@ mod tests {
         }
     }
 
+    fn assert_generators_consistent<P>(pos: &Zobrist<P, u64>)
+    where
+        P: Position + ZobristHash + Clone,
+    {
+        let targets = !pos.board().occupied();
+        let moves = [
+            pos.legal_moves(),
+            pos.castling_moves(CastlingSide::KingSide),
+            pos.castling_moves(CastlingSide::QueenSide),
+            pos.en_passant_moves(),
+            pos.capture_moves(),
+            pos.promotion_moves(),
+            pos.checking_moves(),
+            pos.evasion_moves(),
+            pos.drop_moves(None, targets),
+        ];
+        for m in moves.iter().flatten() {
+            let mut after = pos.clone();
+            assert!(after.validate());
+            after.play_unchecked(m);
+            assert!(after.validate(), "after {}", m);
+        }
+    }
+
+    #[test]
+    fn test_generators_consistent() {
+        for (fen, mode) in [
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                CastlingMode::Standard,
+            ),
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                CastlingMode::Standard,
+            ),
+            (
+                "r3k2r/1P4P1/8/8/8/8/6p1/R3K2R b KQkq - 0 1",
+                CastlingMode::Standard,
+            ),
+            (
+                "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1",
+                CastlingMode::Chess960,
+            ),
+            ("4k3/8/8/8/8/8/4q3/4K3 w - - 0 1", CastlingMode::Standard),
+        ] {
+            let pos: Zobrist<Chess, u64> = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(mode)
+                .expect("legal position");
+            pos.zobrist_hash();
+            assert_generators_consistent(&pos);
+        }
+
+        #[cfg(feature = "variant")]
+        {
+            let pos: Zobrist<crate::variant::Crazyhouse, u64> =
+                "r3k2r/1P4P1/8/8/3Pp3/8/6p1/R3K2R~[Nq] b KQkq d3 0 1"
+                    .parse::<Fen>()
+                    .expect("valid fen")
+                    .into_position(CastlingMode::Standard)
+                    .expect("legal position");
+            pos.zobrist_hash();
+            assert_generators_consistent(&pos);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let pos: Zobrist<Chess, u64> = Zobrist::default();
+        assert!(pos.validate());
+        pos.zobrist_hash();
+        assert!(pos.validate());
+        pos.zobrist.set(Some(0));
+        assert!(!pos.validate());
+    }
+
+    #[test]
+    fn test_null_move() {
+        let mut pos: Zobrist<Chess, u64> = Zobrist::default();
+        for uci in ["e2e4", "c7c5", "e4e5", "d7d5"] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal uci");
+            pos.play_unchecked(&m);
+        }
+        assert!(pos.legal_ep_square().is_some());
+
+        let hash = pos.zobrist_hash();
+        let swapped = pos.as_inner().clone().swap_turn().expect("swap turn");
+        pos.play_null_unchecked();
+        assert_eq!(pos.legal_ep_square(), None);
+        assert_eq!(pos.zobrist_hash(), pos.clone().into_inner().zobrist_hash());
+        assert_eq!(pos.zobrist_hash(), swapped.zobrist_hash());
+
+        pos.play_null_unchecked();
+        assert_ne!(pos.zobrist_hash(), hash);
+        assert_eq!(pos.zobrist_hash(), pos.clone().into_inner().zobrist_hash());
+        assert_eq!((pos.halfmoves(), pos.fullmoves().get()), (2, 4));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let mut pos: Zobrist<Chess, Fingerprint32> = Zobrist::default();
+        assert_eq!(pos.zobrist_hash(), Fingerprint32(0xf5a1_3f74));
+
+        for uci in [
+            "e2e4", "d7d5", "e4e5", "f7f5", "e5f6", "e8f7", "g1f3", "b8c6", "f1c4", "c8e6",
+        ] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal uci");
+
+            pos.play_unchecked(&m);
+
+            let inner = pos.clone().into_inner();
+            assert_eq!(pos.zobrist_hash(), inner.zobrist_hash());
+            assert_eq!(
+                pos.zobrist_hash(),
+                Fingerprint32::fold(inner.zobrist_hash::<u128>())
+            );
+        }
+    }
+
     #[cfg(feature = "variant")]
     #[test]
     fn test_variants_not_distinguished() {
@@ -499,6 +1153,215 @@ mod tests {
         assert_eq!(chess, king_of_the_hill);
     }
 
+    #[test]
+    fn test_canonical_zobrist_hash() {
+        let a: Chess = "8/8/8/2k5/8/8/3P4/4K3 b - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let b: Chess = "3k4/4p3/8/8/5K2/8/8/8 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        assert_ne!(a.zobrist_hash::<u64>(), b.zobrist_hash());
+        assert_eq!(
+            a.canonical_zobrist_hash::<u64>(),
+            b.canonical_zobrist_hash()
+        );
+    }
+
+    #[test]
+    fn test_castling_mode_independent() {
+        let hash = |fen: &str, mode| -> u64 {
+            let mut pos: Zobrist<Chess, u64> = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(mode)
+                .expect("legal position");
+            let before = pos.zobrist_hash();
+            let m = pos.castling_moves(CastlingSide::KingSide)[0].clone();
+            pos.play_unchecked(&m);
+            assert_eq!(pos.zobrist_hash(), pos.as_inner().zobrist_hash());
+            before ^ pos.zobrist_hash().rotate_left(1)
+        };
+
+        let expected = hash(
+            "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1",
+            CastlingMode::Standard,
+        );
+        for (fen, mode) in [
+            (
+                "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1",
+                CastlingMode::Chess960,
+            ),
+            (
+                "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w HAha - 0 1",
+                CastlingMode::Standard,
+            ),
+            (
+                "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w HAha - 0 1",
+                CastlingMode::Chess960,
+            ),
+        ] {
+            assert_eq!(hash(fen, mode), expected, "{} {:?}", fen, mode);
+        }
+    }
+
+    #[test]
+    fn test_no_aliasing() {
+        let mut keys = std::collections::HashSet::new();
+        for table in [
+            &PIECE_MASKS[..],
+            &[WHITE_TURN_MASK],
+            &CASTLING_RIGHT_MASKS,
+            &EN_PASSANT_FILE_MASKS,
+            &REMAINING_CHECKS_MASKS,
+            &PROMOTED_MASKS,
+            &POCKET_MASKS,
+            &DUCK_MASKS,
+            &GATING_MASKS,
+        ] {
+            for key in table {
+                assert!(keys.insert(*key as u64));
+            }
+        }
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_pocket_roles_and_colors() {
+        use crate::variant::Crazyhouse;
+
+        let hash = |fen: &str| -> u64 {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position::<Crazyhouse>(CastlingMode::Standard)
+                .expect("legal position")
+                .zobrist_hash()
+        };
+
+        let queen = hash("4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1");
+        let rook = hash("4k3/8/8/8/8/8/8/4K3[R] w - - 0 1");
+        let black_queen = hash("4k3/8/8/8/8/8/8/4K3[q] w - - 0 1");
+        assert_ne!(queen, rook);
+        assert_ne!(queen, black_queen);
+        assert_ne!(
+            hash("4k3/8/8/8/8/8/8/4K3[Nb] w - - 0 1"),
+            hash("4k3/8/8/8/8/8/8/4K3[Bn] w - - 0 1")
+        );
+
+        // Guard against accidental changes of the stable hash values.
+        assert_eq!(queen, 0xb093_e104_4be1_acbc);
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_crazyhouse_captures_and_drops() {
+        use crate::variant::Crazyhouse;
+
+        let pos = |fen: &str| -> Crazyhouse {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Standard)
+                .expect("legal position")
+        };
+
+        // Capturing a promoted queen puts a pawn into the pocket.
+        let mut zobrist: Zobrist<Crazyhouse, u64> =
+            Zobrist::new(pos("4k3/3p4/4Q~3/8/8/8/8/4K3 b - - 0 1"));
+        for (uci, expected) in [
+            ("d7e6", "4k3/8/4p3/8/8/8/8/4K3[p] w - - 0 2"),
+            ("e1d1", "4k3/8/4p3/8/8/8/8/3K4[p] b - - 1 2"),
+            ("P@d2", "4k3/8/4p3/8/8/8/3p4/3K4 w - - 0 3"),
+        ] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&zobrist)
+                .expect("legal");
+            zobrist.play_unchecked(&m);
+            assert_eq!(
+                zobrist.zobrist_hash(),
+                pos(expected).zobrist_hash::<u64>(),
+                "after {}",
+                uci
+            );
+        }
+    }
+
+    #[test]
+    fn test_zobrist_hash_setup() {
+        for (fen, expected) in [
+            (
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+                0x22a48b5a8e47ff78,
+            ),
+            (
+                "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 1 4",
+                0x5c3f9b829b279560,
+            ),
+        ] {
+            let setup = fen.parse::<Fen>().expect("valid fen").into_setup();
+            assert_eq!(zobrist_hash_setup::<u64>(&setup), expected, "{}", fen);
+        }
+
+        let pos: Chess = "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w GBgb - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        assert_eq!(
+            zobrist_hash_setup::<u128>(&pos.clone().into_setup(EnPassantMode::Legal)),
+            pos.zobrist_hash()
+        );
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_zobrist_hash_variant_setup() {
+        use crate::variant::{Crazyhouse, ThreeCheck};
+
+        let setup = "4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_setup();
+        assert_eq!(zobrist_hash_setup::<u64>(&setup), 0xb093_e104_4be1_acbc);
+
+        for fen in [
+            "4k3/3p4/4Q~3/8/8/8/8/4K3[Nb] b - - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[] w KQkq - 2 3",
+        ] {
+            let pos: Crazyhouse = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Standard)
+                .expect("legal position");
+            assert_eq!(
+                zobrist_hash_setup::<u64>(&pos.clone().into_setup(EnPassantMode::Legal)),
+                pos.zobrist_hash::<u64>(),
+                "{}",
+                fen
+            );
+        }
+
+        let pos: ThreeCheck = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1+2 0 2"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let setup = pos.clone().into_setup(EnPassantMode::Legal);
+        assert_eq!(zobrist_hash_setup::<u64>(&setup), pos.zobrist_hash::<u64>());
+        assert_ne!(
+            zobrist_hash_setup::<u64>(&setup),
+            zobrist_hash_setup::<u64>(&Setup {
+                remaining_checks: None,
+                ..setup
+            })
+        );
+    }
+
     #[test]
     fn test_full_pockets() {
         // 8/8/8/7k/8/8/3K4/8[ppppppppppppppppnnnnbbbbrrrrqq] w - - 0 54
@@ -513,6 +1376,84 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_repetition_tracker() {
+        let mut pos = Chess::default();
+        let mut tracker = RepetitionTracker::<u64>::new(&pos);
+        let mut play = |tracker: &mut RepetitionTracker<u64>, uci: &str| {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal");
+            tracker.play_unchecked(&mut pos, &m);
+        };
+
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            play(&mut tracker, uci);
+        }
+        assert_eq!(tracker.count_repetitions(), 2);
+
+        // A pawn move resets the history.
+        play(&mut tracker, "e2e4");
+        for uci in ["g8f6", "g1f3", "f6g8", "f3g1"] {
+            play(&mut tracker, uci);
+        }
+        assert_eq!(tracker.count_repetitions(), 2);
+        assert!(!tracker.is_threefold());
+        for uci in ["g8f6", "g1f3", "f6g8", "f3g1"] {
+            play(&mut tracker, uci);
+        }
+        assert!(tracker.is_threefold());
+
+        assert!(tracker.pop().is_some());
+        assert_eq!(tracker.count_repetitions(), 2);
+
+        let mut start = RepetitionTracker::from_hash(1u64);
+        assert_eq!(start.pop(), None);
+        assert_eq!(start.current(), 1);
+    }
+
+    #[test]
+    fn test_repetition_tracker_checkpoint() {
+        let mut pos = Chess::default();
+        let mut tracker = RepetitionTracker::<u32>::new(&pos);
+        for uci in ["e2e4", "g8f6", "g1f3", "f6g8", "f3g1"] {
+            let m = uci
+                .parse::<Uci>()
+                .expect("valid uci")
+                .to_move(&pos)
+                .expect("legal");
+            tracker.play_unchecked(&mut pos, &m);
+        }
+
+        let mut checkpoint = Vec::new();
+        tracker.write_bin(&mut checkpoint).expect("write to vec");
+        assert_eq!(checkpoint.len(), 6 * 5);
+
+        let mut resumed = RepetitionTracker::<u32>::read_bin(&checkpoint[..]).expect("valid");
+        assert_eq!(resumed.current(), tracker.current());
+        assert_eq!(resumed.count_repetitions(), 2);
+        assert!(resumed.pop().is_some());
+        assert_eq!(resumed.count_repetitions(), 1);
+
+        for invalid in [&[][..], &checkpoint[..7]] {
+            assert_eq!(
+                RepetitionTracker::<u32>::read_bin(invalid)
+                    .expect_err("truncated")
+                    .kind(),
+                io::ErrorKind::UnexpectedEof
+            );
+        }
+        checkpoint[4] = 2;
+        assert_eq!(
+            RepetitionTracker::<u32>::read_bin(&checkpoint[..])
+                .expect_err("invalid flag")
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
 }
 
 const PIECE_MASKS: [u128; 64 * 6 * 2] = [
@@ -1576,3 +2517,89 @@ const POCKET_MASKS: [u128; 2 * 6 * 16] = [
     0xb4e0_aff4_e90c_cb0d_22c3_ce12_1986_d762,
     0x484e_a0a4_8998_be25_fa2c_98ac_27e2_e5b3,
 ];
+
+const DUCK_MASKS: [u128; 64] = [
+    0x642a_db32_696e_0dbf_07b4_4bba_80b7_0b90,
+    0x184f_a79f_99ba_243b_5031_422d_e502_811f,
+    0x5117_37e9_b9fa_3812_1bf2_c317_4fa0_9851,
+    0xb252_a72d_00e5_26fd_b8c0_2b1d_48c8_7a63,
+    0xb3fb_de81_9e91_5b91_52d3_2112_a240_f10f,
+    0x0150_65b0_51cd_ee3c_7a10_894e_8f05_8702,
+    0x697b_d436_5ece_96ea_b6bf_9575_42ab_4de6,
+    0x7b1e_f060_7bae_1442_f756_dead_3bb8_d350,
+    0x8f99_0c6f_2c3a_51ac_0009_05ea_6ad3_a606,
+    0xb038_1088_bb0e_29fd_b463_98c6_e577_833f,
+    0xb179_2816_94f8_d1e1_985b_14f5_7607_f354,
+    0x2c53_5b2d_18b6_a8e6_3d2f_0791_2b97_2e37,
+    0xda0c_fc68_6980_0524_6dc9_d6e1_6c2a_84b5,
+    0x56ef_7145_4714_3c09_271f_d026_409b_57af,
+    0x0218_237d_5e5e_b29b_2029_8875_3e76_de4e,
+    0x3844_f9d6_2f27_ee88_5fd0_104d_a551_9757,
+    0xbad2_97df_3214_564e_f4b8_8be0_c2a4_5bcf,
+    0x929c_4e93_3c9c_79ab_eda2_128e_9c55_3b88,
+    0x06cc_8890_c876_da8e_053c_6acf_3ffe_bb51,
+    0xde66_09d8_d847_4203_66ab_8759_51de_c40a,
+    0x8a22_9e3e_7485_120d_8738_033c_a01e_8b3a,
+    0xe0a6_3aff_f0d7_9cd1_a207_07a0_f2ef_5f2a,
+    0x6789_7841_8859_7a66_a17e_f6e4_c560_61ee,
+    0x974b_83c7_454d_bde1_0dca_21d5_f646_fba3,
+    0x2257_546e_27b8_42dd_5305_392d_3f9a_4cde,
+    0x4c43_937c_5600_65ba_ee30_384d_083a_ae94,
+    0x2390_a666_ed06_d4c3_6e27_719c_36a8_7920,
+    0xe7ad_00a0_fb0d_6bb2_8602_04da_cb94_930e,
+    0x9270_d03b_2f96_36ea_2dd2_2309_e451_a75c,
+    0x5318_3573_9d37_e513_a05e_a365_ab9a_7eb1,
+    0x3294_7402_33ec_f26b_4fa9_1679_dc60_c201,
+    0x7706_daaf_b26c_281c_8018_e56b_c96d_cf33,
+    0x905f_7b73_d0ef_4075_9a1e_e241_6979_384b,
+    0x6b15_a977_5e62_6dd3_e691_e0e6_0147_64cb,
+    0xf376_8cec_3d1b_fa8e_eb58_6e77_9b18_c517,
+    0xcf86_9aac_7615_5fac_5f66_486e_d834_19f2,
+    0x36ca_6846_66bb_54cb_dfb8_1bed_4d89_27d5,
+    0x8339_9a29_23e2_45a1_1ec0_1360_226f_c2f0,
+    0x8e1f_833f_caef_503d_2913_9188_ac32_1473,
+    0xd72f_5f02_8633_4797_a022_0903_0825_6d64,
+    0x7961_05bd_519d_4dee_4cf9_a6b6_8954_e5b4,
+    0x8286_f7d6_67f7_11ca_e7f0_18a8_1bc9_0f62,
+    0x379d_2e6c_637c_f324_544e_5af8_1236_7937,
+    0x65f2_8e64_0591_4d0f_331f_d76e_f4ca_a691,
+    0x69fc_d0f5_70b5_b855_6df7_52e2_2028_b574,
+    0x757f_b02c_8cc1_a377_3d12_1d9a_00e8_1a92,
+    0x8e84_64be_0f4b_21cf_625b_6016_0ede_879a,
+    0x4c3f_78ef_51f7_09be_954c_266b_91aa_b0fc,
+    0xe1f2_ef16_58a4_eebc_d8dd_4777_4003_c19a,
+    0x3ef5_0279_0463_d311_4c1c_8591_9335_2a83,
+    0x8e8d_7777_ad9d_e66a_ea82_1341_4f7e_ec6c,
+    0x42a4_9c13_ec13_9133_76ac_87c7_e5be_3b7a,
+    0x26a7_24a0_c025_9c2c_5054_fbed_dcf5_af36,
+    0x091b_726b_b33d_38ec_c6c5_dade_f63b_206d,
+    0xdf5d_9463_f846_1646_bcdc_01e6_2ae9_478e,
+    0x33cb_b631_1b0a_c8e6_9295_09a9_acfa_a709,
+    0x9cb9_a1bf_031a_c9ba_74f5_bd0d_4ade_debd,
+    0xdcb5_3112_79e8_a544_d423_c29c_c872_7dbb,
+    0xe13e_fb37_0558_b8ab_9fa2_ed85_6292_e5f6,
+    0x4a87_726f_218e_6b3b_bfad_82be_fcf6_222b,
+    0x1055_fc24_e607_d07b_4552_67a1_f270_12f9,
+    0xa56d_36d7_b5a9_1ddb_6bb4_89cd_c892_2fde,
+    0x5a09_7e50_4afc_9b42_d7d3_bea8_5100_9d59,
+    0xe9d9_8884_4d95_edbf_f911_bd23_256b_d616,
+];
+
+const GATING_MASKS: [u128; 2 * 8] = [
+    0x7eaa_1193_e1d1_bc29_23a3_fee8_da2f_423b,
+    0x7227_83c8_7863_de74_9631_1e54_4e03_482e,
+    0x6520_8c6a_5c36_330c_0ff8_6ade_0ecc_78a1,
+    0x72d1_883c_748a_0f4c_c189_2c86_54d4_b2e3,
+    0x2465_cf6c_d2f7_14c1_eca1_f7fd_71e9_bf37,
+    0x31cc_ff6b_b763_3775_3e60_4586_1d99_30a8,
+    0x7e34_29b0_643e_1638_f83f_ad62_75a2_0af1,
+    0xc2ed_b19e_c6e1_7d0c_8080_d0b8_27b6_8c58,
+    0x595d_c4a0_9e5e_f32f_872a_4914_4706_1a39,
+    0x2fa1_9628_2d28_2b7c_7ac1_6931_f91e_71e7,
+    0xfd5c_97a0_f6b4_86f3_cf37_cd76_55eb_8ed2,
+    0xb8c7_3089_bb02_2d7f_d048_fac4_d024_fc35,
+    0x853d_b688_9ceb_5012_cf8a_c7f1_03d8_d6e3,
+    0x21ce_7d5c_1c2b_329f_a52f_68ab_c514_9c0d,
+    0x3525_3a65_a163_ba1d_b86a_cf9a_01c3_c2c4,
+    0x22a9_6cc3_4d85_13c0_1071_2cc9_22ac_d3d8,
+];